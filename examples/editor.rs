@@ -212,6 +212,13 @@ impl WindowOps<EditorInfo> for EditorWindow {
         }
     }
 
+    fn leave(&mut self, store: &mut Store<EditorInfo>) {
+        match self {
+            EditorWindow::Text(tbox) => tbox.leave(store),
+            EditorWindow::Listing(ls) => ls.leave(store),
+        }
+    }
+
     fn write(
         &mut self,
         path: Option<&str>,
@@ -286,6 +293,13 @@ impl WindowOps<EditorInfo> for EditorWindow {
         }
     }
 
+    fn get_cursor_line(&self) -> Option<String> {
+        match self {
+            EditorWindow::Text(tbox) => tbox.get_cursor_line(),
+            EditorWindow::Listing(ls) => ls.get_cursor_line(),
+        }
+    }
+
     fn get_selected_word(&self) -> Option<String> {
         match self {
             EditorWindow::Text(tbox) => tbox.get_selected_word(),
@@ -587,6 +601,13 @@ impl Editor {
                         // Continue processing; we'll redraw later.
                         continue;
                     },
+                    Err(UIError::NeedConfirm(dialog)) |
+                    Err(UIError::EditingFailure(EditError::NeedConfirm(dialog))) => {
+                        self.bindings.run_dialog(dialog);
+
+                        // Continue processing; we'll redraw later.
+                        continue;
+                    },
                     Err(e) => {
                         self.screen.push_error(e);
 
@@ -665,7 +686,7 @@ impl Editor {
             Action::ShowInfoMessage(msg) => Some(msg),
 
             // Simple delegations.
-            Action::CommandBar(act) => self.screen.command_bar(&act, &ctx)?,
+            Action::CommandBar(act) => self.screen.command_bar(&act, &ctx, &mut self.store)?,
             Action::Editor(act) => self.screen.editor_command(&act, &ctx, &mut self.store)?,
             Action::Macro(act) => self.bindings.macro_command(&act, &ctx, &mut self.store)?,
             Action::Scroll(style) => self.screen.scroll(&style, &ctx, &mut self.store)?,
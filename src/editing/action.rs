@@ -50,6 +50,7 @@ use super::base::*;
 
 /// The various actions that can be taken on text.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum EditAction {
     /// Move the cursor.
     ///
@@ -88,12 +89,22 @@ pub enum EditAction {
 
     /// Change the case of the targeted text.
     ChangeCase(Case),
+
+    /// Insert text in front of the targeted block on each line, shifting the block's contents
+    /// to the right.
+    ///
+    /// The targeted text is always treated as [TargetShape::BlockWise].
+    Prefix(LinePrefix),
+
+    /// Create a closed fold over the lines within the targeted text.
+    Fold,
 }
 
 impl EditAction {
     /// Returns true if this [EditAction] doesn't modify a buffer's text.
     pub fn is_readonly(&self) -> bool {
         match self {
+            EditAction::Fold => true,
             EditAction::Motion => true,
             EditAction::Yank => true,
 
@@ -103,6 +114,7 @@ impl EditAction {
             EditAction::Format => false,
             EditAction::Indent(_) => false,
             EditAction::Join(_) => false,
+            EditAction::Prefix(_) => false,
             EditAction::Replace(_) => false,
         }
     }
@@ -136,6 +148,33 @@ where
     /// Create or update a cursor mark based on the leader's cursor position.
     fn mark(&mut self, name: Mark, ctx: &C, store: &mut S) -> EditResult<EditInfo, I>;
 
+    /// Push or pop positions on the mark rings.
+    ///
+    /// By default, this returns [EditError::Unimplemented] for editors that don't keep mark rings.
+    #[allow(unused_variables)]
+    fn mark_ring(
+        &mut self,
+        act: &MarkRingAction,
+        ctx: &C,
+        store: &mut S,
+    ) -> EditResult<EditInfo, I> {
+        Err(EditError::Unimplemented("Mark rings are not supported here".into()))
+    }
+
+    /// Interactively replace matches of a search string.
+    ///
+    /// By default, this returns [EditError::Unimplemented] for editors that don't support
+    /// interactive replacement.
+    #[allow(unused_variables)]
+    fn query_replace(
+        &mut self,
+        act: &QueryReplaceAction,
+        ctx: &C,
+        store: &mut S,
+    ) -> EditResult<EditInfo, I> {
+        Err(EditError::Unimplemented("Interactive replacement is not supported here".into()))
+    }
+
     /// Complete the text before the cursor group leader.
     fn complete(
         &mut self,
@@ -177,6 +216,14 @@ where
         ctx: &C,
         store: &mut S,
     ) -> EditResult<EditInfo, I>;
+
+    /// Create, open, close or delete folds.
+    ///
+    /// By default, this returns [EditError::Unimplemented] for editors that don't support folding.
+    #[allow(unused_variables)]
+    fn fold(&mut self, act: &FoldAction, ctx: &C, store: &mut S) -> EditResult<EditInfo, I> {
+        Err(EditError::Unimplemented("Folding is not supported here".into()))
+    }
 }
 
 /// Trait for objects which can process [EditorActions](EditorAction).
@@ -222,6 +269,10 @@ pub enum SelectionAction {
     /// Join adjacent selections together.
     Join,
 
+    /// Restore the most recent selections made by the cursor group, and remember the current
+    /// ones in their place if there are any.
+    Restore,
+
     /// Change the bounds of the current selection as described by the
     /// [style](SelectionResizeStyle) and [target](EditTarget).
     ///
@@ -250,6 +301,10 @@ pub enum InsertTextAction {
     OpenLine(TargetShape, MoveDir1D, Count),
 
     /// Paste before or after the current cursor position [*n*](Count) times.
+    ///
+    /// When pasting at the cursor while the context specifies a [TargetShape::BlockWise] shape,
+    /// [TargetShape::BlockWise] text is inserted as a block, even when there is an
+    /// [InsertStyle](EditContext::get_insert_style).
     Paste(PasteStyle, Count),
 
     /// Insert the contents of a [String] on [either side](MoveDir1D) of the cursor.
@@ -257,6 +312,15 @@ pub enum InsertTextAction {
 
     /// Type a [character](Char) on [either side](MoveDir1D) of the cursor [*n*](Count) times.
     Type(Specifier<Char>, MoveDir1D, Count),
+
+    /// Replace the text inserted by the previous paste from [Register::KillRing] with the entry
+    /// [*n*](Count) places away in the kill ring.
+    ///
+    /// [MoveDir1D::Previous] moves towards older kills, and [MoveDir1D::Next] towards newer ones.
+    ///
+    /// This fails if the previous action on the cursor group wasn't a paste from the kill ring or
+    /// another [InsertTextAction::YankPop].
+    YankPop(MoveDir1D, Count),
 }
 
 /// Editing history actions
@@ -266,6 +330,11 @@ pub enum HistoryAction {
     /// Create a new editing history checkpoint.
     Checkpoint,
 
+    /// Undo all of the latest changes made to the most recently changed line.
+    ///
+    /// This is itself a change to that line, so doing it again undoes it.
+    LineUndo,
+
     /// Redo [*n*](Count) edits.
     Redo(Count),
 
@@ -277,6 +346,7 @@ impl HistoryAction {
     /// Returns true if this [HistoryAction] doesn't modify a buffer's text.
     pub fn is_readonly(&self) -> bool {
         match self {
+            HistoryAction::LineUndo => false,
             HistoryAction::Redo(_) => false,
             HistoryAction::Undo(_) => false,
             HistoryAction::Checkpoint => true,
@@ -325,6 +395,110 @@ impl CursorAction {
     }
 }
 
+/// Mark ring actions
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MarkRingAction {
+    /// Push the leader's position onto the buffer's mark ring.
+    ///
+    /// If the most recent entry in the global mark ring belongs to a different buffer, then the
+    /// position also gets pushed onto the global mark ring.
+    Push,
+
+    /// Move the leader to the most recent position in the buffer's mark ring, and rotate it to
+    /// the back of the ring.
+    Pop,
+
+    /// Move the leader to the most recent position in the global mark ring, and rotate it to the
+    /// back of the ring.
+    ///
+    /// If the position belongs to a different buffer, then this fails with
+    /// [EditError::WrongBuffer] and leaves the ring as is.
+    PopGlobal,
+}
+
+impl MarkRingAction {
+    /// Returns true if this [MarkRingAction] is allowed to trigger a [WindowAction::Switch]
+    /// after an error.
+    pub fn is_switchable<C: EditContext>(&self, _: &C) -> bool {
+        match self {
+            MarkRingAction::PopGlobal => true,
+
+            MarkRingAction::Push => false,
+            MarkRingAction::Pop => false,
+        }
+    }
+}
+
+/// Fold actions
+///
+/// Actions that act on a single fold use the one containing the cursor group leader.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FoldAction {
+    /// Create a closed fold over the lines within a [RangeSpec].
+    Create(RangeSpec),
+
+    /// Open the closed fold at the cursor.
+    ///
+    /// If [bool] is true, then open every fold containing the cursor.
+    Open(bool),
+
+    /// Close the innermost open fold at the cursor.
+    ///
+    /// If [bool] is true, then close every fold containing the cursor.
+    Close(bool),
+
+    /// Open the fold at the cursor if it's closed, and close it otherwise.
+    ///
+    /// If [bool] is true, then open or close every fold containing the cursor.
+    Toggle(bool),
+
+    /// Delete the manually created fold at the cursor.
+    ///
+    /// If [bool] is true, then also delete the folds nested inside of it.
+    Delete(bool),
+
+    /// Open every fold in the buffer.
+    OpenAll,
+
+    /// Close every fold in the buffer.
+    CloseAll,
+
+    /// Delete every manually created fold in the buffer.
+    DeleteAll,
+}
+
+/// Interactive replacement actions
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum QueryReplaceAction {
+    /// Start replacing matches of a search string with the replacement text, beginning at the
+    /// leader's position, and then prompt the user about the first match.
+    ///
+    /// If [bool] is `true`, then the search string is a regular expression, and the replacement
+    /// text may refer to its capture groups. Otherwise, both are used literally.
+    Start(String, String, bool),
+
+    /// Replace the current match, and then move on to the next one.
+    Replace,
+
+    /// Replace the current match, and then stop replacing.
+    ReplaceAndStop,
+
+    /// Replace the current match and all of the remaining ones without prompting.
+    ReplaceAll,
+
+    /// Skip over the current match without replacing it.
+    Skip,
+
+    /// Move back to the previous match.
+    Previous,
+
+    /// Stop replacing, leaving the remaining matches as they are.
+    Quit,
+}
+
 /// Command actions
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[non_exhaustive]
@@ -395,8 +569,28 @@ pub enum CommandBarAction {
     /// Focus the command bar
     Focus(CommandType),
 
+    /// Focus the command bar to prompt for a search string and then its replacement, and start
+    /// interactively replacing matches once both have been entered.
+    ///
+    /// If [bool] is `true`, then the search string will be treated as a regular expression.
+    QueryReplace(bool),
+
     /// Unfocus the command bar.
     Unfocus,
+
+    /// Insert the [word](WordStyle) under the cursor in the currently focused window into the
+    /// command bar.
+    InsertWord(WordStyle),
+
+    /// Insert the line under the cursor in the currently focused window into the command bar.
+    InsertLine,
+
+    /// Move the match that the search being typed into the command bar would jump to, using the
+    /// text typed so far.
+    ///
+    /// [MoveDir1D::Next] moves onwards in the direction of the search, and [MoveDir1D::Previous]
+    /// moves back.
+    IncSearch(MoveDir1D),
 }
 
 /// Prompt actions
@@ -440,11 +634,29 @@ pub enum MacroAction {
     /// Execute the contents of the contextually specified Register [*n* times](Count).
     Execute(Count),
 
+    /// Execute the contents of the contextually specified Register [*n* times](Count), and then
+    /// do so again each time the last key typed before this action is pressed again.
+    ExecuteRepeatable(Count),
+
     /// Execute the contents of the previously specified macro [*n* times](Count).
     Repeat(Count),
 
+    /// Copy the most recently recorded macro into the contextually specified Register.
+    Save,
+
     /// Start or stop recording a macro.
     ToggleRecording,
+
+    /// Start recording a macro into the contextually specified Register, unless one is already
+    /// being recorded.
+    StartRecording,
+
+    /// Stop recording a macro, if one is being recorded.
+    StopRecording,
+
+    /// Stop recording a macro if one is being recorded, and otherwise execute the contents of the
+    /// contextually specified Register [*n* times](Count).
+    StopOrExecute(Count),
 }
 
 /// Tab actions
@@ -580,6 +792,18 @@ pub enum EditorAction {
     /// Create a new [Mark] at the current leader position.
     Mark(Specifier<Mark>),
 
+    /// Push or pop positions on the mark rings.
+    MarkRing(MarkRingAction),
+
+    /// Interactively replace matches of a search string.
+    QueryReplace(QueryReplaceAction),
+
+    /// Stop highlighting the matches of the last search until the next one.
+    ClearSearchHighlight,
+
+    /// Create, open, close or delete folds.
+    Fold(FoldAction),
+
     /// Modify the current selection.
     Selection(SelectionAction),
 }
@@ -591,9 +815,13 @@ impl EditorAction {
             EditorAction::Complete(_, _, _) => false,
             EditorAction::History(act) => act.is_readonly(),
             EditorAction::InsertText(_) => false,
+            EditorAction::QueryReplace(_) => false,
 
+            EditorAction::ClearSearchHighlight => true,
             EditorAction::Cursor(_) => true,
+            EditorAction::Fold(_) => true,
             EditorAction::Mark(_) => true,
+            EditorAction::MarkRing(_) => true,
             EditorAction::Selection(_) => true,
 
             EditorAction::Edit(act, _) => ctx.resolve(act).is_readonly(),
@@ -609,8 +837,12 @@ impl EditorAction {
         ctx: &C,
     ) -> SequenceStatus {
         match self {
+            EditorAction::ClearSearchHighlight => SequenceStatus::Break,
+            EditorAction::Fold(_) => SequenceStatus::Break,
             EditorAction::History(_) => SequenceStatus::Break,
             EditorAction::Mark(_) => SequenceStatus::Break,
+            EditorAction::MarkRing(_) => SequenceStatus::Break,
+            EditorAction::QueryReplace(_) => SequenceStatus::Break,
             EditorAction::InsertText(_) => SequenceStatus::Track,
             EditorAction::Cursor(_) => SequenceStatus::Track,
             EditorAction::Selection(_) => SequenceStatus::Track,
@@ -619,6 +851,7 @@ impl EditorAction {
                 match ctx.resolve(act) {
                     EditAction::Motion => motion,
                     EditAction::Yank => SequenceStatus::Ignore,
+                    EditAction::Fold => SequenceStatus::Ignore,
                     _ => SequenceStatus::Track,
                 }
             },
@@ -629,6 +862,7 @@ impl EditorAction {
     pub fn is_last_action<C: EditContext>(&self, _: &C) -> SequenceStatus {
         match self {
             EditorAction::History(HistoryAction::Checkpoint) => SequenceStatus::Ignore,
+            EditorAction::History(HistoryAction::LineUndo) => SequenceStatus::Atom,
             EditorAction::History(HistoryAction::Undo(_)) => SequenceStatus::Atom,
             EditorAction::History(HistoryAction::Redo(_)) => SequenceStatus::Atom,
 
@@ -637,6 +871,10 @@ impl EditorAction {
             EditorAction::Edit(_, _) => SequenceStatus::Atom,
             EditorAction::InsertText(_) => SequenceStatus::Atom,
             EditorAction::Mark(_) => SequenceStatus::Atom,
+            EditorAction::MarkRing(_) => SequenceStatus::Atom,
+            EditorAction::QueryReplace(_) => SequenceStatus::Ignore,
+            EditorAction::ClearSearchHighlight => SequenceStatus::Ignore,
+            EditorAction::Fold(_) => SequenceStatus::Ignore,
             EditorAction::Selection(_) => SequenceStatus::Atom,
         }
     }
@@ -646,16 +884,20 @@ impl EditorAction {
         match self {
             EditorAction::History(_) => SequenceStatus::Ignore,
             EditorAction::Mark(_) => SequenceStatus::Ignore,
+            EditorAction::MarkRing(_) => SequenceStatus::Ignore,
+            EditorAction::QueryReplace(_) => SequenceStatus::Ignore,
             EditorAction::InsertText(_) => SequenceStatus::Ignore,
             EditorAction::Cursor(_) => SequenceStatus::Ignore,
             EditorAction::Complete(_, _, _) => SequenceStatus::Ignore,
+            EditorAction::ClearSearchHighlight => SequenceStatus::Ignore,
+            EditorAction::Fold(_) => SequenceStatus::Ignore,
 
             EditorAction::Selection(SelectionAction::Resize(_, _)) => SequenceStatus::Track,
             EditorAction::Selection(_) => SequenceStatus::Ignore,
 
             EditorAction::Edit(act, _) => {
                 if let EditAction::Motion = ctx.resolve(act) {
-                    if ctx.get_target_shape().is_some() {
+                    if ctx.get_target_shape().is_some() || ctx.is_selecting() {
                         SequenceStatus::Restart
                     } else {
                         SequenceStatus::Ignore
@@ -672,10 +914,14 @@ impl EditorAction {
         match self {
             EditorAction::Cursor(act) => act.is_switchable(ctx),
             EditorAction::Edit(act, _) => ctx.resolve(act).is_switchable(ctx),
+            EditorAction::MarkRing(act) => act.is_switchable(ctx),
+            EditorAction::ClearSearchHighlight => false,
             EditorAction::Complete(_, _, _) => false,
+            EditorAction::Fold(_) => false,
             EditorAction::History(_) => false,
             EditorAction::InsertText(_) => false,
             EditorAction::Mark(_) => false,
+            EditorAction::QueryReplace(_) => false,
             EditorAction::Selection(_) => false,
         }
     }
@@ -687,6 +933,24 @@ impl From<CursorAction> for EditorAction {
     }
 }
 
+impl From<MarkRingAction> for EditorAction {
+    fn from(act: MarkRingAction) -> Self {
+        EditorAction::MarkRing(act)
+    }
+}
+
+impl From<QueryReplaceAction> for EditorAction {
+    fn from(act: QueryReplaceAction) -> Self {
+        EditorAction::QueryReplace(act)
+    }
+}
+
+impl From<FoldAction> for EditorAction {
+    fn from(act: FoldAction) -> Self {
+        EditorAction::Fold(act)
+    }
+}
+
 impl From<HistoryAction> for EditorAction {
     fn from(act: HistoryAction) -> Self {
         EditorAction::History(act)
@@ -74,16 +74,22 @@ pub enum PasteStyle {
 
 /// The source to search for completion candidates.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum CompletionScope {
     /// Only use completion candidates from the current buffer.
     Buffer,
 
     /// Use completion candidates available from all buffers.
     Global,
+
+    /// Use completion candidates from the current buffer, starting with those closest to the
+    /// cursor, and then those available from all other buffers.
+    Nearest,
 }
 
 /// What type of phrase we are completing.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum CompletionSelection {
     /// Navigate through the list of completion candidates.
     List(MoveDir1D),
@@ -96,10 +102,14 @@ pub enum CompletionSelection {
 
     /// If there is only a single completion candidate, select it.
     Single,
+
+    /// Insert all of the completion candidates, separated by spaces.
+    All,
 }
 
 /// What type of phrase we are completing.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum CompletionType {
     /// Determine what to complete by the buffer context.
     Auto,
@@ -112,6 +122,17 @@ pub enum CompletionType {
 
     /// Complete the current word.
     Word(CompletionScope),
+
+    /// Complete the current word using the words loaded from a dictionary.
+    ///
+    /// See [CompletionStore::load_dictionary](crate::editing::store::CompletionStore::load_dictionary).
+    Dictionary,
+
+    /// Complete using only the candidates from [ApplicationInfo::complete], without falling back
+    /// to word completion.
+    ///
+    /// [ApplicationInfo::complete]: crate::editing::application::ApplicationInfo::complete
+    Application,
 }
 
 /// How to display completion candidates.
@@ -300,6 +321,66 @@ impl SearchType {
     }
 }
 
+/// How letter case gets treated when matching search patterns.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SearchCase {
+    /// Patterns only match text with the same case.
+    #[default]
+    Sensitive,
+
+    /// Patterns match text regardless of case (Vim's `'ignorecase'`).
+    Insensitive,
+
+    /// Patterns match text regardless of case, unless they contain an uppercase letter (Vim's
+    /// `'ignorecase'` together with `'smartcase'`).
+    Smart,
+}
+
+/// Where to place the cursor relative to a match found by a [SearchType::Regex] search.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SearchOffset {
+    /// Move to the first column of the line [isize] lines below the start of the match, and
+    /// treat the search as a linewise motion.
+    Line(isize),
+
+    /// Move [isize] characters from the start of the match.
+    Start(isize),
+
+    /// Move [isize] characters from the last character of the match, and treat the search as
+    /// an inclusive motion.
+    End(isize),
+}
+
+impl Default for SearchOffset {
+    fn default() -> Self {
+        SearchOffset::Start(0)
+    }
+}
+
+/// A regular expression search entered into the search bar.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SearchQuery {
+    /// The pattern to search for. An empty pattern reuses the previous one.
+    pub pattern: String,
+
+    /// The direction to search in.
+    pub dir: MoveDir1D,
+
+    /// Where to place the cursor after finding a match.
+    pub offset: SearchOffset,
+}
+
+impl SearchQuery {
+    /// Create a new query for `pattern` without any offset.
+    pub fn new<T: Into<String>>(pattern: T, dir: MoveDir1D) -> Self {
+        SearchQuery {
+            pattern: pattern.into(),
+            dir,
+            offset: SearchOffset::default(),
+        }
+    }
+}
+
 /// The different ways of grouping a buffer's contents into words.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum WordStyle {
@@ -683,6 +764,16 @@ pub enum RangeType {
     ///
     /// When done inclusively, the opening and closing tags are included.
     XmlTag,
+
+    /// Select the spaces and tabs around the cursor on the current line.
+    HorizontalSpace,
+
+    /// Select the blank lines around the cursor.
+    ///
+    /// When the cursor is on a non-blank line, this selects the blank lines that follow it. When
+    /// the cursor is on a blank line with other blank lines around it, this selects all but one
+    /// of them.
+    BlankLines,
 }
 
 /// Specify a movement away from the current cursor position.
@@ -712,6 +803,10 @@ pub enum MoveType {
     /// Move to the first word [*n* lines](Count) away in [MoveDir1D] direction.
     FirstWord(MoveDir1D),
 
+    /// Move to the start of the next fold or the end of the previous fold [*n* times](Count),
+    /// depending on the [MoveDir1D] direction.
+    Fold(MoveDir1D),
+
     /// Move to the matching character of the next item.
     ///
     /// Items are characters like `(`/`)`, `[`/`]`, `{`/`}`, and so on.
@@ -942,6 +1037,86 @@ pub enum IndentChange<I = Count> {
     Increase(I),
 }
 
+/// Options that control how a buffer's lines get indented, named after the Vim options that they
+/// correspond to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IndentOptions {
+    /// The number of columns in each level of indentation (`'shiftwidth'`). When this is zero,
+    /// [IndentOptions::tabstop] is used instead.
+    pub shiftwidth: usize,
+
+    /// The number of columns between tab stops when measuring and creating indentation
+    /// (`'tabstop'`).
+    pub tabstop: usize,
+
+    /// Whether to indent using only spaces instead of tabs (`'expandtab'`).
+    pub expandtab: bool,
+
+    /// Whether shifting lines rounds their indentation to a multiple of the shift width
+    /// (`'shiftround'`).
+    pub shiftround: bool,
+
+    /// Whether new lines copy the indentation of the line they were opened from
+    /// (`'autoindent'`).
+    pub autoindent: bool,
+
+    /// Whether new lines get indented another level after lines ending with an opening bracket
+    /// or `:` (`'smartindent'`). This also copies indentation the way that
+    /// [IndentOptions::autoindent] does.
+    pub smartindent: bool,
+}
+
+impl IndentOptions {
+    /// The number of columns in each level of indentation.
+    pub fn shift_width(&self) -> usize {
+        match self.shiftwidth {
+            0 => self.tabstop.max(1),
+            sw => sw,
+        }
+    }
+
+    /// Create the whitespace to fill from screen column `start` to screen column `end`, using
+    /// tabs where possible unless [IndentOptions::expandtab] is set.
+    pub fn whitespace(&self, start: usize, end: usize) -> String {
+        let mut s = String::new();
+
+        if end <= start {
+            return s;
+        }
+
+        let mut col = start;
+
+        if !self.expandtab && self.tabstop > 0 {
+            loop {
+                let next = (col / self.tabstop + 1) * self.tabstop;
+
+                if next > end {
+                    break;
+                }
+
+                s.push('\t');
+                col = next;
+            }
+        }
+
+        s.push_str(" ".repeat(end - col).as_str());
+        s
+    }
+}
+
+impl Default for IndentOptions {
+    fn default() -> Self {
+        IndentOptions {
+            shiftwidth: 8,
+            tabstop: 8,
+            expandtab: false,
+            shiftround: false,
+            autoindent: false,
+            smartindent: false,
+        }
+    }
+}
+
 /// This represents how to change a number in text.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum NumberChange {
@@ -952,6 +1127,16 @@ pub enum NumberChange {
     Increase(Count),
 }
 
+/// This represents what to insert in front of the text on each line of a block.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LinePrefix {
+    /// Insert whitespace as wide as the targeted block.
+    Blank,
+
+    /// Insert right-aligned line numbers followed by a space, counting up from [*n*](Count).
+    Number(Count),
+}
+
 /// Targets for [WindowAction::Open] and [WindowAction::Switch].
 ///
 /// [WindowAction::Open]: crate::editing::action::WindowAction::Open
@@ -1084,6 +1269,10 @@ impl From<usize> for Count {
 pub enum Mark {
     /// The position of the cursor in the current buffer when it last exited.
     ///
+    /// This gets updated whenever a window showing the buffer is closed, or gets replaced by
+    /// another window in the same part of the screen. Moving to another window through the jump
+    /// list doesn't update it.
+    ///
     /// For example, `'"` in Vim.
     BufferLastExited,
 
@@ -1316,6 +1505,7 @@ pub enum PositionList {
 
 /// This specifies the behaviour of entering and backspacing over characters.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum InsertStyle {
     /// This specifies that typed characters should leave existing ones as is, and backspacing
     /// should remove characters.
@@ -1324,6 +1514,12 @@ pub enum InsertStyle {
     /// This specifies that typed characters should replace existing ones, and backspacing should
     /// restore any overwritten characters.
     Replace,
+
+    /// This specifies that typed characters should replace the screen columns that they take up,
+    /// so that typing over a tab or a wide character doesn't move the text that follows. Typed
+    /// newlines are inserted without replacing anything, and backspacing restores overwritten
+    /// characters.
+    VirtualReplace,
 }
 
 /// A character.
@@ -1345,6 +1541,23 @@ impl From<char> for Char {
     }
 }
 
+/// Control when typing text into a buffer creates undo checkpoints on its own.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum CheckpointPolicy {
+    /// Only create checkpoints when a [HistoryAction::Checkpoint] is performed.
+    ///
+    /// [HistoryAction::Checkpoint]: crate::editing::action::HistoryAction::Checkpoint
+    #[default]
+    Explicit,
+
+    /// Create a checkpoint before the first character of each new word typed.
+    Word,
+
+    /// Create a checkpoint after every given number of characters typed.
+    Characters(usize),
+}
+
 /// Locations for temporarily storing text shared between buffers.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 #[non_exhaustive]
@@ -1367,9 +1580,25 @@ pub enum Register {
 
     /// Recently deleted text.
     ///
+    /// The index of the most recent deletion is 0, the second most recent deletion is 1, and so
+    /// on. These are the entries of the kill ring.
+    ///
     /// For example, `"[1-9]` in Vim.
     RecentlyDeleted(usize),
 
+    /// The entry in the kill ring that the yank pointer currently refers to.
+    ///
+    /// Putting text into this register adds a new entry to the front of the kill ring, or merges
+    /// it into the most recent one when appending or prepending.
+    ///
+    /// For example, what `C-y` inserts in Emacs.
+    KillRing,
+
+    /// The most recently killed or copied rectangle.
+    ///
+    /// For example, what `C-x r y` inserts in Emacs.
+    LastRectangle,
+
     /// Most recently deleted text that was shorted than a line.
     ///
     /// For example, `"-` in Vim.
@@ -1441,6 +1670,8 @@ impl Register {
             Register::Unnamed => false,
             Register::UnnamedMacro => false,
             Register::RecentlyDeleted(_) => false,
+            Register::KillRing => false,
+            Register::LastRectangle => false,
             Register::SmallDelete => false,
             Register::LastCommand => false,
             Register::LastInserted => false,
@@ -1574,6 +1805,10 @@ pub trait Wrappable {
 pub struct ViewportContext<Cursor> {
     /// The line and column offset into the buffer shown at the upper-left hand corner of the
     /// window.
+    ///
+    /// When lines are wrapped, the column is the index of the first character shown on the top
+    /// row. Otherwise, it's the screen column that the window starts at, after tabs and wide
+    /// characters have been laid out.
     pub corner: Cursor,
 
     /// Dimensions of the window.
@@ -1755,6 +1990,7 @@ impl std::ops::Not for InsertStyle {
         match self {
             InsertStyle::Insert => InsertStyle::Replace,
             InsertStyle::Replace => InsertStyle::Insert,
+            InsertStyle::VirtualReplace => InsertStyle::Insert,
         }
     }
 }
@@ -1774,6 +2010,7 @@ impl MoveType {
             MoveType::BufferLinePercent => false,
             MoveType::Column(_, _) => false,
             MoveType::FirstWord(_) => false,
+            MoveType::Fold(_) => false,
             MoveType::Line(_) => false,
             MoveType::LinePercent => false,
             MoveType::LinePos(_) => false,
@@ -1806,6 +2043,7 @@ impl MoveType {
             MoveType::Column(_, _) => false,
             MoveType::FinalNonBlank(_) => false,
             MoveType::FirstWord(_) => false,
+            MoveType::Fold(_) => false,
             MoveType::LineColumnOffset => false,
             MoveType::Line(_) => false,
             MoveType::LinePercent => false,
@@ -1826,6 +2064,7 @@ impl MoveType {
             MoveType::BufferLinePercent => TargetShape::LineWise,
             MoveType::BufferPos(_) => TargetShape::LineWise,
             MoveType::FirstWord(_) => TargetShape::LineWise,
+            MoveType::Fold(_) => TargetShape::LineWise,
             MoveType::Line(_) => TargetShape::LineWise,
             MoveType::ViewportPos(_) => TargetShape::LineWise,
             MoveType::SectionBegin(_) => TargetShape::LineWise,
@@ -1,17 +1,20 @@
 use std::borrow::Cow;
 use std::sync::Arc;
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::editing::{
     action::{EditInfo, EditResult},
     application::ApplicationInfo,
     base::{CompletionDisplay, CompletionScope, CompletionSelection, WordStyle},
     completion::{complete_path, CompletionList},
     context::EditContext,
-    cursor::Adjustable,
+    cursor::{Adjustable, Cursor},
+    rope::EditRope,
     store::Store,
 };
 
-use super::{CursorGroupIdContext, EditBuffer};
+use super::{CursorGroupId, CursorGroupIdContext, EditBuffer};
 
 pub trait CompletionActions<C, I>
 where
@@ -25,6 +28,22 @@ where
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I>;
 
+    fn complete_application(
+        &mut self,
+        selection: &CompletionSelection,
+        display: &CompletionDisplay,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I>;
+
+    fn complete_dictionary(
+        &mut self,
+        selection: &CompletionSelection,
+        display: &CompletionDisplay,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I>;
+
     fn complete_file(
         &mut self,
         selection: &CompletionSelection,
@@ -52,20 +71,54 @@ where
     ) -> EditResult<EditInfo, I>;
 }
 
-impl<'a, 'b, C, I> CompletionActions<CursorGroupIdContext<'a, 'b, C>, I> for EditBuffer<I>
-where
-    C: EditContext,
-    I: ApplicationInfo,
-{
-    fn complete_auto(
+/// Add any candidates that aren't already present, or the prefix itself, to the end of a list.
+fn merge_candidates(list: &mut Vec<String>, candidates: Vec<String>, prefix: &str) {
+    for candidate in candidates {
+        if candidate != prefix && !list.contains(&candidate) {
+            list.push(candidate);
+        }
+    }
+}
+
+/// Find the words in a buffer that start with `prefix`, ordered by how close they are to the
+/// word being completed: first those before it, searching backwards, and then those after it.
+fn nearest_words(text: &EditRope, start: &Cursor, cursor: &Cursor, prefix: &str) -> Vec<String> {
+    let before = text.slice(0.into(), text.cursor_to_offset(start), false);
+    let before = Cow::from(&before);
+    let after = text.slice(text.cursor_to_offset(cursor), text.len_offset(), false);
+    let after = Cow::from(&after);
+
+    let words = UnicodeSegmentation::unicode_words(before.as_ref())
+        .rev()
+        .chain(UnicodeSegmentation::unicode_words(after.as_ref()));
+
+    let mut list = Vec::new();
+
+    for word in words {
+        if word.len() > prefix.len() && word.starts_with(prefix) && !list.iter().any(|w| w == word)
+        {
+            list.push(word.to_string());
+        }
+    }
+
+    return list;
+}
+
+impl<I: ApplicationInfo> EditBuffer<I> {
+    /// Move through the completion candidates already being shown for a cursor group, or else
+    /// start completing the text between the cursor group leader and where `candidates` says the
+    /// text being completed begins.
+    fn _complete<F>(
         &mut self,
+        gid: CursorGroupId,
         selection: &CompletionSelection,
         display: &CompletionDisplay,
-        ctx: &CursorGroupIdContext<'a, 'b, C>,
         store: &mut Store<I>,
-    ) -> EditResult<EditInfo, I> {
-        let gid = ctx.0;
-
+        candidates: F,
+    ) -> EditResult<EditInfo, I>
+    where
+        F: FnOnce(&Self, &Store<I>, &Cursor) -> Option<(Cursor, Vec<String>)>,
+    {
         if !matches!(selection, CompletionSelection::None) {
             if let Some(list) = self.completions.get_mut(&gid) {
                 if let Some(val) = list.select(selection) {
@@ -78,13 +131,13 @@ where
         }
 
         let cursor = self.get_leader(gid);
-        let mut start = cursor.clone();
-        let list = I::complete(&self.text, &mut start, &self.id, store);
+        let (start, list) = match candidates(self, store, &cursor) {
+            Some(res) => res,
+            None => return Ok(None),
+        };
 
         if list.is_empty() {
-            let scope = CompletionScope::Global;
-
-            return self.complete_word(&scope, selection, display, ctx, store);
+            return Ok(None);
         }
 
         let so = self.text.cursor_to_offset(&start);
@@ -110,8 +163,32 @@ where
 
         Ok(None)
     }
+}
 
-    fn complete_file(
+impl<'a, 'b, C, I> CompletionActions<CursorGroupIdContext<'a, 'b, C>, I> for EditBuffer<I>
+where
+    C: EditContext,
+    I: ApplicationInfo,
+{
+    fn complete_auto(
+        &mut self,
+        selection: &CompletionSelection,
+        display: &CompletionDisplay,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        self.complete_application(selection, display, ctx, store)?;
+
+        if self.completions.contains_key(&ctx.0) {
+            return Ok(None);
+        }
+
+        let scope = CompletionScope::Global;
+
+        return self.complete_word(&scope, selection, display, ctx, store);
+    }
+
+    fn complete_application(
         &mut self,
         selection: &CompletionSelection,
         display: &CompletionDisplay,
@@ -131,10 +208,11 @@ where
             }
         }
 
+        self.completions.remove(&gid);
+
         let cursor = self.get_leader(gid);
         let mut start = cursor.clone();
-
-        let list = complete_path(&self.text, &mut start);
+        let list = I::complete(&self.text, &mut start, &self.id, store);
 
         if list.is_empty() {
             return Ok(None);
@@ -164,67 +242,69 @@ where
         Ok(None)
     }
 
-    fn complete_line(
+    fn complete_dictionary(
         &mut self,
-        scope: &CompletionScope,
         selection: &CompletionSelection,
         display: &CompletionDisplay,
         ctx: &CursorGroupIdContext<'a, 'b, C>,
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
-        let gid = ctx.0;
-
-        if !matches!(selection, CompletionSelection::None) {
-            if let Some(list) = self.completions.get_mut(&gid) {
-                if let Some(val) = list.select(selection) {
-                    let adjs = list.replace(&mut self.text, val);
-                    self._adjust_all(adjs, store);
-                }
+        self._complete(ctx.0, selection, display, store, |ebuf, store, cursor| {
+            let mut start = cursor.clone();
+            let prefix = ebuf.text.get_prefix_word_mut(&mut start, &WordStyle::Little)?;
+            let list = store.completions.complete_dictionary(Cow::from(&prefix).as_ref());
 
-                return Ok(None);
-            }
-        }
-
-        let cursor = self.get_leader(gid);
-        let mut start = cursor.clone();
-        start.x = 0;
-
-        let prefix = self.text.slice(
-            self.text.cursor_to_offset(&start),
-            self.text.cursor_to_offset(&cursor),
-            false,
-        );
-
-        let source = match scope {
-            CompletionScope::Buffer => &self.lines,
-            CompletionScope::Global => &store.completions.lines,
-        };
-
-        let prefix = Cow::from(&prefix);
-        let list = source.complete_line(prefix.as_ref());
-
-        if list.is_empty() {
-            return Ok(None);
-        }
-
-        let mut list = CompletionList {
-            prefix: prefix.into_owned(),
-            candidates: Arc::new(list),
-            selected: None,
-            display: display.clone(),
-            cursor,
-            start,
-        };
+            Some((start, list))
+        })
+    }
 
-        if let Some(val) = list.select(selection) {
-            let adjs = list.replace(&mut self.text, val);
-            list.cursor.adjust(&adjs);
-            self._adjust_all(adjs, store);
-        }
+    fn complete_file(
+        &mut self,
+        selection: &CompletionSelection,
+        display: &CompletionDisplay,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        self._complete(ctx.0, selection, display, store, |ebuf, _, cursor| {
+            let mut start = cursor.clone();
+            let list = complete_path(&ebuf.text, &mut start);
 
-        self.completions.insert(gid, list);
+            Some((start, list))
+        })
+    }
 
-        Ok(None)
+    fn complete_line(
+        &mut self,
+        scope: &CompletionScope,
+        selection: &CompletionSelection,
+        display: &CompletionDisplay,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        self._complete(ctx.0, selection, display, store, |ebuf, store, cursor| {
+            let mut start = cursor.clone();
+            start.x = 0;
+
+            let prefix = ebuf.text.slice(
+                ebuf.text.cursor_to_offset(&start),
+                ebuf.text.cursor_to_offset(cursor),
+                false,
+            );
+
+            let prefix = Cow::from(&prefix);
+            let list = match scope {
+                CompletionScope::Buffer => ebuf.lines.complete_line(prefix.as_ref()),
+                CompletionScope::Global => store.completions.lines.complete_line(prefix.as_ref()),
+                CompletionScope::Nearest => {
+                    let mut list = ebuf.lines.complete_line(prefix.as_ref());
+                    let global = store.completions.lines.complete_line(prefix.as_ref());
+                    merge_candidates(&mut list, global, prefix.as_ref());
+                    list
+                },
+            };
+
+            Some((start, list))
+        })
     }
 
     fn complete_word(
@@ -235,57 +315,29 @@ where
         ctx: &CursorGroupIdContext<'a, 'b, C>,
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
-        let gid = ctx.0;
-
-        if !matches!(selection, CompletionSelection::None) {
-            if let Some(list) = self.completions.get_mut(&gid) {
-                if let Some(val) = list.select(selection) {
-                    let adjs = list.replace(&mut self.text, val);
-                    self._adjust_all(adjs, store);
-                }
-
-                return Ok(None);
-            }
-        }
-
-        let cursor = self.get_leader(gid);
-        let mut start = cursor.clone();
-
-        let prefix = match self.text.get_prefix_word_mut(&mut start, &WordStyle::Big) {
-            None => return Ok(None),
-            Some(p) => p,
-        };
-
-        let source = match scope {
-            CompletionScope::Buffer => &self.lines,
-            CompletionScope::Global => &store.completions.lines,
-        };
-
-        let prefix = Cow::from(&prefix);
-        let list = source.complete_word(prefix.as_ref());
-
-        if list.is_empty() {
-            return Ok(None);
-        }
-
-        let mut list = CompletionList {
-            prefix: prefix.into_owned(),
-            candidates: Arc::new(list),
-            selected: None,
-            display: display.clone(),
-            cursor,
-            start,
-        };
-
-        if let Some(val) = list.select(selection) {
-            let adjs = list.replace(&mut self.text, val);
-            list.cursor.adjust(&adjs);
-            self._adjust_all(adjs, store);
-        }
-
-        self.completions.insert(gid, list);
-
-        Ok(None)
+        self._complete(ctx.0, selection, display, store, |ebuf, store, cursor| {
+            let mut start = cursor.clone();
+
+            let style = match scope {
+                CompletionScope::Nearest => WordStyle::Little,
+                _ => WordStyle::Big,
+            };
+
+            let prefix = ebuf.text.get_prefix_word_mut(&mut start, &style)?;
+            let prefix = Cow::from(&prefix);
+            let list = match scope {
+                CompletionScope::Buffer => ebuf.lines.complete_word(prefix.as_ref()),
+                CompletionScope::Global => store.completions.lines.complete_word(prefix.as_ref()),
+                CompletionScope::Nearest => {
+                    let mut list = nearest_words(&ebuf.text, &start, cursor, prefix.as_ref());
+                    let global = store.completions.lines.complete_word(prefix.as_ref());
+                    merge_candidates(&mut list, global, prefix.as_ref());
+                    list
+                },
+            };
+
+            Some((start, list))
+        })
     }
 }
 
@@ -421,6 +473,81 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 7));
     }
 
+    #[test]
+    fn test_complete_application() {
+        let mut ebuf = EditBuffer::new("".to_string());
+        let gid = ebuf.create_group();
+        let vwctx = ViewportContext::default();
+        let vctx = VimContext::<TestInfo>::default();
+        let mut store = Store::<TestInfo>::default();
+        let next = MoveDir1D::Next;
+
+        // Type "p", and complete to "pressed".
+        type_char!(ebuf, 'p', gid, vwctx, vctx, store);
+        ebuf.complete_application(
+            &CompletionSelection::List(next),
+            &CompletionDisplay::None,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        )
+        .unwrap();
+        assert_eq!(ebuf.get_text(), "pressed\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 7));
+
+        // Type " xylo x", with a checkpoint so that "xylo" can be completed as a word.
+        ebuf.completions.remove(&gid);
+
+        for c in " xylo".chars() {
+            type_char!(ebuf, c, gid, vwctx, vctx, store);
+        }
+
+        ebuf.checkpoint(ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        type_char!(ebuf, ' ', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'x', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "pressed xylo x\n");
+
+        // The application has nothing for "x", and we don't fall back to word completion.
+        ebuf.complete_application(
+            &CompletionSelection::List(next),
+            &CompletionDisplay::None,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        )
+        .unwrap();
+        assert_eq!(ebuf.get_text(), "pressed xylo x\n");
+        assert!(!ebuf.completions.contains_key(&gid));
+
+        // Automatic completion does fall back to words.
+        ebuf.complete_auto(
+            &CompletionSelection::List(next),
+            &CompletionDisplay::None,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        )
+        .unwrap();
+        assert_eq!(ebuf.get_text(), "pressed xylo xylo\n");
+    }
+
+    #[test]
+    fn test_complete_all() {
+        let mut ebuf = EditBuffer::new("".to_string());
+        let gid = ebuf.create_group();
+        let vwctx = ViewportContext::default();
+        let vctx = VimContext::<TestInfo>::default();
+        let mut store = Store::<TestInfo>::default();
+
+        // Insert every candidate at once.
+        ebuf.complete_auto(
+            &CompletionSelection::All,
+            &CompletionDisplay::None,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        )
+        .unwrap();
+        assert_eq!(ebuf.get_text(), "dressed pressed\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 15));
+    }
+
     #[test]
     fn test_complete_file() {
         // First, create temporary and files to complete.
@@ -704,4 +831,68 @@ mod tests {
         assert_eq!(ebuf1.get_text(), "foo baz\nbar\n");
         assert_eq!(ebuf1.get_leader(gid), Cursor::new(0, 7));
     }
+
+    #[test]
+    fn test_complete_word_nearest() {
+        let (mut ebuf1, gid, vwctx, vctx, mut store) = mkfivestr("bar\nbaz b\nbat bar\n");
+        let mut ebuf2 = mkbuf();
+        ebuf2.set_text("foo bam bat\n");
+        ebuf2.checkpoint(ctx!(gid, vwctx, vctx), &mut store).unwrap();
+
+        let next = MoveDir1D::Next;
+        ebuf1.set_leader(gid, Cursor::new(1, 5));
+
+        // Words before the cursor come first, starting with the closest, then words after it,
+        // and then words from other buffers.
+        for word in ["baz", "bar", "bat", "bam", "b"] {
+            ebuf1
+                .complete_word(
+                    &CompletionScope::Nearest,
+                    &CompletionSelection::List(next),
+                    &CompletionDisplay::None,
+                    ctx!(gid, vwctx, vctx),
+                    &mut store,
+                )
+                .unwrap();
+            assert_eq!(ebuf1.get_text(), format!("bar\nbaz {word}\nbat bar\n"));
+            assert_eq!(ebuf1.get_leader(gid), Cursor::new(1, 4 + word.len()));
+        }
+    }
+
+    #[test]
+    fn test_complete_dictionary() {
+        let tmp = TempDir::new().unwrap();
+        let words = tmp.child("words");
+        std::fs::write(words.as_path(), "apple\napply\nbanana\n").unwrap();
+
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("an app\n");
+        store.completions.load_dictionary(words.as_path()).unwrap();
+
+        let next = MoveDir1D::Next;
+        ebuf.set_leader(gid, Cursor::new(0, 6));
+
+        for word in ["apple", "apply", "app"] {
+            ebuf.complete_dictionary(
+                &CompletionSelection::List(next),
+                &CompletionDisplay::None,
+                ctx!(gid, vwctx, vctx),
+                &mut store,
+            )
+            .unwrap();
+            assert_eq!(ebuf.get_text(), format!("an {word}\n"));
+            assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 3 + word.len()));
+        }
+
+        // Words not in the dictionary aren't completed.
+        ebuf.completions.remove(&gid);
+        ebuf.set_leader(gid, Cursor::new(0, 2));
+        ebuf.complete_dictionary(
+            &CompletionSelection::List(next),
+            &CompletionDisplay::None,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        )
+        .unwrap();
+        assert_eq!(ebuf.get_text(), "an app\n");
+    }
 }
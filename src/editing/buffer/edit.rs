@@ -1,5 +1,7 @@
 use std::borrow::Cow;
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::editing::{
     action::EditResult,
     application::ApplicationInfo,
@@ -11,6 +13,8 @@ use crate::editing::{
         IndentChange,
         InsertStyle,
         JoinStyle,
+        LinePrefix,
+        MoveDir1D,
         NumberChange,
         Radix,
         Register,
@@ -18,8 +22,8 @@ use crate::editing::{
         WordStyle,
     },
     context::EditContext,
-    cursor::{Adjustable, Cursor, CursorChoice},
-    rope::EditRope,
+    cursor::{block_cursors, Adjustable, Cursor, CursorAdjustment, CursorChoice},
+    rope::{layout::cell_width, CharOff, EditRope},
     store::{RegisterCell, RegisterPutFlags, Store},
 };
 
@@ -63,6 +67,7 @@ where
     fn format(
         &mut self,
         range: &CursorRange,
+        cursor: &Cursor,
         ctx: &C,
         store: &mut Store<I>,
     ) -> EditResult<CursorChoice, I>;
@@ -88,11 +93,27 @@ where
         &mut self,
         change: &IndentChange,
         range: &CursorRange,
+        cursor: &Cursor,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<CursorChoice, I>;
+
+    fn prefix(
+        &mut self,
+        prefix: &LinePrefix,
+        range: &CursorRange,
         ctx: &C,
         store: &mut Store<I>,
     ) -> EditResult<CursorChoice, I>;
 }
 
+fn append_flag(dir: MoveDir1D) -> RegisterPutFlags {
+    match dir {
+        MoveDir1D::Next => RegisterPutFlags::APPEND,
+        MoveDir1D::Previous => RegisterPutFlags::PREPEND,
+    }
+}
+
 impl<'a, 'b, 'c, C, I> EditActions<CursorMovementsContext<'a, 'b, 'c, Cursor, C>, I>
     for EditBuffer<I>
 where
@@ -122,9 +143,11 @@ where
 
             let tlines = text.get_lines();
             let tlen = text.len() as isize;
+            let tail = text.len() - usize::from(text.offset_of_line(tlines));
             let lstart = self.text.line_of_offset(start);
+            let xstart = self.text.offset_to_cursor(start).x;
 
-            deleted = text + deleted;
+            let mut amt_col = -tlen;
 
             match style {
                 InsertStyle::Insert => {
@@ -136,14 +159,47 @@ where
 
                     self.text = prefix + restore + suffix;
                 },
+                InsertStyle::VirtualReplace => {
+                    // Put back exactly what each typed character replaced.
+                    let (restore, extra) = self._restore_overwritten(start, &text);
+                    let suffix = suffix.split(0.into(), extra.into(), false).2;
+
+                    amt_col = restore.len() as isize - tlen - extra as isize;
+                    self.text = prefix + restore + suffix;
+                },
             }
 
+            deleted = text + deleted;
+
             if tlines == 0 {
                 let cstart = self.text.offset_to_cursor(start);
-                self._adjust_columns(cstart.y, cstart.x, 0, -tlen, store);
+                self._adjust_columns(cstart.y, cstart.x, 0, amt_col, store);
             } else {
-                let lend = lstart.saturating_add(tlines - 1);
-                self._adjust_lines(lstart, lend, isize::MAX, -(tlines as isize), store);
+                /*
+                 * Whatever follows the deleted text on its last line gets joined onto the line
+                 * where the deletion started. If the deletion started in the middle of that line,
+                 * then it survives, and only the lines after it are deleted.
+                 */
+                let lend = lstart.saturating_add(tlines);
+                let amt = -(tlines as isize);
+
+                let (first, amt_line, amt_col) = if xstart > 0 {
+                    (lstart + 1, amt, xstart as isize - tail as isize)
+                } else {
+                    (lstart, 0, -(tail as isize))
+                };
+
+                let adjs = vec![
+                    CursorAdjustment::Column { line: lend, column_start: tail, amt_line, amt_col },
+                    CursorAdjustment::Line {
+                        line_start: first,
+                        line_end: first + tlines - 1,
+                        amount: isize::MAX,
+                        amount_after: amt,
+                    },
+                ];
+
+                self._adjust_all(adjs, store);
             }
 
             coff = start;
@@ -156,7 +212,7 @@ where
         let mut flags = RegisterPutFlags::DELETE;
 
         if ctx.context.get_register_append() {
-            flags |= RegisterPutFlags::APPEND
+            flags |= append_flag(ctx.context.get_register_append_dir());
         }
 
         store.registers.put(&register, cell, flags)?;
@@ -198,7 +254,7 @@ where
         let mut flags = RegisterPutFlags::NONE;
 
         if ctx.context.get_register_append() {
-            flags |= RegisterPutFlags::APPEND;
+            flags |= append_flag(ctx.context.get_register_append_dir());
         }
 
         store.registers.put(&register, cell, flags)?;
@@ -227,40 +283,107 @@ where
     fn replace(
         &mut self,
         c: char,
-        _virt: bool,
+        virt: bool,
         range: &CursorRange,
         ctx: &CursorMovementsContext<'a, 'b, 'c, Cursor, C>,
-        _: &mut Store<I>,
+        store: &mut Store<I>,
     ) -> EditResult<CursorChoice, I> {
         let (_, ranges) = self._effective(range, ctx.context.get_target_shape());
         let mut cursor = None;
 
+        if virt {
+            /*
+             * Virtual replacement overwrites the screen columns that the replacement characters
+             * take up, one for each character on each line of the range, the same way that
+             * typing them in InsertStyle::VirtualReplace would.
+             */
+            let tabstop = self.indent_options.tabstop;
+            let mut last: Option<Cursor> = None;
+
+            for (start, end, inclusive) in ranges.into_iter().rev() {
+                let text = self.text.slice(start, end, inclusive).to_string();
+                let mut soff = usize::from(start) + text.chars().count();
+
+                for line in text.rsplit('\n') {
+                    let n = line.chars().count();
+                    soff -= n;
+
+                    if n > 0 {
+                        let lcursor = self.text.offset_to_cursor(soff.into());
+                        let rep = EditRope::from(c.to_string().repeat(n));
+                        let dir = MoveDir1D::Previous;
+                        let (_, adjs) = self.text.vreplace(&lcursor, dir, rep, tabstop);
+
+                        if let Some(ref mut cursor) = last {
+                            cursor.adjust(adjs.as_slice());
+                        } else {
+                            last = Some(self.text.offset_to_cursor((soff + n - 1).into()));
+                        }
+
+                        self._adjust_all(adjs, store);
+                    }
+
+                    soff = soff.saturating_sub(1);
+                }
+            }
+
+            return Ok(last.map(CursorChoice::Single).unwrap_or_default());
+        }
+
         // XXX: if this is a blockwise replace, then whitespace needs to be split into individual
         // spaces first, and then replaced.
 
         for (start, end, inclusive) in ranges.into_iter().rev() {
-            self.text = self.text.transform(start, end, inclusive, |r| {
-                let s: String = r.to_string();
-                let n: String =
-                    s.chars().map(|i| if i == '\n' || i == '\r' { i } else { c }).collect();
-                return EditRope::from(n);
-            });
+            // Each grapheme cluster gets replaced by a single character.
+            let s = self.text.slice(start, end, inclusive).to_string();
+            let mut n = String::new();
+            let mut adjs = vec![];
+            let mut off = usize::from(start);
+
+            for g in s.graphemes(true) {
+                let glen = g.chars().count();
+                off += glen;
+
+                match g {
+                    "\n" | "\r" | "\r\n" => n.push_str(g),
+                    _ => {
+                        n.push(c);
+
+                        if glen > 1 {
+                            // Shift what comes after the cluster on its line to the left.
+                            let gend = self.text.offset_to_cursor(off.into());
+
+                            adjs.push(CursorAdjustment::Column {
+                                line: gend.y,
+                                column_start: gend.x,
+                                amt_line: 0,
+                                amt_col: 1 - glen as isize,
+                            });
+                        }
+                    },
+                }
+            }
+
+            let nlen = n.chars().count();
+
+            self.text = self.text.transform(start, end, inclusive, |_| EditRope::from(n.as_str()));
+
+            // Adjust from the end of the range backwards, so that each column is still accurate.
+            adjs.reverse();
+            self._adjust_all(adjs, store);
 
             /*
              * Unlike most operations, character replacement puts the cursor on the final character
              * in the affected range, and not immediately after it. This allows the cursor to stay
              * in place when doing a single character replacement (e.g. "ra").
              */
-            let _ = if inclusive || end == 0.into() {
-                cursor.get_or_insert(end)
-            } else {
-                cursor.get_or_insert(end - 1.into())
-            };
+            if cursor.is_none() {
+                let off = start + nlen.saturating_sub(1).into();
+                cursor = Some(self.text.offset_to_cursor(off));
+            }
         }
 
-        let choice = cursor
-            .map(|off| self.text.offset_to_cursor(off).into())
-            .unwrap_or_default();
+        let choice = cursor.map(CursorChoice::from).unwrap_or_default();
 
         Ok(choice)
     }
@@ -312,28 +435,130 @@ where
 
     fn indent(
         &mut self,
-        _: &IndentChange,
-        _: &CursorRange,
-        _: &CursorMovementsContext<'a, 'b, 'c, Cursor, C>,
-        _: &mut Store<I>,
+        change: &IndentChange,
+        range: &CursorRange,
+        cursor: &Cursor,
+        ctx: &CursorMovementsContext<'a, 'b, 'c, Cursor, C>,
+        store: &mut Store<I>,
     ) -> EditResult<CursorChoice, I> {
-        // XXX: implement (:help <, :help >, :help v_b_<, :help v_b_>)
+        let sw = self.indent_options.shift_width();
+        let shape = ctx.context.get_target_shape().unwrap_or(range.shape);
 
-        return Ok(CursorChoice::Empty);
+        /*
+         * Blocks get shifted starting from their left edge, while other shapes shift the
+         * indentation at the start of each line.
+         */
+        let (mut start, mut end) = match shape {
+            TargetShape::BlockWise => block_cursors(&range.start, &range.end),
+            TargetShape::CharWise | TargetShape::LineWise => {
+                (Cursor::new(range.start.y, 0), Cursor::new(range.end.y, 0))
+            },
+        };
+        let round = self.indent_options.shiftround && shape != TargetShape::BlockWise;
+        let left = start.x;
+        let mut cursor = cursor.clone();
+
+        for line in start.y..=end.y {
+            // Lines that end before the shift starts are left alone.
+            if self.text.get_columns(line) <= left {
+                continue;
+            }
+
+            let (soff, eoff, col, width) = self._whitespace_at(line, left);
+            let width = match change {
+                IndentChange::Auto => self._auto_indent(line),
+                IndentChange::Increase(count) => {
+                    let amt = ctx.context.resolve(count).saturating_mul(sw);
+                    let width = if round { width / sw * sw } else { width };
+
+                    width.saturating_add(amt)
+                },
+                IndentChange::Decrease(count) => {
+                    let amt = ctx.context.resolve(count).saturating_mul(sw);
+                    let width = if round {
+                        (width + sw - 1) / sw * sw
+                    } else {
+                        width
+                    };
+
+                    width.saturating_sub(amt)
+                },
+            };
+
+            let ws = self.indent_options.whitespace(col, col + width);
+
+            if self.text.slice(soff, eoff, false).to_string() == ws {
+                continue;
+            }
+
+            let (_, adjs) = self.text.replace(soff, eoff, false, ws.into());
+
+            cursor.adjust(&adjs);
+            start.adjust(&adjs);
+            end.adjust(&adjs);
+            self._adjust_all(adjs, store);
+        }
+
+        // While typing, the cursor stays on the same character.
+        let default = if ctx.context.get_insert_style().is_some() {
+            cursor
+        } else if shape == TargetShape::BlockWise {
+            start.clone()
+        } else {
+            self.text.first_word(&start, ctx)
+        };
+
+        return Ok(CursorChoice::Range(start, end, default));
     }
 
     fn format(
         &mut self,
-        _: &CursorRange,
-        _: &CursorMovementsContext<'a, 'b, 'c, Cursor, C>,
-        _: &mut Store<I>,
+        range: &CursorRange,
+        cursor: &Cursor,
+        ctx: &CursorMovementsContext<'a, 'b, 'c, Cursor, C>,
+        store: &mut Store<I>,
     ) -> EditResult<CursorChoice, I> {
         /*
-         * Automatically formatting lines requires a whole lot of logic that just doesn't exist
-         * in this codebase yet. At some point, if some kind of filetype detection is added, then
-         * this function can be made to do something useful.
+         * Formatting is always done on whole lines, which get filled as paragraphs of words. Any
+         * language-specific formatting would need some kind of filetype detection first.
          */
-        return Ok(CursorChoice::Empty);
+        let width = ctx.context.get_text_width();
+        let mut cursor = cursor.clone();
+        let mut end = Cursor::new(range.end.y, 0);
+        let mut line = range.end.y.min(self.text.last().y);
+
+        loop {
+            // Find the next paragraph, working backwards so that earlier lines are unaffected.
+            while line > range.start.y && self.text.is_blank_line(line) {
+                line -= 1;
+            }
+
+            if self.text.is_blank_line(line) {
+                break;
+            }
+
+            let pend = line;
+
+            while line > range.start.y && !self.text.is_blank_line(line - 1) {
+                line -= 1;
+            }
+
+            for adjs in self._fill(line, pend, width) {
+                cursor.adjust(&adjs);
+                end.adjust(&adjs);
+                self._adjust_all(adjs, store);
+            }
+
+            if line <= range.start.y {
+                break;
+            }
+
+            line -= 1;
+        }
+
+        let start = Cursor::new(range.start.y, 0);
+
+        return Ok(CursorChoice::Range(start, end, cursor));
     }
 
     fn changenum(
@@ -468,6 +693,222 @@ where
 
         return Ok(cursor.map(CursorChoice::Single).unwrap_or_default());
     }
+
+    fn prefix(
+        &mut self,
+        prefix: &LinePrefix,
+        range: &CursorRange,
+        ctx: &CursorMovementsContext<'a, 'b, 'c, Cursor, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<CursorChoice, I> {
+        // Prefixing is always forced into a BlockWise movement.
+        let (lc, rc) = block_cursors(&range.start, &range.end);
+        let lines = lc.y..=rc.y;
+
+        let text = match prefix {
+            LinePrefix::Blank => {
+                let blank = " ".repeat(rc.x - lc.x + 1);
+
+                // Lines that end before the block starts are left alone.
+                lines
+                    .map(|y| {
+                        if self.text.get_columns(y) > lc.x {
+                            blank.as_str()
+                        } else {
+                            ""
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+            LinePrefix::Number(count) => {
+                let first = ctx.context.resolve(count);
+                let last = first.saturating_add(rc.y - lc.y);
+                let width = last.to_string().len();
+
+                (first..=last)
+                    .map(|n| format!("{n:>width$} "))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            },
+        };
+
+        let (choice, adjs) = self.text.insert_block(&lc, text.into());
+        self._adjust_all(adjs, store);
+
+        return Ok(choice);
+    }
+}
+
+impl<I> EditBuffer<I>
+where
+    I: ApplicationInfo,
+{
+    /// Find the run of spaces and tabs that starts at column `x` of `line`, and return its start
+    /// and end offsets, the screen column it starts at, and how many screen columns it spans.
+    fn _whitespace_at(&self, line: usize, x: usize) -> (CharOff, CharOff, usize, usize) {
+        let tabstop = self.indent_options.tabstop;
+        let start = self.text.offset_of_line(line) + CharOff::from(x);
+        let len = self.text.chars(start).take_while(|c| matches!(c, ' ' | '\t')).count();
+        let end = start + CharOff::from(len);
+
+        let scol = self.text._virtual_column(start, tabstop);
+        let ecol = self.text._virtual_column(end, tabstop);
+
+        (start, end, scol, ecol - scol)
+    }
+
+    /// The number of screen columns that a line is indented by.
+    fn _indent_width(&self, line: usize) -> usize {
+        self._whitespace_at(line, 0).3
+    }
+
+    /// Whether a line ends with a character that opens an indented block.
+    fn _opens_block(&self, line: usize) -> bool {
+        let text = self.text.get_line(line).map(|l| l.to_string()).unwrap_or_default();
+
+        matches!(text.trim_end().chars().last(), Some('{' | '[' | '(' | ':'))
+    }
+
+    /// Whether a line starts with a character that closes an indented block.
+    fn _closes_block(&self, line: usize) -> bool {
+        let text = self.text.get_line(line).map(|l| l.to_string()).unwrap_or_default();
+
+        matches!(text.trim_start().chars().next(), Some('}' | ']' | ')'))
+    }
+
+    /// Determine how far to indent a line based on the nearest non-blank line above it.
+    fn _auto_indent(&self, line: usize) -> usize {
+        let sw = self.indent_options.shift_width();
+        let mut width = 0;
+
+        if let Some(prev) = (0..line).rev().find(|l| !self.text.is_blank_line(*l)) {
+            width = self._indent_width(prev);
+
+            if self._opens_block(prev) {
+                width += sw;
+            }
+        }
+
+        if self._closes_block(line) {
+            width = width.saturating_sub(sw);
+        }
+
+        width
+    }
+
+    /// The indentation that a new line opened next to `line`, in direction `dir`, should start
+    /// with, if the buffer's options call for any.
+    pub(super) fn _new_line_indent(&self, line: usize, dir: MoveDir1D) -> Option<String> {
+        let opts = &self.indent_options;
+
+        if !opts.autoindent && !opts.smartindent {
+            return None;
+        }
+
+        let nested = match dir {
+            MoveDir1D::Next => self._opens_block(line),
+            MoveDir1D::Previous => self._closes_block(line),
+        };
+        let mut width = self._indent_width(line);
+
+        if opts.smartindent && nested {
+            width += opts.shift_width();
+        }
+
+        Some(opts.whitespace(0, width))
+    }
+
+    /// Refill the words on lines `start` through `end` so that they fit within `width` columns,
+    /// and return the cursor adjustments for each replacement in the order they were made.
+    fn _fill(&mut self, start: usize, end: usize, width: usize) -> Vec<Vec<CursorAdjustment>> {
+        let indent = |line: usize| -> String {
+            let off = self.text.offset_of_line(line);
+
+            self.text.chars(off).take_while(|c| matches!(c, ' ' | '\t')).collect()
+        };
+
+        // The first line keeps its indentation, and the rest use the second line's.
+        let first = indent(start);
+        let rest = if end > start {
+            indent(start + 1)
+        } else {
+            first.clone()
+        };
+
+        let mut words: Vec<(usize, usize)> = vec![];
+
+        for line in start..=end {
+            let loff = usize::from(self.text.offset_of_line(line));
+            let columns = self.text.get_columns(line);
+            let mut wstart = None;
+
+            for (x, c) in self.text.chars(loff.into()).take(columns).enumerate() {
+                if !c.is_whitespace() {
+                    wstart.get_or_insert(x);
+                } else if let Some(ws) = wstart.take() {
+                    words.push((loff + ws, loff + x));
+                }
+            }
+
+            if let Some(ws) = wstart {
+                words.push((loff + ws, loff + columns));
+            }
+        }
+
+        let (fword, words) = match words.split_first() {
+            Some(split) => split,
+            None => return vec![],
+        };
+
+        // Measure text by the screen columns it takes up when it starts at column `col`.
+        let tabstop = self.indent_options.tabstop;
+        let advance = |col: usize, s: &str| -> usize {
+            s.chars().fold(col, |col, c| col + cell_width(c, col, tabstop))
+        };
+        let word = |(wstart, wend): (usize, usize)| -> String {
+            self.text.slice(wstart.into(), wend.into(), false).to_string()
+        };
+
+        let mut col = advance(advance(0, &first), &word(*fword));
+        let mut prev = fword.1;
+        let mut gaps = Vec::with_capacity(words.len() + 1);
+
+        for (wstart, wend) in words.iter() {
+            let w = word((*wstart, *wend));
+            let next = advance(col + 1, &w);
+
+            if next <= width {
+                gaps.push((prev, *wstart, Cow::Borrowed(" ")));
+                col = next;
+            } else {
+                gaps.push((prev, *wstart, Cow::Owned(format!("\n{rest}"))));
+                col = advance(advance(0, &rest), &w);
+            }
+
+            prev = *wend;
+        }
+
+        // Remove any trailing whitespace.
+        let lend = usize::from(self.text.offset_of_line(end)) + self.text.get_columns(end);
+        gaps.push((prev, lend, Cow::Borrowed("")));
+
+        let mut res = vec![];
+
+        for (gstart, gend, sep) in gaps.into_iter().rev() {
+            let gap = self.text.slice(gstart.into(), gend.into(), false);
+
+            if Cow::from(&gap) == sep {
+                continue;
+            }
+
+            let (_, adjs) =
+                self.text.replace(gstart.into(), gend.into(), false, sep.as_ref().into());
+            res.push(adjs);
+        }
+
+        return res;
+    }
 }
 
 #[cfg(test)]
@@ -475,7 +916,8 @@ mod tests {
     use super::super::tests::*;
     use super::*;
 
-    use crate::editing::base::CursorEnd;
+    use crate::editing::base::{CursorEnd, IndentOptions};
+    use crate::editing::lineinfo::LineDeletePolicy;
 
     macro_rules! get_reg {
         ($store: expr, $reg: expr) => {
@@ -522,6 +964,102 @@ mod tests {
         assert_eq!(ebuf.get_leader(curid), Cursor::new(1, 8));
     }
 
+    #[test]
+    fn test_graphemes_delete_replace() {
+        let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr(
+            "e\u{301}\u{1F1FA}\u{1F1F8}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1100}\u{1161}\u{11A8}x",
+        );
+
+        // "x" deletes the whole combining sequence.
+        let mov = MoveType::Column(MoveDir1D::Next, false);
+        edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(
+            ebuf.get_text(),
+            "\u{1F1FA}\u{1F1F8}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1100}\u{1161}\u{11A8}x\n"
+        );
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 0));
+
+        // "2r!" replaces the flag and the ZWJ sequence with one character each.
+        vctx.action.replace = Some('!'.into());
+        edit!(ebuf, EditAction::Replace(false), mv!(mov, 2), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "!!\u{1100}\u{1161}\u{11A8}x\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 1));
+
+        // "r?" replaces the Hangul syllable's jamo with one character.
+        ebuf.set_leader(curid, Cursor::new(0, 2));
+        vctx.action.replace = Some('?'.into());
+        edit!(ebuf, EditAction::Replace(false), mv!(mov), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "!!?x\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+
+        // Backspacing in Insert mode removes a whole cluster.
+        ebuf.set_text("ab\u{1100}\u{1161}\n");
+        ebuf.set_leader(curid, Cursor::new(0, 4));
+        vctx.persist.insert = Some(InsertStyle::Insert);
+
+        let mov = MoveType::Column(MoveDir1D::Previous, true);
+        edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "ab\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+    }
+
+    #[test]
+    fn test_graphemes_replace_adjusts_marks() {
+        let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr("e\u{301}bc\nd\u{301}ef\n");
+
+        // Mark the "c" after the combining sequence.
+        ebuf.set_leader(curid, Cursor::new(0, 3));
+        ebuf.mark(mark!('a'), ctx!(curid, vwctx, vctx), &mut store).unwrap();
+
+        // "r!" replaces the two characters of the sequence with one, and the mark follows "c".
+        ebuf.set_leader(curid, Cursor::new(0, 0));
+        vctx.action.replace = Some('!'.into());
+        let mov = MoveType::Column(MoveDir1D::Next, false);
+        edit!(ebuf, EditAction::Replace(false), mv!(mov), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "!bc\nd\u{301}ef\n");
+        assert_eq!(store.cursors.get_mark("".to_string(), mark!('a')).unwrap(), Cursor::new(0, 2));
+
+        // Replacing over several lines adjusts each line separately.
+        ebuf.set_leader(curid, Cursor::new(1, 3));
+        ebuf.mark(mark!('b'), ctx!(curid, vwctx, vctx), &mut store).unwrap();
+
+        ebuf.set_leader(curid, Cursor::new(0, 0));
+        vctx.action.replace = Some('?'.into());
+        let mov = MoveType::Line(MoveDir1D::Next);
+        vctx.persist.shape = Some(TargetShape::LineWise);
+        edit!(ebuf, EditAction::Replace(false), mv!(mov), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "???\n???\n");
+        assert_eq!(store.cursors.get_mark("".to_string(), mark!('a')).unwrap(), Cursor::new(0, 2));
+        assert_eq!(store.cursors.get_mark("".to_string(), mark!('b')).unwrap(), Cursor::new(1, 2));
+    }
+
+    #[test]
+    fn test_replace_virtual() {
+        let (mut ebuf, curid, vwctx, mut vctx, mut store) =
+            mkfivestr("\tabc\nab\tcdefgh\n日本語\n");
+
+        // 3gr! only eats into the tab.
+        let mov = MoveType::Column(MoveDir1D::Next, false);
+        vctx.action.replace = Some('!'.into());
+        edit!(ebuf, EditAction::Replace(true), mv!(mov, 3), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "!!!\tabc\nab\tcdefgh\n日本語\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+
+        // 8gr? uses up the whole tab.
+        ebuf.set_leader(curid, Cursor::new(1, 0));
+        vctx.action.replace = Some('?'.into());
+        edit!(ebuf, EditAction::Replace(true), mv!(mov, 8), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "!!!\tabc\n????????cdefgh\n日本語\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(1, 7));
+
+        // gr- replaces half of a wide character, and pads the rest of its width.
+        ebuf.set_leader(curid, Cursor::new(2, 1));
+        vctx.action.replace = Some('-'.into());
+        edit!(ebuf, EditAction::Replace(true), mv!(mov, 1), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "!!!\tabc\n????????cdefgh\n日- 語\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(2, 1));
+    }
+
     #[test]
     fn test_yank() {
         let (mut ebuf, curid, vwctx, mut vctx, mut store) =
@@ -728,6 +1266,78 @@ mod tests {
         assert_eq!(ebuf.get_text(), "hel\na b c d e f\n\n\n1 2 3 4 5 6\n");
     }
 
+    #[test]
+    fn test_delete_line_info() {
+        let (mut ebuf, curid, vwctx, vctx, mut store) = mkfivestr("a\nb\nc\nd\ne\n");
+
+        for line in 0..5 {
+            ebuf.set_line_info(line, line);
+        }
+
+        // Deleting a line drops its annotation, and moves the ones after it up.
+        ebuf.set_leader(curid, Cursor::new(1, 0));
+        let rt = range!(RangeType::Line);
+        edit!(ebuf, EditAction::Delete, rt, ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "a\nc\nd\ne\n");
+        assert_eq!(ebuf.get_line_info::<usize>(0), Some(&0));
+        assert_eq!(ebuf.get_line_info::<usize>(1), Some(&2));
+        assert_eq!(ebuf.get_line_info::<usize>(2), Some(&3));
+        assert_eq!(ebuf.get_line_info::<usize>(3), Some(&4));
+        assert_eq!(ebuf.get_line_info::<usize>(4), None);
+
+        // Deleting the last line leaves the line before it alone.
+        ebuf.set_leader(curid, Cursor::new(3, 0));
+        edit!(ebuf, EditAction::Delete, rt, ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "a\nc\nd\n");
+        assert_eq!(ebuf.get_line_info::<usize>(2), Some(&3));
+        assert_eq!(ebuf.get_line_info::<usize>(3), None);
+
+        // Annotations can be moved to a neighbouring line instead.
+        ebuf.set_line_info_policy::<usize>(LineDeletePolicy::Previous);
+        ebuf.remove_line_info::<usize>(0);
+        ebuf.set_leader(curid, Cursor::new(1, 0));
+        edit!(ebuf, EditAction::Delete, rt, ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "a\nd\n");
+        assert_eq!(ebuf.get_line_info::<usize>(0), Some(&2));
+        assert_eq!(ebuf.get_line_info::<usize>(1), Some(&3));
+    }
+
+    #[test]
+    fn test_delete_line_info_charwise() {
+        let (mut ebuf, curid, vwctx, vctx, mut store) = mkfivestr("abc\ndef\nghi\njkl\n");
+
+        for line in 0..4 {
+            ebuf.set_line_info(line, line);
+        }
+
+        ebuf.add_range_info(Cursor::new(0, 0), Cursor::new(0, 2), "ab");
+        ebuf.add_range_info(Cursor::new(2, 2), Cursor::new(2, 3), "i");
+        ebuf.add_range_info(Cursor::new(3, 0), Cursor::new(3, 3), "jkl");
+
+        // Delete from the middle of the first line into the third one.
+        ebuf.set_leader(curid, Cursor::new(0, 2));
+        let mov = MoveType::Column(MoveDir1D::Next, true);
+        edit!(ebuf, EditAction::Delete, mv!(mov, 7), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "abhi\njkl\n");
+
+        // The first line survives, and only the lines after it count as deleted.
+        assert_eq!(ebuf.get_line_info::<usize>(0), Some(&0));
+        assert_eq!(ebuf.get_line_info::<usize>(1), Some(&3));
+        assert_eq!(ebuf.get_line_info::<usize>(2), None);
+
+        // Ranges on the surviving text follow it.
+        let ranges = ebuf
+            .get_range_info::<&'static str>()
+            .iter()
+            .map(|r| (r.start.clone(), r.end.clone(), r.info))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![
+            (Cursor::new(0, 0), Cursor::new(0, 2), "ab"),
+            (Cursor::new(0, 3), Cursor::new(0, 4), "i"),
+            (Cursor::new(1, 0), Cursor::new(1, 3), "jkl"),
+        ]);
+    }
+
     #[test]
     fn test_change() {
         let (mut ebuf, curid, vwctx, mut vctx, mut store) =
@@ -1119,6 +1729,220 @@ mod tests {
         assert_eq!(ebuf.get_text(), "foo first\n");
     }
 
+    #[test]
+    fn test_format() {
+        let words = (0..15).map(|i| format!("word{i:05}")).collect::<Vec<_>>();
+        let text = format!(
+            "  {}\n    {}\n{}  \n\nlast\n",
+            words[..3].join("  "),
+            words[3..12].join(" "),
+            words[12..].join(" ")
+        );
+        let (mut ebuf, curid, vwctx, vctx, mut store) = mkfivestr(text.as_str());
+
+        // Start on "word00004".
+        ebuf.set_leader(curid, Cursor::new(1, 14));
+
+        // Lines are filled to 79 columns, using the second line's indent after the first.
+        edit!(
+            ebuf,
+            EditAction::Format,
+            range!(RangeType::Paragraph),
+            ctx!(curid, vwctx, vctx),
+            store
+        );
+        assert_eq!(
+            ebuf.get_text(),
+            format!(
+                "  {}\n    {}\n    {}\n\nlast\n",
+                words[..7].join(" "),
+                words[7..14].join(" "),
+                words[14]
+            )
+        );
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 42));
+
+        // Filling an already filled paragraph leaves it alone.
+        let before = ebuf.get_text();
+        edit!(
+            ebuf,
+            EditAction::Format,
+            range!(RangeType::Paragraph),
+            ctx!(curid, vwctx, vctx),
+            store
+        );
+        assert_eq!(ebuf.get_text(), before);
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 42));
+
+        // Formatting several paragraphs leaves the blank lines between them.
+        ebuf.set_text("a\nb\n\nc\nd\n");
+        ebuf.set_leader(curid, Cursor::new(0, 0));
+        edit!(
+            ebuf,
+            EditAction::Format,
+            range!(RangeType::Paragraph, 2),
+            ctx!(curid, vwctx, vctx),
+            store
+        );
+        assert_eq!(ebuf.get_text(), "a b\n\nc d\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 0));
+    }
+
+    #[test]
+    fn test_format_display_width() {
+        let word = "字".repeat(10);
+        let words = [word.as_str(); 5];
+        let text = format!("\t{}\n", words.join(" "));
+        let (mut ebuf, curid, vwctx, vctx, mut store) = mkfivestr(text.as_str());
+
+        // Wide characters take up two columns, and the tab takes up eight.
+        edit!(
+            ebuf,
+            EditAction::Format,
+            range!(RangeType::Paragraph),
+            ctx!(curid, vwctx, vctx),
+            store
+        );
+        assert_eq!(
+            ebuf.get_text(),
+            format!("\t{}\n\t{}\n", words[..3].join(" "), words[3..].join(" "))
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        let (mut ebuf, curid, vwctx, vctx, mut store) =
+            mkfivestr("hello world\n1\n  a b c d e f\n");
+
+        // Open up blank space in front of the block from (0, 7) to (2, 2).
+        ebuf.set_leader(curid, Cursor::new(0, 7));
+        edit!(
+            ebuf,
+            EditAction::Prefix(LinePrefix::Blank),
+            range!(RangeType::Line, 3),
+            ctx!(curid, vwctx, vctx),
+            store
+        );
+        assert_eq!(ebuf.get_text(), "he      llo world\n1\n        a b c d e f\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+
+        // Number the lines instead, padding the short line out to the block.
+        ebuf.set_text("hello world\n1\n  a b c d e f\n");
+        ebuf.set_leader(curid, Cursor::new(0, 7));
+        edit!(
+            ebuf,
+            EditAction::Prefix(LinePrefix::Number(Count::Exact(9))),
+            range!(RangeType::Line, 3),
+            ctx!(curid, vwctx, vctx),
+            store
+        );
+        assert_eq!(ebuf.get_text(), "he 9 llo world\n1 10 \n  11 a b c d e f\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+    }
+
+    #[test]
+    fn test_indent() {
+        let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr("foo\n  bar\n\nbaz\n");
+        let inc = EditAction::Indent(IndentChange::Increase(Count::Exact(1)));
+        let dec = EditAction::Indent(IndentChange::Decrease(Count::Exact(1)));
+
+        ebuf.set_indent_options(IndentOptions { shiftwidth: 4, ..Default::default() });
+
+        // Shift the first three lines right, leaving the empty line alone ("3>>").
+        edit!(ebuf, inc, range!(RangeType::Line, 3), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "    foo\n      bar\n\nbaz\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 4));
+
+        // Once the indentation reaches the tabstop, it uses tabs.
+        edit!(ebuf, inc, range!(RangeType::Line, 2), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "\tfoo\n\t  bar\n\nbaz\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 1));
+
+        // A count shifts several levels at once.
+        vctx.action.count = Some(2);
+        let decn = EditAction::Indent(IndentChange::Decrease(Count::Contextual));
+        edit!(ebuf, decn, range!(RangeType::Line, 2), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "foo\n  bar\n\nbaz\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 0));
+        vctx.action.count = None;
+
+        // With 'expandtab', only spaces are used.
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 4,
+            expandtab: true,
+            ..Default::default()
+        });
+        ebuf.set_leader(curid, Cursor::new(1, 0));
+        edit!(ebuf, inc, range!(RangeType::Line, 1), ctx!(curid, vwctx, vctx), store);
+        edit!(ebuf, inc, range!(RangeType::Line, 1), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "foo\n          bar\n\nbaz\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(1, 10));
+
+        // With 'shiftround', shifting rounds to a multiple of the shift width.
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 4,
+            expandtab: true,
+            shiftround: true,
+            ..Default::default()
+        });
+        edit!(ebuf, dec, range!(RangeType::Line, 1), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "foo\n        bar\n\nbaz\n");
+        edit!(ebuf, dec, range!(RangeType::Line, 1), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "foo\n    bar\n\nbaz\n");
+
+        // While typing, the cursor stays on the same character ("<C-T>").
+        vctx.persist.insert = Some(InsertStyle::Insert);
+        ebuf.set_leader(curid, Cursor::new(3, 2));
+        edit!(ebuf, inc, range!(RangeType::Line, 1), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "foo\n    bar\n\n    baz\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(3, 6));
+    }
+
+    #[test]
+    fn test_indent_blockwise() {
+        let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr("ab cd\ni\nef  gh\n");
+        let inc = EditAction::Indent(IndentChange::Increase(Count::Exact(1)));
+        let dec = EditAction::Indent(IndentChange::Decrease(Count::Exact(1)));
+        let mov = mv!(MoveType::Line(MoveDir1D::Next), 2);
+
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 2,
+            expandtab: true,
+            ..Default::default()
+        });
+        vctx.persist.shape = Some(TargetShape::BlockWise);
+
+        // Shift the text from the left edge of the block onwards, skipping short lines.
+        ebuf.set_leader(curid, Cursor::new(0, 2));
+        edit!(ebuf, inc, mov.clone(), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "ab   cd\ni\nef    gh\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+
+        // Shifting left only removes the whitespace after the left edge.
+        edit!(ebuf, dec, mov.clone(), ctx!(curid, vwctx, vctx), store);
+        edit!(ebuf, dec, mov.clone(), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "abcd\ni\nefgh\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
+    }
+
+    #[test]
+    fn test_indent_auto() {
+        let (mut ebuf, curid, vwctx, vctx, mut store) =
+            mkfivestr("fn f() {\nif x {\ny();\n}\n\n      }\nz();\n");
+        let auto = EditAction::Indent(IndentChange::Auto);
+
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 4,
+            expandtab: true,
+            ..Default::default()
+        });
+
+        // Indent lines based on the lines above them ("=G").
+        edit!(ebuf, auto, range!(RangeType::Line, 7), ctx!(curid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "fn f() {\n    if x {\n        y();\n    }\n\n}\nz();\n");
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 0));
+    }
+
     #[test]
     fn test_changenum() {
         let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr("a 1 b 2 c\nd 3 e 4 f\n");
@@ -1172,7 +1996,7 @@ mod tests {
         assert_eq!(ebuf.get_text(), "a 1 b 2 c\nd 3 e 4 f\n");
         assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 2));
 
-        ebuf.set_leader(curid, Cursor::new(1, 4));
+        ebuf.set_leader(curid, Cursor::new(1, 3));
 
         vctx.action.count = Some(2);
         edit!(
@@ -0,0 +1,539 @@
+use crate::editing::{
+    action::{EditError, EditInfo, EditResult, FoldAction},
+    application::ApplicationInfo,
+    base::{
+        Count,
+        CursorMovements,
+        CursorMovementsContext,
+        EditRange,
+        MoveDir1D,
+        MoveType,
+        RangeEnding,
+        RangeEndingModifier,
+        RangeEndingType,
+        RangeSpec,
+        RangeType,
+        TargetShape,
+    },
+    buffer::{CursorGroupIdContext, CursorRange, EditBuffer},
+    context::EditContext,
+    cursor::{Cursor, CursorChoice},
+    store::Store,
+};
+
+pub trait FoldActions<C, I>
+where
+    I: ApplicationInfo,
+{
+    /// Create a closed fold over the lines within a [RangeSpec].
+    fn fold_create(
+        &mut self,
+        range: &RangeSpec,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I>;
+
+    /// Open, close or delete folds.
+    fn fold_update(&mut self, act: &FoldAction, ctx: &C) -> EditResult<EditInfo, I>;
+}
+
+impl<I> EditBuffer<I>
+where
+    I: ApplicationInfo,
+{
+    /// Recalculate the folds from the buffer's [FoldProvider](crate::editing::fold::FoldProvider),
+    /// leaving any new folds around `line` open.
+    pub(super) fn _refresh_folds(&mut self, line: Option<usize>) {
+        if let Some(provider) = &self.fold_provider {
+            let folds = provider.folds(&self.text, &self.indent_options);
+
+            self.folds.provide(folds, line);
+        }
+
+        self.folds_stale = false;
+    }
+
+    /// Create a closed fold over the lines within a targeted range.
+    pub(super) fn _fold_range(&mut self, range: &CursorRange) -> CursorChoice {
+        self.folds.create(range.start.y, range.end.y);
+
+        CursorChoice::Range(range.start.clone(), range.end.clone(), range.start.clone())
+    }
+
+    /// Move a cursor, treating the lines within each closed fold as a single line.
+    pub(super) fn _movement<C: EditContext>(
+        &self,
+        cursor: &Cursor,
+        movement: &MoveType,
+        count: &Count,
+        ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
+    ) -> Option<Cursor> {
+        let line = match movement {
+            MoveType::Line(dir) => {
+                let count = ctx.context.resolve(count);
+                let max = self.text.get_lines().saturating_sub(1);
+
+                self.folds.step(cursor.y, *dir, count, max)
+            },
+            MoveType::Fold(dir) => {
+                let count = ctx.context.resolve(count);
+
+                self.folds.find(cursor.y, *dir, count)?
+            },
+            MoveType::ScreenFirstWord(_) |
+            MoveType::ScreenLine(_) |
+            MoveType::ScreenLinePos(_) |
+            MoveType::ViewportPos(_) => {
+                return self.text.screen_movement(cursor, movement, count, ctx, self);
+            },
+            _ => return self.text.movement(cursor, movement, count, ctx),
+        };
+
+        let (dir, count) = if line < cursor.y {
+            (MoveDir1D::Previous, cursor.y - line)
+        } else {
+            (MoveDir1D::Next, line - cursor.y)
+        };
+
+        self.text.movement(cursor, &MoveType::Line(dir), &Count::Exact(count), ctx)
+    }
+
+    /// Get the range covered by a movement, treating each closed fold as a single line.
+    pub(super) fn _range_of_movement<C: EditContext>(
+        &self,
+        cursor: &Cursor,
+        movement: &MoveType,
+        count: &Count,
+        ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
+    ) -> Option<CursorRange> {
+        match movement {
+            MoveType::Line(_) | MoveType::Fold(_) => {
+                let nc = self._movement(cursor, movement, count, ctx)?;
+                let shape = movement.shape();
+
+                let range = if nc < *cursor {
+                    EditRange::exclusive(nc, cursor.clone(), shape)
+                } else {
+                    EditRange::exclusive(cursor.clone(), nc, shape)
+                };
+
+                Some(range)
+            },
+            MoveType::ScreenFirstWord(_) |
+            MoveType::ScreenLine(_) |
+            MoveType::ScreenLinePos(_) |
+            MoveType::ViewportPos(_) => {
+                let nc = self._movement(cursor, movement, count, ctx)?;
+
+                Some(self.text.motion_range(cursor, nc, movement))
+            },
+            _ => self.text.range_of_movement(cursor, movement, count, ctx),
+        }
+    }
+
+    /// Get the range covered by a [RangeType], treating each closed fold as a single line.
+    pub(super) fn _range<C: EditContext>(
+        &self,
+        cursor: &Cursor,
+        range: &RangeType,
+        inclusive: bool,
+        count: &Count,
+        ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
+    ) -> Option<CursorRange> {
+        if let RangeType::Line = range {
+            let n = ctx.context.resolve(count).saturating_sub(1);
+            let max = self.text.get_lines().saturating_sub(1);
+            let end = self.folds.step(cursor.y, MoveDir1D::Next, n, max);
+            let count = Count::Exact(end - cursor.y + 1);
+
+            return self.text.range(cursor, range, inclusive, &count, ctx);
+        }
+
+        self.text.range(cursor, range, inclusive, count, ctx)
+    }
+
+    /// Expand a line-wise range to include all of the lines within closed folds at either end.
+    pub(super) fn _unfold_range(
+        &self,
+        mut range: CursorRange,
+        forced: Option<TargetShape>,
+    ) -> CursorRange {
+        if forced.unwrap_or(range.shape) != TargetShape::LineWise {
+            return range;
+        }
+
+        let start = self.folds.display_start(range.start.y);
+        let end = self.folds.display_end(range.end.y);
+
+        if start != range.start.y {
+            range.start = Cursor::new(start, 0);
+        }
+
+        if end != range.end.y {
+            range.end = Cursor::new(end, 0);
+        }
+
+        range
+    }
+
+    fn _range_ending<C: EditContext>(
+        &self,
+        ending: &RangeEnding,
+        cursor: &Cursor,
+        end: bool,
+        ctx: &C,
+        store: &Store<I>,
+    ) -> EditResult<usize, I> {
+        let RangeEnding(ty, mods) = ending;
+        let max = self.text.get_lines().saturating_sub(1);
+
+        let mut line = match ty {
+            RangeEndingType::Absolute(count) => ctx.resolve(count).saturating_sub(1),
+            RangeEndingType::All if end => max,
+            RangeEndingType::All => 0,
+            RangeEndingType::Current | RangeEndingType::Unspecified => cursor.y,
+            RangeEndingType::Last => max,
+            RangeEndingType::Mark(mark) => {
+                store.cursors.get_mark(self.id.clone(), ctx.resolve(mark))?.y
+            },
+            RangeEndingType::Search(_) | RangeEndingType::SubPatSearch(_) => {
+                let msg = "Searching within a line range".to_string();
+
+                return Err(EditError::Unimplemented(msg));
+            },
+        };
+
+        for modifier in mods.iter() {
+            match modifier {
+                RangeEndingModifier::Offset(MoveDir1D::Next, count) => {
+                    line = line.saturating_add(ctx.resolve(count));
+                },
+                RangeEndingModifier::Offset(MoveDir1D::Previous, count) => {
+                    line = line.saturating_sub(ctx.resolve(count));
+                },
+            }
+        }
+
+        Ok(line.min(max))
+    }
+}
+
+impl<'a, 'b, C, I> FoldActions<CursorGroupIdContext<'a, 'b, C>, I> for EditBuffer<I>
+where
+    C: EditContext,
+    I: ApplicationInfo,
+{
+    fn fold_create(
+        &mut self,
+        range: &RangeSpec,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let cursor = self.get_leader(ctx.0);
+
+        let (start, end) = match range {
+            RangeSpec::Single(ending) => {
+                let start = self._range_ending(ending, &cursor, false, ctx.2, store)?;
+                let end = self._range_ending(ending, &cursor, true, ctx.2, store)?;
+
+                (start, end)
+            },
+            RangeSpec::Double(start, end, _) => {
+                let start = self._range_ending(start, &cursor, false, ctx.2, store)?;
+                let end = self._range_ending(end, &cursor, true, ctx.2, store)?;
+
+                (start, end)
+            },
+        };
+
+        self.folds.create(start, end);
+
+        Ok(None)
+    }
+
+    fn fold_update(
+        &mut self,
+        act: &FoldAction,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+    ) -> EditResult<EditInfo, I> {
+        let line = self.get_leader(ctx.0).y;
+
+        let found = match act {
+            FoldAction::Open(recursive) => self.folds.open(line, *recursive),
+            FoldAction::Close(recursive) => self.folds.close(line, *recursive),
+            FoldAction::Toggle(recursive) => self.folds.toggle(line, *recursive),
+            FoldAction::Delete(recursive) => self.folds.delete(line, *recursive),
+            FoldAction::OpenAll => {
+                self.folds.set_closed(false);
+                true
+            },
+            FoldAction::CloseAll => {
+                self.folds.set_closed(true);
+                true
+            },
+            FoldAction::DeleteAll => {
+                self.folds.delete_all();
+                true
+            },
+            FoldAction::Create(_) => {
+                panic!("Unexpected FoldAction::Create!");
+            },
+        };
+
+        if found {
+            Ok(None)
+        } else {
+            Err(EditError::Failure("No fold found".into()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::*;
+    use super::*;
+
+    use crate::editing::base::{IndentOptions, InsertStyle, RangeSearchInit};
+    use crate::editing::fold::IndentFolds;
+
+    const TEXT: &str = "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+
+    fn ending(ty: RangeEndingType) -> RangeEnding {
+        RangeEnding(ty, vec![])
+    }
+
+    fn lines(start: usize, end: usize) -> RangeSpec {
+        let start = ending(RangeEndingType::Absolute(Count::Exact(start + 1)));
+        let end = ending(RangeEndingType::Absolute(Count::Exact(end + 1)));
+
+        RangeSpec::Double(start, end, RangeSearchInit::Cursor)
+    }
+
+    fn closed(ebuf: &EditBuffer<EmptyInfo>, line: usize) -> Option<(usize, usize)> {
+        ebuf.get_closed_fold(line).map(|f| (f.start(), f.end()))
+    }
+
+    #[test]
+    fn test_fold_motion() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr(TEXT);
+        let j = mv!(MoveType::Line(MoveDir1D::Next));
+        let k = mv!(MoveType::Line(MoveDir1D::Previous));
+        let zj = mv!(MoveType::Fold(MoveDir1D::Next));
+        let zk = mv!(MoveType::Fold(MoveDir1D::Previous));
+
+        ebuf.fold_create(&lines(2, 4), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.fold_create(&lines(6, 7), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+
+        // Closed folds get moved over as a single line.
+        edit!(ebuf, EditAction::Motion, j, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+
+        edit!(ebuf, EditAction::Motion, j, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(5, 0));
+
+        vctx.action.count = Some(2);
+        edit!(ebuf, EditAction::Motion, j, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(8, 0));
+
+        edit!(ebuf, EditAction::Motion, k, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(5, 0));
+
+        vctx.action.count = None;
+        edit!(ebuf, EditAction::Motion, k, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+
+        // Open folds are moved through line by line.
+        ebuf.fold_update(&FoldAction::Open(false), ctx!(gid, vwctx, vctx)).unwrap();
+        edit!(ebuf, EditAction::Motion, j, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(3, 0));
+
+        // Move to the start of the next fold, and the end of the previous one.
+        edit!(ebuf, EditAction::Motion, zj, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(6, 0));
+
+        ebuf.set_leader(gid, Cursor::new(9, 0));
+        edit!(ebuf, EditAction::Motion, zk, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(7, 0));
+
+        vctx.action.count = Some(2);
+        edit!(ebuf, EditAction::Motion, zk, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(4, 0));
+    }
+
+    #[test]
+    fn test_fold_delete_lines() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr(TEXT);
+        let dd = range!(RangeType::Line);
+        let dj = mv!(MoveType::Line(MoveDir1D::Next));
+
+        ebuf.fold_create(&lines(2, 4), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+
+        // Deleting a closed fold deletes all of its lines.
+        ebuf.set_leader(gid, Cursor::new(3, 0));
+        edit!(ebuf, EditAction::Delete, dd, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "0\n1\n5\n6\n7\n8\n9\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+        assert_eq!(ebuf.get_folds().len(), 0);
+
+        // Line motions onto a closed fold include all of its lines.
+        ebuf.fold_create(&lines(3, 4), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        edit!(ebuf, EditAction::Delete, dj, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "0\n1\n8\n9\n");
+
+        // Counts treat closed folds as single lines.
+        ebuf.fold_create(&lines(0, 1), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        vctx.action.count = Some(2);
+        edit!(ebuf, EditAction::Delete, dd, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "9\n");
+    }
+
+    #[test]
+    fn test_fold_operator() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr(TEXT);
+
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        edit!(
+            ebuf,
+            EditAction::Fold,
+            mv!(MoveType::Line(MoveDir1D::Next), 2),
+            ctx!(gid, vwctx, vctx),
+            store
+        );
+        assert_eq!(closed(&ebuf, 2), Some((1, 3)));
+        assert_eq!(ebuf.get_text(), TEXT);
+
+        // Folds follow the lines they were created on.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, EditAction::Delete, range!(RangeType::Line), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(closed(&ebuf, 2), Some((0, 2)));
+
+        // Open, close and delete the fold.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        ebuf.fold_update(&FoldAction::Toggle(false), ctx!(gid, vwctx, vctx))
+            .unwrap();
+        assert_eq!(closed(&ebuf, 1), None);
+        assert_eq!(ebuf.fold_level(1), 1);
+
+        ebuf.fold_update(&FoldAction::CloseAll, ctx!(gid, vwctx, vctx)).unwrap();
+        assert_eq!(closed(&ebuf, 1), Some((0, 2)));
+
+        ebuf.fold_update(&FoldAction::Delete(false), ctx!(gid, vwctx, vctx))
+            .unwrap();
+        assert_eq!(closed(&ebuf, 1), None);
+        assert_eq!(ebuf.fold_level(1), 0);
+
+        let res = ebuf.fold_update(&FoldAction::Open(false), ctx!(gid, vwctx, vctx));
+        assert!(matches!(res, Err(EditError::Failure(_))));
+    }
+
+    #[test]
+    fn test_fold_create_range() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr(TEXT);
+        let next = |n| RangeEndingModifier::Offset(MoveDir1D::Next, Count::Exact(n));
+
+        // :.,+2fold
+        ebuf.set_leader(gid, Cursor::new(4, 0));
+        let start = ending(RangeEndingType::Current);
+        let end = RangeEnding(RangeEndingType::Unspecified, vec![next(2)]);
+        let spec = RangeSpec::Double(start, end, RangeSearchInit::Cursor);
+        ebuf.fold_create(&spec, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(closed(&ebuf, 5), Some((4, 6)));
+
+        // :'a,$fold
+        ebuf.set_leader(gid, Cursor::new(8, 0));
+        ebuf.mark(mark!('a'), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+
+        let start = ending(RangeEndingType::Mark(Specifier::Exact(mark!('a'))));
+        let end = ending(RangeEndingType::Last);
+        let spec = RangeSpec::Double(start, end, RangeSearchInit::Cursor);
+        ebuf.fold_create(&spec, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(closed(&ebuf, 9), Some((8, 9)));
+
+        // :%fold
+        let spec = RangeSpec::Single(ending(RangeEndingType::All));
+        ebuf.fold_create(&spec, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(closed(&ebuf, 5), Some((0, 9)));
+    }
+
+    #[test]
+    fn test_fold_provider() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("a\n  b\n  c\nd\n");
+
+        ebuf.set_indent_options(IndentOptions { shiftwidth: 2, ..Default::default() });
+        ebuf.set_fold_provider(Some(Box::new(IndentFolds)));
+        assert_eq!(closed(&ebuf, 2), Some((1, 2)));
+
+        // Folds get recalculated at the next checkpoint after the text changes.
+        ebuf.set_leader(gid, Cursor::new(3, 0));
+        let act =
+            InsertTextAction::Transcribe("  e\n".into(), MoveDir1D::Previous, Count::Exact(1));
+        let act = EditorAction::InsertText(act);
+        ebuf.editor_command(&act, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "a\n  b\n  c\n  e\nd\n");
+        assert_eq!(closed(&ebuf, 3), None);
+
+        let checkpoint = EditorAction::History(HistoryAction::Checkpoint);
+        ebuf.editor_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(closed(&ebuf, 3), Some((1, 3)));
+
+        // Folds that were opened stay open.
+        ebuf.set_leader(gid, Cursor::new(3, 0));
+        ebuf.fold_update(&FoldAction::Open(false), ctx!(gid, vwctx, vctx)).unwrap();
+        ebuf.editor_command(&act, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.editor_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_folds().len(), 1);
+        assert_eq!(closed(&ebuf, 3), None);
+
+        // Provided folds can't be deleted.
+        let res = ebuf.fold_update(&FoldAction::Delete(false), ctx!(gid, vwctx, vctx));
+        assert!(matches!(res, Err(EditError::Failure(_))));
+    }
+
+    #[test]
+    fn test_fold_provider_insert() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("a\nb\n");
+
+        ebuf.set_indent_options(IndentOptions { shiftwidth: 2, ..Default::default() });
+        ebuf.set_fold_provider(Some(Box::new(IndentFolds)));
+        assert_eq!(ebuf.get_folds().len(), 0);
+
+        // Typing an indented line creates a fold around it, which starts open.
+        vctx.persist.insert = Some(InsertStyle::Insert);
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        let act = InsertTextAction::Transcribe("  c\n".into(), MoveDir1D::Previous, 1.into());
+        ebuf.editor_command(&act.into(), ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_text(), "a\n  c\nb\n");
+
+        ebuf.set_leader(gid, Cursor::new(1, 3));
+        let checkpoint = EditorAction::History(HistoryAction::Checkpoint);
+        ebuf.editor_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_folds().len(), 1);
+        assert_eq!(closed(&ebuf, 1), None);
+
+        // It stays open after leaving Insert mode.
+        vctx.persist.insert = None;
+        ebuf.set_leader(gid, Cursor::new(1, 2));
+        ebuf.editor_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(closed(&ebuf, 1), None);
+
+        // New folds away from the cursor still start closed.
+        ebuf.set_leader(gid, Cursor::new(3, 0));
+        let act = InsertTextAction::Transcribe("  d\n".into(), MoveDir1D::Previous, 1.into());
+        ebuf.editor_command(&act.into(), ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        ebuf.editor_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_text(), "a\n  c\nb\n  d\n");
+        assert_eq!(closed(&ebuf, 1), None);
+        assert_eq!(closed(&ebuf, 3), Some((3, 3)));
+    }
+}
@@ -1,11 +1,21 @@
 use crate::editing::{
-    action::{EditInfo, EditResult},
+    action::{EditError, EditInfo, EditResult},
     application::ApplicationInfo,
-    base::{Char, Count, CursorEnd, InsertStyle, MoveDir1D, PasteStyle, Register, TargetShape},
-    buffer::{CursorGroupIdContext, EditBuffer},
+    base::{
+        Char,
+        CheckpointPolicy,
+        Count,
+        CursorEnd,
+        InsertStyle,
+        MoveDir1D,
+        PasteStyle,
+        Register,
+        TargetShape,
+    },
+    buffer::{CursorGroupIdContext, EditBuffer, HistoryActions},
     context::EditContext,
-    cursor::{Adjustable, CursorChoice, CursorState},
-    rope::EditRope,
+    cursor::{Adjustable, Cursor, CursorAdjustment, CursorChoice, CursorState},
+    rope::{CharOff, EditRope},
     store::Store,
 };
 
@@ -51,6 +61,136 @@ where
         ctx: &C,
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I>;
+
+    /// Replace just-pasted text with another entry from the kill ring.
+    fn yank_pop(
+        &mut self,
+        dir: MoveDir1D,
+        count: &Count,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I>;
+}
+
+/// What a character typed over screen columns replaced, so that deleting the character can put
+/// the text back.
+#[derive(Clone, Debug)]
+pub(super) struct Overwritten {
+    /// Where the typed character is.
+    cursor: Cursor,
+
+    /// The text that the character replaced.
+    text: EditRope,
+
+    /// How many spaces were added after the character to keep the rest of the line in place.
+    pad: usize,
+}
+
+impl Overwritten {
+    fn new(cursor: Cursor, text: EditRope, pad: usize) -> Self {
+        Overwritten { cursor, text, pad }
+    }
+
+    fn at(&self, cursor: &Cursor) -> bool {
+        self.cursor.y == cursor.y && self.cursor.x == cursor.x
+    }
+
+    /// Account for the columns after this character getting replaced, starting with any spaces
+    /// that were added after it.
+    fn extend(&mut self, text: EditRope, pad: usize) {
+        let used = self.pad.min(text.len());
+        let rest = text.slice(used.into(), text.len().into(), false);
+
+        self.text += rest;
+        self.pad = self.pad - used + pad;
+    }
+}
+
+impl Adjustable for Overwritten {
+    fn zero(&mut self) {
+        self.cursor.zero();
+    }
+
+    fn adjust(&mut self, adjs: &[CursorAdjustment]) {
+        self.cursor.adjust(adjs);
+    }
+}
+
+/// Enter text into a rope using the given [InsertStyle], laying out tabs every `tabstop`
+/// columns when replacing screen columns.
+fn type_text(
+    rope: &mut EditRope,
+    cursor: &Cursor,
+    dir: MoveDir1D,
+    text: EditRope,
+    style: InsertStyle,
+    tabstop: usize,
+) -> (CursorChoice, Vec<CursorAdjustment>) {
+    match style {
+        InsertStyle::VirtualReplace => rope.vreplace(cursor, dir, text, tabstop),
+        style => rope.insert(cursor, dir, text, style),
+    }
+}
+
+/// Shift the offsets of previously pasted text that comes after an edit at `at`.
+fn shift_yanked(yanked: &mut [(usize, usize)], at: usize, amt: isize) {
+    for (start, end) in yanked.iter_mut() {
+        if *start >= at {
+            *start = start.saturating_add_signed(amt);
+            *end = end.saturating_add_signed(amt);
+        }
+    }
+}
+
+impl<I> EditBuffer<I>
+where
+    I: ApplicationInfo,
+{
+    /// Get what to put back in place of `text` at `start` when deleting it while replacing
+    /// screen columns, and how many of the characters after it also get replaced.
+    ///
+    /// Characters that weren't typed over other text get kept.
+    pub(super) fn _restore_overwritten(
+        &mut self,
+        start: CharOff,
+        text: &EditRope,
+    ) -> (EditRope, usize) {
+        let len = text.len();
+        let mut chars: Vec<char> = text.to_string().chars().collect();
+        let mut found = Vec::with_capacity(len);
+        let mut pad = 0;
+
+        for i in 0..len {
+            let cursor = self.text.offset_to_cursor(start + i.into());
+            let idx = self.overwritten.iter().position(|o| o.at(&cursor));
+            let overwritten = idx.map(|idx| self.overwritten.remove(idx));
+
+            pad += overwritten.as_ref().map(|o| o.pad).unwrap_or(0);
+            found.push(overwritten);
+        }
+
+        // Any spaces added after the typed characters get removed along with them.
+        let after = self.text.chars(start + len.into()).take(pad).take_while(|c| *c == ' ');
+        chars.extend(after);
+
+        let extra = chars.len() - len;
+
+        for (i, overwritten) in found.into_iter().enumerate().rev() {
+            let Some(overwritten) = overwritten else {
+                continue;
+            };
+
+            let mut n = 1;
+
+            while n <= overwritten.pad && chars.get(i + n) == Some(&' ') {
+                n += 1;
+            }
+
+            chars.splice(i..i + n, overwritten.text.to_string().chars());
+        }
+
+        return (EditRope::from(chars.into_iter().collect::<String>()), extra);
+    }
 }
 
 impl<'a, 'b, C, I> InsertTextActions<CursorGroupIdContext<'a, 'b, C>, I> for EditBuffer<I>
@@ -67,12 +207,19 @@ where
     ) -> EditResult<EditInfo, I> {
         let count = ctx.2.resolve(count);
         let insty = ctx.2.get_insert_style();
-        let cell = store.registers.get(&ctx.2.get_register().unwrap_or(Register::Unnamed))?;
+        let tabstop = self.indent_options.tabstop;
+        let register = ctx.2.get_register().unwrap_or(Register::Unnamed);
+        let cell = store.registers.get(&register)?;
         let text = cell.value.repeat(cell.shape, count);
         let end = ctx.2.get_cursor_end();
 
+        // Blocks get inserted as blocks when the context asks for it, even while typing.
+        let block = cell.shape == TargetShape::BlockWise &&
+            ctx.2.get_target_shape() == Some(TargetShape::BlockWise);
+
         let gid = ctx.0;
         let mut group = self.get_group(gid);
+        let mut yanked = vec![];
 
         self.push_change(&group);
 
@@ -82,8 +229,19 @@ where
                     let cursor = state.cursor();
                     let dir = MoveDir1D::Previous;
 
-                    if let Some(style) = insty {
-                        self.text.insert(cursor, dir, text.clone(), style)
+                    if register == Register::KillRing && insty == Some(InsertStyle::Insert) {
+                        // Remember where the text went, so that it can be swapped out later.
+                        let start = self.text.cursor_to_offset(cursor).into();
+                        let len = text.len();
+
+                        shift_yanked(&mut yanked, start, len as isize);
+                        yanked.push((start, start + len));
+                    }
+
+                    if block {
+                        self.text.insert_block(cursor, text.clone())
+                    } else if let Some(style) = insty {
+                        type_text(&mut self.text, cursor, dir, text.clone(), style, tabstop)
                     } else {
                         self.text.paste(cursor, dir, text.clone(), cell.shape)
                     }
@@ -95,7 +253,7 @@ where
                     };
 
                     if let Some(style) = insty {
-                        self.text.insert(cursor, *dir, text.clone(), style)
+                        type_text(&mut self.text, cursor, *dir, text.clone(), style, tabstop)
                     } else {
                         self.text.paste(cursor, *dir, text.clone(), cell.shape)
                     }
@@ -110,6 +268,20 @@ where
 
             self._adjust_all(adjs, store);
 
+            if let CursorChoice::Range(s, e, _) = &choice {
+                // Remember where the pasted text went.
+                let e = if cell.shape == TargetShape::BlockWise {
+                    e.clone()
+                } else {
+                    let soff = self.text.cursor_to_offset(s);
+                    let eoff = soff + text.len_offset() - CharOff::from(1);
+
+                    self.text.offset_to_cursor(eoff)
+                };
+
+                self._set_change_marks(s.clone(), e, store);
+            }
+
             if let Some(cursor) = choice.resolve(end) {
                 state.set(cursor);
                 self.clamp_state(state, ctx);
@@ -118,6 +290,63 @@ where
 
         self.set_group(gid, group);
 
+        if yanked.is_empty() {
+            self.yanked.remove(&gid);
+        } else {
+            self.yanked.insert(gid, yanked);
+        }
+
+        Ok(None)
+    }
+
+    fn yank_pop(
+        &mut self,
+        dir: MoveDir1D,
+        count: &Count,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let gid = ctx.0;
+        let count = ctx.2.resolve(count);
+
+        let mut yanked = self
+            .yanked
+            .remove(&gid)
+            .ok_or_else(|| EditError::Failure("Previous command was not a yank".into()))?;
+
+        if yanked.iter().any(|(_, e)| *e > self.text.len()) {
+            return Err(EditError::Failure("Previous command was not a yank".into()));
+        }
+
+        let cell = store.registers.cycle_kill_ring(dir, count)?;
+        let text = cell.value;
+        let tlen = text.len();
+
+        let mut group = self.get_group(gid);
+
+        self.push_change(&group);
+
+        for (idx, state) in group.iter_mut().enumerate() {
+            let (start, stop) = match yanked.get(idx) {
+                Some(range) => *range,
+                None => break,
+            };
+
+            let (_, adjs) = self.text.replace(start.into(), stop.into(), false, text.clone());
+
+            self._adjust_all(adjs, store);
+
+            shift_yanked(&mut yanked, stop, tlen as isize - (stop - start) as isize);
+            yanked[idx] = (start, start + tlen);
+
+            // Leave the cursor after the newly inserted text.
+            state.set(self.text.offset_to_cursor((start + tlen).into()).into());
+            self.clamp_state(state, ctx);
+        }
+
+        self.set_group(gid, group);
+        self.yanked.insert(gid, yanked);
+
         Ok(None)
     }
 
@@ -130,7 +359,6 @@ where
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
         let count = ctx.2.resolve(count);
-        let text = EditRope::from("\n").repeat(TargetShape::CharWise, count);
         let end = ctx.2.get_cursor_end();
 
         let gid = ctx.0;
@@ -139,12 +367,26 @@ where
         self.push_change(&group);
 
         for state in group.iter_mut() {
-            let (choice, adjs) = self.text.paste(state.cursor(), dir, text.clone(), shape);
+            let indent = match shape {
+                TargetShape::LineWise => self._new_line_indent(state.cursor().y, dir),
+                TargetShape::CharWise | TargetShape::BlockWise => None,
+            };
+            let indent = indent.unwrap_or_default();
+            let text = EditRope::from(format!("{indent}\n")).repeat(TargetShape::CharWise, count);
+            let (choice, adjs) = self.text.paste(state.cursor(), dir, text, shape);
 
             self._adjust_all(adjs, store);
 
             if let Some(cursor) = choice.resolve(end) {
                 state.set(cursor);
+
+                if !indent.is_empty() {
+                    // Leave the cursor after the new line's indentation.
+                    let mut cursor = state.cursor().clone();
+                    cursor.set_x(indent.chars().count());
+                    state.set_cursor(cursor);
+                }
+
                 self.clamp_state(state, ctx);
             }
         }
@@ -163,6 +405,7 @@ where
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
         let style = ctx.2.get_insert_style().unwrap_or(InsertStyle::Insert);
+        let tabstop = self.indent_options.tabstop;
         let count = ctx.2.resolve(count);
         let end = ctx.2.get_cursor_end();
 
@@ -177,7 +420,8 @@ where
         for state in group.iter_mut() {
             state.adjust(adjs.as_slice());
 
-            let (choice, mut adj) = self.text.insert(state.cursor(), dir, text.clone(), style);
+            let (choice, mut adj) =
+                type_text(&mut self.text, state.cursor(), dir, text.clone(), style, tabstop);
 
             if let Some(cursor) = choice.resolve(end) {
                 state.set(cursor);
@@ -204,9 +448,24 @@ where
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
         let style = ctx.2.get_insert_style().unwrap_or(InsertStyle::Insert);
+        let tabstop = self.indent_options.tabstop;
         let count = ctx.2.resolve(count);
         let end = ctx.2.get_cursor_end();
 
+        let space = matches!(ch, Char::Single(c) if c.is_whitespace());
+        let checkpoint = match self.checkpoint_policy {
+            CheckpointPolicy::Explicit => false,
+            CheckpointPolicy::Word => self.typed_space && !space,
+            CheckpointPolicy::Characters(n) => n > 0 && self.typed >= n,
+        };
+
+        if checkpoint {
+            self.checkpoint(ctx, store)?;
+        }
+
+        self.typed += count;
+        self.typed_space = space;
+
         let gid = ctx.0;
         let mut group = self.get_group(gid);
 
@@ -214,23 +473,94 @@ where
 
         let mut typed: Vec<&mut CursorState> = vec![];
         let mut adjs = vec![];
+        let mut front: Option<usize> = None;
+
+        // Keep track of what gets replaced, to restore it if the typed text gets deleted.
+        let vreplace = style == InsertStyle::VirtualReplace;
+        let mut overwritten = std::mem::take(&mut self.overwritten);
 
         for state in group.iter_mut().rev() {
             let mut choice = CursorChoice::Single(state.cursor().clone());
 
+            /*
+             * When replacing screen columns, cursors that trail another one on the same line (like
+             * the copies made for a count in "3gRab") insert what they type, and the frontmost
+             * cursor overwrites the columns for all of them, so that they don't overwrite each
+             * other.
+             */
+            let trailing = match front {
+                Some(idx) if style == InsertStyle::VirtualReplace => {
+                    Some(idx).filter(|idx| typed[*idx].cursor().y == state.cursor().y)
+                },
+                _ => None,
+            };
+
             for _ in 0..count {
                 if let Some(cursor) = choice.get(CursorEnd::Auto) {
-                    let s = self._str(ch.clone(), cursor, &store.digraphs)?;
+                    let mut s = self._str(ch.clone(), cursor, &store.digraphs)?;
+
+                    if s == "\n" {
+                        // Start the new line with the indentation it should get.
+                        let indent = self._new_line_indent(cursor.y, MoveDir1D::Next);
+                        s.push_str(indent.unwrap_or_default().as_str());
+                    }
+
                     let text = EditRope::from(s.as_str());
+                    let tlen = text.len();
+                    let mut replaced = (EditRope::from(""), 0);
+
+                    let mut res = if let Some(idx) = trailing {
+                        let front = typed[idx].cursor().clone();
+                        let removed =
+                            self.text.vreplaced(&front, MoveDir1D::Previous, &text, tabstop);
+                        let last = usize::from(self.text.cursor_to_offset(&front)).checked_sub(1);
+                        let last = last.map(|off| self.text.offset_to_cursor(off.into()));
+                        let mut radjs = self.text.remove_overwritten(&front, &text, tabstop);
+
+                        // The frontmost cursor's last character now covers the removed columns.
+                        match last.and_then(|c| overwritten.iter_mut().find(|o| o.at(&c))) {
+                            Some(last) => last.extend(removed.0, removed.1),
+                            None => replaced = removed,
+                        }
+
+                        for typed in typed.iter_mut() {
+                            typed.adjust(radjs.as_slice());
+                        }
+
+                        overwritten.adjust(radjs.as_slice());
+                        adjs.append(&mut radjs);
+
+                        self.text.insert(cursor, dir, text, InsertStyle::Insert)
+                    } else {
+                        if vreplace {
+                            replaced = self.text.vreplaced(cursor, dir, &text, tabstop);
+                        }
 
-                    let mut res = self.text.insert(cursor, dir, text, style);
+                        type_text(&mut self.text, cursor, dir, text, style, tabstop)
+                    };
                     choice = res.0;
 
                     for typed in typed.iter_mut() {
                         typed.adjust(res.1.as_slice());
                     }
 
+                    overwritten.adjust(res.1.as_slice());
                     adjs.append(&mut res.1);
+
+                    if let (true, CursorChoice::Range(start, _, _)) = (vreplace, &choice) {
+                        let off = self.text.cursor_to_offset(start);
+
+                        for i in 0..tlen {
+                            let cursor = self.text.offset_to_cursor(off + i.into());
+                            let (text, pad) = if i == 0 {
+                                std::mem::replace(&mut replaced, (EditRope::from(""), 0))
+                            } else {
+                                (EditRope::from(""), 0)
+                            };
+
+                            overwritten.push(Overwritten::new(cursor, text, pad));
+                        }
+                    }
                 }
             }
 
@@ -238,10 +568,15 @@ where
                 state.set(cursor);
             }
 
+            if trailing.is_none() {
+                front = Some(typed.len());
+            }
+
             typed.push(state);
         }
 
         self._adjust_all(adjs, store);
+        self.overwritten = overwritten;
         self.set_group(gid, group);
 
         Ok(None)
@@ -252,6 +587,7 @@ where
 mod tests {
     use super::super::tests::*;
     use super::*;
+    use crate::editing::base::IndentOptions;
 
     macro_rules! type_digraph {
         ($ebuf: expr, $d1: expr, $d2: expr, $ctx: expr, $store: expr) => {
@@ -313,6 +649,41 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 1));
     }
 
+    #[test]
+    fn test_typing_autoindent() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("  if x:\n");
+
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 4,
+            expandtab: true,
+            autoindent: true,
+            ..Default::default()
+        });
+
+        // New lines copy the indentation of the line they were split from.
+        ebuf.set_leader(gid, Cursor::new(0, 7));
+        type_char!(ebuf, '\n', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'a', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "  if x:\n  a\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 3));
+
+        // With 'smartindent', lines after a ":" get indented another level.
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 4,
+            expandtab: true,
+            smartindent: true,
+            ..Default::default()
+        });
+        ebuf.set_text("  if x:\n");
+        ebuf.set_leader(gid, Cursor::new(0, 7));
+        type_char!(ebuf, '\n', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'a', gid, vwctx, vctx, store);
+        type_char!(ebuf, '\n', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'b', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "  if x:\n      a\n      b\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 7));
+    }
+
     #[test]
     fn test_typing_insert_digraph() {
         let (mut ebuf, gid, vwctx, vctx, mut store) = mkfive();
@@ -416,6 +787,195 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
     }
 
+    #[test]
+    fn test_typing_vreplace_count() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("\tabc\n");
+
+        vctx.persist.insert = Some(InsertStyle::VirtualReplace);
+
+        // Cursors split for a count ("3gRab") overwrite the columns once between them.
+        ebuf.cursor_split(&Count::Exact(2), ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+
+        type_char!(ebuf, 'a', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'b', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "ababab\tabc\n");
+
+        type_char!(ebuf, 'c', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "abcabcabcbc\n");
+
+        // Repeating "3gRab<Esc>" with "." types over the columns after the cursor again.
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("123456789abcdef\n");
+        let close = CursorAction::Close(crate::editing::base::CursorCloseTarget::Followers);
+        let left = MoveType::Column(MoveDir1D::Previous, false);
+
+        for _ in 0..2 {
+            vctx.persist.insert = Some(InsertStyle::VirtualReplace);
+            ebuf.cursor_split(&Count::Exact(2), ctx!(gid, vwctx, vctx), &mut store)
+                .unwrap();
+            type_char!(ebuf, 'a', gid, vwctx, vctx, store);
+            type_char!(ebuf, 'b', gid, vwctx, vctx, store);
+
+            vctx.persist.insert = None;
+            ebuf.editor_command(&close.clone().into(), ctx!(gid, vwctx, vctx), &mut store)
+                .unwrap();
+            edit!(ebuf, EditAction::Motion, mv!(left), ctx!(gid, vwctx, vctx), store);
+            ebuf.checkpoint(ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        }
+
+        // Like with "3iab<Esc>", the leader is left after the first copy of the typed text.
+        assert_eq!(ebuf.get_text(), "aababab89abcdef\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
+    }
+
+    #[test]
+    fn test_typing_vreplace() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("a\tb\n日本\n");
+
+        vctx.persist.insert = Some(InsertStyle::VirtualReplace);
+
+        // Typed characters eat into the tab until its width is used up.
+        type_char!(ebuf, 'x', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'y', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "xy\tb\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
+
+        for c in "abcdef".chars() {
+            type_char!(ebuf, c, gid, vwctx, vctx, store);
+        }
+
+        assert_eq!(ebuf.get_text(), "xyabcdefb\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 8));
+
+        // Typing a newline doesn't delete any text.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        type_char!(ebuf, '\n', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "xyabcdefb\n\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+
+        // Wide characters take up two cells.
+        type_char!(ebuf, 'x', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "xyabcdefb\n\nx 本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 1));
+    }
+
+    #[test]
+    fn test_typing_vreplace_backspace() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("a\tb\n日本\n");
+        let mov = MoveType::Column(MoveDir1D::Previous, true);
+
+        vctx.persist.insert = Some(InsertStyle::VirtualReplace);
+
+        // Backspace only gives back what each typed character replaced.
+        type_char!(ebuf, 'x', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'y', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "xy\tb\n日本\n");
+
+        edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "x\tb\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 1));
+
+        edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "a\tb\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
+
+        // Deleting the character that used up the tab puts the tab back.
+        for c in "abcdefgh".chars() {
+            type_char!(ebuf, c, gid, vwctx, vctx, store);
+        }
+
+        assert_eq!(ebuf.get_text(), "abcdefghb\n日本\n");
+
+        edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "abcdefg\tb\n日本\n");
+
+        // Wide characters come back without the space that padded them out.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        type_char!(ebuf, 'x', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "abcdefg\tb\nx 本\n");
+
+        edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "abcdefg\tb\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 0));
+
+        // The padding can get typed over too.
+        type_char!(ebuf, 'x', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'y', gid, vwctx, vctx, store);
+        type_char!(ebuf, 'z', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "abcdefg\tb\nxyz \n");
+
+        for _ in 0..3 {
+            edit!(ebuf, EditAction::Delete, mv!(mov), ctx!(gid, vwctx, vctx), store);
+        }
+
+        assert_eq!(ebuf.get_text(), "abcdefg\tb\n日本\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 0));
+    }
+
+    #[test]
+    fn test_typing_checkpoint_policy() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfive();
+        let undo = HistoryAction::Undo(Count::Exact(1));
+        let checkpoint = HistoryAction::Checkpoint;
+
+        // Break up undo before each new word.
+        ebuf.set_checkpoint_policy(CheckpointPolicy::Word);
+
+        for c in "ab cd  ef".chars() {
+            type_char!(ebuf, c, gid, vwctx, vctx, store);
+        }
+
+        assert_eq!(ebuf.get_text(), "ab cd  ef\n");
+
+        // Leaving Insert mode creates the final checkpoint.
+        ebuf.history_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "ab cd  \n");
+
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "ab \n");
+
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "\n");
+
+        // Break up undo after every three characters.
+        ebuf.set_checkpoint_policy(CheckpointPolicy::Characters(3));
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+
+        for c in "abcdefgh".chars() {
+            type_char!(ebuf, c, gid, vwctx, vctx, store);
+        }
+
+        assert_eq!(ebuf.get_text(), "abcdefgh\n");
+
+        ebuf.history_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "abcdef\n");
+
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "abc\n");
+
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "\n");
+
+        // The default only creates checkpoints when asked to.
+        ebuf.set_checkpoint_policy(CheckpointPolicy::Explicit);
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+
+        for c in "ab cd".chars() {
+            type_char!(ebuf, c, gid, vwctx, vctx, store);
+        }
+
+        ebuf.history_command(&checkpoint, ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        ebuf.history_command(&undo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "\n");
+    }
+
     #[test]
     fn test_open_line() {
         let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("hello world\nhello world\n");
@@ -447,6 +1007,57 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 0));
     }
 
+    #[test]
+    fn test_open_line_autoindent() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("fn f() {\n\tg();\n}\n");
+
+        vctx.persist.insert = Some(InsertStyle::Insert);
+        ebuf.set_indent_options(IndentOptions {
+            shiftwidth: 4,
+            tabstop: 4,
+            smartindent: true,
+            ..Default::default()
+        });
+
+        // Opening a line below one that ends with "{" indents it another level.
+        open_line!(ebuf, TargetShape::LineWise, MoveDir1D::Next, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "fn f() {\n\t\n\tg();\n}\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 1));
+
+        // Opening a line above one that starts with "}" does too.
+        ebuf.set_leader(gid, Cursor::new(3, 0));
+        open_line!(ebuf, TargetShape::LineWise, MoveDir1D::Previous, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "fn f() {\n\t\n\tg();\n\t\n}\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(3, 1));
+
+        // Other lines just copy the indentation.
+        ebuf.set_leader(gid, Cursor::new(2, 0));
+        open_line!(ebuf, TargetShape::LineWise, MoveDir1D::Next, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "fn f() {\n\t\n\tg();\n\t\n\t\n}\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(3, 1));
+    }
+
+    #[test]
+    fn test_open_line_info() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("a\nb\nc\n");
+        ebuf.set_line_info(1, "b");
+        ebuf.set_line_info(2, "c");
+        ebuf.add_range_info(Cursor::new(1, 0), Cursor::new(2, 1), "bc");
+
+        // Opening a line above "b" moves its annotations down.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        open_line!(ebuf, TargetShape::LineWise, MoveDir1D::Previous, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "a\n\nb\nc\n");
+        assert_eq!(ebuf.get_line_info::<&str>(1), None);
+        assert_eq!(ebuf.get_line_info::<&str>(2), Some(&"b"));
+        assert_eq!(ebuf.get_line_info::<&str>(3), Some(&"c"));
+
+        let ranges = ebuf.get_range_info::<&str>();
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].start, Cursor::new(2, 0));
+        assert_eq!(ranges[0].end, Cursor::new(3, 1));
+    }
+
     #[test]
     fn test_paste() {
         let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfive();
@@ -577,6 +1188,13 @@ mod tests {
         paste_dir!(ebuf, MoveDir1D::Previous, Count::Contextual, ctx!(gid, vwctx, vctx), store);
         assert_eq!(ebuf.get_text(), "hello foo\na\nb\ncworld\n");
         assert_eq!(ebuf.get_leader(gid), Cursor::new(3, 1));
+
+        // With a BlockWise shape, "b gets placed at the cursor as a block, padding short lines.
+        vctx.persist.shape = Some(TargetShape::BlockWise);
+        ebuf.set_leader(gid, Cursor::new(0, 2));
+        paste!(ebuf, PasteStyle::Cursor, Count::Contextual, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "heallo foo\na b\nb c\ncworld\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
     }
 
     #[test]
@@ -605,4 +1223,57 @@ mod tests {
         assert_eq!(ebuf.get_text(), "1aaaa 2 3\n1bbbb 2 3\nhccccellohellohellohellohello\n");
         assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 1));
     }
+
+    #[test]
+    fn test_yank_pop() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("hello world\n");
+
+        set_reg!(store, Register::KillRing, TargetShape::CharWise, "foo");
+        set_reg!(store, Register::KillRing, TargetShape::CharWise, "bar");
+        set_reg!(store, Register::KillRing, TargetShape::CharWise, "quux");
+
+        vctx.persist.insert = Some(InsertStyle::Insert);
+        vctx.action.register = Some(Register::KillRing);
+
+        // Start out at (0, 6).
+        ebuf.set_leader(gid, Cursor::new(0, 6));
+
+        // Yanking pastes the most recent kill.
+        paste!(ebuf, PasteStyle::Cursor, Count::Contextual, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "hello quuxworld\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 10));
+
+        // Cycle through older kills, wrapping around at the end of the ring.
+        ebuf.yank_pop(MoveDir1D::Previous, &1.into(), ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_text(), "hello barworld\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 9));
+
+        ebuf.yank_pop(MoveDir1D::Previous, &2.into(), ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_text(), "hello quuxworld\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 10));
+
+        ebuf.yank_pop(MoveDir1D::Next, &1.into(), ctx!(gid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_text(), "hello fooworld\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 9));
+
+        // Moving the cursor ends the sequence of yanks.
+        let act = EditorAction::Edit(
+            EditAction::Motion.into(),
+            mv!(MoveType::Column(MoveDir1D::Next, false), 1),
+        );
+        ebuf.editor_command(&act, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 10));
+
+        let res = ebuf.yank_pop(MoveDir1D::Previous, &1.into(), ctx!(gid, vwctx, vctx), &mut store);
+        assert!(res.is_err());
+        assert_eq!(ebuf.get_text(), "hello fooworld\n");
+
+        // Yanking again uses the entry that the last yank-pop left off at.
+        paste!(ebuf, PasteStyle::Cursor, Count::Contextual, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "hello foowfooorld\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 13));
+    }
 }
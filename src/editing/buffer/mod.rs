@@ -32,9 +32,13 @@ use crate::editing::{
         Editable,
         EditorAction,
         EditorActions,
+        FoldAction,
         HistoryAction,
+        InfoMessage,
         InsertTextAction,
         Jumpable,
+        MarkRingAction,
+        QueryReplaceAction,
         Searchable,
         SelectionAction,
         UIResult,
@@ -42,6 +46,7 @@ use crate::editing::{
     application::ApplicationInfo,
     base::{
         Char,
+        CheckpointPolicy,
         CompletionDisplay,
         CompletionSelection,
         CompletionType,
@@ -51,12 +56,15 @@ use crate::editing::{
         CursorSearch,
         EditRange,
         EditTarget,
+        IndentOptions,
         Mark,
         MoveDir1D,
         MoveDirMod,
         MoveTerminus,
         PositionList,
         Register,
+        SearchCase,
+        SearchOffset,
         SearchType,
         SelectionResizeStyle,
         Specifier,
@@ -77,9 +85,17 @@ use crate::editing::{
         Selection,
         Selections,
     },
+    fold::{Fold, FoldList, FoldProvider},
     history::HistoryList,
-    lineinfo::LineInfoStore,
-    rope::{CharOff, CursorContext, EditRope, LineIterator, PrivateCursorOps},
+    lineinfo::{LineDeletePolicy, LineInfoStore, RangeId, RangeInfo, VirtualSpace},
+    rope::{
+        layout::{LineLayout, ScreenLayout},
+        CharOff,
+        CursorContext,
+        EditRope,
+        LineIterator,
+        PrivateCursorOps,
+    },
     store::{AdjustStore, DigraphStore, GlobalAdjustable, SharedBuffer, Store},
 };
 
@@ -90,20 +106,30 @@ mod macros_test;
 mod complete;
 mod cursor;
 mod edit;
+mod fold;
 mod insert_text;
+mod replace;
 mod selection;
 
 use self::complete::*;
 use self::cursor::*;
 use self::edit::*;
+use self::fold::*;
 use self::insert_text::*;
+use self::replace::*;
 use self::selection::*;
 
 #[cfg(feature = "intervaltree")]
 use intervaltree::IntervalTree;
 
+#[cfg(feature = "intervaltree")]
+use crate::editing::store::SearchHighlight;
+
 const BUFFER_HISTORY_LEN: usize = 100;
 
+/// The most search matches to count when showing which one the cursor is on.
+const SEARCH_COUNT_MAX: usize = 99;
+
 /// Identifier for a specific cursor group.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub struct CursorGroupId(u64);
@@ -111,6 +137,33 @@ pub struct CursorGroupId(u64);
 #[doc(hidden)]
 pub type CursorRange = EditRange<Cursor>;
 
+/// Apply adjustments to a cursor at the start of a line, and return whether the line gets deleted
+/// or joined with another one along the way.
+fn line_removed(line: &mut Cursor, adjs: &[CursorAdjustment]) -> bool {
+    for adj in adjs {
+        let removed = match adj {
+            CursorAdjustment::Line { line_start, line_end, amount, .. } => {
+                *amount == isize::MAX && (*line_start..=*line_end).contains(&line.y)
+            },
+            CursorAdjustment::Column { line: end, amt_line, .. } if *amt_line < 0 => {
+                // The end of one line moving upwards joins it with the line it lands on.
+                let start = end.saturating_sub(amt_line.unsigned_abs());
+
+                (start..=*end).contains(&line.y)
+            },
+            CursorAdjustment::Column { .. } => false,
+        };
+
+        if removed {
+            return true;
+        }
+
+        line.adjust(std::slice::from_ref(adj));
+    }
+
+    return false;
+}
+
 /// A text buffer.
 pub struct EditBuffer<I: ApplicationInfo> {
     /// A unique identifier for this buffer.
@@ -132,14 +185,45 @@ pub struct EditBuffer<I: ApplicationInfo> {
     /// Tracks the jumplist for cursor groups within given buffers.
     jumped: AdjustStore<CursorGroupId, HistoryList<CursorGroup>>,
 
+    /// Tracks the most recent selections made by cursor groups.
+    selections: AdjustStore<CursorGroupId, CursorGroup>,
+
     completions: HashMap<CursorGroupId, CompletionList>,
     lines: LineCompleter,
 
+    /// Tracks the offsets of text pasted from the kill ring, so that it can be replaced.
+    yanked: HashMap<CursorGroupId, Vec<(usize, usize)>>,
+
+    /// Tracks any interactive replacements that are in progress.
+    replacing: HashMap<CursorGroupId, QueryReplaceState>,
+
+    /// Tracks what the characters typed over screen columns replaced.
+    overwritten: Vec<Overwritten>,
+
+    /// The most recently changed line, and its contents before it started being changed.
+    lastline: Option<(Cursor, EditRope)>,
+
     history: HistoryList<EditRope>,
-    lineinfo: LineInfoStore<usize>,
+    lineinfo: LineInfoStore,
+
+    /// The folds within the buffer, and what determines the ones not created manually.
+    folds: FoldList,
+    fold_provider: Option<Box<dyn FoldProvider>>,
+    folds_stale: bool,
 
     push_next_change: bool,
 
+    /// When to checkpoint while typing, and what has been typed since the last checkpoint.
+    checkpoint_policy: CheckpointPolicy,
+    typed: usize,
+    typed_space: bool,
+
+    /// How letter case gets treated when searching this buffer.
+    search_case: SearchCase,
+
+    /// How lines in this buffer get indented.
+    indent_options: IndentOptions,
+
     _p: PhantomData<I>,
 }
 
@@ -149,6 +233,7 @@ where
 {
     fn redo(&mut self, count: &Count, ctx: &C, store: &mut Store<I>) -> EditResult<EditInfo, I>;
     fn undo(&mut self, count: &Count, ctx: &C, store: &mut Store<I>) -> EditResult<EditInfo, I>;
+    fn line_undo(&mut self, ctx: &C, store: &mut Store<I>) -> EditResult<EditInfo, I>;
     fn checkpoint(&mut self, ctx: &C, store: &mut Store<I>) -> EditResult<EditInfo, I>;
 }
 
@@ -172,6 +257,7 @@ where
         let history = HistoryList::new(text.clone(), 100);
         let lineinfo = LineInfoStore::new();
         let jumped = AdjustStore::new();
+        let selections = AdjustStore::new();
         let cgidgen = IdGenerator::default();
 
         let changed = VecDeque::new();
@@ -185,11 +271,24 @@ where
             changed,
             changed_idx,
             jumped,
+            selections,
             history,
             lineinfo,
+            folds: FoldList::default(),
+            fold_provider: None,
+            folds_stale: false,
             completions: HashMap::new(),
             lines: LineCompleter::default(),
+            yanked: HashMap::new(),
+            replacing: HashMap::new(),
+            overwritten: vec![],
+            lastline: None,
             push_next_change: true,
+            checkpoint_policy: CheckpointPolicy::default(),
+            typed: 0,
+            typed_space: false,
+            search_case: SearchCase::default(),
+            indent_options: IndentOptions::default(),
             _p: PhantomData,
         }
     }
@@ -348,21 +447,105 @@ where
         Ok(res)
     }
 
+    fn _offset_cursor(&self, cursor: &Cursor, n: isize) -> Cursor {
+        if n == 0 {
+            return cursor.clone();
+        }
+
+        let off = usize::from(self.text.cursor_to_offset(cursor)) as isize;
+        let last = usize::from(self.text.last_offset()) as isize;
+        let off = off.saturating_add(n).clamp(0, last) as usize;
+
+        self.text.offset_to_cursor(off.into())
+    }
+
+    /// Find the `count`th match for `needle` after `cursor`, and apply `offset` to it.
+    ///
+    /// The returned range starts and ends where the cursor should move to, and has the shape
+    /// and inclusivity that the search motion should have.
+    fn _find_regex_offset(
+        &self,
+        cursor: &Cursor,
+        dir: MoveDir1D,
+        needle: &Regex,
+        offset: &SearchOffset,
+        count: usize,
+    ) -> Option<CursorRange> {
+        // Undo any character offset before searching, so that we don't get stuck on the
+        // match that the cursor is already at.
+        let start = match offset {
+            SearchOffset::Start(n) | SearchOffset::End(n) => self._offset_cursor(cursor, -n),
+            SearchOffset::Line(_) => cursor.clone(),
+        };
+
+        let mut from = start.clone();
+
+        if let (SearchOffset::End(_), MoveDir1D::Previous) = (offset, dir) {
+            // Skip over the match that ends at the cursor.
+            if let Some(r) = self.text.find_regex(&start, dir, needle, 1) {
+                if r.end >= start {
+                    from = r.start;
+                }
+            }
+        }
+
+        let m = self.text.find_regex(&from, dir, needle, count)?;
+
+        let range = match offset {
+            SearchOffset::Line(n) => {
+                let last = self.text.get_lines().saturating_sub(1) as isize;
+                let y = (m.start.y as isize).saturating_add(*n).clamp(0, last) as usize;
+                let target = Cursor::new(y, 0);
+
+                CursorRange::exclusive(target.clone(), target, TargetShape::LineWise)
+            },
+            SearchOffset::Start(n) => {
+                let target = self._offset_cursor(&m.start, *n);
+
+                CursorRange::exclusive(target.clone(), target, TargetShape::CharWise)
+            },
+            SearchOffset::End(n) => {
+                let target = self._offset_cursor(&m.end, *n);
+
+                CursorRange::inclusive(target.clone(), target, TargetShape::CharWise)
+            },
+        };
+
+        Some(range)
+    }
+
     fn _regexsearch<C: EditContext>(
         &self,
         cursor: &Cursor,
         flip: &MoveDirMod,
         count: &Count,
         ctx: &C,
-        store: &Store<I>,
+        store: &mut Store<I>,
     ) -> EditResult<Option<CursorRange>, I> {
-        let needle = self._get_regex(ctx, store)?;
-
-        let count = ctx.resolve(count);
+        let mut cursor = cursor.clone();
+        let mut count = ctx.resolve(count);
         let dir = ctx.get_search_regex_dir();
         let dir = flip.resolve(&dir);
 
-        let res = self.text.find_regex(cursor, dir, &needle, count);
+        // Run any searches that were chained before this one in the search bar.
+        for query in store.registers.take_search_chain() {
+            let needle = ctx.compile_search_regex(query.pattern.as_str(), self.search_case)?;
+
+            match self._find_regex_offset(&cursor, query.dir, &needle, &query.offset, count) {
+                Some(r) => cursor = r.start,
+                None => return Ok(None),
+            }
+
+            count = 1;
+        }
+
+        let needle = self._get_regex(ctx, store)?;
+        store.search.set_last_regex(needle.clone());
+
+        let (lsdir, offset) = store.registers.get_last_search_offset();
+        let dir = lsdir.resolve(&dir);
+
+        let res = self._find_regex_offset(&cursor, dir, &needle, &offset, count);
 
         Ok(res)
     }
@@ -386,15 +569,12 @@ where
             .text
             .get_cursor_word_mut(&mut cursor, style)
             .ok_or(EditError::NoCursorWord)?;
-        let word = regex::escape(word.to_string().as_str());
-
-        let needle = if boundary {
-            Regex::new(format!("\\b{word}\\b").as_str())
-        } else {
-            Regex::new(word.as_str())
-        }?;
+        let pattern =
+            ctx.get_word_search_pattern(word.to_string().as_str(), boundary, self.search_case);
+        let needle = ctx.compile_search_regex(pattern.as_str(), self.search_case)?;
 
-        store.set_last_search(needle.to_string());
+        store.set_last_search(pattern);
+        store.search.set_last_regex(needle.clone());
 
         let res = self.text.find_regex(&cursor, dir, &needle, count);
 
@@ -429,17 +609,62 @@ where
         }
 
         let lsearch = store.registers.get(&Register::LastSearch)?.value;
-        let regex = Regex::new(lsearch.to_string().as_ref())?;
+        let regex = ctx.compile_search_regex(lsearch.to_string().as_ref(), self.search_case)?;
 
         return Ok(regex);
     }
 
+    /*
+     * Describe which of the matches for the last search the cursor is on, like "[3/17]".
+     *
+     * Like Vim, this stops counting after SEARCH_COUNT_MAX matches so that searching a large
+     * buffer doesn't need to find all of them, and shows any larger counts as ">99".
+     */
+    fn _search_count(&self, cursor: &Cursor, store: &Store<I>) -> EditInfo {
+        let needle = store.search.get_last_regex()?;
+
+        if self.text.is_empty() {
+            return None;
+        }
+
+        let (idx, total) = self.text.count_matches(cursor, needle, SEARCH_COUNT_MAX + 1);
+
+        if total == 0 {
+            return None;
+        }
+
+        let show = |n: usize| {
+            if n > SEARCH_COUNT_MAX {
+                format!(">{}", SEARCH_COUNT_MAX)
+            } else {
+                n.to_string()
+            }
+        };
+        let msg = format!("[{}/{}]", show(idx.max(1)), show(total));
+
+        Some(InfoMessage::from(msg))
+    }
+
     fn _target<C: EditContext>(
         &mut self,
         state: &CursorState,
         target: &EditTarget,
         ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
         store: &mut Store<I>,
+    ) -> EditResult<Option<CursorRange>, I> {
+        let range = self._target_text(state, target, ctx, store)?;
+        let forced = ctx.context.get_target_shape();
+
+        // Closed folds get acted on as a whole.
+        Ok(range.map(|range| self._unfold_range(range, forced)))
+    }
+
+    fn _target_text<C: EditContext>(
+        &mut self,
+        state: &CursorState,
+        target: &EditTarget,
+        ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
+        store: &mut Store<I>,
     ) -> EditResult<Option<CursorRange>, I> {
         let cursor = state.cursor().clone();
 
@@ -480,10 +705,14 @@ where
                 let range = self._search(&cursor, search, flip, count, ctx.context, store)?;
 
                 let range = range.map(|r| {
-                    let shape = TargetShape::CharWise;
+                    // Regular expression searches determine their own shape and inclusivity,
+                    // since they can depend on the search offset.
+                    let (shape, inclusive) = match search {
+                        SearchType::Regex => (r.shape, r.inclusive),
+                        _ => (TargetShape::CharWise, search.is_inclusive_motion()),
+                    };
 
                     if r.start > cursor {
-                        let inclusive = search.is_inclusive_motion();
                         CursorRange::new(cursor, r.start, shape, inclusive)
                     } else {
                         // Backwards motions are exclusive.
@@ -502,10 +731,10 @@ where
                 return Ok(Some(range));
             },
             EditTarget::Motion(motion, count) => {
-                return Ok(self.text.range_of_movement(&cursor, motion, count, ctx));
+                return Ok(self._range_of_movement(&cursor, motion, count, ctx));
             },
             EditTarget::Range(range, inclusive, count) => {
-                return Ok(self.text.range(&cursor, range, *inclusive, count, ctx));
+                return Ok(self._range(&cursor, range, *inclusive, count, ctx));
             },
         }
     }
@@ -571,6 +800,8 @@ where
         self.cursors.zero();
         self.changed.zero();
         self.jumped.zero();
+        self.selections.zero();
+        self.folds.clear();
     }
 
     fn _zero(&mut self, store: &mut Store<I>) {
@@ -582,6 +813,17 @@ where
         self.cursors.adjust(adjs);
         self.changed.adjust(adjs);
         self.jumped.adjust(adjs);
+        self.selections.adjust(adjs);
+        self.lineinfo.adjust(adjs);
+        self.folds.adjust(adjs);
+        self.overwritten.adjust(adjs);
+
+        if let Some((line, _)) = &mut self.lastline {
+            if line_removed(line, adjs) {
+                // Like Vim's u_clearline(), forget the line once it's gone.
+                self.lastline = None;
+            }
+        }
 
         for completion in self.completions.values_mut() {
             completion.adjust(adjs);
@@ -594,6 +836,65 @@ where
         self._adjust(adjs.as_slice(), store);
     }
 
+    /// Remember a cursor group's selections, and update the [Mark::VisualBegin] and
+    /// [Mark::VisualEnd] marks to match its leader.
+    fn _set_last_selection(
+        &mut self,
+        gid: CursorGroupId,
+        group: CursorGroup,
+        store: &mut Store<I>,
+    ) {
+        let id = self.id.clone();
+
+        store
+            .cursors
+            .set_mark(id.clone(), Mark::VisualBegin, group.leader.start().clone());
+        store.cursors.set_mark(id, Mark::VisualEnd, group.leader.end().clone());
+
+        self.selections.put(gid, group);
+    }
+
+    /// Get the first and last characters covered by a range, as it would be acted on.
+    fn _range_bounds(&self, range: &CursorRange, forced: Option<TargetShape>) -> (Cursor, Cursor) {
+        let (_, ranges) = self._effective(range, forced);
+
+        let (start, end) = match (ranges.first(), ranges.last()) {
+            (Some((start, _, _)), Some((_, end, inclusive))) => {
+                let end = if !inclusive && end > start {
+                    *end - CharOff::from(1)
+                } else {
+                    *end
+                };
+
+                (*start, end)
+            },
+            _ => return (range.start.clone(), range.end.clone()),
+        };
+
+        (self.text.offset_to_cursor(start), self.text.offset_to_cursor(end))
+    }
+
+    /// Update the [Mark::LastYankedBegin] and [Mark::LastYankedEnd] marks to surround the most
+    /// recently yanked or changed text.
+    fn _set_change_marks(&self, start: Cursor, end: Cursor, store: &mut Store<I>) {
+        let id = self.id.clone();
+
+        store.cursors.set_mark(id.clone(), Mark::LastYankedBegin, start);
+        store.cursors.set_mark(id, Mark::LastYankedEnd, end);
+    }
+
+    /// Remember the contents of the leader's line before it gets changed, if it isn't the line
+    /// that was most recently changed.
+    fn _remember_line(&mut self, gid: CursorGroupId) {
+        let y = self.get_leader(gid).y;
+
+        if matches!(&self.lastline, Some((line, _)) if line.y == y) {
+            return;
+        }
+
+        self.lastline = self.text.get_line(y).map(|text| (Cursor::new(y, 0), text));
+    }
+
     fn _adjust_columns(
         &mut self,
         line: usize,
@@ -648,7 +949,7 @@ where
     ) -> EditResult<EditInfo, I> {
         let shape = ictx.2.get_target_shape();
 
-        if shape.is_some() {
+        if shape.is_some() || ictx.2.is_selecting() {
             return self.selection_resize(&SelectionResizeStyle::Extend, target, ictx, store);
         }
 
@@ -690,7 +991,7 @@ where
                     state.set_cursor(nc);
                 },
                 EditTarget::Motion(mv, count) => {
-                    if let Some(nc) = self.text.movement(cursor, mv, count, &ctx) {
+                    if let Some(nc) = self._movement(cursor, mv, count, &ctx) {
                         state.set_cursor(nc);
                     }
                 },
@@ -709,9 +1010,44 @@ where
             }
         }
 
+        let info = match target {
+            EditTarget::Search(SearchType::Regex | SearchType::Word(_, _), _, _) => {
+                self._search_count(group.leader.cursor(), store)
+            },
+            _ => None,
+        };
+
         self.set_group(gid, group);
 
-        Ok(None)
+        Ok(info)
+    }
+
+    /// Set when typing text into this buffer should create undo checkpoints.
+    pub fn set_checkpoint_policy(&mut self, policy: CheckpointPolicy) {
+        self.checkpoint_policy = policy;
+    }
+
+    /// Set how letter case gets treated when searching this buffer, corresponding to Vim's
+    /// `'ignorecase'` and `'smartcase'` options.
+    pub fn set_search_case(&mut self, case: SearchCase) {
+        self.search_case = case;
+    }
+
+    /// Get how letter case gets treated when searching this buffer.
+    pub fn get_search_case(&self) -> SearchCase {
+        self.search_case
+    }
+
+    /// Set how lines in this buffer get shifted and automatically indented, and how wide its
+    /// tabs are.
+    pub fn set_indent_options(&mut self, options: IndentOptions) {
+        self.indent_options = options;
+        self._refresh_folds(None);
+    }
+
+    /// Get the options for how lines in this buffer get indented.
+    pub fn get_indent_options(&self) -> &IndentOptions {
+        &self.indent_options
     }
 
     /// Indicates whether this buffer contains only whitespace.
@@ -742,8 +1078,13 @@ where
         // XXX: Need to zero out global marks on rope change.
         self._zero_local();
 
-        // Any existing completions are now invalid.
+        // Any existing completions, pasted text offsets and replacements are now invalid.
         self.completions.clear();
+        self.yanked.clear();
+        self.replacing.clear();
+        self.overwritten.clear();
+        self.lastline = None;
+        self._refresh_folds(None);
 
         return rope;
     }
@@ -760,6 +1101,7 @@ where
         let start = self.get_lines();
         self.text += t.into();
         self.text.trailing_newline();
+        self._refresh_folds(None);
         let end = self.get_lines();
 
         Range { start, end }
@@ -792,6 +1134,14 @@ where
         self.text.get_cursor_word(cursor, style).map(|r| r.to_string())
     }
 
+    /// Returns the line that the cursor group's leader is currently positioned on.
+    pub fn get_cursor_line(&self, gid: CursorGroupId) -> Option<String> {
+        let group = self.cursors.get(gid)?;
+        let line = self.text.lines(group.leader.cursor().y).next()?;
+
+        line.to_string().trim_end_matches('\n').to_string().into()
+    }
+
     /// Returns the text currently selected by the cursor group's leader.
     pub fn get_selected_word(&self, gid: CursorGroupId) -> Option<String> {
         let group = self.cursors.get(gid)?;
@@ -950,6 +1300,81 @@ where
         self.lineinfo.set(line, info);
     }
 
+    /// Remove the information of type `T` on a given line, and return it if there was any.
+    pub fn remove_line_info<T: Send + Sync + 'static>(&mut self, line: usize) -> Option<T> {
+        self.lineinfo.remove(&line)
+    }
+
+    /// Control what happens to the information of type `T` on a line when the line gets deleted.
+    pub fn set_line_info_policy<T: Send + Sync + 'static>(&mut self, policy: LineDeletePolicy) {
+        self.lineinfo.set_delete_policy::<T>(policy);
+    }
+
+    /// Attach information of type `T` to the text from `start` up to, but not including, `end`,
+    /// and return an identifier that can be used to remove it later.
+    pub fn add_range_info<T: Send + Sync + 'static>(
+        &mut self,
+        start: Cursor,
+        end: Cursor,
+        info: T,
+    ) -> RangeId {
+        self.lineinfo.add_range(start, end, info)
+    }
+
+    /// Remove the information of type `T` attached to a range of text by
+    /// [add_range_info](Self::add_range_info), and return it if it hasn't already been removed.
+    pub fn remove_range_info<T: Send + Sync + 'static>(
+        &mut self,
+        id: RangeId,
+    ) -> Option<RangeInfo<T>> {
+        self.lineinfo.remove_range(id)
+    }
+
+    /// Fetch all of the ranges of text that have information of type `T` attached, sorted by
+    /// where they start.
+    pub fn get_range_info<T: Send + Sync + 'static>(&self) -> &[RangeInfo<T>] {
+        self.lineinfo.get_ranges()
+    }
+
+    /// Fetch the ranges of text starting on a given line that have information of type `T`
+    /// attached.
+    pub fn get_line_range_info<T: Send + Sync + 'static>(&self, line: usize) -> &[RangeInfo<T>] {
+        self.lineinfo.get_line_ranges(line)
+    }
+
+    /// Account for the space taken up on screen by the information of type `T` attached to
+    /// ranges of text, when moving by screen lines or to positions within the viewport.
+    pub fn set_range_info_space<T: VirtualSpace + Send + Sync + 'static>(&mut self) {
+        self.lineinfo.set_virtual_space::<T>();
+    }
+
+    /// Remove the information of type `T` from all ranges of text, and return it.
+    pub fn clear_range_info<T: Send + Sync + 'static>(&mut self) -> Vec<RangeInfo<T>> {
+        self.lineinfo.clear_ranges()
+    }
+
+    /// Set what determines the folds within this buffer, alongside the ones created manually.
+    pub fn set_fold_provider(&mut self, provider: Option<Box<dyn FoldProvider>>) {
+        self.fold_provider = provider;
+        self.folds.provide(vec![], None);
+        self._refresh_folds(None);
+    }
+
+    /// Get all of the folds within this buffer, with outer folds before the ones nested in them.
+    pub fn get_folds(&self) -> &[Fold] {
+        self.folds.get()
+    }
+
+    /// Get the outermost closed fold containing a given line, which the line is displayed as.
+    pub fn get_closed_fold(&self, line: usize) -> Option<&Fold> {
+        self.folds.closed_at(line)
+    }
+
+    /// Get how many folds contain a given line.
+    pub fn fold_level(&self, line: usize) -> usize {
+        self.folds.level(line)
+    }
+
     /// Clamp the line and column of the cursors in a [CursorState] so that they refer to a valid
     /// point within the buffer.
     pub fn clamp_state<C: EditContext>(
@@ -1014,6 +1439,47 @@ where
             .collect()
     }
 
+    /*
+     * Find the matches of any search within `lines`, along with the match that the cursor would
+     * jump to if this is an incremental search.
+     */
+    #[cfg(feature = "intervaltree")]
+    pub(crate) fn _search_intervals(
+        &self,
+        cursor: &Cursor,
+        hl: Option<&SearchHighlight>,
+        lines: Range<usize>,
+    ) -> (HighlightInfo, HighlightInfo) {
+        let tohl =
+            |r: CursorRange| (r.start.y..r.end.y.saturating_add(1), (r.start, r.end, r.shape));
+
+        let matches = match hl {
+            Some(hl) if !self.text.is_empty() && !lines.is_empty() => {
+                let start = Cursor::new(lines.start, 0);
+                let end = if lines.end <= self.text.get_lines() {
+                    let off: usize = self.text.cursor_to_offset(&Cursor::new(lines.end, 0)).into();
+                    self.text.offset_to_cursor(off.saturating_sub(1).into())
+                } else {
+                    self.text.last()
+                };
+
+                self.text.find_matches(&start, &end, &hl.needle)
+            },
+            _ => vec![],
+        };
+
+        let current = hl.and_then(|hl| {
+            let (dir, count) = hl.incremental?;
+
+            self.text.find_regex(cursor, dir, &hl.needle, count)
+        });
+
+        let matches = matches.into_iter().map(tohl).collect();
+        let current = current.into_iter().map(tohl).collect();
+
+        (matches, current)
+    }
+
     #[cfg(feature = "intervaltree")]
     pub(crate) fn _follower_intervals(&self, gid: CursorGroupId) -> FollowersInfo {
         self.get_followers(gid)
@@ -1023,6 +1489,25 @@ where
     }
 }
 
+impl<I> ScreenLayout for EditBuffer<I>
+where
+    I: ApplicationInfo,
+{
+    fn layout(&self, line: usize, wrap: Option<usize>) -> Option<LineLayout> {
+        if line >= self.text.get_lines() {
+            return None;
+        }
+
+        let s = self.text.lines(line).next()?;
+        let tabstop = self.indent_options.tabstop;
+        let space = self.lineinfo.virtual_space(line);
+        let mut layout = LineLayout::new(&s, tabstop, wrap, &space.inline);
+        layout.above = space.above;
+
+        Some(layout)
+    }
+}
+
 impl<'a, 'b, C, I> HistoryActions<CursorGroupIdContext<'a, 'b, C>, I> for EditBuffer<I>
 where
     C: EditContext,
@@ -1060,11 +1545,53 @@ where
         Ok(None)
     }
 
+    fn line_undo(
+        &mut self,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let (line, text) = self
+            .lastline
+            .take()
+            .ok_or_else(|| EditError::Failure("No line to undo".into()))?;
+        let y = line.y;
+
+        let current = self
+            .text
+            .get_line(y)
+            .ok_or_else(|| EditError::Failure("No line to undo".into()))?;
+        let start = self.text.offset_of_line(y);
+        let end = start + current.len_offset();
+
+        let (_, adjs) = self.text.replace(start, end, false, text);
+        self._adjust_all(adjs, store);
+
+        // Remember what we replaced, so that doing this again undoes it.
+        self.lastline = Some((line, current));
+
+        let mut leader = self.get_leader(ctx.0);
+        leader.set_y(y);
+        self.clamp(&mut leader, ctx);
+        self.set_leader(ctx.0, leader);
+
+        Ok(None)
+    }
+
     fn checkpoint(
         &mut self,
-        _: &CursorGroupIdContext<'a, 'b, C>,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
+        if self.folds_stale {
+            let line = self.get_leader(ctx.0).y;
+            self._refresh_folds(Some(line));
+        }
+
+        if ctx.2.get_insert_style().is_none() {
+            // Typed text can't be restored once Insert mode is over.
+            self.overwritten.clear();
+        }
+
         if &self.text != self.history.current() {
             // First, increment lines that are in the latest rope.
             for line in self.text.lines(0).map(Cow::from) {
@@ -1080,6 +1607,8 @@ where
 
             self.history.push(self.text.clone());
             self.push_next_change = true;
+            self.typed = 0;
+            self.typed_space = false;
         } else if self.lines.is_empty() {
             // Generate completions on first checkpoint.
             for line in self.text.lines(0).map(Cow::from) {
@@ -1116,7 +1645,12 @@ where
         let mut group = self.get_group(gid);
 
         for state in group.iter_mut() {
-            let choice = match (self._target(state, target, ctx, store)?, action) {
+            let range = self._target(state, target, ctx, store)?;
+            let bounds = range
+                .as_ref()
+                .map(|range| self._range_bounds(range, ctx.context.get_target_shape()));
+
+            let choice = match (range, action) {
                 (Some(range), EditAction::Delete) => self.delete(&range, ctx, store)?,
                 (Some(range), EditAction::Yank) => self.yank(&range, ctx, store)?,
                 (Some(range), EditAction::Replace(v)) => {
@@ -1134,7 +1668,9 @@ where
                         },
                     }
                 },
-                (Some(range), EditAction::Format) => self.format(&range, ctx, store)?,
+                (Some(range), EditAction::Format) => {
+                    self.format(&range, state.cursor(), ctx, store)?
+                },
                 (Some(range), EditAction::ChangeCase(case)) => {
                     self.changecase(case, &range, ctx, store)?
                 },
@@ -1145,12 +1681,35 @@ where
                     self.join(*spaces, &range, ctx, store)?
                 },
                 (Some(range), EditAction::Indent(change)) => {
-                    self.indent(change, &range, ctx, store)?
+                    self.indent(change, &range, state.cursor(), ctx, store)?
+                },
+                (Some(range), EditAction::Prefix(prefix)) => {
+                    self.prefix(prefix, &range, ctx, store)?
                 },
+                (Some(range), EditAction::Fold) => self._fold_range(&range),
                 (Some(_), EditAction::Motion) => panic!("Unexpected EditAction::Motion!"),
                 (None, _) => CursorChoice::Empty,
             };
 
+            if let Some((start, end)) = bounds {
+                // Remember what was just yanked or changed.
+                let end = match (action, &choice) {
+                    (EditAction::Yank, _) => end,
+                    (EditAction::Delete, _) => start.clone(),
+                    (_, CursorChoice::Range(_, end, _)) => end.clone(),
+                    (_, _) => end,
+                };
+
+                if !action.is_readonly() {
+                    store.cursors.set_mark(self.id.clone(), Mark::LastChanged, start.clone());
+                }
+
+                // Folding doesn't yank or change any text.
+                if !matches!(action, EditAction::Fold) {
+                    self._set_change_marks(start, end, store);
+                }
+            }
+
             if let Some(cursor) = choice.resolve(end) {
                 state.set(cursor);
                 self.clamp_state(state, ictx);
@@ -1176,6 +1735,51 @@ where
         Ok(None)
     }
 
+    fn mark_ring(
+        &mut self,
+        act: &MarkRingAction,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let gid = ctx.0;
+        self.completions.remove(&gid);
+
+        let cursor = match act {
+            MarkRingAction::Push => {
+                let leader = self.get_leader(gid);
+                store.cursors.push_mark(self.id.clone(), leader);
+
+                return Ok(None);
+            },
+            MarkRingAction::Pop => store.cursors.pop_mark(self.id.clone())?,
+            MarkRingAction::PopGlobal => store.cursors.pop_global_mark(self.id.clone())?,
+        };
+
+        let mut group = self.get_group(gid);
+        group.leader.unselect();
+        group.leader.set_cursor(cursor);
+        self.clamp_state(&mut group.leader, ctx);
+        self.set_group(gid, group);
+
+        Ok(None)
+    }
+
+    fn query_replace(
+        &mut self,
+        act: &QueryReplaceAction,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        self.completions.remove(&ctx.0);
+
+        match act {
+            QueryReplaceAction::Start(needle, replacement, regex) => {
+                self.query_replace_start(needle, replacement, *regex, ctx, store)
+            },
+            act => self.query_replace_step(act, ctx, store),
+        }
+    }
+
     fn complete(
         &mut self,
         comptype: &CompletionType,
@@ -1193,6 +1797,10 @@ where
             CompletionType::Word(scope) => {
                 self.complete_word(scope, selection, display, ctx, store)
             },
+            CompletionType::Dictionary => self.complete_dictionary(selection, display, ctx, store),
+            CompletionType::Application => {
+                self.complete_application(selection, display, ctx, store)
+            },
         }
     }
 
@@ -1204,7 +1812,7 @@ where
     ) -> EditResult<EditInfo, I> {
         self.completions.remove(&ctx.0);
 
-        match act {
+        let res = match act {
             InsertTextAction::OpenLine(shape, dir, count) => {
                 self.open_line(*shape, *dir, count, ctx, store)
             },
@@ -1219,7 +1827,20 @@ where
                     Ok(None)
                 }
             },
+            InsertTextAction::YankPop(dir, count) => self.yank_pop(*dir, count, ctx, store),
+        }?;
+
+        // Remember where the text went.
+        let id = self.id.clone();
+        let leader = self.get_leader(ctx.0);
+
+        if ctx.2.get_insert_style().is_some() {
+            store.cursors.set_mark(id.clone(), Mark::LastInserted, leader.clone());
         }
+
+        store.cursors.set_mark(id, Mark::LastChanged, leader);
+
+        Ok(res)
     }
 
     fn selection_command(
@@ -1240,6 +1861,7 @@ where
             },
             SelectionAction::Filter(drop) => self.selection_filter(*drop, ctx, store),
             SelectionAction::Join => self.selection_join(ctx, store),
+            SelectionAction::Restore => self.selection_restore(ctx, store),
             SelectionAction::Resize(style, target) => {
                 self.selection_resize(style, target, ctx, store)
             },
@@ -1269,6 +1891,20 @@ where
         }
     }
 
+    fn fold(
+        &mut self,
+        act: &FoldAction,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        self.completions.remove(&ctx.0);
+
+        match act {
+            FoldAction::Create(range) => self.fold_create(range, ctx, store),
+            act => self.fold_update(act, ctx),
+        }
+    }
+
     fn history_command(
         &mut self,
         act: &HistoryAction,
@@ -1279,6 +1915,7 @@ where
 
         match act {
             HistoryAction::Checkpoint => self.checkpoint(ctx, store),
+            HistoryAction::LineUndo => self.line_undo(ctx, store),
             HistoryAction::Undo(count) => self.undo(count, ctx, store),
             HistoryAction::Redo(count) => self.redo(count, ctx, store),
         }
@@ -1296,7 +1933,25 @@ where
         ctx: &CursorGroupIdContext<'a, 'b, C>,
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
-        match act {
+        if !matches!(
+            act,
+            EditorAction::InsertText(InsertTextAction::Paste(..) | InsertTextAction::YankPop(..))
+        ) {
+            // Anything else breaks up a sequence of pastes from the kill ring.
+            self.yanked.remove(&ctx.0);
+        }
+
+        let gid = ctx.0;
+
+        // Remember what the line looked like before changing it, so that it can be restored.
+        if !act.is_readonly(ctx.2) && !matches!(act, EditorAction::History(_)) {
+            self._remember_line(gid);
+        }
+
+        // Remember the selections if this action ends them, so that they can be restored.
+        let selected = self.get_leader_state(gid).is_selection().then(|| self.get_group(gid));
+
+        let res = match act {
             EditorAction::Edit(ea, et) => {
                 let ea = ctx.2.resolve(ea);
 
@@ -1304,13 +1959,34 @@ where
             },
 
             EditorAction::Cursor(act) => self.cursor_command(act, ctx, store),
+            EditorAction::Fold(act) => self.fold(act, ctx, store),
             EditorAction::History(act) => self.history_command(act, ctx, store),
             EditorAction::InsertText(act) => self.insert_text(act, ctx, store),
             EditorAction::Mark(name) => self.mark(ctx.2.resolve(name), ctx, store),
+            EditorAction::MarkRing(act) => self.mark_ring(act, ctx, store),
+            EditorAction::QueryReplace(act) => self.query_replace(act, ctx, store),
             EditorAction::Selection(act) => self.selection_command(act, ctx, store),
 
             EditorAction::Complete(ct, sel, disp) => self.complete(ct, sel, disp, ctx, store),
+            EditorAction::ClearSearchHighlight => {
+                store.search.nohlsearch();
+
+                Ok(None)
+            },
+        };
+
+        if let Some(group) = selected {
+            if !self.get_leader_state(gid).is_selection() {
+                self._set_last_selection(gid, group, store);
+            }
         }
+
+        if !act.is_readonly(ctx.2) {
+            // Provided folds get recalculated at the next checkpoint.
+            self.folds_stale = true;
+        }
+
+        return res;
     }
 }
 
@@ -1860,6 +2536,146 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(4, 4));
     }
 
+    #[test]
+    fn test_special_marks() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("hello world\nfoo bar\n");
+        let id = ebuf.id();
+        let word = MoveType::WordBegin(WordStyle::Little, MoveDir1D::Next);
+
+        macro_rules! special {
+            ($mark: expr) => {
+                store.cursors.get_mark(id.clone(), $mark).unwrap()
+            };
+        }
+
+        // Yanking "hello " sets '[ and '], but not '.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, EditAction::Yank, mv!(word.clone()), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(special!(Mark::LastYankedBegin), Cursor::new(0, 0));
+        assert_eq!(special!(Mark::LastYankedEnd), Cursor::new(0, 5));
+        assert!(store.cursors.get_mark(id.clone(), Mark::LastChanged).is_err());
+
+        // Pasting it after "foo " surrounds the pasted text.
+        ebuf.set_leader(gid, Cursor::new(1, 3));
+        let paste = InsertTextAction::Paste(PasteStyle::Side(MoveDir1D::Next), Count::Exact(1));
+        ebuf.insert_text(&paste, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "hello world\nfoo hello bar\n");
+        assert_eq!(special!(Mark::LastYankedBegin), Cursor::new(1, 4));
+        assert_eq!(special!(Mark::LastYankedEnd), Cursor::new(1, 9));
+        assert_eq!(special!(Mark::LastChanged), Cursor::new(1, 9));
+
+        // Deleting "world" leaves all three at the start of the change.
+        ebuf.set_leader(gid, Cursor::new(0, 6));
+        edit!(ebuf, EditAction::Delete, mv!(word.clone()), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "hello \nfoo hello bar\n");
+        assert_eq!(special!(Mark::LastChanged), Cursor::new(0, 6));
+        assert_eq!(special!(Mark::LastYankedBegin), Cursor::new(0, 6));
+        assert_eq!(special!(Mark::LastYankedEnd), Cursor::new(0, 6));
+
+        // Typing in Insert mode updates '^ and '.
+        vctx.persist.insert = Some(InsertStyle::Insert);
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+
+        let typed = InsertTextAction::Type(Char::from('x').into(), MoveDir1D::Previous, 1.into());
+        ebuf.insert_text(&typed, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "hello \nxfoo hello bar\n");
+        assert_eq!(special!(Mark::LastInserted), Cursor::new(1, 1));
+        assert_eq!(special!(Mark::LastChanged), Cursor::new(1, 1));
+
+        // Inserting a line above moves all of the marks down.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+
+        let typed = InsertTextAction::Type(Char::from('\n').into(), MoveDir1D::Previous, 1.into());
+        ebuf.insert_text(&typed, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "\nhello \nxfoo hello bar\n");
+        assert_eq!(special!(Mark::LastInserted), Cursor::new(1, 0));
+        assert_eq!(special!(Mark::LastYankedBegin), Cursor::new(1, 6));
+        assert_eq!(special!(Mark::LastYankedEnd), Cursor::new(1, 6));
+
+        // Deleting the blank line in Normal mode moves '^ back up.
+        vctx.persist.insert = None;
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        let lines = EditTarget::Range(RangeType::Line, true, Count::Exact(1));
+        edit!(ebuf, EditAction::Delete, lines, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "hello \nxfoo hello bar\n");
+        assert_eq!(special!(Mark::LastInserted), Cursor::new(0, 0));
+        assert_eq!(special!(Mark::LastChanged), Cursor::new(0, 0));
+
+        // Creating a fold doesn't touch any of them.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(
+            ebuf,
+            EditAction::Fold,
+            mv!(MoveType::Line(MoveDir1D::Next)),
+            ctx!(gid, vwctx, vctx),
+            store
+        );
+        assert_eq!(special!(Mark::LastYankedBegin), Cursor::new(0, 0));
+        assert_eq!(special!(Mark::LastYankedEnd), Cursor::new(0, 0));
+        assert_eq!(special!(Mark::LastChanged), Cursor::new(0, 0));
+    }
+
+    #[test]
+    fn test_mark_ring() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) =
+            mkfivestr("12345\n67890\nabcde\nfghij\nklmno\n");
+
+        let push = MarkRingAction::Push;
+        let pop = MarkRingAction::Pop;
+        let pop_global = MarkRingAction::PopGlobal;
+
+        // Popping an empty mark ring fails.
+        let res = ebuf.mark_ring(&pop, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(res.is_err());
+
+        // Push several positions onto the ring.
+        ebuf.set_leader(gid, Cursor::new(0, 2));
+        ebuf.mark_ring(&push, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.set_leader(gid, Cursor::new(2, 3));
+        ebuf.mark_ring(&push, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.set_leader(gid, Cursor::new(4, 1));
+        ebuf.mark_ring(&push, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+
+        // Typing a newline before the marks moves them down.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        type_char!(ebuf, '\n', gid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "12345\n\n67890\nabcde\nfghij\nklmno\n");
+
+        // Popping cycles through the ring, starting with the most recent position.
+        ebuf.mark_ring(&pop, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(5, 1));
+        ebuf.mark_ring(&pop, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(3, 3));
+        ebuf.mark_ring(&pop, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
+        ebuf.mark_ring(&pop, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(5, 1));
+
+        // Only the first push in this buffer went onto the global ring.
+        ebuf.mark_ring(&pop_global, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
+
+        // Pushing in another buffer adds to the global ring.
+        let mut other = EditBuffer::new("other".to_string());
+        let ogid = other.create_group();
+        other.set_text("hello\nworld\n");
+        other.set_leader(ogid, Cursor::new(1, 2));
+        other.mark_ring(&push, ctx!(ogid, vwctx, vctx), &mut store).unwrap();
+        other.set_leader(ogid, Cursor::new(0, 0));
+
+        // The global ring now refers to the other buffer.
+        let res = ebuf.mark_ring(&pop_global, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(matches!(res, Err(EditError::WrongBuffer(id)) if id == "other"));
+
+        other.mark_ring(&pop_global, ctx!(ogid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(other.get_leader(ogid), Cursor::new(1, 2));
+
+        // After rotating, we're back to this buffer's entry.
+        ebuf.set_leader(gid, Cursor::new(4, 4));
+        ebuf.mark_ring(&pop_global, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
+    }
+
     #[test]
     fn test_changelist() {
         let (mut ebuf, gid, vwctx, vctx, mut store) =
@@ -2143,6 +2959,65 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
     }
 
+    #[test]
+    fn test_search_count() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("a b a\nb a\n");
+
+        let op = EditAction::Motion;
+        let mv = EditTarget::Search(SearchType::Regex, MoveDirMod::Same, Count::Contextual);
+
+        store.set_last_search("a");
+
+        let res = edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 4));
+        assert_eq!(res, Some(InfoMessage::from("[2/3]")));
+
+        // Counting stops after 99 matches.
+        ebuf.set_text("a".repeat(150));
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+
+        let res = edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 1));
+        assert_eq!(res, Some(InfoMessage::from("[2/>99]")));
+
+        ebuf.set_leader(gid, Cursor::new(0, 98));
+
+        let res = edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 99));
+        assert_eq!(res, Some(InfoMessage::from("[>99/>99]")));
+    }
+
+    #[test]
+    fn test_search_regex_vim_syntax() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) =
+            mkfivestr("hello world\nhelp helm writhe\nwhisk helium\n");
+
+        let op = EditAction::Motion;
+        let mv = EditTarget::Search(SearchType::Regex, MoveDirMod::Same, Count::Contextual);
+
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        vctx.action.count = Some(1);
+
+        // Vim's syntax for word boundaries, groups and alternation gets translated.
+        store.set_last_search("\\<\\(helm\\|whisk\\)\\>");
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 5));
+
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+
+        // Very magic patterns work too, and "^" only matches at the start of a line.
+        store.set_last_search("\\v^h(e|i)l{2}");
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
+
+        // Unsupported constructs produce an error.
+        store.set_last_search("hel\\zsp");
+        let res = ebuf.edit(&op, &mv, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(matches!(res, Err(EditError::InvalidRegex(_))));
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
+    }
+
     #[test]
     fn test_search_word_bound() {
         let (mut ebuf, gid, vwctx, mut vctx, mut store) =
@@ -2163,6 +3038,10 @@ mod tests {
         edit!(ebuf, op, word, ctx!(gid, vwctx, vctx), store);
         assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 9));
 
+        // The pattern saved for repeating the search uses Vim's syntax.
+        let lsearch = store.registers.get(&Register::LastSearch).unwrap().value;
+        assert_eq!(lsearch.to_string(), "\\<hello\\>");
+
         vctx.persist.regexsearch_dir = MoveDir1D::Previous;
 
         vctx.action.count = Some(1);
@@ -2221,6 +3100,153 @@ mod tests {
         assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 9));
     }
 
+    #[test]
+    fn test_search_case() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) =
+            mkfivestr("foo bar\nFoo baz foo\nbar FOO\n");
+
+        let op = EditAction::Motion;
+        let mv = EditTarget::Search(SearchType::Regex, MoveDirMod::Same, Count::Contextual);
+
+        vctx.action.count = Some(1);
+        store.set_last_search("foo");
+
+        // Searches are case sensitive by default.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 8));
+
+        // Ignore case.
+        ebuf.set_search_case(SearchCase::Insensitive);
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 0));
+
+        // Smart case ignores case when the pattern is all lowercase...
+        ebuf.set_search_case(SearchCase::Smart);
+        ebuf.set_leader(gid, Cursor::new(1, 8));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 4));
+
+        // ...but not when it contains uppercase letters.
+        store.set_last_search("FOO");
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 4));
+
+        // Searching for the word under the cursor doesn't use smart case.
+        let word = EditTarget::Search(
+            SearchType::Word(WordStyle::Little, true),
+            MoveDirMod::Same,
+            Count::Contextual,
+        );
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        edit!(ebuf, op, word, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 8));
+
+        // And repeating it keeps ignoring case.
+        let lsearch = store.registers.get(&Register::LastSearch).unwrap().value;
+        assert_eq!(lsearch.to_string(), "\\c\\<Foo\\>");
+
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 4));
+    }
+
+    #[test]
+    fn test_search_offsets() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) =
+            mkfivestr("hello world\nhelp helm writhe\nwhisk helium\n");
+
+        let op = EditAction::Motion;
+        let mv = EditTarget::Search(SearchType::Regex, MoveDirMod::Same, Count::Contextual);
+
+        macro_rules! search {
+            ($text: expr, $dir: expr) => {
+                vctx.persist.regexsearch_dir = $dir;
+                store.set_last_search_queries($text, vctx.get_search_queries($text, $dir));
+            };
+        }
+
+        vctx.action.count = Some(1);
+
+        // Move to the end of the match, and then keep going with "n".
+        search!("he/e", MoveDir1D::Next);
+        ebuf.set_leader(gid, Cursor::new(0, 6));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 1));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 6));
+
+        // Offsets from the start of the match don't get stuck on the current match.
+        search!("he/b+1", MoveDir1D::Next);
+        ebuf.set_leader(gid, Cursor::new(0, 6));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 1));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 6));
+
+        // Searching backwards for the end of a match skips the one we're at the end of.
+        search!("he?e", MoveDir1D::Previous);
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 1));
+
+        // Line offsets move to the first column.
+        search!("helm/+1", MoveDir1D::Next);
+        ebuf.set_leader(gid, Cursor::new(0, 3));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+
+        // Chained searches run one after the other.
+        search!("help/;/he", MoveDir1D::Next);
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 5));
+
+        // Only the last search gets repeated.
+        let lsearch = store.registers.get(&Register::LastSearch).unwrap().value;
+        assert_eq!(lsearch.to_string(), "he");
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 14));
+
+        // Empty patterns reuse the previous one.
+        search!("/;//", MoveDir1D::Next);
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
+
+        // When the last search goes the other way, repeating it does too.
+        search!("hel/;?he", MoveDir1D::Next);
+        ebuf.set_leader(gid, Cursor::new(0, 6));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 0));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 6));
+    }
+
+    #[test]
+    fn test_search_offsets_operator() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) =
+            mkfivestr("hello world\nhelp helm writhe\nwhisk helium\n");
+
+        let op = EditAction::Delete;
+        let mv = EditTarget::Search(SearchType::Regex, MoveDirMod::Same, Count::Contextual);
+
+        vctx.action.count = Some(1);
+
+        // An offset from the end of the match makes the motion inclusive.
+        let text = "wor/e";
+        store.set_last_search_queries(text, vctx.get_search_queries(text, MoveDir1D::Next));
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "ld\nhelp helm writhe\nwhisk helium\n");
+
+        // A line offset makes the motion linewise.
+        let text = "helium/-1";
+        store.set_last_search_queries(text, vctx.get_search_queries(text, MoveDir1D::Next));
+        ebuf.set_leader(gid, Cursor::new(0, 1));
+        edit!(ebuf, op, mv, ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "whisk helium\n");
+    }
+
     #[test]
     fn test_history() {
         let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfive();
@@ -2272,6 +3298,98 @@ mod tests {
         // XXX: need to test that marks also get adjusted.
     }
 
+    #[test]
+    fn test_line_undo() {
+        let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("hello world\nfoo bar\n");
+        let word = MoveType::WordBegin(WordStyle::Little, MoveDir1D::Next);
+        let delete = EditorAction::Edit(EditAction::Delete.into(), mv!(word));
+        let typed = EditorAction::InsertText(InsertTextAction::Type(
+            Char::from('x').into(),
+            MoveDir1D::Previous,
+            1.into(),
+        ));
+        let lineundo = EditorAction::History(HistoryAction::LineUndo);
+
+        // Nothing has been changed yet.
+        let res = ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(res.is_err());
+
+        // Make several changes to the first line.
+        ebuf.editor_command(&delete, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        vctx.persist.insert = Some(InsertStyle::Insert);
+        ebuf.editor_command(&typed, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        vctx.persist.insert = None;
+        ebuf.checkpoint(ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "xworld\nfoo bar\n");
+
+        // Undo all of them at once.
+        ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        ebuf.checkpoint(ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "hello world\nfoo bar\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 1));
+
+        // Doing it again undoes the line undo.
+        ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "xworld\nfoo bar\n");
+
+        ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "hello world\nfoo bar\n");
+
+        // It's a normal change that can be undone.
+        ebuf.undo(&1.into(), ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "xworld\nfoo bar\n");
+
+        // Changing another line forgets about the first one.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        ebuf.editor_command(&delete, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "xworld\nbar\n");
+
+        // Opening a line above it moves the remembered line down.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        let open = InsertTextAction::OpenLine(TargetShape::LineWise, MoveDir1D::Previous, 1.into());
+        ebuf.insert_text(&open, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "\nxworld\nbar\n");
+
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "\nxworld\nfoo bar\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(2, 0));
+    }
+
+    #[test]
+    fn test_line_undo_removed() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("hello world\nfoo bar\nbaz\n");
+        let word = MoveType::WordBegin(WordStyle::Little, MoveDir1D::Next);
+        let delete = EditorAction::Edit(EditAction::Delete.into(), mv!(word));
+        let lineundo = EditorAction::History(HistoryAction::LineUndo);
+
+        // Deleting the changed line forgets about it.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        ebuf.editor_command(&delete, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "hello world\nbar\nbaz\n");
+
+        edit!(ebuf, EditAction::Delete, range!(RangeType::Line), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "hello world\nbaz\n");
+
+        let res = ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(res.is_err());
+        assert_eq!(ebuf.get_text(), "hello world\nbaz\n");
+
+        // Joining the changed line onto the previous one forgets about it.
+        ebuf.set_leader(gid, Cursor::new(1, 0));
+        ebuf.editor_command(&delete, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "hello world\n\n");
+
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+        let operation = EditAction::Join(JoinStyle::OneSpace);
+        edit!(ebuf, operation, range!(RangeType::Line, 2), ctx!(gid, vwctx, vctx), store);
+        assert_eq!(ebuf.get_text(), "hello world\n");
+
+        let res = ebuf.editor_command(&lineundo, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(res.is_err());
+        assert_eq!(ebuf.get_text(), "hello world\n");
+    }
+
     #[test]
     fn test_visual_motion() {
         let (mut ebuf, gid, vwctx, mut vctx, mut store) = mkfivestr("foo\nbar\nbaz\n");
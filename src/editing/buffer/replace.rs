@@ -0,0 +1,554 @@
+use std::borrow::Cow;
+
+use regex::Regex;
+
+use crate::editing::{
+    action::{
+        Action,
+        EditError,
+        EditInfo,
+        EditResult,
+        EditorAction,
+        InfoMessage,
+        QueryReplaceAction,
+    },
+    application::ApplicationInfo,
+    base::TargetShape,
+    buffer::{CursorGroupIdContext, EditBuffer, HistoryActions},
+    context::EditContext,
+    cursor::CursorState,
+    store::Store,
+};
+
+use crate::input::dialog::{Dialog, PromptChoice};
+
+/// Progress through an interactive replacement.
+pub(super) struct QueryReplaceState {
+    /// The search string as the user typed it.
+    search: String,
+    needle: Regex,
+    replacement: String,
+    expand: bool,
+
+    /// The bounds of every match visited so far.
+    matches: Vec<(usize, usize)>,
+
+    /// Index of the match currently being shown to the user.
+    idx: usize,
+
+    /// How many of the visited matches have already been replaced or skipped.
+    handled: usize,
+
+    /// Where to look for the next match.
+    resume: usize,
+
+    /// How many matches have been replaced.
+    replaced: usize,
+}
+
+pub trait ReplaceActions<C, I>
+where
+    I: ApplicationInfo,
+{
+    /// Start replacing matches of a search string, and prompt the user about the first one.
+    fn query_replace_start(
+        &mut self,
+        needle: &str,
+        replacement: &str,
+        is_regex: bool,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I>;
+
+    /// Respond to the prompt about the current match.
+    fn query_replace_step(
+        &mut self,
+        act: &QueryReplaceAction,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I>;
+}
+
+fn choice(c: char, act: QueryReplaceAction) -> (char, Vec<QueryReplaceAction>) {
+    (c, vec![act])
+}
+
+fn into_actions<I: ApplicationInfo>(acts: Vec<QueryReplaceAction>) -> Vec<Action<I>> {
+    acts.into_iter()
+        .map(|act| EditorAction::QueryReplace(act).into())
+        .collect()
+}
+
+/// Prompt for what to do with the current match.
+///
+/// The choices are kept as [QueryReplaceAction] values, and only converted into an [Action] once
+/// picked, so that the dialog doesn't depend on the lifetime of the application's types.
+#[derive(Debug)]
+struct QueryReplacePrompt(PromptChoice<QueryReplaceAction>);
+
+impl<I: ApplicationInfo> Dialog<Action<I>> for QueryReplacePrompt {
+    fn render(&mut self, max_rows: usize, max_cols: usize) -> Vec<Cow<'_, str>> {
+        self.0.render(max_rows, max_cols)
+    }
+
+    fn input(&mut self, c: char) -> Option<Vec<Action<I>>> {
+        self.0.input(c).map(into_actions)
+    }
+
+    fn exit(&mut self, interrupt: bool) -> Option<Vec<Action<I>>> {
+        self.0.exit(interrupt).map(into_actions)
+    }
+}
+
+impl<I> EditBuffer<I>
+where
+    I: ApplicationInfo,
+{
+    /// Move to the match after the current one, searching for a new one if needed.
+    fn _replace_next(&self, state: &mut QueryReplaceState) -> bool {
+        state.idx += 1;
+
+        if state.idx < state.matches.len() {
+            return true;
+        }
+
+        let res = self.text.find_replacement(
+            state.resume.into(),
+            &state.needle,
+            &state.replacement,
+            state.expand,
+        );
+
+        match res {
+            Some((start, end, _)) => {
+                state.matches.push((start.into(), end.into()));
+
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Replace the text of the current match.
+    fn _replace_current(&mut self, state: &mut QueryReplaceState, store: &mut Store<I>) {
+        let (start, end) = state.matches[state.idx];
+
+        let res = self.text.find_replacement(
+            start.into(),
+            &state.needle,
+            &state.replacement,
+            state.expand,
+        );
+
+        let subst = match res {
+            Some((ms, me, subst)) if (usize::from(ms), usize::from(me)) == (start, end) => subst,
+            _ => return,
+        };
+
+        let slen = subst.chars().count();
+        let (_, adjs) = self.text.replace(start.into(), end.into(), false, subst.into());
+
+        self._adjust_all(adjs, store);
+
+        state.matches[state.idx] = (start, start + slen);
+        state.replaced += 1;
+    }
+
+    /// Mark the current match as handled, so that searching continues after it.
+    fn _replace_handled(&self, state: &mut QueryReplaceState, empty: bool) {
+        let (_, end) = state.matches[state.idx];
+
+        state.handled += 1;
+        state.resume = end;
+
+        if empty {
+            // Step over empty matches so that we don't find them again.
+            state.resume += 1;
+        }
+    }
+
+    fn _replace_prompt<C: EditContext>(
+        &mut self,
+        state: QueryReplaceState,
+        ctx: &CursorGroupIdContext<'_, '_, C>,
+    ) -> EditResult<EditInfo, I> {
+        let (start, end) = state.matches[state.idx];
+        let anchor = self.text.offset_to_cursor(start.into());
+
+        // Highlight the match that the user is being asked about.
+        let mut leader = if end > start {
+            let cursor = self.text.offset_to_cursor((end - 1).into());
+
+            CursorState::Selection(cursor, anchor, TargetShape::CharWise)
+        } else {
+            CursorState::Location(anchor)
+        };
+
+        self.clamp_state(&mut leader, ctx);
+        self.get_group_mut(ctx.0).leader = leader;
+
+        let msg = format!(
+            "Query replacing {} with {}: (y, n, !, q, ., ^)",
+            state.search, state.replacement
+        );
+        let choices = vec![
+            choice(' ', QueryReplaceAction::Replace),
+            choice('y', QueryReplaceAction::Replace),
+            choice('n', QueryReplaceAction::Skip),
+            choice('!', QueryReplaceAction::ReplaceAll),
+            choice('q', QueryReplaceAction::Quit),
+            choice('.', QueryReplaceAction::ReplaceAndStop),
+            choice('^', QueryReplaceAction::Previous),
+        ];
+
+        self.replacing.insert(ctx.0, state);
+
+        // Any other key stops replacing, as in Emacs.
+        let quit = choice('q', QueryReplaceAction::Quit).1;
+        let dialog = QueryReplacePrompt(PromptChoice::new(msg, choices).on_exit(quit));
+
+        Err(EditError::NeedConfirm(Box::new(dialog)))
+    }
+
+    fn _replace_finish<C: EditContext>(
+        &mut self,
+        state: QueryReplaceState,
+        off: usize,
+        ctx: &CursorGroupIdContext<'_, '_, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let mut leader = CursorState::Location(self.text.offset_to_cursor(off.into()));
+        self.clamp_state(&mut leader, ctx);
+        self.get_group_mut(ctx.0).leader = leader;
+
+        // Save all of the replacements as a single step in the buffer's history.
+        self.checkpoint(ctx, store)?;
+
+        let msg = match state.replaced {
+            1 => "Replaced 1 occurrence".to_string(),
+            n => format!("Replaced {n} occurrences"),
+        };
+
+        Ok(Some(InfoMessage::from(msg)))
+    }
+}
+
+impl<'a, 'b, C, I> ReplaceActions<CursorGroupIdContext<'a, 'b, C>, I> for EditBuffer<I>
+where
+    C: EditContext,
+    I: ApplicationInfo,
+{
+    fn query_replace_start(
+        &mut self,
+        needle: &str,
+        replacement: &str,
+        is_regex: bool,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let gid = ctx.0;
+        let search = needle.to_string();
+        let needle = if is_regex {
+            Regex::new(needle)?
+        } else {
+            Regex::new(regex::escape(needle).as_str())?
+        };
+
+        self.replacing.remove(&gid);
+
+        let leader = self.get_leader(gid);
+        let start = self.text.cursor_to_offset(&leader);
+
+        let (mstart, mend, _) =
+            self.text
+                .find_replacement(start, &needle, replacement, is_regex)
+                .ok_or_else(|| EditError::Failure(format!("No matches for {search}")))?;
+
+        let state = QueryReplaceState {
+            search,
+            needle,
+            replacement: replacement.to_string(),
+            expand: is_regex,
+            matches: vec![(mstart.into(), mend.into())],
+            idx: 0,
+            handled: 0,
+            resume: start.into(),
+            replaced: 0,
+        };
+
+        // Remember where we started, and make sure that we can undo back to this point.
+        store.cursors.push_mark(self.id.clone(), leader);
+        self.checkpoint(ctx, store)?;
+
+        self._replace_prompt(state, ctx)
+    }
+
+    fn query_replace_step(
+        &mut self,
+        act: &QueryReplaceAction,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let mut state = self
+            .replacing
+            .remove(&ctx.0)
+            .ok_or_else(|| EditError::Failure("No replacement in progress".into()))?;
+
+        if state.matches.iter().any(|(_, e)| *e > self.text.len()) {
+            return Err(EditError::Failure("No replacement in progress".into()));
+        }
+
+        match act {
+            QueryReplaceAction::Start(..) => {
+                self.replacing.insert(ctx.0, state);
+
+                return Err(EditError::Failure("Replacement already in progress".into()));
+            },
+            QueryReplaceAction::Previous => {
+                if state.idx == 0 {
+                    self.replacing.insert(ctx.0, state);
+
+                    return Err(EditError::Failure("No previous match".into()));
+                }
+
+                state.idx -= 1;
+
+                return self._replace_prompt(state, ctx);
+            },
+            QueryReplaceAction::Quit => {
+                // Leave the cursor at the start of the current match.
+                let (start, _) = state.matches[state.idx];
+
+                return self._replace_finish(state, start, ctx, store);
+            },
+            QueryReplaceAction::Replace |
+            QueryReplaceAction::ReplaceAndStop |
+            QueryReplaceAction::Skip => {
+                // Matches that we've moved back to have already been handled.
+                if state.idx == state.handled {
+                    let (start, end) = state.matches[state.idx];
+
+                    if !matches!(act, QueryReplaceAction::Skip) {
+                        self._replace_current(&mut state, store);
+                    }
+
+                    self._replace_handled(&mut state, start == end);
+                }
+
+                // Leave the cursor after the current match when we're done.
+                let (_, end) = state.matches[state.idx];
+
+                if matches!(act, QueryReplaceAction::ReplaceAndStop) {
+                    return self._replace_finish(state, end, ctx, store);
+                }
+
+                if self._replace_next(&mut state) {
+                    return self._replace_prompt(state, ctx);
+                } else {
+                    return self._replace_finish(state, end, ctx, store);
+                }
+            },
+            QueryReplaceAction::ReplaceAll => {
+                loop {
+                    if state.idx == state.handled {
+                        let (start, end) = state.matches[state.idx];
+
+                        self._replace_current(&mut state, store);
+                        self._replace_handled(&mut state, start == end);
+                    }
+
+                    let (_, end) = state.matches[state.idx];
+
+                    if !self._replace_next(&mut state) {
+                        return self._replace_finish(state, end, ctx, store);
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::*;
+    use super::*;
+
+    macro_rules! assert_prompt {
+        ($res: expr) => {
+            assert!(matches!($res, Err(EditError::NeedConfirm(_))))
+        };
+    }
+
+    macro_rules! assert_msg {
+        ($res: expr, $msg: expr) => {
+            assert_eq!($res.unwrap(), Some(InfoMessage::from($msg)))
+        };
+    }
+
+    #[test]
+    fn test_query_replace() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("foo bar foo baz foo\nfoo\n");
+
+        // Start from the middle of the first line.
+        ebuf.set_leader(gid, Cursor::new(0, 4));
+
+        let res =
+            ebuf.query_replace_start("foo", "quux", false, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(0, 8), Cursor::new(0, 10), CharWise))
+        );
+
+        // Replace the first match, and move on to the next one.
+        let act = QueryReplaceAction::Replace;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        assert_eq!(ebuf.get_text(), "foo bar quux baz foo\nfoo\n");
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(0, 17), Cursor::new(0, 19), CharWise))
+        );
+
+        // Skip the second match.
+        let act = QueryReplaceAction::Skip;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        assert_eq!(ebuf.get_text(), "foo bar quux baz foo\nfoo\n");
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(1, 0), Cursor::new(1, 2), CharWise))
+        );
+
+        // Go back to look at the skipped match.
+        let act = QueryReplaceAction::Previous;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(0, 17), Cursor::new(0, 19), CharWise))
+        );
+
+        // Going back twice reaches the replaced match, but no further.
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(0, 8), Cursor::new(0, 11), CharWise))
+        );
+
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(matches!(res, Err(EditError::Failure(_))));
+
+        // Answering for matches that were already handled just moves forward.
+        let act = QueryReplaceAction::Replace;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+        assert_eq!(ebuf.get_text(), "foo bar quux baz foo\nfoo\n");
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(1, 0), Cursor::new(1, 2), CharWise))
+        );
+
+        // Replacing the last match finishes, and doesn't wrap around.
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_msg!(res, "Replaced 2 occurrences");
+        assert_eq!(ebuf.get_text(), "foo bar quux baz foo\nquux\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(1, 3));
+
+        // There's nothing left to respond to.
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(matches!(res, Err(EditError::Failure(_))));
+
+        // All of the replacements get undone together.
+        let act = HistoryAction::Undo(Count::Exact(1));
+        ebuf.history_command(&act, ctx!(gid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_text(), "foo bar foo baz foo\nfoo\n");
+    }
+
+    #[test]
+    fn test_query_replace_regex() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("a1 b22 c333\nd4444\n");
+
+        // Strings that aren't regular expressions are matched literally.
+        let res = ebuf.query_replace_start("\\d", "", false, ctx!(gid, vwctx, vctx), &mut store);
+        assert!(matches!(res, Err(EditError::Failure(_))));
+
+        // Replacements can refer to capture groups.
+        let res = ebuf.query_replace_start(
+            "(\\w)(\\d+)",
+            "$2$1",
+            true,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        );
+        assert_prompt!(res);
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(0, 0), Cursor::new(0, 1), CharWise))
+        );
+
+        // Replace this match, and then stop.
+        let act = QueryReplaceAction::ReplaceAndStop;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_msg!(res, "Replaced 1 occurrence");
+        assert_eq!(ebuf.get_text(), "1a b22 c333\nd4444\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 2));
+
+        // Start again, and replace everything else.
+        let res = ebuf.query_replace_start(
+            "(\\w)(\\d+)",
+            "$2$1",
+            true,
+            ctx!(gid, vwctx, vctx),
+            &mut store,
+        );
+        assert_prompt!(res);
+        assert_eq!(
+            ebuf.get_leader_selection(gid),
+            Some((Cursor::new(0, 3), Cursor::new(0, 5), CharWise))
+        );
+
+        let act = QueryReplaceAction::ReplaceAll;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_msg!(res, "Replaced 3 occurrences");
+        assert_eq!(ebuf.get_text(), "1a 22b 333c\n4444d\n");
+
+        // Quitting leaves the cursor at the start of the current match.
+        ebuf.set_leader(gid, Cursor::new(0, 0));
+
+        let res = ebuf.query_replace_start("\\d+", "#", true, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+
+        let act = QueryReplaceAction::Skip;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_prompt!(res);
+
+        let act = QueryReplaceAction::Quit;
+        let res = ebuf.query_replace_step(&act, ctx!(gid, vwctx, vctx), &mut store);
+        assert_msg!(res, "Replaced 0 occurrences");
+        assert_eq!(ebuf.get_text(), "1a 22b 333c\n4444d\n");
+        assert_eq!(ebuf.get_leader(gid), Cursor::new(0, 3));
+    }
+
+    #[test]
+    fn test_query_replace_prompt() {
+        let (mut ebuf, gid, vwctx, vctx, mut store) = mkfivestr("a.b axb a.b\n");
+
+        // The prompt shows the search string as it was typed, not the escaped regex.
+        let res = ebuf.query_replace_start("a.b", "c", false, ctx!(gid, vwctx, vctx), &mut store);
+        let Err(EditError::NeedConfirm(mut dialog)) = res else {
+            panic!("expected a prompt, got {:?}", res);
+        };
+        assert_eq!(dialog.render(1, 80), vec!["Query replacing a.b with c: (y, n, !, q, ., ^)"]);
+
+        // Regular expressions are shown as written too.
+        let res = ebuf.query_replace_start("\\w\\.", "", true, ctx!(gid, vwctx, vctx), &mut store);
+        let Err(EditError::NeedConfirm(mut dialog)) = res else {
+            panic!("expected a prompt, got {:?}", res);
+        };
+        assert_eq!(dialog.render(1, 80), vec!["Query replacing \\w\\. with : (y, n, !, q, ., ^)"]);
+    }
+}
@@ -66,6 +66,9 @@ where
     /// Join adjacent selections.
     fn selection_join(&mut self, ctx: &C, store: &mut Store<I>) -> EditResult<EditInfo, I>;
 
+    /// Restore the most recent selections, swapping them with any current ones.
+    fn selection_restore(&mut self, ctx: &C, store: &mut Store<I>) -> EditResult<EditInfo, I>;
+
     /// Change the boundaries of the selection to be exactly those of the range.
     fn selection_resize(
         &mut self,
@@ -412,6 +415,34 @@ where
         Ok(None)
     }
 
+    fn selection_restore(
+        &mut self,
+        ctx: &CursorGroupIdContext<'a, 'b, C>,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let gid = ctx.0;
+        let mut restored = self
+            .selections
+            .get(gid)
+            .cloned()
+            .ok_or_else(|| EditError::Failure("No previous selection".into()))?;
+
+        let group = self.get_group(gid);
+
+        if group.leader.is_selection() {
+            self._set_last_selection(gid, group, store);
+        }
+
+        for state in restored.iter_mut() {
+            self.clamp_state(state, ctx);
+        }
+
+        ctx.2.set_restored_shape(restored.leader.shape());
+        self.set_group(gid, restored);
+
+        Ok(None)
+    }
+
     fn selection_resize(
         &mut self,
         style: &SelectionResizeStyle,
@@ -422,6 +453,7 @@ where
         let ctx = &self._ctx_cgi2es(&EditAction::Motion, ictx);
         let gid = ictx.0;
         let shape = ctx.context.get_target_shape();
+        let selecting = ctx.context.is_selecting();
 
         let (reset, obj) = match style {
             SelectionResizeStyle::Extend => (false, false),
@@ -439,6 +471,13 @@ where
         for state in group.iter_mut() {
             let cursor = state.cursor().clone();
 
+            // Selections keep their shapes unless the context specifies one.
+            let shape = match (shape, state.is_selection()) {
+                (None, true) if selecting => Some(state.shape()),
+                (None, false) if selecting => Some(TargetShape::CharWise),
+                (shape, _) => shape,
+            };
+
             if reset || !state.is_selection() {
                 state.set_anchor(cursor.clone());
             }
@@ -475,7 +514,7 @@ where
                     TargetShape::LineWise
                 },
                 EditTarget::Motion(mv, count) => {
-                    if let Some(nc) = self.text.movement(&cursor, mv, count, ctx) {
+                    if let Some(nc) = self._movement(&cursor, mv, count, ctx) {
                         state.set_cursor(nc);
                     }
 
@@ -1107,6 +1146,135 @@ mod tests {
         assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 10));
     }
 
+    #[test]
+    fn test_selection_restore() {
+        let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr("hello world a b c d\n");
+        let id = ebuf.id();
+        let restore = EditorAction::Selection(SelectionAction::Restore);
+        let right = EditorAction::Edit(
+            EditAction::Motion.into(),
+            mv!(MoveType::Column(MoveDir1D::Next, false), 1),
+        );
+
+        // Nothing has been selected yet.
+        let res = ebuf.editor_command(&restore, ctx!(curid, vwctx, vctx), &mut store);
+        assert!(res.is_err());
+
+        // Select "hello ".
+        ebuf.set_leader(curid, Cursor::new(0, 0));
+        vctx.persist.shape = Some(TargetShape::CharWise);
+
+        let mov = MoveType::WordBegin(WordStyle::Little, MoveDir1D::Next);
+        edit!(ebuf, EditAction::Motion, mv!(mov), ctx!(curid, vwctx, vctx), store);
+
+        let selection = (Cursor::new(0, 0), Cursor::new(0, 6), TargetShape::CharWise);
+        assert_eq!(ebuf.get_leader_selection(curid), Some(selection.clone()));
+
+        // Leaving the selection remembers it and updates the marks.
+        vctx.persist.shape = None;
+        ebuf.editor_command(&right, ctx!(curid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader_selection(curid), None);
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 7));
+        assert_eq!(
+            store.cursors.get_mark(id.clone(), Mark::VisualBegin).unwrap(),
+            Cursor::new(0, 0)
+        );
+        assert_eq!(store.cursors.get_mark(id.clone(), Mark::VisualEnd).unwrap(), Cursor::new(0, 6));
+
+        // Restore the selection.
+        ebuf.editor_command(&restore, ctx!(curid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_leader_selection(curid), Some(selection.clone()));
+        assert_eq!(ebuf.get_leader(curid), Cursor::new(0, 6));
+
+        // Make a new selection, and then swap back to the old one.
+        vctx.persist.shape = Some(TargetShape::LineWise);
+        ebuf.editor_command(&right, ctx!(curid, vwctx, vctx), &mut store).unwrap();
+        let lines = (Cursor::new(0, 0), Cursor::new(0, 7), TargetShape::LineWise);
+        assert_eq!(ebuf.get_leader_selection(curid), Some(lines.clone()));
+
+        ebuf.editor_command(&restore, ctx!(curid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_leader_selection(curid), Some(selection.clone()));
+
+        ebuf.editor_command(&restore, ctx!(curid, vwctx, vctx), &mut store)
+            .unwrap();
+        assert_eq!(ebuf.get_leader_selection(curid), Some(lines));
+
+        // Leave the selection, and then insert a line above it.
+        vctx.persist.shape = None;
+        ebuf.editor_command(&right, ctx!(curid, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf.get_leader_selection(curid), None);
+
+        ebuf.set_leader(curid, Cursor::new(0, 0));
+        type_char!(ebuf, '\n', curid, vwctx, vctx, store);
+        assert_eq!(ebuf.get_text(), "\nhello world a b c d\n");
+        assert_eq!(
+            store.cursors.get_mark(id.clone(), Mark::VisualBegin).unwrap(),
+            Cursor::new(1, 0)
+        );
+        assert_eq!(store.cursors.get_mark(id, Mark::VisualEnd).unwrap(), Cursor::new(1, 7));
+
+        // The remembered selection was moved down as well.
+        ebuf.editor_command(&restore, ctx!(curid, vwctx, vctx), &mut store)
+            .unwrap();
+        let selection = (Cursor::new(1, 0), Cursor::new(1, 7), TargetShape::LineWise);
+        assert_eq!(ebuf.get_leader_selection(curid), Some(selection));
+    }
+
+    #[test]
+    fn test_selection_restore_shape() {
+        let (mut ebuf1, gid1, vwctx, mut vctx, mut store) = mkfivestr("abc\ndef\nghi\n");
+        let mut ebuf2 = mkbuf();
+        let gid2 = ebuf2.create_group();
+        ebuf2.set_text("abc\ndef\nghi\n");
+
+        let restore = EditorAction::Selection(SelectionAction::Restore);
+        let down =
+            EditorAction::Edit(EditAction::Motion.into(), mv!(MoveType::Line(MoveDir1D::Next), 1));
+        let right = EditorAction::Edit(
+            EditAction::Motion.into(),
+            mv!(MoveType::Column(MoveDir1D::Next, false), 1),
+        );
+
+        // Select lines in the first buffer, and a block in the second.
+        vctx.persist.shape = Some(TargetShape::LineWise);
+        ebuf1.editor_command(&down, ctx!(gid1, vwctx, vctx), &mut store).unwrap();
+        vctx.persist.shape = Some(TargetShape::BlockWise);
+        ebuf2.editor_command(&right, ctx!(gid2, vwctx, vctx), &mut store).unwrap();
+
+        vctx.persist.shape = None;
+        ebuf1.editor_command(&right, ctx!(gid1, vwctx, vctx), &mut store).unwrap();
+        ebuf2.editor_command(&down, ctx!(gid2, vwctx, vctx), &mut store).unwrap();
+        assert_eq!(ebuf1.get_leader_selection(gid1), None);
+        assert_eq!(ebuf2.get_leader_selection(gid2), None);
+
+        // Each buffer restores its own selection's shape, reports it, and motions then keep it.
+        vctx.persist.selecting = true;
+
+        ebuf1
+            .editor_command(&restore, ctx!(gid1, vwctx, vctx), &mut store)
+            .unwrap();
+        let lines = (Cursor::new(0, 0), Cursor::new(1, 0), TargetShape::LineWise);
+        assert_eq!(ebuf1.get_leader_selection(gid1), Some(lines));
+        assert_eq!(vctx.persist.get_shape(), Some(TargetShape::LineWise));
+
+        ebuf1.editor_command(&down, ctx!(gid1, vwctx, vctx), &mut store).unwrap();
+        let lines = (Cursor::new(0, 0), Cursor::new(2, 0), TargetShape::LineWise);
+        assert_eq!(ebuf1.get_leader_selection(gid1), Some(lines));
+
+        ebuf2
+            .editor_command(&restore, ctx!(gid2, vwctx, vctx), &mut store)
+            .unwrap();
+        let block = (Cursor::new(0, 0), Cursor::new(0, 1), TargetShape::BlockWise);
+        assert_eq!(ebuf2.get_leader_selection(gid2), Some(block));
+        assert_eq!(vctx.persist.get_shape(), Some(TargetShape::BlockWise));
+
+        ebuf2.editor_command(&down, ctx!(gid2, vwctx, vctx), &mut store).unwrap();
+        let block = (Cursor::new(0, 0), Cursor::new(1, 1), TargetShape::BlockWise);
+        assert_eq!(ebuf2.get_leader_selection(gid2), Some(block));
+    }
+
     #[test]
     fn test_selection_resize_range_grows() {
         let (mut ebuf, curid, vwctx, mut vctx, mut store) = mkfivestr("hello world\n");
@@ -102,6 +102,13 @@ impl CompletionList {
                     return None;
                 }
             },
+            CompletionSelection::All => {
+                if self.candidates.is_empty() {
+                    return None;
+                } else {
+                    return Some(self.candidates.join(" "));
+                }
+            },
         }
     }
 }
@@ -3,7 +3,7 @@
 //! ## Overview
 //!
 //! This module contains the contexts used by the editing buffer.
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 
 use crate::input::InputContext;
 
@@ -26,6 +26,28 @@ pub trait EditContext:
     /// Indicates a shape to be applied to an [EditAction].
     fn get_target_shape(&self) -> Option<TargetShape>;
 
+    /// Indicates that motions should extend the current selections, keeping whatever shapes they
+    /// already have, when [EditContext::get_target_shape] doesn't specify one.
+    ///
+    /// This allows environments to return to a selection saved in the buffer, like the one
+    /// restored by [SelectionAction::Restore], without needing to know its shape.
+    ///
+    /// [SelectionAction::Restore]: super::action::SelectionAction::Restore
+    fn is_selecting(&self) -> bool {
+        false
+    }
+
+    /// Called after [SelectionAction::Restore] has gone back to a selection saved in the buffer,
+    /// with the shape of that selection.
+    ///
+    /// Environments using [EditContext::is_selecting] can use this to learn what kind of
+    /// selection they're now extending.
+    ///
+    /// [SelectionAction::Restore]: super::action::SelectionAction::Restore
+    fn set_restored_shape(&self, shape: TargetShape) {
+        let _ = shape;
+    }
+
     /// Indicates the style by which text should be inserted into the buffer.
     fn get_insert_style(&self) -> Option<InsertStyle>;
 
@@ -38,12 +60,70 @@ pub trait EditContext:
     /// Indicates whether should be appended to the target register when yanking or deleting text.
     fn get_register_append(&self) -> bool;
 
+    /// Indicates which side of the target register's current contents text should be added to
+    /// when [EditContext::get_register_append] is true.
+    fn get_register_append_dir(&self) -> MoveDir1D {
+        MoveDir1D::Next
+    }
+
     /// Returns a regular expression to search for in the buffer.
     ///
     /// If the context doesn't specify a search regex, then consumers should fall back to using
     /// the contents of [Register::LastSearch].
     fn get_search_regex(&self) -> Option<Regex>;
 
+    /// Compile a search pattern typed by the user, like the contents of [Register::LastSearch],
+    /// into a [Regex], treating letter case as described by `case`.
+    ///
+    /// By default, patterns are written using the syntax of the [regex] crate, but environments
+    /// can override this to support their own syntax.
+    fn compile_search_regex(&self, pattern: &str, case: SearchCase) -> Result<Regex, regex::Error> {
+        let insensitive = match case {
+            SearchCase::Sensitive => false,
+            SearchCase::Insensitive => true,
+            SearchCase::Smart => {
+                let mut escaped = false;
+
+                !pattern.chars().any(|c| {
+                    let upper = !escaped && c.is_uppercase();
+                    escaped = !escaped && c == '\\';
+                    upper
+                })
+            },
+        };
+
+        RegexBuilder::new(pattern).case_insensitive(insensitive).build()
+    }
+
+    /// Returns a search pattern, in the syntax accepted by
+    /// [EditContext::compile_search_regex], that matches `word` literally. When `boundary` is
+    /// true, the pattern should only match whole words.
+    ///
+    /// Searching for the word under the cursor doesn't use [SearchCase::Smart], so when `case`
+    /// is [SearchCase::Smart] the pattern should ignore case even if `word` contains uppercase
+    /// letters.
+    fn get_word_search_pattern(&self, word: &str, boundary: bool, case: SearchCase) -> String {
+        let word = regex::escape(word);
+        let word = if boundary {
+            format!("\\b{word}\\b")
+        } else {
+            word
+        };
+
+        if case == SearchCase::Smart && word.chars().any(char::is_uppercase) {
+            format!("(?i){word}")
+        } else {
+            word
+        }
+    }
+
+    /// Split text entered into the search bar into the searches that it describes.
+    ///
+    /// By default, the whole text is a single pattern to search for in the direction `dir`.
+    fn get_search_queries(&self, text: &str, dir: MoveDir1D) -> Vec<SearchQuery> {
+        vec![SearchQuery::new(text, dir)]
+    }
+
     /// Get the direction in which to search.
     fn get_search_regex_dir(&self) -> MoveDir1D;
 
@@ -56,6 +136,11 @@ pub trait EditContext:
 
     /// Whether to perform incremental searches while typing in the search bar.
     fn is_search_incremental(&self) -> bool;
+
+    /// The number of columns that text should fit within when it gets formatted.
+    fn get_text_width(&self) -> usize {
+        79
+    }
 }
 
 /// Trait for values that can be converted by the [EditContext].
@@ -0,0 +1,660 @@
+//! # Code folding
+//!
+//! ## Overview
+//!
+//! This module contains the types used to track folded lines within a buffer.
+//!
+//! Folds are either created manually, or determined from a buffer's contents by a
+//! [FoldProvider], like [IndentFolds]. Folds can be nested inside of each other, and are moved
+//! as lines are inserted and deleted around them, in the same way that cursors and marks are
+//! updated via [CursorAdjustment].
+//!
+//! When a fold is closed, the lines within it are displayed and moved over as if they were a
+//! single line.
+//!
+use crate::editing::base::{IndentOptions, MoveDir1D};
+use crate::editing::cursor::CursorAdjustment;
+use crate::editing::rope::EditRope;
+
+/// A range of lines that can be collapsed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Fold {
+    start: usize,
+    end: usize,
+    closed: bool,
+    manual: bool,
+}
+
+impl Fold {
+    fn new(start: usize, end: usize, manual: bool) -> Self {
+        Fold { start, end, closed: true, manual }
+    }
+
+    /// The first line within this fold.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The last line within this fold.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// The number of lines within this fold.
+    pub fn lines(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    /// Whether this fold is currently collapsed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Whether this fold was created by the user instead of a [FoldProvider].
+    pub fn is_manual(&self) -> bool {
+        self.manual
+    }
+
+    /// Whether a line is within this fold.
+    pub fn contains(&self, line: usize) -> bool {
+        self.start <= line && line <= self.end
+    }
+
+    fn contains_fold(&self, other: &Fold) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    fn adjust_line(line: usize, adj: &CursorAdjustment, end: bool) -> usize {
+        match adj {
+            CursorAdjustment::Line { line_start, line_end, amount, amount_after } => {
+                if *line_start <= line && line <= *line_end {
+                    line.saturating_add_signed(*amount)
+                } else if line > *line_end {
+                    line.saturating_add_signed(*amount_after)
+                } else {
+                    line
+                }
+            },
+            CursorAdjustment::Column { line: l, column_start, amt_line, .. } => {
+                // The first line only moves when all of its text does, while the last line
+                // moves whenever some of its text does.
+                if *l == line && (end || *column_start == 0) {
+                    line.saturating_add_signed(*amt_line)
+                } else {
+                    line
+                }
+            },
+        }
+    }
+
+    fn adjust(&mut self, adj: &CursorAdjustment) -> bool {
+        if let CursorAdjustment::Line {
+            line_start,
+            line_end,
+            amount: isize::MAX,
+            amount_after,
+        } = adj
+        {
+            let deleted = *line_start..=*line_end;
+
+            if deleted.contains(&self.start) {
+                self.start = *line_start;
+            } else if self.start > *line_end {
+                self.start = self.start.saturating_add_signed(*amount_after);
+            }
+
+            if deleted.contains(&self.end) {
+                if *line_start == 0 {
+                    return false;
+                }
+
+                self.end = line_start - 1;
+            } else if self.end > *line_end {
+                self.end = self.end.saturating_add_signed(*amount_after);
+            }
+
+            return self.start <= self.end;
+        }
+
+        self.start = Fold::adjust_line(self.start, adj, false);
+        self.end = Fold::adjust_line(self.end, adj, true);
+
+        return self.start <= self.end;
+    }
+}
+
+/// Determines where folds belong within some text.
+///
+/// Folds from a provider are recalculated at the first checkpoint after a buffer changes, and are
+/// kept alongside any that were created manually.
+pub trait FoldProvider: Send + Sync {
+    /// Get the first and last lines of each fold within the text, which gets indented using the
+    /// given options.
+    fn folds(&self, text: &EditRope, indent: &IndentOptions) -> Vec<(usize, usize)>;
+}
+
+/// Create folds for each level of indentation.
+///
+/// Lines whose indentation is at least *n* [shift widths](IndentOptions::shift_width) are
+/// placed in a fold with their neighbours at the same level or deeper, like Vim's
+/// `foldmethod=indent`. Blank lines are treated as being at the lower of the levels around them.
+#[derive(Clone, Debug, Default)]
+pub struct IndentFolds;
+
+impl IndentFolds {
+    fn level(line: impl Iterator<Item = char>, indent: &IndentOptions) -> Option<usize> {
+        let tabstop = indent.tabstop.max(1);
+        let mut width = 0;
+
+        for c in line {
+            match c {
+                ' ' => width += 1,
+                '\t' => width += tabstop - width % tabstop,
+                c if c.is_whitespace() => width += 1,
+                _ => return Some(width / indent.shift_width()),
+            }
+        }
+
+        return None;
+    }
+}
+
+impl FoldProvider for IndentFolds {
+    fn folds(&self, text: &EditRope, indent: &IndentOptions) -> Vec<(usize, usize)> {
+        let mut levels: Vec<Option<usize>> =
+            text.lines(0).map(|l| IndentFolds::level(l.chars(), indent)).collect();
+        let nlines = levels.len();
+
+        // Blank lines take the lower level of the closest non-blank lines around them.
+        let mut prev = vec![0; nlines];
+        let mut last = 0;
+
+        for (line, level) in levels.iter().enumerate() {
+            last = level.unwrap_or(last);
+            prev[line] = last;
+        }
+
+        let mut next = 0;
+
+        for line in (0..nlines).rev() {
+            next = levels[line].unwrap_or(next);
+            levels[line] = Some(levels[line].unwrap_or_else(|| prev[line].min(next)));
+        }
+
+        let levels: Vec<usize> = levels.into_iter().flatten().collect();
+        let mut folds = vec![];
+        let mut open: Vec<usize> = vec![];
+
+        for line in 0..=nlines {
+            let level = levels.get(line).copied().unwrap_or(0);
+
+            while open.len() > level {
+                let start = open.pop().unwrap();
+                folds.push((start, line - 1));
+            }
+
+            while open.len() < level {
+                open.push(line);
+            }
+        }
+
+        return folds;
+    }
+}
+
+/// The folds within a buffer.
+#[derive(Debug, Default)]
+pub(crate) struct FoldList {
+    /// Folds sorted by their first line, with outer folds before the ones nested in them.
+    folds: Vec<Fold>,
+}
+
+impl FoldList {
+    fn sort(&mut self) {
+        self.folds.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+        self.folds.dedup_by(|a, b| a.start == b.start && a.end == b.end);
+    }
+
+    pub fn get(&self) -> &[Fold] {
+        self.folds.as_slice()
+    }
+
+    pub fn clear(&mut self) {
+        self.folds.clear();
+    }
+
+    pub fn adjust(&mut self, adjs: &[CursorAdjustment]) {
+        for adj in adjs {
+            self.folds.retain_mut(|fold| fold.adjust(adj));
+        }
+
+        self.sort();
+    }
+
+    /// Create a new closed fold.
+    pub fn create(&mut self, start: usize, end: usize) {
+        let (start, end) = if start <= end {
+            (start, end)
+        } else {
+            (end, start)
+        };
+
+        self.folds.push(Fold::new(start, end, true));
+        self.sort();
+    }
+
+    /// Replace the folds from a [FoldProvider], keeping folds at the same position open or
+    /// closed.
+    ///
+    /// New folds start closed, unless they contain `line`, so that the line being edited doesn't
+    /// get hidden.
+    pub fn provide(&mut self, provided: Vec<(usize, usize)>, line: Option<usize>) {
+        let old = std::mem::take(&mut self.folds);
+        let mut open = vec![];
+        let mut closed = vec![];
+
+        for fold in old.into_iter() {
+            if fold.manual {
+                self.folds.push(fold);
+            } else if fold.closed {
+                closed.push(fold.start);
+            } else {
+                open.push(fold.start);
+            }
+        }
+
+        for (start, end) in provided.into_iter() {
+            let mut fold = Fold::new(start, end, false);
+
+            if open.contains(&start) {
+                fold.closed = false;
+            } else if !closed.contains(&start) {
+                fold.closed = !matches!(line, Some(line) if fold.contains(line));
+            }
+
+            self.folds.push(fold);
+        }
+
+        self.sort();
+    }
+
+    /// Get the outermost closed fold containing a line, which is what gets displayed for it.
+    pub fn closed_at(&self, line: usize) -> Option<&Fold> {
+        self.folds.iter().find(|f| f.closed && f.contains(line))
+    }
+
+    /// Get the line at which the line displayed for a given line starts.
+    pub fn display_start(&self, line: usize) -> usize {
+        self.closed_at(line).map(|f| f.start).unwrap_or(line)
+    }
+
+    /// Get the line at which the line displayed for a given line ends.
+    pub fn display_end(&self, line: usize) -> usize {
+        self.closed_at(line).map(|f| f.end).unwrap_or(line)
+    }
+
+    /// How many folds contain a line.
+    pub fn level(&self, line: usize) -> usize {
+        self.folds.iter().filter(|f| f.contains(line)).count()
+    }
+
+    /// Move over *count* displayed lines, treating each closed fold as a single line.
+    pub fn step(&self, line: usize, dir: MoveDir1D, count: usize, max: usize) -> usize {
+        let mut line = line;
+
+        for _ in 0..count {
+            match dir {
+                MoveDir1D::Next => {
+                    let end = self.display_end(line);
+
+                    if end >= max {
+                        break;
+                    }
+
+                    line = end + 1;
+                },
+                MoveDir1D::Previous => {
+                    let start = self.display_start(line);
+
+                    if start == 0 {
+                        break;
+                    }
+
+                    line = self.display_start(start - 1);
+                },
+            }
+        }
+
+        return line;
+    }
+
+    /// Find where the next fold starts, or where the previous one ends.
+    pub fn find(&self, line: usize, dir: MoveDir1D, count: usize) -> Option<usize> {
+        let mut line = line;
+        let mut found = None;
+
+        for _ in 0..count {
+            let next = match dir {
+                MoveDir1D::Next => self.folds.iter().map(|f| f.start).find(|s| *s > line),
+                MoveDir1D::Previous => self.folds.iter().map(|f| f.end).filter(|e| *e < line).max(),
+            };
+
+            match next {
+                Some(l) => {
+                    line = l;
+                    found = Some(l);
+                },
+                None => break,
+            }
+        }
+
+        return found;
+    }
+
+    fn innermost<F>(&self, line: usize, f: F) -> Option<usize>
+    where
+        F: Fn(&Fold) -> bool,
+    {
+        self.folds.iter().rposition(|fold| fold.contains(line) && f(fold))
+    }
+
+    /// Open the displayed fold at a line, or all of the folds containing it.
+    pub fn open(&mut self, line: usize, recursive: bool) -> bool {
+        if recursive {
+            return self.set_all(line, false);
+        }
+
+        match self.folds.iter_mut().find(|f| f.closed && f.contains(line)) {
+            Some(fold) => {
+                fold.closed = false;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Close the innermost open fold containing the line displayed at a line, or all of the
+    /// folds containing it.
+    pub fn close(&mut self, line: usize, recursive: bool) -> bool {
+        if recursive {
+            return self.set_all(line, true);
+        }
+
+        let idx = match self.closed_at(line).cloned() {
+            Some(shown) => self.innermost(line, |f| !f.closed && f.contains_fold(&shown)),
+            None => self.innermost(line, |f| !f.closed),
+        };
+
+        match idx {
+            Some(idx) => {
+                self.folds[idx].closed = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Open the displayed fold at a line, or close the innermost one when none are closed.
+    pub fn toggle(&mut self, line: usize, recursive: bool) -> bool {
+        if self.closed_at(line).is_some() {
+            self.open(line, recursive)
+        } else {
+            self.close(line, recursive)
+        }
+    }
+
+    fn set_all(&mut self, line: usize, closed: bool) -> bool {
+        let mut found = false;
+
+        for fold in self.folds.iter_mut().filter(|f| f.contains(line)) {
+            fold.closed = closed;
+            found = true;
+        }
+
+        return found;
+    }
+
+    /// Delete the manually created fold displayed at a line, or the innermost one if none of
+    /// them are closed. If `recursive` is true, then the folds nested in it are deleted too.
+    pub fn delete(&mut self, line: usize, recursive: bool) -> bool {
+        let shown = self.folds.iter().position(|f| f.manual && f.closed && f.contains(line));
+        let idx = shown.or_else(|| self.innermost(line, |f| f.manual));
+
+        let Some(idx) = idx else {
+            return false;
+        };
+
+        let fold = self.folds.remove(idx);
+
+        if recursive {
+            self.folds.retain(|f| !f.manual || !fold.contains_fold(f));
+        }
+
+        return true;
+    }
+
+    /// Open or close every fold.
+    pub fn set_closed(&mut self, closed: bool) {
+        for fold in self.folds.iter_mut() {
+            fold.closed = closed;
+        }
+    }
+
+    /// Delete all manually created folds.
+    pub fn delete_all(&mut self) {
+        self.folds.retain(|f| !f.manual);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delete_lines(line_start: usize, line_end: usize) -> CursorAdjustment {
+        let amount_after = -((line_end - line_start + 1) as isize);
+
+        CursorAdjustment::Line {
+            line_start,
+            line_end,
+            amount: isize::MAX,
+            amount_after,
+        }
+    }
+
+    fn insert_lines(line: usize, amount: isize) -> CursorAdjustment {
+        CursorAdjustment::Line {
+            line_start: line,
+            line_end: usize::MAX,
+            amount,
+            amount_after: 0,
+        }
+    }
+
+    fn bounds(list: &FoldList) -> Vec<(usize, usize)> {
+        list.get().iter().map(|f| (f.start(), f.end())).collect()
+    }
+
+    #[test]
+    fn test_adjust_lines() {
+        let mut list = FoldList::default();
+        list.create(2, 5);
+        list.create(3, 4);
+
+        // Inserting lines above folds moves them down.
+        list.adjust(&[insert_lines(0, 2)]);
+        assert_eq!(bounds(&list), vec![(4, 7), (5, 6)]);
+
+        // Inserting lines within a fold grows it.
+        list.adjust(&[insert_lines(6, 1)]);
+        assert_eq!(bounds(&list), vec![(4, 8), (5, 7)]);
+
+        // Deleting the end of a fold shrinks it.
+        list.adjust(&[delete_lines(7, 9)]);
+        assert_eq!(bounds(&list), vec![(4, 6), (5, 6)]);
+
+        // Deleting the start of a fold moves it to the following line.
+        list.adjust(&[delete_lines(3, 4)]);
+        assert_eq!(bounds(&list), vec![(3, 4)]);
+
+        // Deleting all of a fold's lines removes it.
+        list.adjust(&[delete_lines(3, 4)]);
+        assert_eq!(bounds(&list), vec![]);
+    }
+
+    #[test]
+    fn test_adjust_columns() {
+        let mut list = FoldList::default();
+        list.create(1, 3);
+
+        // Splitting the first line only moves the fold when all of its text moves.
+        let split = CursorAdjustment::Column { line: 1, column_start: 2, amt_line: 1, amt_col: -2 };
+        list.adjust(&[insert_lines(2, 1), split]);
+        assert_eq!(bounds(&list), vec![(1, 4)]);
+
+        let split = CursorAdjustment::Column { line: 1, column_start: 0, amt_line: 1, amt_col: 0 };
+        list.adjust(&[insert_lines(2, 1), split]);
+        assert_eq!(bounds(&list), vec![(2, 5)]);
+
+        // Joining the last line onto the previous one shrinks the fold.
+        let join = CursorAdjustment::Column { line: 5, column_start: 0, amt_line: -1, amt_col: 3 };
+        list.adjust(&[join, insert_lines(6, -1)]);
+        assert_eq!(bounds(&list), vec![(2, 4)]);
+    }
+
+    #[test]
+    fn test_open_close() {
+        let mut list = FoldList::default();
+        list.create(0, 9);
+        list.create(2, 5);
+        list.create(3, 4);
+
+        assert_eq!(list.closed_at(3).map(Fold::start), Some(0));
+        assert_eq!(list.level(3), 3);
+        assert_eq!(list.level(6), 1);
+
+        // Opening a fold reveals the closed ones inside of it.
+        assert!(list.open(3, false));
+        assert_eq!(list.closed_at(3).map(Fold::start), Some(2));
+        assert_eq!(list.closed_at(6), None);
+
+        // Closing a closed fold closes the one containing it.
+        assert!(list.close(3, false));
+        assert_eq!(list.closed_at(3).map(Fold::start), Some(0));
+
+        // Opening recursively opens every fold containing the line.
+        assert!(list.open(3, true));
+        assert_eq!(list.closed_at(3), None);
+
+        // Closing an open fold closes the innermost one first.
+        assert!(list.close(3, false));
+        assert_eq!(list.closed_at(3).map(Fold::start), Some(3));
+        assert_eq!(list.closed_at(2), None);
+
+        // Toggling switches between the two.
+        assert!(list.toggle(3, false));
+        assert_eq!(list.closed_at(3), None);
+        assert!(list.toggle(3, false));
+        assert_eq!(list.closed_at(3).map(Fold::start), Some(3));
+
+        assert!(list.close(3, true));
+        assert_eq!(list.closed_at(3).map(Fold::start), Some(0));
+
+        list.set_closed(false);
+        assert_eq!(list.closed_at(3), None);
+
+        // Nothing to do outside of any folds.
+        assert!(!list.open(12, false));
+        assert!(!list.close(12, false));
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut list = FoldList::default();
+        list.create(0, 9);
+        list.create(2, 5);
+        list.create(3, 4);
+        list.create(7, 8);
+
+        // Delete the displayed fold, leaving the nested ones.
+        assert!(list.delete(3, false));
+        assert_eq!(bounds(&list), vec![(2, 5), (3, 4), (7, 8)]);
+
+        // Delete a fold and the ones nested within it.
+        assert!(list.delete(3, true));
+        assert_eq!(bounds(&list), vec![(7, 8)]);
+
+        // Provided folds can't be deleted.
+        list.provide(vec![(0, 1)], None);
+        assert!(!list.delete(0, false));
+
+        list.delete_all();
+        assert_eq!(bounds(&list), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut list = FoldList::default();
+        list.create(2, 4);
+        list.create(6, 7);
+
+        assert_eq!(list.step(1, MoveDir1D::Next, 1, 9), 2);
+        assert_eq!(list.step(2, MoveDir1D::Next, 1, 9), 5);
+        assert_eq!(list.step(1, MoveDir1D::Next, 3, 9), 6);
+        assert_eq!(list.step(1, MoveDir1D::Next, 10, 9), 9);
+
+        assert_eq!(list.step(8, MoveDir1D::Previous, 1, 9), 6);
+        assert_eq!(list.step(6, MoveDir1D::Previous, 2, 9), 2);
+        assert_eq!(list.step(3, MoveDir1D::Previous, 1, 9), 1);
+        assert_eq!(list.step(3, MoveDir1D::Previous, 10, 9), 0);
+
+        // Closed folds at the end of the buffer can't be moved past.
+        list.create(8, 9);
+        assert_eq!(list.step(8, MoveDir1D::Next, 1, 9), 8);
+
+        assert_eq!(list.find(0, MoveDir1D::Next, 1), Some(2));
+        assert_eq!(list.find(0, MoveDir1D::Next, 2), Some(6));
+        assert_eq!(list.find(9, MoveDir1D::Previous, 1), Some(7));
+        assert_eq!(list.find(2, MoveDir1D::Previous, 1), None);
+    }
+
+    #[test]
+    fn test_provide() {
+        let mut list = FoldList::default();
+        list.create(0, 1);
+        list.provide(vec![(2, 5), (3, 4)], None);
+        assert_eq!(bounds(&list), vec![(0, 1), (2, 5), (3, 4)]);
+
+        // Folds that were left open stay open when they're recalculated.
+        list.open(3, true);
+        list.provide(vec![(2, 6), (3, 5), (7, 8)], None);
+        assert_eq!(bounds(&list), vec![(0, 1), (2, 6), (3, 5), (7, 8)]);
+        assert_eq!(list.closed_at(3), None);
+        assert_eq!(list.closed_at(7).map(Fold::start), Some(7));
+        assert_eq!(list.closed_at(0).map(Fold::start), Some(0));
+
+        // New folds around the line being edited start open, but existing ones stay closed.
+        list.provide(vec![(2, 6), (3, 5), (7, 9), (10, 11)], Some(7));
+        assert_eq!(list.closed_at(7).map(Fold::start), Some(7));
+        assert_eq!(list.closed_at(10).map(Fold::start), Some(10));
+
+        list.provide(vec![(2, 6), (3, 5), (7, 9), (10, 12), (13, 14)], Some(13));
+        assert_eq!(list.closed_at(10).map(Fold::start), Some(10));
+        assert_eq!(list.closed_at(13), None);
+    }
+
+    #[test]
+    fn test_indent_folds() {
+        let text = EditRope::from("a\n  b\n    c\n\n    d\n  e\nf\n  g\n");
+        let indent = IndentOptions { shiftwidth: 2, ..Default::default() };
+        let folds = IndentFolds.folds(&text, &indent);
+
+        assert_eq!(folds, vec![(2, 4), (1, 5), (7, 7)]);
+
+        // Tabs count up to the next tabstop.
+        let text = EditRope::from("a\n\tb\n\t\tc\n");
+        let indent = IndentOptions { shiftwidth: 0, tabstop: 4, ..Default::default() };
+        let folds = IndentFolds.folds(&text, &indent);
+
+        assert_eq!(folds, vec![(2, 2), (1, 2)]);
+    }
+}
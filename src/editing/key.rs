@@ -46,8 +46,11 @@ where
 {
     bindings: Box<dyn BindingMachine<K, A, S, C>>,
     keystack: VecDeque<K>,
+    last_key: Option<K>,
+    repeating: Option<(K, Vec<K>, usize)>,
 
     recording: Option<(Register, bool)>,
+    last_recorded: Register,
     commit_on_input: bool,
     committed: EditRope,
     pending: EditRope,
@@ -65,8 +68,11 @@ where
         Self {
             bindings,
             keystack: VecDeque::new(),
+            last_key: None,
+            repeating: None,
 
             recording: None,
+            last_recorded: Register::UnnamedMacro,
             commit_on_input: false,
             committed: EditRope::from(""),
             pending: EditRope::from(""),
@@ -80,42 +86,63 @@ where
         ctx: &C,
         store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
+        if matches!(act, MacroAction::StopOrExecute(_)) && self.recording.is_some() {
+            self.stop_recording(store)?;
+
+            return Ok(None);
+        }
+
         let (mstr, count) = match act {
-            MacroAction::Execute(count) => {
+            MacroAction::Execute(count) | MacroAction::StopOrExecute(count) => {
                 let reg = ctx.get_register().unwrap_or(Register::UnnamedMacro);
                 let rope = store.registers.get_macro(reg)?;
 
                 (rope.to_string(), ctx.resolve(count))
             },
+            MacroAction::ExecuteRepeatable(count) => {
+                let reg = ctx.get_register().unwrap_or(Register::UnnamedMacro);
+                let rope = store.registers.get_macro(reg)?;
+                let count = ctx.resolve(count);
+
+                if let Some(key) = &self.last_key {
+                    let keys = K::from_macro_str(rope.to_string().as_str())?;
+                    self.repeating = Some((key.clone(), keys, count));
+                }
+
+                (rope.to_string(), count)
+            },
             MacroAction::Repeat(count) => {
                 let rope = store.registers.get_last_macro()?;
 
                 (rope.to_string(), ctx.resolve(count))
             },
-            MacroAction::ToggleRecording => {
-                if let Some((reg, append)) = &self.recording {
-                    // Save macro to register.
-                    let mut rope = EditRope::from("");
-                    std::mem::swap(&mut rope, &mut self.committed);
-
-                    let mut flags = RegisterPutFlags::NOTEXT;
-
-                    if *append {
-                        flags |= RegisterPutFlags::APPEND;
-                    }
+            MacroAction::Save => {
+                let reg = ctx.get_register().unwrap_or(Register::UnnamedMacro);
+                let rope = store.registers.get(&self.last_recorded)?.value;
 
-                    store.registers.put(reg, rope.into(), flags)?;
+                store.registers.put(&reg, rope.into(), RegisterPutFlags::NOTEXT)?;
 
-                    // Stop recording.
-                    self.recording = None;
-                    self.commit_on_input = false;
-                    self.pending = EditRope::from("");
+                return Ok(None);
+            },
+            MacroAction::ToggleRecording => {
+                if self.recording.is_some() {
+                    self.stop_recording(store)?;
                 } else {
-                    let reg = ctx.get_register().unwrap_or(Register::UnnamedMacro);
+                    self.start_recording(ctx);
+                }
 
-                    self.recording = Some((reg, ctx.get_register_append()));
+                return Ok(None);
+            },
+            MacroAction::StartRecording => {
+                if self.recording.is_none() {
+                    self.start_recording(ctx);
                 }
 
+                return Ok(None);
+            },
+            MacroAction::StopRecording => {
+                self.stop_recording(store)?;
+
                 return Ok(None);
             },
         };
@@ -128,6 +155,36 @@ where
 
         return Ok(None);
     }
+
+    fn start_recording(&mut self, ctx: &C) {
+        let reg = ctx.get_register().unwrap_or(Register::UnnamedMacro);
+
+        self.recording = Some((reg, ctx.get_register_append()));
+    }
+
+    fn stop_recording<I: ApplicationInfo>(&mut self, store: &mut Store<I>) -> EditResult<(), I> {
+        if let Some((reg, append)) = &self.recording {
+            // Save macro to register.
+            let mut rope = EditRope::from("");
+            std::mem::swap(&mut rope, &mut self.committed);
+
+            let mut flags = RegisterPutFlags::NOTEXT;
+
+            if *append {
+                flags |= RegisterPutFlags::APPEND;
+            }
+
+            store.registers.put(reg, rope.into(), flags)?;
+
+            // Stop recording.
+            self.last_recorded = reg.clone();
+            self.recording = None;
+            self.commit_on_input = false;
+            self.pending = EditRope::from("");
+        }
+
+        return Ok(());
+    }
 }
 
 impl<K, A, S, C> BindingMachine<K, A, S, C> for KeyManager<K, A, S, C>
@@ -149,6 +206,21 @@ where
         }
 
         self.keystack.clear();
+
+        if let Some((rkey, keys, count)) = self.repeating.take() {
+            if rkey == key {
+                // Typing the same key again repeats the macro.
+                for _ in 0..count {
+                    self.keystack.extend(keys.iter().cloned());
+                }
+
+                self.repeating = Some((rkey, keys, count));
+
+                return;
+            }
+        }
+
+        self.last_key = Some(key.clone());
         self.bindings.input_key(key);
     }
 
@@ -327,6 +399,34 @@ mod tests {
             &vec![(Once, Key("Q".parse().unwrap()))],
             &TestAction::Macro(MacroAction::Execute(Count::Contextual)).into(),
         );
+        bindings.add_mapping(
+            TestMode::Normal,
+            &vec![
+                (Once, Key("g".parse().unwrap())),
+                (Once, Key("e".parse().unwrap())),
+            ],
+            &TestAction::Macro(MacroAction::ExecuteRepeatable(Count::Contextual)).into(),
+        );
+        bindings.add_mapping(
+            TestMode::Normal,
+            &vec![(Once, Key("S".parse().unwrap()))],
+            &TestAction::Macro(MacroAction::Save).into(),
+        );
+        bindings.add_mapping(
+            TestMode::Normal,
+            &vec![(Once, Key("(".parse().unwrap()))],
+            &TestAction::Macro(MacroAction::StartRecording).into(),
+        );
+        bindings.add_mapping(
+            TestMode::Normal,
+            &vec![(Once, Key(")".parse().unwrap()))],
+            &TestAction::Macro(MacroAction::StopRecording).into(),
+        );
+        bindings.add_mapping(
+            TestMode::Normal,
+            &vec![(Once, Key("E".parse().unwrap()))],
+            &TestAction::Macro(MacroAction::StopOrExecute(Count::Contextual)).into(),
+        );
         bindings.add_mapping(
             TestMode::Normal,
             &vec![(Once, Key("f".parse().unwrap()))],
@@ -515,6 +615,141 @@ mod tests {
         assert_eq!(flag, true);
     }
 
+    #[test]
+    fn test_repeat_and_save() {
+        let (mut bindings, mut store) = setup_bindings(true);
+        let mut s = String::new();
+        let mut flag = false;
+        let mut err = None;
+
+        macro_rules! get_register {
+            ($reg: expr) => {
+                store.registers.get(&$reg).unwrap().value
+            };
+        }
+
+        macro_rules! input {
+            ($key: expr) => {
+                input($key, &mut bindings, &mut store, &mut s, &mut flag, &mut err)
+            };
+        }
+
+        // Record an unnamed macro.
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('i'));
+        input!(key!('a'));
+        input!(key!('b'));
+        input!("<Esc>".parse().unwrap());
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('q'));
+        assert_eq!(get_register!(Register::UnnamedMacro).to_string(), "iab<Esc>");
+        assert_eq!(s, "ab");
+
+        // Execute the macro twice.
+        input!(key!('2'));
+        input!(key!('g'));
+        input!(key!('e'));
+        assert_eq!(s, "ababab");
+
+        // Typing the last key again repeats the macro with the same count.
+        input!(key!('e'));
+        assert_eq!(s, "ababababab");
+        input!(key!('e'));
+        assert_eq!(s, "ababababababab");
+
+        // Typing any other key stops repeating.
+        input!(key!('l'));
+        input!(key!('e'));
+        assert_eq!(s, "ababababababab");
+
+        // Save the recorded macro to "b.
+        input!(key!('"'));
+        input!(key!('b'));
+        input!(key!('S'));
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(get_register!(Register::Named('b')).to_string(), "iab<Esc>");
+
+        // Record a macro to "c, and save that to "d.
+        input!(key!('"'));
+        input!(key!('c'));
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('i'));
+        input!(key!('c'));
+        input!("<Esc>".parse().unwrap());
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('q'));
+        input!(key!('"'));
+        input!(key!('d'));
+        input!(key!('S'));
+        assert_eq!(get_register!(Register::Named('d')).to_string(), "ic<Esc>");
+        assert_eq!(get_register!(Register::UnnamedMacro).to_string(), "iab<Esc>");
+    }
+
+    #[test]
+    fn test_start_and_stop() {
+        let (mut bindings, mut store) = setup_bindings(true);
+        let mut s = String::new();
+        let mut flag = false;
+        let mut err = None;
+
+        macro_rules! get_register {
+            ($reg: expr) => {
+                store.registers.get(&$reg).unwrap().value
+            };
+        }
+
+        macro_rules! input {
+            ($key: expr) => {
+                input($key, &mut bindings, &mut store, &mut s, &mut flag, &mut err)
+            };
+        }
+
+        // Stopping when nothing is being recorded does nothing.
+        input!(key!(')'));
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(get_register!(Register::UnnamedMacro).to_string(), "");
+
+        // Record a macro.
+        input!(key!('('));
+        input!(key!('i'));
+        input!(key!('a'));
+        input!("<Esc>".parse().unwrap());
+        input!(key!(')'));
+        assert_eq!(get_register!(Register::UnnamedMacro).to_string(), "ia<Esc>");
+        assert_eq!(s, "a");
+
+        // Stopping again leaves the recorded macro alone.
+        input!(key!(')'));
+        assert!(err.is_none(), "{:?}", err);
+        assert_eq!(get_register!(Register::UnnamedMacro).to_string(), "ia<Esc>");
+
+        // When nothing is being recorded, StopOrExecute executes the macro.
+        input!(key!('E'));
+        assert_eq!(s, "aa");
+
+        // Starting while already recording keeps the current recording going.
+        input!(key!('('));
+        input!(key!('i'));
+        input!(key!('b'));
+        input!(key!('('));
+        input!(key!('c'));
+        input!("<Esc>".parse().unwrap());
+
+        // While recording, StopOrExecute stops recording.
+        input!(key!('E'));
+        assert_eq!(get_register!(Register::UnnamedMacro).to_string(), "ib(c<Esc>");
+        assert_eq!(s, "aab(c");
+
+        input!(key!('E'));
+        assert_eq!(s, "aab(cb(c");
+    }
+
     #[test]
     fn test_macro_dialog() {
         let (mut bindings, mut store) = setup_bindings(false);
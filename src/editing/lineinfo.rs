@@ -4,59 +4,586 @@
 //!
 //! This module contains logic for tracking line annotations.
 //!
-use std::cmp::Eq;
-use std::collections::HashMap;
-use std::hash::Hash;
+//! Annotations are anchored to the text they were placed on, and get moved as lines are inserted
+//! and deleted around them, in the same way that cursors and marks are updated via
+//! [CursorAdjustment]. What happens to a line annotation when its line gets deleted is controlled
+//! by the [LineDeletePolicy] for its type.
+//!
+//! Besides annotating individual lines, annotations can also be placed on ranges of text. Range
+//! annotations that get drawn alongside the text can implement [VirtualSpace], so that movements
+//! which depend on how the text is laid out on screen account for them.
+//!
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap};
 
 use anymap2::SendSyncAnyMap;
 
-/// Tracks different types of annotations for each line within a given buffer.
-pub struct LineInfoStore<Line: Eq + Hash> {
-    map: HashMap<Line, SendSyncAnyMap>,
+use crate::editing::cursor::{Adjustable, Cursor, CursorAdjustment};
+use crate::util::IdGenerator;
+
+/// What to do with a line annotation when the line it is on gets deleted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LineDeletePolicy {
+    /// Remove the annotation.
+    #[default]
+    Drop,
+
+    /// Move the annotation to the line before the deleted ones.
+    ///
+    /// If the deleted lines were at the start of the buffer, the annotation moves to the new
+    /// first line.
+    Previous,
+
+    /// Move the annotation to the line that followed the deleted ones.
+    Next,
+}
+
+/// Range annotations that take up space on screen when drawn alongside the text, such as
+/// virtual text.
+///
+/// Once a type has been registered with [LineInfoStore::set_virtual_space], movements that
+/// depend on how the text is laid out, like moving by screen lines, account for the space taken
+/// up by the ranges of that type starting on each line.
+pub trait VirtualSpace {
+    /// The number of columns drawn just before the first character of the range.
+    fn inline_width(&self) -> usize {
+        0
+    }
+
+    /// The number of rows drawn above the line that the range starts on.
+    fn rows_above(&self) -> usize {
+        0
+    }
+}
+
+/// The space taken up on a line by range annotations implementing [VirtualSpace].
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct LineSpace {
+    /// The index of each character that has columns drawn before it, and how many.
+    pub inline: Vec<(usize, usize)>,
+
+    /// The number of rows drawn above the line.
+    pub above: usize,
+}
+
+/// Identifier for a range annotation within a [LineInfoStore].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct RangeId(u64);
+
+/// An annotation on a range of text.
+///
+/// The range starts at [RangeInfo::start], and ends just before [RangeInfo::end].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RangeInfo<T> {
+    /// The identifier returned when the range was added.
+    pub id: RangeId,
+
+    /// The first character within the range.
+    pub start: Cursor,
+
+    /// The position just after the last character within the range.
+    pub end: Cursor,
+
+    /// The information attached to the range.
+    pub info: T,
+}
+
+impl<T> RangeInfo<T> {
+    /// Whether this range covers any characters on a given line.
+    pub fn contains_line(&self, line: usize) -> bool {
+        self.start.y <= line && (line < self.end.y || (line == self.end.y && self.end.x > 0))
+    }
+
+    fn adjust(&mut self, adj: &CursorAdjustment) -> bool {
+        if let CursorAdjustment::Line { line_start, line_end, amount: isize::MAX, .. } = adj {
+            let deleted = *line_start..=*line_end;
+            let mut clamped = false;
+
+            for cursor in [&mut self.start, &mut self.end] {
+                if deleted.contains(&cursor.y) {
+                    // Whatever follows the deleted lines now begins at the start of line_start.
+                    *cursor = Cursor::new(*line_start, 0);
+                    clamped = true;
+                } else {
+                    cursor.adjust(std::slice::from_ref(adj));
+                }
+            }
+
+            // Drop ranges whose contents were deleted entirely.
+            return !clamped || self.start < self.end;
+        }
+
+        self.start.adjust(std::slice::from_ref(adj));
+        self.end.adjust(std::slice::from_ref(adj));
+
+        return true;
+    }
+}
+
+/// The annotations of a single type.
+///
+/// Range annotations are kept sorted by where they start, so that the ones starting on a given
+/// line can be found without looking through all of them.
+struct Annotations<T> {
+    policy: LineDeletePolicy,
+    lines: BTreeMap<usize, T>,
+    ranges: Vec<RangeInfo<T>>,
+}
+
+impl<T> Annotations<T> {
+    fn adjust(&mut self, adj: &CursorAdjustment) {
+        let lines = std::mem::take(&mut self.lines);
+        let mut relocated = vec![];
+
+        for (line, info) in lines.into_iter() {
+            match adj {
+                CursorAdjustment::Line { line_start, line_end, amount: isize::MAX, .. }
+                    if *line_start <= line && line <= *line_end =>
+                {
+                    let target = match self.policy {
+                        LineDeletePolicy::Drop => continue,
+                        LineDeletePolicy::Previous => line_start.saturating_sub(1),
+                        LineDeletePolicy::Next => *line_start,
+                    };
+
+                    relocated.push((target, info));
+                },
+                _ => {
+                    // Annotations are anchored to the start of their line.
+                    let mut cursor = Cursor::new(line, 0);
+                    cursor.adjust(std::slice::from_ref(adj));
+
+                    self.lines.entry(cursor.y).or_insert(info);
+                },
+            }
+        }
+
+        // Annotations that get moved never replace the ones already on a line.
+        for (line, info) in relocated.into_iter() {
+            self.lines.entry(line).or_insert(info);
+        }
+
+        self.ranges.retain_mut(|range| range.adjust(adj));
+
+        // Adjusting can clamp the ends of ranges onto the same position, so restore the order.
+        self.ranges.sort_by(|a, b| a.start.cmp(&b.start));
+    }
+
+    fn add_range(&mut self, range: RangeInfo<T>) {
+        let idx = self.ranges.partition_point(|r| r.start <= range.start);
+
+        self.ranges.insert(idx, range);
+    }
+
+    fn remove_range(&mut self, id: RangeId) -> Option<RangeInfo<T>> {
+        let idx = self.ranges.iter().position(|r| r.id == id)?;
+
+        Some(self.ranges.remove(idx))
+    }
+
+    fn line_ranges(&self, line: usize) -> &[RangeInfo<T>] {
+        let start = self.ranges.partition_point(|r| r.start.y < line);
+        let end = self.ranges.partition_point(|r| r.start.y <= line);
+
+        &self.ranges[start..end]
+    }
+}
+
+impl<T> Default for Annotations<T> {
+    fn default() -> Self {
+        Annotations {
+            policy: LineDeletePolicy::default(),
+            lines: BTreeMap::new(),
+            ranges: vec![],
+        }
+    }
 }
 
-impl<L> LineInfoStore<L>
-where
-    L: Eq + Hash,
-{
+type Adjuster = fn(&mut SendSyncAnyMap, &[CursorAdjustment]);
+
+fn adjust_annotations<T: Send + Sync + 'static>(
+    map: &mut SendSyncAnyMap,
+    adjs: &[CursorAdjustment],
+) {
+    if let Some(annotations) = map.get_mut::<Annotations<T>>() {
+        for adj in adjs {
+            annotations.adjust(adj);
+        }
+    }
+}
+
+type Measurer = fn(&SendSyncAnyMap, usize, &mut LineSpace);
+
+fn measure_annotations<T: VirtualSpace + Send + Sync + 'static>(
+    map: &SendSyncAnyMap,
+    line: usize,
+    space: &mut LineSpace,
+) {
+    if let Some(annotations) = map.get::<Annotations<T>>() {
+        for range in annotations.line_ranges(line) {
+            let width = range.info.inline_width();
+
+            if width > 0 {
+                space.inline.push((range.start.x, width));
+            }
+
+            space.above += range.info.rows_above();
+        }
+    }
+}
+
+/// Tracks different types of annotations for the lines and ranges within a given buffer.
+///
+/// Annotations are anchored to text positions, so lines are always identified by their index.
+pub struct LineInfoStore {
+    map: SendSyncAnyMap,
+    adjusters: HashMap<TypeId, Adjuster>,
+    measurers: HashMap<TypeId, Measurer>,
+    rangeidgen: IdGenerator,
+}
+
+impl LineInfoStore {
     /// Create a new store.
     pub fn new() -> Self {
-        let map = HashMap::new();
+        let map = SendSyncAnyMap::new();
+        let adjusters = HashMap::new();
+        let measurers = HashMap::new();
+        let rangeidgen = IdGenerator::default();
+
+        LineInfoStore { map, adjusters, measurers, rangeidgen }
+    }
+
+    fn annotations<T: Send + Sync + 'static>(&self) -> Option<&Annotations<T>> {
+        self.map.get::<Annotations<T>>()
+    }
+
+    fn annotations_mut<T: Send + Sync + 'static>(&mut self) -> &mut Annotations<T> {
+        self.adjusters.entry(TypeId::of::<T>()).or_insert(adjust_annotations::<T>);
+
+        self.map.entry::<Annotations<T>>().or_default()
+    }
+
+    /// Get a reference to the annotation of type `T` on a given line.
+    pub fn get<T: Send + Sync + 'static>(&self, line: &usize) -> Option<&T> {
+        self.annotations()?.lines.get(line)
+    }
+
+    /// Get a mutable reference to the annotation of type `T` on a given line.
+    pub fn get_mut<T: Send + Sync + 'static>(&mut self, line: &usize) -> Option<&mut T> {
+        self.map.get_mut::<Annotations<T>>()?.lines.get_mut(line)
+    }
+
+    /// Create or overwrite an annotation of type `T` on a given line.
+    pub fn set<T: Send + Sync + 'static>(&mut self, line: usize, info: T) {
+        self.annotations_mut().lines.insert(line, info);
+    }
 
-        LineInfoStore { map }
+    /// Remove the annotation of type `T` on a given line, and return it if there was one.
+    pub fn remove<T: Send + Sync + 'static>(&mut self, line: &usize) -> Option<T> {
+        self.map.get_mut::<Annotations<T>>()?.lines.remove(line)
     }
 
-    /// Get a reference to the annotation of type `T` on line `L`.
-    pub fn get<T: Send + Sync + 'static>(&self, line: &L) -> Option<&T> {
-        let info = self.map.get(line)?;
+    /// Control what happens to annotations of type `T` when the line they are on gets deleted.
+    pub fn set_delete_policy<T: Send + Sync + 'static>(&mut self, policy: LineDeletePolicy) {
+        self.annotations_mut::<T>().policy = policy;
+    }
+
+    /// Annotate the text from `start` up to, but not including, `end` with `info`, and return an
+    /// identifier that can be used to remove it later.
+    pub fn add_range<T: Send + Sync + 'static>(
+        &mut self,
+        start: Cursor,
+        end: Cursor,
+        info: T,
+    ) -> RangeId {
+        let id = RangeId(self.rangeidgen.next());
+
+        self.annotations_mut().add_range(RangeInfo { id, start, end, info });
+
+        return id;
+    }
 
-        return info.get();
+    /// Remove the range annotation of type `T` with the given identifier, and return it if it
+    /// hasn't already been removed.
+    pub fn remove_range<T: Send + Sync + 'static>(&mut self, id: RangeId) -> Option<RangeInfo<T>> {
+        self.map.get_mut::<Annotations<T>>()?.remove_range(id)
     }
 
-    /// Get a mutable reference to the annotation of type `T` on line `L`.
-    pub fn get_mut<T: Send + Sync + 'static>(&mut self, line: &L) -> Option<&mut T> {
-        let info = self.map.get_mut(line)?;
+    /// Get all of the range annotations of type `T`, sorted by where they start.
+    pub fn get_ranges<T: Send + Sync + 'static>(&self) -> &[RangeInfo<T>] {
+        match self.annotations() {
+            Some(annotations) => annotations.ranges.as_slice(),
+            None => &[],
+        }
+    }
 
-        return info.get_mut();
+    /// Get the range annotations of type `T` that start on a given line.
+    pub fn get_line_ranges<T: Send + Sync + 'static>(&self, line: usize) -> &[RangeInfo<T>] {
+        match self.annotations() {
+            Some(annotations) => annotations.line_ranges(line),
+            None => &[],
+        }
     }
 
-    /// Create or overwrite an annotation of type `T` on line `L`.
-    pub fn set<T: Send + Sync + 'static>(&mut self, line: L, info: T) {
-        if let Some(ref mut any) = self.map.get_mut(&line) {
-            any.insert(info);
-        } else {
-            let mut any = SendSyncAnyMap::new();
-            any.insert(info);
-            self.map.insert(line, any);
+    /// Remove all of the range annotations of type `T`, and return them.
+    pub fn clear_ranges<T: Send + Sync + 'static>(&mut self) -> Vec<RangeInfo<T>> {
+        match self.map.get_mut::<Annotations<T>>() {
+            Some(annotations) => std::mem::take(&mut annotations.ranges),
+            None => vec![],
+        }
+    }
+
+    /// Account for the space taken up on screen by the range annotations of type `T`.
+    pub fn set_virtual_space<T: VirtualSpace + Send + Sync + 'static>(&mut self) {
+        self.measurers.insert(TypeId::of::<T>(), measure_annotations::<T>);
+    }
+
+    /// Get the space taken up on a line by range annotations implementing [VirtualSpace].
+    pub(crate) fn virtual_space(&self, line: usize) -> LineSpace {
+        let mut space = LineSpace::default();
+
+        for measurer in self.measurers.values() {
+            measurer(&self.map, line, &mut space);
+        }
+
+        space.inline.sort_by_key(|(x, _)| *x);
+
+        return space;
+    }
+
+    /// Move annotations to follow the text they were placed on after it gets modified.
+    pub fn adjust(&mut self, adjs: &[CursorAdjustment]) {
+        for adjuster in self.adjusters.values() {
+            adjuster(&mut self.map, adjs);
         }
     }
 }
 
-impl<L> Default for LineInfoStore<L>
-where
-    L: Eq + Hash,
-{
+impl Default for LineInfoStore {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Breakpoint(usize);
+
+    #[derive(Debug, Eq, PartialEq)]
+    struct Note(&'static str);
+
+    fn delete_lines(line_start: usize, line_end: usize) -> CursorAdjustment {
+        let amount_after = -((line_end - line_start + 1) as isize);
+
+        CursorAdjustment::Line {
+            line_start,
+            line_end,
+            amount: isize::MAX,
+            amount_after,
+        }
+    }
+
+    #[test]
+    fn test_insert_lines() {
+        let mut store = LineInfoStore::new();
+        store.set(2, Breakpoint(2));
+        store.set(5, Breakpoint(5));
+        store.set(5, Note("five"));
+
+        // Open two lines after line 3.
+        store.adjust(&[CursorAdjustment::Line {
+            line_start: 3,
+            line_end: 3,
+            amount: 0,
+            amount_after: 2,
+        }]);
+
+        assert_eq!(store.get::<Breakpoint>(&2), Some(&Breakpoint(2)));
+        assert_eq!(store.get::<Breakpoint>(&5), None);
+        assert_eq!(store.get::<Breakpoint>(&7), Some(&Breakpoint(5)));
+        assert_eq!(store.get::<Note>(&5), None);
+        assert_eq!(store.get::<Note>(&7), Some(&Note("five")));
+
+        // Split line 2 at its start, which pushes its contents down.
+        store.adjust(&[
+            CursorAdjustment::Line {
+                line_start: 2,
+                line_end: 2,
+                amount: 0,
+                amount_after: 1,
+            },
+            CursorAdjustment::Column { line: 2, column_start: 0, amt_line: 1, amt_col: 0 },
+        ]);
+
+        assert_eq!(store.get::<Breakpoint>(&2), None);
+        assert_eq!(store.get::<Breakpoint>(&3), Some(&Breakpoint(2)));
+        assert_eq!(store.get::<Breakpoint>(&8), Some(&Breakpoint(5)));
+
+        // Splitting later in the line leaves the annotation in place.
+        store.adjust(&[
+            CursorAdjustment::Line {
+                line_start: 3,
+                line_end: 3,
+                amount: 0,
+                amount_after: 1,
+            },
+            CursorAdjustment::Column { line: 3, column_start: 4, amt_line: 1, amt_col: -4 },
+        ]);
+
+        assert_eq!(store.get::<Breakpoint>(&3), Some(&Breakpoint(2)));
+        assert_eq!(store.get::<Breakpoint>(&9), Some(&Breakpoint(5)));
+    }
+
+    #[test]
+    fn test_delete_policy() {
+        let mut store = LineInfoStore::new();
+        store.set_delete_policy::<Note>(LineDeletePolicy::Next);
+
+        for line in 0..6 {
+            store.set(line, Breakpoint(line));
+            store.set(line, Note("note"));
+        }
+
+        // By default, annotations on deleted lines get dropped.
+        store.adjust(&[delete_lines(2, 3)]);
+
+        assert_eq!(store.get::<Breakpoint>(&1), Some(&Breakpoint(1)));
+        assert_eq!(store.get::<Breakpoint>(&2), Some(&Breakpoint(4)));
+        assert_eq!(store.get::<Breakpoint>(&3), Some(&Breakpoint(5)));
+        assert_eq!(store.get::<Breakpoint>(&4), None);
+
+        // Moved annotations don't replace existing ones.
+        assert_eq!(store.get::<Note>(&2), Some(&Note("note")));
+        assert_eq!(store.get::<Note>(&4), None);
+
+        store.remove::<Note>(&2);
+        store.adjust(&[delete_lines(1, 1)]);
+        assert_eq!(store.get::<Note>(&1), Some(&Note("note")));
+
+        store.remove::<Note>(&1);
+        store.adjust(&[delete_lines(1, 1)]);
+        assert_eq!(store.get::<Note>(&1), Some(&Note("note")));
+        assert_eq!(store.get::<Note>(&2), None);
+
+        // Move annotations upwards instead.
+        store.set_delete_policy::<Breakpoint>(LineDeletePolicy::Previous);
+        store.set(3, Breakpoint(3));
+        store.adjust(&[delete_lines(3, 3)]);
+        assert_eq!(store.get::<Breakpoint>(&2), Some(&Breakpoint(3)));
+        assert_eq!(store.get::<Breakpoint>(&3), None);
+
+        store.remove::<Breakpoint>(&1);
+        store.adjust(&[delete_lines(0, 0)]);
+        assert_eq!(store.get::<Breakpoint>(&0), Some(&Breakpoint(0)));
+        assert_eq!(store.get::<Breakpoint>(&1), Some(&Breakpoint(3)));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut store = LineInfoStore::new();
+        store.add_range(Cursor::new(1, 2), Cursor::new(3, 0), Note("a"));
+        store.add_range(Cursor::new(4, 1), Cursor::new(6, 3), Note("b"));
+        store.add_range(Cursor::new(8, 0), Cursor::new(9, 5), Note("c"));
+
+        let ranges = store.get_ranges::<Note>();
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges[0].contains_line(1));
+        assert!(ranges[0].contains_line(2));
+        assert!(!ranges[0].contains_line(3));
+        assert!(store.get_ranges::<Breakpoint>().is_empty());
+
+        // Deleting the start of a range shrinks it, deleting all of it removes it.
+        store.adjust(&[delete_lines(5, 9)]);
+
+        let ranges = store.get_ranges::<Note>();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].start, Cursor::new(1, 2));
+        assert_eq!(ranges[0].end, Cursor::new(3, 0));
+        assert_eq!(ranges[1].start, Cursor::new(4, 1));
+        assert_eq!(ranges[1].end, Cursor::new(5, 0));
+
+        // Text inserted before a range's start on the same line shifts its columns.
+        store.adjust(&[CursorAdjustment::Column {
+            line: 1,
+            column_start: 0,
+            amt_line: 0,
+            amt_col: 3,
+        }]);
+
+        let ranges = store.get_ranges::<Note>();
+        assert_eq!(ranges[0].start, Cursor::new(1, 5));
+
+        // Ranges can be removed using the identifier returned when adding them.
+        let id = store.add_range(Cursor::new(2, 0), Cursor::new(2, 1), Note("d"));
+        assert_eq!(store.get_ranges::<Note>().len(), 3);
+        assert_eq!(store.remove_range::<Breakpoint>(id), None);
+        assert_eq!(store.remove_range::<Note>(id).unwrap().info, Note("d"));
+        assert_eq!(store.remove_range::<Note>(id), None);
+
+        let ranges = store.clear_ranges::<Note>();
+        assert_eq!(ranges.len(), 2);
+        assert!(store.get_ranges::<Note>().is_empty());
+    }
+
+    #[test]
+    fn test_line_ranges() {
+        let mut store = LineInfoStore::new();
+        store.add_range(Cursor::new(8, 0), Cursor::new(9, 5), Note("c"));
+        store.add_range(Cursor::new(4, 1), Cursor::new(6, 3), Note("b"));
+        store.add_range(Cursor::new(1, 2), Cursor::new(3, 0), Note("a"));
+        store.add_range(Cursor::new(4, 0), Cursor::new(4, 1), Note("d"));
+        store.add_range(Cursor::new(4, 1), Cursor::new(4, 2), Note("e"));
+
+        // Ranges are sorted by where they start, in the order they were added.
+        let notes: Vec<_> = store.get_ranges::<Note>().iter().map(|r| r.info.0).collect();
+        assert_eq!(notes, vec!["a", "d", "b", "e", "c"]);
+
+        // Ranges can be looked up by the line they start on.
+        let notes: Vec<_> = store.get_line_ranges::<Note>(4).iter().map(|r| r.info.0).collect();
+        assert_eq!(notes, vec!["d", "b", "e"]);
+        assert!(store.get_line_ranges::<Note>(2).is_empty());
+        assert!(store.get_line_ranges::<Breakpoint>(4).is_empty());
+
+        // Lookups still work after the ranges get moved around.
+        store.adjust(&[delete_lines(2, 3)]);
+
+        let notes: Vec<_> = store.get_line_ranges::<Note>(2).iter().map(|r| r.info.0).collect();
+        assert_eq!(notes, vec!["d", "b", "e"]);
+
+        let notes: Vec<_> = store.get_line_ranges::<Note>(1).iter().map(|r| r.info.0).collect();
+        assert_eq!(notes, vec!["a"]);
+    }
+
+    impl VirtualSpace for Note {
+        fn inline_width(&self) -> usize {
+            self.0.len()
+        }
+
+        fn rows_above(&self) -> usize {
+            1
+        }
+    }
+
+    #[test]
+    fn test_virtual_space() {
+        let mut store = LineInfoStore::new();
+        store.add_range(Cursor::new(1, 3), Cursor::new(1, 3), Note("abc"));
+        store.add_range(Cursor::new(1, 0), Cursor::new(1, 0), Note("de"));
+        store.add_range(Cursor::new(2, 0), Cursor::new(2, 0), Note("f"));
+
+        // Types that haven't been registered don't take up any space.
+        assert_eq!(store.virtual_space(1), LineSpace::default());
+
+        store.set_virtual_space::<Note>();
+
+        let space = store.virtual_space(1);
+        assert_eq!(space.inline, vec![(0, 2), (3, 3)]);
+        assert_eq!(space.above, 2);
+
+        let space = store.virtual_space(0);
+        assert_eq!(space, LineSpace::default());
+    }
+}
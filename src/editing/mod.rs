@@ -18,6 +18,7 @@ pub mod buffer;
 pub mod completion;
 pub mod context;
 pub mod cursor;
+pub mod fold;
 pub mod history;
 pub mod key;
 pub mod lineinfo;
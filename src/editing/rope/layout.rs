@@ -0,0 +1,295 @@
+//! # Line layout
+//!
+//! ## Overview
+//!
+//! This module contains the column model used to lay out a line's characters on screen, which
+//! is shared by the widgets that draw text and the movements that depend on how it is displayed,
+//! like [MoveType::ScreenLine](crate::editing::base::MoveType::ScreenLine).
+//!
+//! Tabs expand to the next tabstop, wide characters take up two cells, and control characters
+//! are shown as "^X". Inline virtual text takes up columns before the character it's attached
+//! to.
+//!
+//! When wrapping, columns keep counting across rows, so that row `n` covers the columns starting
+//! at `n * width`. Wide characters that don't fit at the end of a row get pushed onto the next
+//! one, and tabs get cut short at the end of a row.
+use std::ops::Range;
+
+use ropey::RopeSlice;
+use unicode_width::UnicodeWidthChar;
+
+/// Control characters are shown using caret notation (e.g., "^A" or "^?"), or as their hex value
+/// when they fall outside of ASCII.
+pub(crate) fn control_text(c: char) -> String {
+    match c as u32 {
+        n @ 0..=0x1F => format!("^{}", char::from(n as u8 + 0x40)),
+        0x7F => "^?".into(),
+        n => format!("<{:02x}>", n),
+    }
+}
+
+/// The number of screen columns a character takes up when it starts at column `col`.
+pub(crate) fn cell_width(c: char, col: usize, tabstop: usize) -> usize {
+    if c == '\t' {
+        let tabstop = tabstop.max(1);
+
+        tabstop - col % tabstop
+    } else if c.is_control() {
+        control_text(c).len()
+    } else {
+        UnicodeWidthChar::width(c).unwrap_or(0)
+    }
+}
+
+/// Where a single character of a line gets drawn, in screen columns relative to the line start.
+pub(crate) struct CharCell {
+    pub c: char,
+    pub col: usize,
+    pub width: usize,
+    pub row: usize,
+}
+
+impl CharCell {
+    /// The text to draw for this character.
+    pub fn text(&self) -> String {
+        if self.c == '\t' {
+            " ".repeat(self.width)
+        } else if self.c.is_control() {
+            control_text(self.c)
+        } else {
+            self.c.to_string()
+        }
+    }
+}
+
+/// Where a piece of inline virtual text gets drawn, in screen columns relative to the line start.
+pub(crate) struct VirtualCell {
+    pub col: usize,
+    pub width: usize,
+}
+
+impl VirtualCell {
+    /// Place virtual text that is `width` columns wide at column `col`, and advance `col` past
+    /// it. When wrapping, text that doesn't fit at the end of a row gets pushed onto the next
+    /// one, and cut short if it's wider than a row.
+    fn place(mut width: usize, col: &mut usize, wrap: Option<usize>) -> Self {
+        if let Some(w) = wrap.filter(|w| *w > 0) {
+            let off = *col % w;
+
+            width = width.min(w);
+
+            if off > 0 && off + width > w {
+                *col += w - off;
+            }
+        }
+
+        let cell = VirtualCell { col: *col, width };
+
+        *col += width;
+
+        cell
+    }
+}
+
+/// The columns and rows that each character of a line gets drawn at.
+pub(crate) struct LineLayout {
+    pub cells: Vec<CharCell>,
+    pub inline: Vec<VirtualCell>,
+    pub end: usize,
+
+    /// The number of rows of virtual text drawn above the line.
+    pub above: usize,
+
+    vrow: usize,
+}
+
+impl LineLayout {
+    /// Lay out a line using the given tab width, wrapping it at `wrap` columns if given.
+    ///
+    /// Each entry in `inline` is the index of the character that some inline virtual text gets
+    /// drawn before, and the width of that text, sorted by index.
+    pub fn new(
+        s: &RopeSlice<'_>,
+        tabstop: usize,
+        wrap: Option<usize>,
+        inline: &[(usize, usize)],
+    ) -> Self {
+        let mut cells = Vec::with_capacity(s.len_chars());
+        let mut virt = Vec::with_capacity(inline.len());
+        let mut inline = inline.iter().peekable();
+        let mut col = 0;
+        let mut row = 0;
+
+        for (idx, c) in s.chars().enumerate() {
+            while let Some((_, width)) = inline.next_if(|(x, _)| *x <= idx) {
+                virt.push(VirtualCell::place(*width, &mut col, wrap));
+            }
+
+            let mut width = cell_width(c, col, tabstop);
+
+            match wrap {
+                Some(w) if w > 0 && width > 0 => {
+                    let off = col % w;
+
+                    if c == '\t' {
+                        width = width.min(w - off);
+                    } else if off > 0 && off + width > w {
+                        col += w - off;
+                    }
+
+                    row = col / w;
+                },
+                _ => {
+                    // Zero-width characters stay on the row of the character they modify.
+                },
+            }
+
+            cells.push(CharCell { c, col, width, row });
+            col += width;
+        }
+
+        for (_, width) in inline {
+            virt.push(VirtualCell::place(*width, &mut col, wrap));
+        }
+
+        // The last row that inline virtual text is drawn on, so that it gets a row of its own
+        // when it comes after the last character.
+        let vrow = match (wrap, virt.last()) {
+            (Some(w), Some(vc)) if w > 0 => (vc.col + vc.width.max(1) - 1) / w,
+            _ => 0,
+        };
+
+        LineLayout { cells, inline: virt, end: col, above: 0, vrow }
+    }
+
+    /// The number of characters in the line.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// The column and width of the character at `idx`, or of the cell just past the line end.
+    pub fn span(&self, idx: usize) -> (usize, usize) {
+        match self.cells.get(idx) {
+            Some(cell) => (cell.col, cell.width.max(1)),
+            None => (self.end, 1),
+        }
+    }
+
+    /// The index of the character drawn at column `col`, or the line length if it's past the
+    /// end.
+    pub fn index(&self, col: usize) -> usize {
+        self.cells
+            .iter()
+            .position(|cell| col < cell.col + cell.width)
+            .unwrap_or(self.cells.len())
+    }
+
+    /// The index of the first character that is drawn entirely at or after column `left`.
+    pub fn first_within(&self, left: usize) -> usize {
+        let idx = self.index(left);
+
+        if idx < self.len() && self.span(idx).0 < left {
+            idx + 1
+        } else {
+            idx
+        }
+    }
+
+    /// The index of the last character that is drawn entirely within the columns `left..right`,
+    /// or of the closest one to them if there isn't one.
+    pub fn last_within(&self, left: usize, right: usize) -> usize {
+        let idx = self.index(right.saturating_sub(1));
+        let (col, width) = self.span(idx);
+
+        if col + width > right && col > left {
+            idx.saturating_sub(1)
+        } else {
+            idx
+        }
+    }
+
+    /// Split the line into the characters drawn on each row when wrapping, along with the column
+    /// each row starts at. This doesn't include the rows drawn above the line.
+    pub fn rows(&self, width: usize) -> Vec<(Range<usize>, usize)> {
+        let mut rows = vec![];
+        let mut start = 0;
+
+        for idx in 1..self.cells.len() {
+            if self.cells[idx].row != self.cells[idx - 1].row {
+                rows.push((start..idx, self.cells[start].row * width));
+                start = idx;
+            }
+        }
+
+        let left = self.cells.get(start).map(|cell| cell.row * width).unwrap_or(0);
+        rows.push((start..self.cells.len(), left));
+
+        let len = self.cells.len();
+        let last = self.cells.last().map(|cell| cell.row).unwrap_or(0);
+
+        for row in (last + 1)..=self.vrow {
+            rows.push((len..len, row * width));
+        }
+
+        rows
+    }
+
+    /// The number of rows needed to draw this line when wrapping, including the ones above it.
+    pub fn height(&self) -> usize {
+        self.cells.last().map(|cell| cell.row).unwrap_or(0).max(self.vrow) + 1 + self.above
+    }
+}
+
+/// How the lines of some text get laid out on screen.
+pub(crate) trait ScreenLayout {
+    /// Lay out a line, wrapping it at `wrap` columns if given.
+    fn layout(&self, line: usize, wrap: Option<usize>) -> Option<LineLayout>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ropey::Rope;
+
+    fn layout(s: &str, tabstop: usize, wrap: Option<usize>) -> LineLayout {
+        LineLayout::new(&Rope::from(s).slice(..), tabstop, wrap, &[])
+    }
+
+    #[test]
+    fn test_layout_columns() {
+        let layout = layout("a\t日\u{1}b", 4, None);
+        let cols: Vec<_> = layout.cells.iter().map(|cell| (cell.col, cell.width)).collect();
+
+        assert_eq!(cols, vec![(0, 1), (1, 3), (4, 2), (6, 2), (8, 1)]);
+        assert_eq!(layout.index(2), 1);
+        assert_eq!(layout.index(5), 2);
+        assert_eq!(layout.index(20), 5);
+        assert_eq!(layout.span(5), (9, 1));
+
+        // Characters only partially within the columns are skipped over.
+        assert_eq!(layout.first_within(2), 2);
+        assert_eq!(layout.first_within(4), 2);
+        assert_eq!(layout.last_within(2, 8), 3);
+        assert_eq!(layout.last_within(2, 7), 2);
+    }
+
+    #[test]
+    fn test_layout_rows() {
+        // Wide characters get pushed onto the next row, and tabs are cut short.
+        let layout = layout("abc日\tde", 8, Some(4));
+
+        assert_eq!(layout.rows(4), vec![(0..3, 0), (3..5, 4), (5..7, 8)]);
+        assert_eq!(layout.height(), 3);
+
+        // Inline virtual text takes up columns before its character.
+        let s = Rope::from("abcdef");
+        let layout = LineLayout::new(&s.slice(..), 8, Some(4), &[(2, 3)]);
+
+        assert_eq!(layout.span(2), (7, 1));
+        assert_eq!(layout.rows(4), vec![(0..2, 0), (2..3, 4), (3..6, 8)]);
+
+        // Virtual text at the end of the line can need a row of its own.
+        let layout = LineLayout::new(&s.slice(0..4), 8, Some(4), &[(4, 2)]);
+        assert_eq!(layout.rows(4), vec![(0..4, 0), (4..4, 4)]);
+    }
+}
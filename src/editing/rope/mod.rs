@@ -13,9 +13,12 @@ use std::fmt::Debug;
 use std::io::Write;
 use std::ops::Add;
 use std::ops::AddAssign;
+use std::ops::Range;
 
 use regex::{Match, Regex};
 use ropey::{Rope, RopeSlice};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+use unicode_width::UnicodeWidthChar;
 
 use crate::editing::{
     action::EditAction,
@@ -28,6 +31,7 @@ use crate::editing::{
         CursorMovementsContext,
         CursorSearch,
         EditRange,
+        IndentOptions,
         InsertStyle,
         MoveDir1D,
         MovePosition,
@@ -43,6 +47,9 @@ use crate::editing::{
 };
 
 mod diff;
+pub(crate) mod layout;
+
+use self::layout::{LineLayout, ScreenLayout};
 
 type CowStr<'a> = Cow<'a, str>;
 
@@ -84,9 +91,6 @@ pub(super) trait PrivateCursorOps {
     fn column(&mut self, dir: MoveDir1D, wrap: bool, count: usize, ctx: &CursorContext<'_>);
     fn textpos(&mut self, pos: MovePosition, start: usize, width: usize, ctx: &CursorContext<'_>);
 
-    fn screen_line(&mut self, dir: MoveDir1D, count: usize, ctx: &CursorContext<'_>);
-    fn screen_linepos(&mut self, pos: MovePosition, ctx: &CursorContext<'_>);
-
     fn bufpos(&mut self, pos: MovePosition, ctx: &CursorContext<'_>);
     fn skip_space(&mut self, ctx: &CursorContext<'_>);
     fn skip_space_rev(&mut self, ctx: &CursorContext<'_>);
@@ -105,12 +109,14 @@ impl PrivateCursorOps for Cursor {
 
         let xmax = ctx.0.max_column_idx(self.y, ctx.2);
 
-        self.x = x.min(xmax);
+        self.x = ctx.0.grapheme_column(self.y, x.min(xmax));
         self.xgoal = self.x;
     }
 
     fn set_column(&mut self, x: usize, ctx: &CursorContext<'_>) {
-        self.x = x.min(ctx.0.max_column_idx(self.y, ctx.2));
+        let xmax = ctx.0.max_column_idx(self.y, ctx.2);
+
+        self.x = ctx.0.grapheme_column(self.y, x.min(xmax));
         self.xgoal = self.x;
     }
 
@@ -118,7 +124,10 @@ impl PrivateCursorOps for Cursor {
         let nlines = ctx.0.max_line_idx();
 
         self.y = y.min(nlines);
-        self.x = self.xgoal.min(ctx.0.max_column_idx(self.y, ctx.2));
+
+        let xmax = ctx.0.max_column_idx(self.y, ctx.2);
+
+        self.x = ctx.0.grapheme_column(self.y, self.xgoal.min(xmax));
     }
 
     fn bufpos(&mut self, pos: MovePosition, ctx: &CursorContext<'_>) {
@@ -137,55 +146,6 @@ impl PrivateCursorOps for Cursor {
         }
     }
 
-    fn screen_line(&mut self, dir: MoveDir1D, mut count: usize, ctx: &CursorContext<'_>) {
-        let width = ctx.1;
-
-        match dir {
-            MoveDir1D::Previous => {
-                while count > 0 {
-                    if (self.x / width) > 0 {
-                        self.set_column(self.x - width, ctx);
-                    } else if self.y > 0 {
-                        let above = self.y.saturating_sub(1);
-                        let lines = ctx.0.max_column_idx(above, ctx.2) / width;
-                        let column = lines * width + self.x;
-                        self.set(above, column, ctx);
-                    }
-
-                    count -= 1;
-                }
-            },
-            MoveDir1D::Next => {
-                while count > 0 {
-                    let lines = ctx.0.max_column_idx(self.y, ctx.2) / width;
-
-                    if (self.x / width) < lines {
-                        self.set_column(self.x + width, ctx);
-                    } else {
-                        let below = self.y.saturating_add(1);
-
-                        if below <= ctx.0.max_line_idx() {
-                            self.set(below, self.x % width, ctx);
-                        }
-                    }
-
-                    count -= 1;
-                }
-            },
-        }
-    }
-
-    fn screen_linepos(&mut self, pos: MovePosition, ctx: &CursorContext<'_>) {
-        let width = ctx.1;
-
-        if width == 0 {
-            return;
-        }
-
-        let start = self.x - (self.x % width);
-        self.textpos(pos, start, width, ctx);
-    }
-
     fn textpos(&mut self, pos: MovePosition, start: usize, width: usize, ctx: &CursorContext<'_>) {
         match pos {
             MovePosition::Beginning => {
@@ -264,19 +224,30 @@ impl PrivateCursorOps for Cursor {
     fn column(&mut self, dir: MoveDir1D, wrap: bool, mut count: usize, ctx: &CursorContext<'_>) {
         match (wrap, dir) {
             (false, MoveDir1D::Previous) => {
-                let line = self.x.saturating_sub(count);
+                let mut x = self.x;
 
-                self.set_column(line, ctx);
+                while count > 0 && x > 0 {
+                    x = ctx.0.prev_column(self.y, x);
+                    count -= 1;
+                }
+
+                self.set_column(x, ctx);
             },
             (false, MoveDir1D::Next) => {
-                let line = self.x.saturating_add(count);
+                let max = ctx.0.max_column_idx(self.y, ctx.2);
+                let mut x = self.x;
+
+                while count > 0 && x < max {
+                    x = ctx.0.next_column(self.y, x);
+                    count -= 1;
+                }
 
-                self.set_column(line, ctx);
+                self.set_column(x, ctx);
             },
             (true, MoveDir1D::Previous) => {
                 while count > 0 {
                     if self.x > 0 {
-                        self.set_x(self.x - 1);
+                        self.set_x(ctx.0.prev_column(self.y, self.x));
                     } else if self.y > 0 {
                         let above = self.y.saturating_sub(1);
                         let column = ctx.0.max_column_idx(above, ctx.2);
@@ -291,7 +262,7 @@ impl PrivateCursorOps for Cursor {
                     let max = ctx.0.max_column_idx(self.y, ctx.2);
 
                     if self.x < max {
-                        self.set_x(self.x + 1);
+                        self.set_x(ctx.0.next_column(self.y, self.x));
                     } else {
                         let below = self.y.saturating_add(1);
 
@@ -325,6 +296,73 @@ fn next_utf8(text: &[u8], i: usize) -> usize {
     i + inc
 }
 
+/*
+ * Grapheme cluster boundaries, adapted from the ropey examples.
+ *
+ * These operate on byte indexes, and walk the rope's chunks so that clusters that cross chunk
+ * boundaries are handled correctly.
+ */
+fn next_grapheme_boundary(rope: &Rope, byte: usize) -> usize {
+    let (mut chunk, mut chunk_byte, _, _) = rope.chunk_at_byte(byte);
+    let mut gc = GraphemeCursor::new(byte, rope.len_bytes(), true);
+
+    loop {
+        match gc.next_boundary(chunk, chunk_byte) {
+            Ok(None) => return rope.len_bytes(),
+            Ok(Some(n)) => return n,
+            Err(GraphemeIncomplete::NextChunk) => {
+                chunk_byte += chunk.len();
+                let (c, cb, _, _) = rope.chunk_at_byte(chunk_byte);
+                chunk = c;
+                chunk_byte = cb;
+            },
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let ctx = rope.chunk_at_byte(n - 1).0;
+                gc.provide_context(ctx, n - ctx.len());
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn prev_grapheme_boundary(rope: &Rope, byte: usize) -> usize {
+    let (mut chunk, mut chunk_byte, _, _) = rope.chunk_at_byte(byte);
+    let mut gc = GraphemeCursor::new(byte, rope.len_bytes(), true);
+
+    loop {
+        match gc.prev_boundary(chunk, chunk_byte) {
+            Ok(None) => return 0,
+            Ok(Some(n)) => return n,
+            Err(GraphemeIncomplete::PrevChunk) => {
+                let (c, cb, _, _) = rope.chunk_at_byte(chunk_byte - 1);
+                chunk = c;
+                chunk_byte = cb;
+            },
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let ctx = rope.chunk_at_byte(n - 1).0;
+                gc.provide_context(ctx, n - ctx.len());
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn is_grapheme_boundary(rope: &Rope, byte: usize) -> bool {
+    let (chunk, chunk_byte, _, _) = rope.chunk_at_byte(byte);
+    let mut gc = GraphemeCursor::new(byte, rope.len_bytes(), true);
+
+    loop {
+        match gc.is_boundary(chunk, chunk_byte) {
+            Ok(n) => return n,
+            Err(GraphemeIncomplete::PreContext(n)) => {
+                let ctx = rope.chunk_at_byte(n - 1).0;
+                gc.provide_context(ctx, n - ctx.len());
+            },
+            _ => unreachable!(),
+        }
+    }
+}
+
 /// Iterator over a rope's characters.
 pub struct CharacterIterator<'a> {
     rc_pos: RopeCursor<'a>,
@@ -335,6 +373,8 @@ pub struct CharacterIterator<'a> {
 
     first: usize,
     last: usize,
+
+    graphemes: bool,
 }
 
 /// Iterator over a rope's lines.
@@ -361,9 +401,20 @@ impl<'a> CharacterIterator<'a> {
 
             pos: None,
             end: None,
+
+            graphemes: false,
         }
     }
 
+    /// Step over whole grapheme clusters instead of individual characters.
+    ///
+    /// Each step returns the first character of a cluster, and [pos](CharacterIterator::pos) and
+    /// [pos_back](CharacterIterator::pos_back) return the offset of the cluster's start.
+    pub fn graphemes(mut self) -> Self {
+        self.graphemes = true;
+        self
+    }
+
     #[inline]
     fn done(&self) -> bool {
         match (self.pos, self.end) {
@@ -393,7 +444,12 @@ impl<'a> Iterator for CharacterIterator<'a> {
         let res = self.rc_pos.peek()?;
 
         self.pos = self.rc_pos.pos().into();
-        self.rc_pos.next();
+
+        if self.graphemes {
+            self.rc_pos.next_grapheme();
+        } else {
+            self.rc_pos.next();
+        }
 
         if self.done() {
             return None;
@@ -405,6 +461,10 @@ impl<'a> Iterator for CharacterIterator<'a> {
 
 impl<'a> DoubleEndedIterator for CharacterIterator<'a> {
     fn next_back(&mut self) -> Option<Self::Item> {
+        if self.graphemes {
+            self.rc_end.grapheme_start();
+        }
+
         let res = self.rc_end.peek()?;
 
         self.end = self.rc_end.pos().into();
@@ -616,6 +676,25 @@ impl<'a> RopeCursor<'a> {
         }
     }
 
+    fn next_grapheme(&mut self) {
+        if let Some(idx) = self.index {
+            let len = self.rope.rope.len_chars();
+            let nxt = self.rope.next_grapheme(idx.into()).0;
+
+            if nxt < len {
+                self.index = Some(nxt);
+            } else {
+                self.index = None;
+            }
+        }
+    }
+
+    fn grapheme_start(&mut self) {
+        if let Some(idx) = self.index {
+            self.index = Some(self.rope.grapheme_start(idx.into()).0);
+        }
+    }
+
     fn to_cursor(&self) -> Cursor {
         self.rope.offset_to_cursor(self.index.expect("invalid position").into())
     }
@@ -653,6 +732,17 @@ fn roperepeat(rope: &Rope, shape: TargetShape, mut times: usize) -> Rope {
     }
 }
 
+/// The number of screen columns that a character takes up when it starts at column `col`.
+fn char_width(c: char, col: usize, tabstop: usize) -> usize {
+    if c == '\t' {
+        let tabstop = tabstop.max(1);
+
+        tabstop - col % tabstop
+    } else {
+        UnicodeWidthChar::width(c).unwrap_or(0)
+    }
+}
+
 fn togglecase_char(c: char) -> String {
     if c.is_lowercase() {
         c.to_uppercase().to_string()
@@ -684,7 +774,7 @@ fn titlecase(s: String) -> String {
 }
 
 fn get_last_column<C: EditContext>(ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>) -> bool {
-    let shaped = ctx.context.get_target_shape().is_some();
+    let shaped = ctx.context.get_target_shape().is_some() || ctx.context.is_selecting();
 
     if ctx.action.is_motion() {
         return ctx.context.get_last_column() || shaped;
@@ -725,7 +815,7 @@ impl EditRope {
         if lastcol {
             columns
         } else {
-            columns.saturating_sub(1)
+            self.grapheme_column(y, columns.saturating_sub(1))
         }
     }
 
@@ -737,31 +827,54 @@ impl EditRope {
         self.get_lines().saturating_sub(1)
     }
 
-    fn _bottom_line_idx(&self, view: &ViewportContext<Cursor>) -> usize {
+    /// The line shown on each row of a wrapped viewport.
+    fn _screen_rows(
+        &self,
+        view: &ViewportContext<Cursor>,
+        layout: &dyn ScreenLayout,
+    ) -> Vec<usize> {
         let (width, height) = view.dimensions;
-        let start = view.corner.y;
+        let mut lines = vec![];
 
-        if view.wrap {
-            let mut wrapped: usize = 0;
-            let mut actual: usize = 0;
+        if width == 0 {
+            return lines;
+        }
 
-            if width == 0 {
-                return start;
-            }
+        for line in view.corner.y..=self.max_line_idx() {
+            let lay = match layout.layout(line, Some(width)) {
+                Some(lay) => lay,
+                None => break,
+            };
+            let rows = lay.rows(width);
+
+            // Skip any rows above the corner of the viewport.
+            let skip = if line == view.corner.y {
+                rows.iter().position(|(r, _)| r.contains(&view.corner.x)).unwrap_or(0)
+            } else {
+                0
+            };
 
-            for line in self.lines_at(start, view.corner.x) {
-                wrapped += 1;
-                wrapped += line.len_chars().saturating_sub(1) / width;
-                actual += 1;
+            // Rows of virtual text above the line are only shown along with its first row.
+            let above = if skip == 0 { lay.above } else { 0 };
 
-                if wrapped >= height {
-                    break;
-                }
+            lines.extend(std::iter::repeat(line).take(above + rows.len() - skip));
+
+            if lines.len() >= height {
+                break;
             }
+        }
 
-            let off = actual.saturating_sub(1);
+        lines.truncate(height);
+
+        return lines;
+    }
 
-            return start.saturating_add(off);
+    fn _bottom_line_idx(&self, view: &ViewportContext<Cursor>, layout: &dyn ScreenLayout) -> usize {
+        let (_, height) = view.dimensions;
+        let start = view.corner.y;
+
+        if view.wrap {
+            return self._screen_rows(view, layout).last().copied().unwrap_or(start);
         } else {
             let last = self.get_lines();
             let off = height.saturating_sub(1);
@@ -770,37 +883,234 @@ impl EditRope {
         }
     }
 
-    fn _middle_line_idx(&self, view: &ViewportContext<Cursor>) -> usize {
+    fn _middle_line_idx(&self, view: &ViewportContext<Cursor>, layout: &dyn ScreenLayout) -> usize {
         if view.wrap {
-            let (width, height) = view.dimensions;
-            let mut lines = Vec::new();
+            let lines = self._screen_rows(view, layout);
+
+            lines.get(lines.len() / 2).copied().unwrap_or(view.corner.y)
+        } else {
+            let top = view.corner.y;
+            let bot = self._bottom_line_idx(view, layout);
+            top + (bot - top) / 2
+        }
+    }
 
-            for (l, line) in self.lines_at(view.corner.y, view.corner.x).enumerate() {
-                let w = 1 + line.len_chars().saturating_sub(1) / width;
+    /// Move a cursor `count` rows up or down through the wrapped lines, trying to stay at the
+    /// same screen column within the row. This returns the layout of the line the cursor ends up
+    /// on, along with the characters on its row and the column the row starts at.
+    fn _screen_line(
+        &self,
+        cursor: &mut Cursor,
+        dir: MoveDir1D,
+        count: usize,
+        width: usize,
+        layout: &dyn ScreenLayout,
+        ctx: &CursorContext<'_>,
+    ) -> Option<(LineLayout, Range<usize>, usize)> {
+        let mut y = cursor.y;
+        let mut lay = layout.layout(y, Some(width))?;
+        let mut rows = lay.rows(width);
+        let mut row = rows
+            .iter()
+            .position(|(r, _)| r.contains(&cursor.x))
+            .unwrap_or(rows.len() - 1);
+        let goal = lay.span(cursor.x).0.saturating_sub(rows[row].1);
+
+        for _ in 0..count {
+            match dir {
+                MoveDir1D::Previous => {
+                    if row > 0 {
+                        row -= 1;
+                    } else if y > 0 {
+                        y -= 1;
+                        lay = layout.layout(y, Some(width))?;
+                        rows = lay.rows(width);
+                        row = rows.len() - 1;
+                    } else {
+                        break;
+                    }
+                },
+                MoveDir1D::Next => {
+                    if row + 1 < rows.len() {
+                        row += 1;
+                    } else if y < self.max_line_idx() {
+                        y += 1;
+                        lay = layout.layout(y, Some(width))?;
+                        rows = lay.rows(width);
+                        row = 0;
+                    } else {
+                        break;
+                    }
+                },
+            }
+        }
 
-                for _ in 0..w {
-                    lines.push(l);
-                }
+        let (range, left) = rows.swap_remove(row);
+        let last = range.end.saturating_sub(1).max(range.start);
+        let x = range
+            .clone()
+            .find(|idx| {
+                let (col, w) = lay.span(*idx);
+                left + goal < col + w
+            })
+            .unwrap_or(last);
 
-                if lines.len() >= height {
-                    break;
-                }
+        cursor.set(y, x, ctx);
+
+        return Some((lay, range, left));
+    }
+
+    /// Move a cursor to a position within the part of its line that is shown on screen.
+    fn _screen_linepos(
+        &self,
+        cursor: &mut Cursor,
+        pos: MovePosition,
+        view: &ViewportContext<Cursor>,
+        layout: &dyn ScreenLayout,
+        ctx: &CursorContext<'_>,
+    ) -> Option<()> {
+        let width = view.get_width();
+
+        let x = if view.wrap {
+            if width == 0 {
+                return Some(());
             }
 
-            lines.truncate(height);
+            let (lay, range, left) =
+                self._screen_line(cursor, MoveDir1D::Next, 0, width, layout, ctx)?;
 
-            let len = lines.len();
+            match pos {
+                MovePosition::Beginning => range.start,
+                MovePosition::Middle => lay.index(left + width / 2).clamp(range.start, range.end),
+                MovePosition::End if range.end < lay.len() => range.end.saturating_sub(1),
+                MovePosition::End => range.end,
+            }
+        } else {
+            let lay = layout.layout(cursor.y, None)?;
+            let left = view.corner.x.min(lay.span(cursor.x).0);
+            let width = width.max(1);
+
+            match pos {
+                MovePosition::Beginning => lay.first_within(left),
+                MovePosition::Middle => lay.index(left + (width - 1) / 2),
+                MovePosition::End => lay.last_within(left, left + width),
+            }
+        };
 
-            if len == 0 {
-                view.corner.y
-            } else {
-                view.corner.y + lines.get(len / 2).unwrap()
+        cursor.set_column(x, ctx);
+
+        Some(())
+    }
+
+    /// Get the range between a cursor and where a movement took it to.
+    pub(crate) fn motion_range(
+        &self,
+        cursor: &Cursor,
+        mut nc: Cursor,
+        movement: &MoveType,
+    ) -> EditRange<Cursor> {
+        let shape = movement.shape();
+        let mut cc = cursor.clone();
+
+        if nc < cc {
+            // This was a leftwards movements.
+            if movement.is_inclusive_motion() {
+                cc.set_x(self.prev_column(cc.y, cc.x));
             }
+
+            EditRange { start: nc, end: cc, shape, inclusive: false }
         } else {
-            let top = view.corner.y;
-            let bot = self._bottom_line_idx(view);
-            top + (bot - top) / 2
+            // Otherwise, this was a rightwards movement.
+            let inclusive = movement.is_inclusive_motion();
+
+            if inclusive && shape == TargetShape::CharWise {
+                // Include the rest of the grapheme cluster at the end of the range.
+                let off = self.cursor_to_offset(&nc);
+                let end = self.next_grapheme(off);
+
+                if end.0 > off.0 + 1 {
+                    nc = self.offset_to_cursor(end - CharOff(1));
+                }
+            }
+
+            EditRange { start: cc, end: nc, shape, inclusive }
+        }
+    }
+
+    /// Perform a movement that depends on how the text gets drawn on screen, like
+    /// [MoveType::ScreenLine] or [MoveType::ViewportPos], using `layout` to place each line.
+    pub(crate) fn screen_movement<C: EditContext>(
+        &self,
+        cursor: &Cursor,
+        movement: &MoveType,
+        count: &Count,
+        ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
+        layout: &dyn ScreenLayout,
+    ) -> Option<Cursor> {
+        let lastcol = get_last_column(ctx);
+        let cctx = &(self, ctx.view.get_width(), lastcol);
+        let width = ctx.view.get_width();
+        let wrap = ctx.view.wrap && width > 0;
+        let mut nc = cursor.clone();
+
+        match (movement, ctx.context.resolve(count)) {
+            // screen line movements
+            (MoveType::ScreenFirstWord(dir), count) => {
+                if wrap {
+                    self._screen_line(&mut nc, *dir, count, width, layout, cctx)?;
+                } else {
+                    nc.line(*dir, count, cctx);
+                }
+
+                self._screen_linepos(&mut nc, MovePosition::Beginning, ctx.view, layout, cctx)?;
+                nc.skip_space(cctx);
+            },
+            (MoveType::ScreenLine(dir), count) => {
+                if wrap {
+                    self._screen_line(&mut nc, *dir, count, width, layout, cctx)?;
+                } else {
+                    nc.line(*dir, count, cctx);
+                }
+            },
+            (MoveType::ScreenLinePos(pos), count) => {
+                if wrap {
+                    self._screen_line(&mut nc, MoveDir1D::Next, count, width, layout, cctx)?;
+                } else {
+                    nc.line(MoveDir1D::Next, count, cctx);
+                }
+
+                self._screen_linepos(&mut nc, *pos, ctx.view, layout, cctx)?;
+            },
+
+            // viewport position movements
+            (MoveType::ViewportPos(MovePosition::Beginning), count) => {
+                let count = count.saturating_sub(1);
+                let top = ctx.view.corner.y;
+                let bot = self._bottom_line_idx(ctx.view, layout);
+                let line = top.saturating_add(count).min(bot);
+
+                nc.set_line(line, cctx);
+                nc.first_word(cctx);
+            },
+            (MoveType::ViewportPos(MovePosition::Middle), _) => {
+                let line = self._middle_line_idx(ctx.view, layout);
+                nc.set_line(line, cctx);
+                nc.first_word(cctx);
+            },
+            (MoveType::ViewportPos(MovePosition::End), count) => {
+                let count = count.saturating_sub(1);
+                let top = ctx.view.corner.y;
+                let bot = self._bottom_line_idx(ctx.view, layout);
+                let line = bot.saturating_sub(count).max(top);
+
+                nc.set_line(line, cctx);
+                nc.first_word(cctx);
+            },
+
+            _ => return self.movement(cursor, movement, count, ctx),
         }
+
+        return Some(nc);
     }
 
     /// Split the rope into three different parts.
@@ -1192,11 +1502,15 @@ impl EditRope {
 
     /// Do a blockwise insertion of some text. Lines within the block should be separated by a
     /// newline, and there should be no trailing newline.
+    ///
+    /// If `pad` is true, then lines that are too short to reach the insertion column get spaces
+    /// added to them before their part of the block.
     fn _insert_block(
         &mut self,
         cursor: &Cursor,
         off: usize,
         text: EditRope,
+        pad: bool,
     ) -> (CursorChoice, Vec<CursorAdjustment>) {
         let colmax = self.max_column_idx(cursor.y, true);
         let cstart = cursor.x.saturating_add(off).min(colmax);
@@ -1214,8 +1528,15 @@ impl EditRope {
 
         for line in text.rope.slice(0..loff.0).lines().map(trimnl) {
             let colmax = self.max_column_idx(c.y, true);
-            let cstart = c.x.saturating_add(off).min(colmax);
+            let column = c.x.saturating_add(off);
+            let cstart = column.min(colmax);
             let ioff = self.lincol_to_offset(c.y, cstart).0;
+            let mut line = Rope::from(line);
+
+            if pad && column > colmax && line.len_chars() > 0 {
+                line.insert(0, " ".repeat(column - colmax).as_str());
+            }
+
             let tlen = line.len_chars();
 
             adjs.push(CursorAdjustment::Column {
@@ -1225,7 +1546,7 @@ impl EditRope {
                 amt_col: tlen as isize,
             });
 
-            self._insert_at(ioff, Rope::from(line));
+            self._insert_at(ioff, line);
 
             eoff = CharOff(ioff + tlen);
 
@@ -1237,8 +1558,23 @@ impl EditRope {
         if ilines > alines {
             let start = text.offset_of_line(alines).0;
             let append = text.rope.slice(start..);
-            self.rope.append(append.into());
-            self.rope.append(Rope::from("\n"));
+
+            if pad {
+                let indent = " ".repeat(cursor.x.saturating_add(off));
+
+                for line in append.lines().map(trimnl) {
+                    if line.len_chars() > 0 {
+                        self.rope.append(Rope::from(indent.as_str()));
+                    }
+
+                    self.rope.append(line.into());
+                    self.rope.append(Rope::from("\n"));
+                }
+            } else {
+                self.rope.append(append.into());
+                self.rope.append(Rope::from("\n"));
+            }
+
             eoff = self.len_offset();
         }
 
@@ -1277,7 +1613,7 @@ impl EditRope {
                 self.rope.remove(ioff.0..ioff.0 + tlen);
                 self._insert_at(ioff.0, text.rope);
             },
-            InsertStyle::Insert => {
+            InsertStyle::Insert | InsertStyle::VirtualReplace => {
                 self._insert_at(ioff.0, text.rope);
             },
         }
@@ -1322,7 +1658,151 @@ impl EditRope {
         return (CursorChoice::Range(start, end, default), adjs);
     }
 
+    /// Calculate the screen column that a character offset is displayed at.
+    pub(crate) fn _virtual_column(&self, off: CharOff, tabstop: usize) -> usize {
+        let lstart = self.offset_of_line(self.line_of_offset(off));
+
+        self.rope
+            .slice(lstart.0..off.0)
+            .chars()
+            .fold(0, |col, c| col + char_width(c, col, tabstop))
+    }
+
+    /// Find where overwriting the screen columns that `text` takes up at `off` should stop
+    /// removing characters, and how many spaces are needed afterwards to keep the rest of the
+    /// line in place.
+    fn _overwritten(&self, off: CharOff, text: &EditRope, tabstop: usize) -> (CharOff, usize) {
+        let mut end = off.0;
+
+        if text.get_lines() > 0 {
+            // Typed newlines don't replace anything.
+            return (off, 0);
+        }
+
+        let chars = self.rope.chars_at(off.0).take_while(|c| *c != '\n');
+        let mut col = self._virtual_column(off, tabstop);
+        let ecol = text.rope.chars().fold(col, |col, c| col + char_width(c, col, tabstop));
+
+        for c in chars {
+            if col >= ecol {
+                break;
+            }
+
+            let width = char_width(c, col, tabstop);
+
+            if c == '\t' && col + width > ecol {
+                // Only replace a tab once its whole width has been used up.
+                break;
+            }
+
+            col += width;
+            end += 1;
+        }
+
+        return (end.into(), col.saturating_sub(ecol));
+    }
+
+    fn _overwrite(
+        &mut self,
+        cursor: &Cursor,
+        off: usize,
+        co: usize,
+        text: EditRope,
+        tabstop: usize,
+    ) -> (CursorChoice, Vec<CursorAdjustment>) {
+        if text.get_lines() > 0 {
+            return self._insert(cursor, off, co, text, InsertStyle::Insert);
+        }
+
+        let colmax = self.max_column_idx(cursor.y, true);
+        let cstart = cursor.x.saturating_add(off).min(colmax);
+
+        let coff = self.cursor_to_offset(cursor);
+        let ioff = self.lincol_to_offset(cursor.y, cstart);
+
+        let tlen = text.len();
+        let (eoff, pad) = self._overwritten(ioff, &text, tabstop);
+        let text = text + EditRope::from(" ".repeat(pad));
+        let (_, adjs) = self.replace(ioff, eoff, false, text);
+
+        let start = self.offset_to_cursor(ioff);
+        let end = self.offset_to_cursor(CharOff(ioff.0 + tlen));
+
+        let noff = (coff.0 + tlen).saturating_sub(1).saturating_add(co).into();
+        let default = self.offset_to_cursor(noff);
+
+        return (CursorChoice::Range(start, end, default), adjs);
+    }
+
+    /// Remove the characters that typing `text` at a cursor position would overwrite with
+    /// [InsertStyle::VirtualReplace], without inserting the text itself.
+    pub fn remove_overwritten(
+        &mut self,
+        cursor: &Cursor,
+        text: &EditRope,
+        tabstop: usize,
+    ) -> Vec<CursorAdjustment> {
+        let off = self.cursor_to_offset(cursor);
+        let (end, pad) = self._overwritten(off, text, tabstop);
+
+        if end == off && pad == 0 {
+            return vec![];
+        }
+
+        return self.replace(off, end, false, EditRope::from(" ".repeat(pad))).1;
+    }
+
+    /// Get the text that [EditRope::vreplace] would replace when typing `text` before or after a
+    /// given cursor position, and how many spaces it would add after `text` to keep the rest of
+    /// the line in place.
+    pub(crate) fn vreplaced(
+        &self,
+        cursor: &Cursor,
+        dir: MoveDir1D,
+        text: &EditRope,
+        tabstop: usize,
+    ) -> (EditRope, usize) {
+        let off = match dir {
+            MoveDir1D::Previous => 0,
+            MoveDir1D::Next => 1,
+        };
+
+        let colmax = self.max_column_idx(cursor.y, true);
+        let cstart = cursor.x.saturating_add(off).min(colmax);
+        let ioff = self.lincol_to_offset(cursor.y, cstart);
+        let (eoff, pad) = self._overwritten(ioff, text, tabstop);
+
+        return (self.slice(ioff, eoff, false), pad);
+    }
+
+    /// Replace the screen columns taken up by text typed before or after a given cursor
+    /// position, as done by [InsertStyle::VirtualReplace], with tab stops every `tabstop`
+    /// columns.
+    pub fn vreplace(
+        &mut self,
+        cursor: &Cursor,
+        dir: MoveDir1D,
+        text: EditRope,
+        tabstop: usize,
+    ) -> (CursorChoice, Vec<CursorAdjustment>) {
+        if text.is_empty() {
+            return (CursorChoice::Single(cursor.clone()), vec![]);
+        }
+
+        match dir {
+            MoveDir1D::Previous => {
+                return self._overwrite(cursor, 0, 1, text, tabstop);
+            },
+            MoveDir1D::Next => {
+                return self._overwrite(cursor, 1, 0, text, tabstop);
+            },
+        }
+    }
+
     /// Insert or replace text before or after a given cursor position.
+    ///
+    /// [InsertStyle::VirtualReplace] assumes the default tab width; use [EditRope::vreplace] to
+    /// lay text out with a different one.
     pub fn insert(
         &mut self,
         cursor: &Cursor,
@@ -1330,6 +1810,10 @@ impl EditRope {
         text: EditRope,
         style: InsertStyle,
     ) -> (CursorChoice, Vec<CursorAdjustment>) {
+        if let InsertStyle::VirtualReplace = style {
+            return self.vreplace(cursor, dir, text, IndentOptions::default().tabstop);
+        }
+
         if text.is_empty() {
             return (CursorChoice::Single(cursor.clone()), vec![]);
         }
@@ -1370,14 +1854,30 @@ impl EditRope {
                 return self._insert_below(cursor, text);
             },
             (TargetShape::BlockWise, MoveDir1D::Previous) => {
-                return self._insert_block(cursor, 0, text);
+                return self._insert_block(cursor, 0, text, false);
             },
             (TargetShape::BlockWise, MoveDir1D::Next) => {
-                return self._insert_block(cursor, 1, text);
+                return self._insert_block(cursor, 1, text, false);
             },
         }
     }
 
+    /// Insert a block of text so that each of its lines starts at the cursor's column.
+    ///
+    /// Unlike a [TargetShape::BlockWise] [paste](EditRope::paste), lines that end before the
+    /// cursor's column are padded with spaces, so that the block keeps its shape.
+    pub fn insert_block(
+        &mut self,
+        cursor: &Cursor,
+        text: EditRope,
+    ) -> (CursorChoice, Vec<CursorAdjustment>) {
+        if text.is_empty() {
+            return (CursorChoice::Single(cursor.clone()), vec![]);
+        }
+
+        return self._insert_block(cursor, 0, text, true);
+    }
+
     /// Mutably force this rope to contain a trailing newline if it doesn't already.
     pub fn trailing_newline(&mut self) {
         let len = self.rope.len_chars();
@@ -1534,6 +2034,93 @@ impl EditRope {
         Cursor::new(line, off.0 - loff.0)
     }
 
+    /// Return the offset of the start of the grapheme cluster containing `off`.
+    ///
+    /// Newlines always start their own cluster, so that a carriage return at the end of a line
+    /// never gets joined to the next line.
+    pub fn grapheme_start(&self, off: CharOff) -> CharOff {
+        if off.0 == 0 || off.0 >= self.rope.len_chars() {
+            return off;
+        }
+
+        if self.rope.char(off.0).is_ascii() {
+            // ASCII characters always begin a new cluster.
+            return off;
+        }
+
+        let byte = self.rope.char_to_byte(off.0);
+
+        if is_grapheme_boundary(&self.rope, byte) {
+            return off;
+        }
+
+        let start = prev_grapheme_boundary(&self.rope, byte);
+
+        CharOff(self.rope.byte_to_char(start))
+    }
+
+    /// Return the offset of the start of the grapheme cluster following the one containing
+    /// `off`.
+    pub fn next_grapheme(&self, off: CharOff) -> CharOff {
+        let len = self.rope.len_chars();
+        let next = off.0 + 1;
+
+        if next >= len {
+            return CharOff(next.min(len.max(off.0)));
+        }
+
+        if self.rope.char(next).is_ascii() {
+            return CharOff(next);
+        }
+
+        let byte = self.rope.char_to_byte(off.0);
+        let end = next_grapheme_boundary(&self.rope, byte);
+
+        CharOff(self.rope.byte_to_char(end))
+    }
+
+    /// Return the offset of the start of the grapheme cluster preceding the one containing
+    /// `off`.
+    pub fn prev_grapheme(&self, off: CharOff) -> CharOff {
+        let off = self.grapheme_start(off);
+
+        if off.0 == 0 {
+            return off;
+        }
+
+        let prev = off.0 - 1;
+
+        if self.rope.char(prev).is_ascii() {
+            return CharOff(prev);
+        }
+
+        let byte = self.rope.char_to_byte(off.0);
+        let start = prev_grapheme_boundary(&self.rope, byte);
+
+        CharOff(self.rope.byte_to_char(start))
+    }
+
+    /// Move column `x` on line `y` back to the start of its grapheme cluster.
+    pub(crate) fn grapheme_column(&self, y: usize, x: usize) -> usize {
+        let loff = self.offset_of_line(y);
+
+        (self.grapheme_start(loff + CharOff(x)) - loff).0
+    }
+
+    /// The column of the grapheme cluster following the one at column `x` on line `y`.
+    pub(crate) fn next_column(&self, y: usize, x: usize) -> usize {
+        let loff = self.offset_of_line(y);
+
+        (self.next_grapheme(loff + CharOff(x)) - loff).0
+    }
+
+    /// The column of the grapheme cluster preceding the one at column `x` on line `y`.
+    pub(crate) fn prev_column(&self, y: usize, x: usize) -> usize {
+        let loff = self.offset_of_line(y);
+
+        (self.prev_grapheme(loff + CharOff(x)) - loff).0
+    }
+
     fn offset_to_rc(&self, off: CharOff) -> RopeCursor<'_> {
         RopeCursor::new(self, off.0)
     }
@@ -1713,6 +2300,65 @@ impl EditRope {
         return None;
     }
 
+    /// Find the first match of `needle` that starts at or after `start`, and return its bounds
+    /// along with the text that should replace it.
+    ///
+    /// If `expand` is `true`, then references to capture groups within `replacement` are expanded.
+    /// Otherwise, it's used as is.
+    pub(crate) fn find_replacement(
+        &self,
+        start: CharOff,
+        needle: &Regex,
+        replacement: &str,
+        expand: bool,
+    ) -> Option<(CharOff, CharOff, String)> {
+        if start > self.len_offset() {
+            return None;
+        }
+
+        let text = CowStr::from(&self.rope);
+        let pos = self.rope.char_to_byte(start.0);
+        let caps = needle.captures_at(&text, pos)?;
+        let m = caps.get(0)?;
+
+        let mso = CharOff(self.rope.byte_to_char(m.start()));
+        let meo = CharOff(self.rope.byte_to_char(m.end()));
+
+        let mut subst = String::new();
+
+        if expand {
+            caps.expand(replacement, &mut subst);
+        } else {
+            subst.push_str(replacement);
+        }
+
+        Some((mso, meo, subst))
+    }
+
+    /// Count the matches of `needle` that start at or before `cursor`, and the matches within the
+    /// whole text, stopping once `limit` matches have been found.
+    pub(crate) fn count_matches(
+        &self,
+        cursor: &Cursor,
+        needle: &Regex,
+        limit: usize,
+    ) -> (usize, usize) {
+        let text = CowStr::from(&self.rope);
+        let pos = self.rope.char_to_byte(self.cursor_to_offset(cursor).0);
+        let mut before = 0;
+        let mut total = 0;
+
+        for m in needle.find_iter(&text).take(limit) {
+            if m.start() <= pos {
+                before += 1;
+            }
+
+            total += 1;
+        }
+
+        (before, total)
+    }
+
     fn _find_regex_next(
         &self,
         start: usize,
@@ -1785,8 +2431,8 @@ impl EditRope {
     ) -> Option<BoundaryTestIterator> {
         let off = self.cursor_to_offset(nc);
 
-        let boff = off.0.saturating_sub(1).into();
-        let aoff = off.0.saturating_add(1).into();
+        let boff = self.prev_grapheme(off);
+        let aoff = self.next_grapheme(off);
 
         let (chars, skip_first) = match dir {
             MoveDir1D::Next => {
@@ -1807,6 +2453,8 @@ impl EditRope {
             },
         };
 
+        let chars = chars.graphemes();
+
         let ctx = BoundaryTestContext {
             current: ' ',
             before: None,
@@ -2120,6 +2768,119 @@ impl EditRope {
         Some(range)
     }
 
+    fn find_paragraph(
+        &self,
+        cursor: &Cursor,
+        inclusive: bool,
+        count: usize,
+    ) -> Option<EditRange<Cursor>> {
+        let last = self.last().y;
+        let blank = self.is_blank_line(cursor.y);
+
+        // Find the last line in the run of lines that share the blankness of the given line.
+        let run_end = |line: usize| {
+            let b = self.is_blank_line(line);
+            let mut end = line;
+
+            while end < last && self.is_blank_line(end + 1) == b {
+                end += 1;
+            }
+
+            end
+        };
+
+        let mut start = cursor.y;
+
+        while start > 0 && self.is_blank_line(start - 1) == blank {
+            start -= 1;
+        }
+
+        // An inclusive range also consumes the run of lines that follows each paragraph.
+        let runs = if inclusive {
+            count.saturating_mul(2)
+        } else {
+            count
+        };
+        let mut end = run_end(start);
+
+        for n in 1..runs {
+            if end < last {
+                end = run_end(end + 1);
+                continue;
+            }
+
+            if n + 1 == runs && !self.is_blank_line(end) {
+                // There are no trailing blank lines, so take the preceding ones instead.
+                while start > 0 && self.is_blank_line(start - 1) {
+                    start -= 1;
+                }
+
+                break;
+            }
+
+            return None;
+        }
+
+        let start = Cursor::new(start, 0);
+        let end = Cursor::new(end, 0);
+
+        EditRange::inclusive(start, end, TargetShape::LineWise).into()
+    }
+
+    fn find_horizontal_space(&self, cursor: &Cursor) -> Option<EditRange<Cursor>> {
+        let columns = self.get_columns(cursor.y);
+        let chars = self.chars(self.offset_of_line(cursor.y)).take(columns).collect::<Vec<_>>();
+        let blank = |c: &&char| matches!(c, ' ' | '\t');
+
+        let x = cursor.x.min(chars.len());
+        let start = x - chars[..x].iter().rev().take_while(blank).count();
+        let end = x + chars[x..].iter().take_while(blank).count();
+
+        if start == end {
+            return None;
+        }
+
+        let start = Cursor::new(cursor.y, start);
+        let end = Cursor::new(cursor.y, end);
+
+        EditRange::exclusive(start, end, TargetShape::CharWise).into()
+    }
+
+    fn find_blank_lines(&self, cursor: &Cursor) -> Option<EditRange<Cursor>> {
+        let last = self.last().y;
+        let mut start = cursor.y;
+        let mut end = cursor.y;
+
+        if self.is_blank_line(cursor.y) {
+            while start > 0 && self.is_blank_line(start - 1) {
+                start -= 1;
+            }
+
+            while end < last && self.is_blank_line(end + 1) {
+                end += 1;
+            }
+
+            // Leave a single blank line behind, unless it was the only one.
+            end = end.saturating_sub(1).max(start);
+        } else {
+            if end >= last || !self.is_blank_line(end + 1) {
+                return None;
+            }
+
+            start = end + 1;
+            end = start;
+
+            while end < last && self.is_blank_line(end + 1) {
+                end += 1;
+            }
+        }
+
+        let start = Cursor::new(start, 0);
+        let end = Cursor::new(end, 0);
+
+        EditRange::inclusive(start, end, TargetShape::LineWise).into()
+    }
+
     fn seek_next<'a, O: BoundaryTest>(
         &'a self,
         obj: &O,
@@ -2315,6 +3076,19 @@ impl ToString for EditRope {
     }
 }
 
+impl ScreenLayout for EditRope {
+    fn layout(&self, line: usize, wrap: Option<usize>) -> Option<LineLayout> {
+        if line > self.max_line_idx() {
+            return None;
+        }
+
+        let s = self.lines(line).next()?;
+        let tabstop = IndentOptions::default().tabstop;
+
+        Some(LineLayout::new(&s, tabstop, wrap, &[]))
+    }
+}
+
 impl<C: EditContext> CursorMovements<Cursor, C> for EditRope {
     fn first_word(
         &self,
@@ -2394,6 +3168,12 @@ impl<C: EditContext> CursorMovements<Cursor, C> for EditRope {
                 return None;
             },
 
+            // fold-wise movement
+            (MoveType::Fold(_), _) => {
+                // Folds are tracked by the buffer.
+                return None;
+            },
+
             // wordwise movement
             (MoveType::FinalNonBlank(dir), count) => {
                 nc.line(*dir, count, cctx);
@@ -2456,77 +3236,26 @@ impl<C: EditContext> CursorMovements<Cursor, C> for EditRope {
 
                 // The final column is also allowed here when performing operations.
                 let lastcol = cctx.2 || !ctx.action.is_motion();
-                let maxcol = self.max_column_idx(nc.y, lastcol);
-
-                // Move the cursor to the line position.
-                let cctx = &(cctx.0, cctx.1, lastcol);
-                nc.textpos(*pos, 0, maxcol, cctx);
-
-                if let MovePosition::End = pos {
-                    nc.xgoal = usize::MAX;
-                }
-            },
-
-            // screen line movements
-            (MoveType::ScreenFirstWord(dir), count) => {
-                if ctx.view.wrap {
-                    nc.screen_line(*dir, count, cctx);
-                    nc.screen_linepos(MovePosition::Beginning, cctx);
-                    nc.skip_space(cctx);
-                } else {
-                    nc.line(*dir, count, cctx);
-
-                    let start = ctx.view.corner.x.min(nc.x);
-                    let width = cctx.1.saturating_sub(1);
-
-                    nc.textpos(MovePosition::Beginning, start, width, cctx);
-                    nc.skip_space(cctx);
-                }
-            },
-            (MoveType::ScreenLine(dir), count) => {
-                if ctx.view.wrap {
-                    nc.screen_line(*dir, count, cctx);
-                } else {
-                    nc.line(*dir, count, cctx);
-                }
-            },
-            (MoveType::ScreenLinePos(pos), count) => {
-                if ctx.view.wrap {
-                    nc.screen_line(MoveDir1D::Next, count, cctx);
-                    nc.screen_linepos(*pos, cctx);
-                } else {
-                    nc.line(MoveDir1D::Next, count, cctx);
+                let maxcol = self.max_column_idx(nc.y, lastcol);
 
-                    let start = ctx.view.corner.x.min(nc.x);
-                    let width = cctx.1.saturating_sub(1);
+                // Move the cursor to the line position.
+                let cctx = &(cctx.0, cctx.1, lastcol);
+                nc.textpos(*pos, 0, maxcol, cctx);
 
-                    nc.textpos(*pos, start, width, cctx);
+                if let MovePosition::End = pos {
+                    nc.xgoal = usize::MAX;
                 }
             },
 
-            // viewport position movements
-            (MoveType::ViewportPos(MovePosition::Beginning), count) => {
-                let count = count.saturating_sub(1);
-                let top = ctx.view.corner.y;
-                let bot = self._bottom_line_idx(ctx.view);
-                let line = top.saturating_add(count).min(bot);
-
-                nc.set_line(line, cctx);
-                nc.first_word(cctx);
-            },
-            (MoveType::ViewportPos(MovePosition::Middle), _) => {
-                let line = self._middle_line_idx(ctx.view);
-                nc.set_line(line, cctx);
-                nc.first_word(cctx);
-            },
-            (MoveType::ViewportPos(MovePosition::End), count) => {
-                let count = count.saturating_sub(1);
-                let top = ctx.view.corner.y;
-                let bot = self._bottom_line_idx(ctx.view);
-                let line = bot.saturating_sub(count).max(top);
-
-                nc.set_line(line, cctx);
-                nc.first_word(cctx);
+            // screen line and viewport position movements
+            (
+                MoveType::ScreenFirstWord(_) |
+                MoveType::ScreenLine(_) |
+                MoveType::ScreenLinePos(_) |
+                MoveType::ViewportPos(_),
+                _,
+            ) => {
+                return self.screen_movement(cursor, movement, count, ctx, self);
             },
         }
 
@@ -2601,9 +3330,10 @@ impl<C: EditContext> CursorMovements<Cursor, C> for EditRope {
 
                 EditRange::exclusive(start, end, TargetShape::LineWise).into()
             },
-            (RangeType::Paragraph, _) => {
-                // XXX: implement
-                None
+            (RangeType::Paragraph, count) => {
+                let count = ctx.context.resolve(count);
+
+                self.find_paragraph(cursor, inclusive, count)
             },
             (RangeType::Sentence, _) => {
                 // XXX: implement
@@ -2619,6 +3349,8 @@ impl<C: EditContext> CursorMovements<Cursor, C> for EditRope {
                 // XXX: implement
                 None
             },
+            (RangeType::HorizontalSpace, _) => self.find_horizontal_space(cursor),
+            (RangeType::BlankLines, _) => self.find_blank_lines(cursor),
         }
     }
 
@@ -2634,27 +3366,8 @@ impl<C: EditContext> CursorMovements<Cursor, C> for EditRope {
         ctx: &CursorMovementsContext<'_, '_, '_, Cursor, C>,
     ) -> Option<EditRange<Cursor>> {
         let nc = self.movement(cursor, movement, count, ctx)?;
-        let shape = movement.shape();
-
-        let range = {
-            let mut cc = cursor.clone();
 
-            if nc < cc {
-                // This was a leftwards movements.
-                if movement.is_inclusive_motion() {
-                    cc.left(1);
-                }
-
-                EditRange { start: nc, end: cc, shape, inclusive: false }
-            } else {
-                // Otherwise, this was a rightwards movement.
-                let inclusive = movement.is_inclusive_motion();
-
-                EditRange { start: cc, end: nc, shape, inclusive }
-            }
-        };
-
-        Some(range)
+        Some(self.motion_range(cursor, nc, movement))
     }
 }
 
@@ -2715,7 +3428,7 @@ impl CursorSearch<Cursor> for EditRope {
                 let mso = rope.byte_to_char(m.start());
                 let meo = rope.byte_to_char(m.end());
                 let sc = self.offset_to_cursor(so + CharOff(mso));
-                let ec = self.offset_to_cursor(eo + CharOff(meo));
+                let ec = self.offset_to_cursor(so + CharOff(meo.saturating_sub(1).max(mso)));
 
                 EditRange::inclusive(sc, ec, TargetShape::CharWise)
             })
@@ -3053,6 +3766,73 @@ mod tests {
         assert_eq!(iter.next_back(), None);
     }
 
+    #[test]
+    fn test_chars_graphemes_iter() {
+        let rope = EditRope::from("e\u{301}\u{1F1FA}\u{1F1F8}x\n");
+
+        let mut iter = rope.chars(0.into()).graphemes();
+        assert_eq!(iter.next(), Some('e'));
+        assert_eq!(iter.pos(), 0.into());
+        assert_eq!(iter.next(), Some('\u{1F1FA}'));
+        assert_eq!(iter.pos(), 2.into());
+        assert_eq!(iter.next(), Some('x'));
+        assert_eq!(iter.pos(), 4.into());
+        assert_eq!(iter.next(), Some('\n'));
+        assert_eq!(iter.pos(), 5.into());
+        assert_eq!(iter.next(), None);
+
+        let mut iter = rope.chars(0.into()).graphemes();
+        assert_eq!(iter.next_back(), Some('\n'));
+        assert_eq!(iter.pos_back(), 5.into());
+        assert_eq!(iter.next_back(), Some('x'));
+        assert_eq!(iter.pos_back(), 4.into());
+        assert_eq!(iter.next_back(), Some('\u{1F1FA}'));
+        assert_eq!(iter.pos_back(), 2.into());
+        assert_eq!(iter.next_back(), Some('e'));
+        assert_eq!(iter.pos_back(), 0.into());
+        assert_eq!(iter.next_back(), None);
+    }
+
+    #[test]
+    fn test_grapheme_offsets() {
+        let rope = EditRope::from(
+            "e\u{301}\u{1F1FA}\u{1F1F8}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1100}\u{1161}\u{11A8}x\r\n",
+        );
+
+        let next = |off: usize| rope.next_grapheme(off.into()).0;
+        let prev = |off: usize| rope.prev_grapheme(off.into()).0;
+        let start = |off: usize| rope.grapheme_start(off.into()).0;
+
+        assert_eq!(next(0), 2);
+        assert_eq!(next(1), 2);
+        assert_eq!(next(2), 4);
+        assert_eq!(next(4), 9);
+        assert_eq!(next(6), 9);
+        assert_eq!(next(9), 12);
+        assert_eq!(next(12), 13);
+
+        // The carriage return and newline are kept separate.
+        assert_eq!(next(13), 14);
+        assert_eq!(next(14), 15);
+
+        assert_eq!(prev(15), 14);
+        assert_eq!(prev(14), 13);
+        assert_eq!(prev(13), 12);
+        assert_eq!(prev(12), 9);
+        assert_eq!(prev(9), 4);
+        assert_eq!(prev(7), 2);
+        assert_eq!(prev(4), 2);
+        assert_eq!(prev(2), 0);
+        assert_eq!(prev(1), 0);
+        assert_eq!(prev(0), 0);
+
+        assert_eq!(start(1), 0);
+        assert_eq!(start(3), 2);
+        assert_eq!(start(7), 4);
+        assert_eq!(start(11), 9);
+        assert_eq!(start(14), 14);
+    }
+
     #[test]
     fn test_lines_iter() {
         let r = EditRope::from("a\nbc\ndef\ng\nhijklm\n");
@@ -3367,6 +4147,50 @@ mod tests {
         assert_eq!(choice, CursorChoice::Single(Cursor::new(0, 2)));
     }
 
+    #[test]
+    fn test_rope_vreplace() {
+        let mut rope = EditRope::from("a\tb\n");
+        let style = InsertStyle::VirtualReplace;
+        let prev = MoveDir1D::Previous;
+
+        // Typing over a tab only eats into it.
+        let choice = rope.vreplace(&Cursor::new(0, 0), prev, "xy".into(), 8).0;
+        assert_eq!(rope.to_string(), "xy\tb\n");
+        assert_eq!(choice.resolve(CursorEnd::Auto).unwrap().cursor(), &Cursor::new(0, 2));
+
+        // Using up the tab's width replaces it.
+        let choice = rope.vreplace(&Cursor::new(0, 2), prev, "zabcde".into(), 8).0;
+        assert_eq!(rope.to_string(), "xyzabcdeb\n");
+        assert_eq!(choice.resolve(CursorEnd::Auto).unwrap().cursor(), &Cursor::new(0, 8));
+
+        // Smaller tab stops get used up sooner.
+        let mut rope = EditRope::from("a\tb\n");
+        rope.vreplace(&Cursor::new(0, 0), prev, "xyzw".into(), 4);
+        assert_eq!(rope.to_string(), "xyzwb\n");
+
+        // Wide characters take up two columns.
+        let mut rope = EditRope::from("abcd\n");
+        rope.insert(&Cursor::new(0, 0), prev, "日".into(), style);
+        assert_eq!(rope.to_string(), "日cd\n");
+
+        // Replacing half of a wide character pads out the rest of its width.
+        let mut rope = EditRope::from("日本語\n");
+        let choice = rope.vreplace(&Cursor::new(0, 0), prev, "x".into(), 8).0;
+        assert_eq!(rope.to_string(), "x 本語\n");
+        assert_eq!(choice.resolve(CursorEnd::Auto).unwrap().cursor(), &Cursor::new(0, 1));
+
+        // Typed newlines don't replace anything.
+        let mut rope = EditRope::from("a\tb\n");
+        let choice = rope.vreplace(&Cursor::new(0, 1), prev, "\n".into(), 8).0;
+        assert_eq!(rope.to_string(), "a\n\tb\n");
+        assert_eq!(choice.resolve(CursorEnd::Auto).unwrap().cursor(), &Cursor::new(1, 0));
+
+        // Replacing stops at the end of the line.
+        let mut rope = EditRope::from("ab\ncd\n");
+        rope.vreplace(&Cursor::new(0, 1), prev, "xyz".into(), 8);
+        assert_eq!(rope.to_string(), "axyz\ncd\n");
+    }
+
     #[test]
     fn test_rope_insert() {
         let mut rope = EditRope::from("world");
@@ -3421,6 +4245,25 @@ mod tests {
         assert_eq!(cursor, &Cursor::new(0, 2));
     }
 
+    #[test]
+    fn test_rope_insert_block() {
+        let mut rope = EditRope::from("hello\na\n\nworld\n");
+        let block = EditRope::from("12\n34\n\n56\n78");
+
+        // Short lines get padded, and extra lines get appended.
+        let choice = rope.insert_block(&Cursor::new(0, 3), block).0;
+        assert_eq!(rope.to_string(), "hel12lo\na  34\n\nwor56ld\n   78\n");
+        assert_eq!(
+            choice,
+            CursorChoice::Range(Cursor::new(0, 3), Cursor::new(4, 5), Cursor::new(0, 3))
+        );
+
+        // Inserting an empty block does nothing.
+        let choice = rope.insert_block(&Cursor::new(1, 1), "".into()).0;
+        assert_eq!(rope.to_string(), "hel12lo\na  34\n\nwor56ld\n   78\n");
+        assert_eq!(choice, CursorChoice::Single(Cursor::new(1, 1)));
+    }
+
     #[test]
     fn test_rope_repeat() {
         let rope1 = EditRope::from("a b c");
@@ -3933,6 +4776,43 @@ mod tests {
         assert_eq!(res, EditRange::inclusive(Cursor::new(0, 7), Cursor::new(0, 8), cw));
     }
 
+    #[test]
+    fn test_find_matches() {
+        let needle = Regex::new("he").unwrap();
+        let cw = TargetShape::CharWise;
+        let multi = EditRope::from("writhe helium\nworld help\nhey\n");
+
+        // Find all matches in the text.
+        let res = multi.find_matches(&Cursor::new(0, 0), &Cursor::new(2, 3), &needle);
+        assert_eq!(res, vec![
+            EditRange::inclusive(Cursor::new(0, 4), Cursor::new(0, 5), cw),
+            EditRange::inclusive(Cursor::new(0, 7), Cursor::new(0, 8), cw),
+            EditRange::inclusive(Cursor::new(1, 6), Cursor::new(1, 7), cw),
+            EditRange::inclusive(Cursor::new(2, 0), Cursor::new(2, 1), cw),
+        ]);
+
+        // Only find matches within the range.
+        let res = multi.find_matches(&Cursor::new(0, 6), &Cursor::new(1, 7), &needle);
+        assert_eq!(res, vec![
+            EditRange::inclusive(Cursor::new(0, 7), Cursor::new(0, 8), cw),
+            EditRange::inclusive(Cursor::new(1, 6), Cursor::new(1, 7), cw),
+        ]);
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let needle = Regex::new("he").unwrap();
+        let multi = EditRope::from("writhe helium\nworld help\nhey\n");
+
+        assert_eq!(multi.count_matches(&Cursor::new(0, 0), &needle, 100), (0, 4));
+        assert_eq!(multi.count_matches(&Cursor::new(0, 4), &needle, 100), (1, 4));
+        assert_eq!(multi.count_matches(&Cursor::new(1, 8), &needle, 100), (3, 4));
+
+        // Counting stops at the limit.
+        assert_eq!(multi.count_matches(&Cursor::new(0, 4), &needle, 2), (1, 2));
+        assert_eq!(multi.count_matches(&Cursor::new(2, 0), &needle, 2), (2, 2));
+    }
+
     #[test]
     fn test_find_item() {
         let rope = EditRope::from("hello world ( a b c { d ( e () f ) g } h )");
@@ -4130,6 +5010,56 @@ mod tests {
         assert_eq!(cursor, Cursor::new(0, 5));
     }
 
+    #[test]
+    fn test_motion_column_graphemes() {
+        // A combining accent, a flag, a ZWJ sequence, and Hangul jamo.
+        let rope = EditRope::from(
+            "e\u{301}\u{1F1FA}\u{1F1F8}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{1100}\u{1161}\u{11A8}x\n\
+             ab\u{1100}\u{1161}\n",
+        );
+        let vwctx = ViewportContext::<Cursor>::default();
+        let mut vctx: VimContext = VimContext::default();
+        let mut cursor = rope.first();
+        let count = Count::Contextual;
+
+        // "l" moves over one cluster at a time.
+        let mov = MoveType::Column(MoveDir1D::Next, false);
+
+        for x in [2, 4, 9, 12, 12] {
+            cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+            assert_eq!(cursor, Cursor::new(0, x));
+        }
+
+        // "2h" moves back over two clusters.
+        let mov = MoveType::Column(MoveDir1D::Previous, false);
+        vctx.action.count = Some(2);
+        cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(0, 4));
+
+        // "j" snaps the column to the start of the cluster beneath it.
+        let mov = MoveType::Line(MoveDir1D::Next);
+        vctx.action.count = Some(1);
+        cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!((cursor.y, cursor.x), (1, 2));
+
+        // The last column is the start of the final cluster.
+        let mov = MoveType::Column(MoveDir1D::Next, false);
+        vctx.action.count = Some(10);
+        cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(1, 2));
+
+        // Moving with wrapping also steps over clusters.
+        let mov = MoveType::Column(MoveDir1D::Previous, true);
+        vctx.action.count = Some(4);
+        cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(0, 9));
+
+        let mov = MoveType::Column(MoveDir1D::Next, true);
+        vctx.action.count = Some(3);
+        cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(1, 1));
+    }
+
     #[test]
     fn test_motion_word_accents() {
         let rope = EditRope::from("árvíztűrő tükörfúrógép");
@@ -4151,6 +5081,31 @@ mod tests {
         assert_eq!(cursor, Cursor::new(0, 0));
     }
 
+    #[test]
+    fn test_motion_word_graphemes() {
+        let rope = EditRope::from("cafe\u{301} bar\n");
+        let vwctx = ViewportContext::<Cursor>::default();
+        let vctx: VimContext = VimContext::default();
+        let cursor = rope.first();
+        let count = Count::Contextual;
+
+        // "w" doesn't stop on the combining accent.
+        let mov = MoveType::WordBegin(WordStyle::Little, MoveDir1D::Next);
+        let res = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(res, Cursor::new(0, 6));
+
+        // "e" lands on the start of the final cluster.
+        let mov = MoveType::WordEnd(WordStyle::Little, MoveDir1D::Next);
+        let res = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(res, Cursor::new(0, 3));
+
+        // But an inclusive range over it covers the whole cluster.
+        let res = rope.range_of_movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(res.start, Cursor::new(0, 0));
+        assert_eq!(res.end, Cursor::new(0, 4));
+        assert!(res.inclusive);
+    }
+
     #[test]
     fn test_motion_word() {
         let rope = EditRope::from("hello world\na,b,c,d e,f,g,h\n");
@@ -5127,6 +6082,90 @@ mod tests {
         assert_eq!(cursor, Cursor::new(1, 7));
     }
 
+    #[test]
+    fn test_motion_screen_layout_wrap() {
+        let rope = EditRope::from("\tab\n日本語です\n");
+        let mut vwctx = ViewportContext::<Cursor>::default();
+        let vctx: VimContext = VimContext::default();
+
+        vwctx.corner = Cursor::new(0, 0);
+        vwctx.set_wrap(true);
+        vwctx.dimensions = (5, 5);
+
+        // The tab fills the first row, and wide characters that don't fit get pushed onto the
+        // next row, so the second line is drawn as "日本", "語で" and "す".
+        let mov = MoveType::ScreenLine(MoveDir1D::Previous);
+        let count = Count::Exact(1);
+        let cursor = rope
+            .movement(&Cursor::new(1, 3), &mov, &count, cmctx!(vwctx, vctx))
+            .unwrap();
+        assert_eq!(cursor, Cursor::new(1, 1));
+
+        let cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(0, 2));
+
+        let mov = MoveType::ScreenLine(MoveDir1D::Next);
+        let cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(1, 0));
+
+        let count = Count::Exact(2);
+        let cursor = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(cursor, Cursor::new(1, 4));
+
+        // Move within the screen lines.
+        let mov = MoveType::ScreenLinePos(MovePosition::End);
+        let count = Count::MinusOne;
+        let cursor = rope
+            .movement(&Cursor::new(1, 0), &mov, &count, cmctx!(vwctx, vctx))
+            .unwrap();
+        assert_eq!(cursor, Cursor::new(1, 1));
+
+        let mov = MoveType::ScreenLinePos(MovePosition::Middle);
+        let count = Count::Exact(0);
+        let cursor = rope
+            .movement(&Cursor::new(1, 2), &mov, &count, cmctx!(vwctx, vctx))
+            .unwrap();
+        assert_eq!(cursor, Cursor::new(1, 3));
+
+        // The bottom of the viewport counts rows using the layout.
+        vwctx.dimensions = (5, 3);
+
+        let mov = MoveType::ViewportPos(MovePosition::End);
+        let count = Count::Exact(1);
+        let cursor = rope
+            .movement(&Cursor::new(0, 0), &mov, &count, cmctx!(vwctx, vctx))
+            .unwrap();
+        assert_eq!(cursor, Cursor::new(1, 0));
+    }
+
+    #[test]
+    fn test_motion_screen_layout_nowrap() {
+        let rope = EditRope::from("\tabcdef\n");
+        let mut vwctx = ViewportContext::<Cursor>::default();
+        let vctx: VimContext = VimContext::default();
+
+        // The window starts at the 6th screen column, partway through the tab.
+        vwctx.corner = Cursor::new(0, 6);
+        vwctx.set_wrap(false);
+        vwctx.dimensions = (5, 5);
+
+        let cursor = Cursor::new(0, 2);
+
+        let mov = MoveType::ScreenLinePos(MovePosition::Beginning);
+        let count = Count::Exact(0);
+        let res = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(res, Cursor::new(0, 1));
+
+        let mov = MoveType::ScreenLinePos(MovePosition::Middle);
+        let res = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(res, Cursor::new(0, 1));
+
+        let mov = MoveType::ScreenLinePos(MovePosition::End);
+        let count = Count::MinusOne;
+        let res = rope.movement(&cursor, &mov, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(res, Cursor::new(0, 3));
+    }
+
     #[test]
     fn test_range_buffer() {
         let rope = EditRope::from("abcdef\nghijklmn\n");
@@ -5589,6 +6628,113 @@ mod tests {
         assert_eq!(er, EditRange::inclusive(Cursor::new(0, 26), Cursor::new(0, 34), cw));
     }
 
+    #[test]
+    fn test_range_paragraph() {
+        let rope = EditRope::from("a\nb\n\n\nc\nd\n\ne\n");
+        let vwctx = ViewportContext::<Cursor>::default();
+        let mut vctx: VimContext = VimContext::default();
+        let count = Count::Contextual;
+        let rt = RangeType::Paragraph;
+        let lw = TargetShape::LineWise;
+
+        // Inner paragraph from the first paragraph.
+        let cursor = Cursor::new(0, 0);
+        let er = rope.range(&cursor, &rt, false, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(0, 0), Cursor::new(1, 0), lw));
+
+        // A paragraph includes the blank lines that follow it.
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(0, 0), Cursor::new(3, 0), lw));
+
+        // Blank lines count as their own inner paragraph.
+        vctx.action.count = Some(2);
+        let er = rope.range(&cursor, &rt, false, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(0, 0), Cursor::new(3, 0), lw));
+
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(0, 0), Cursor::new(6, 0), lw));
+
+        // Starting on blank lines.
+        vctx.action.count = Some(1);
+        let cursor = Cursor::new(3, 0);
+        let er = rope.range(&cursor, &rt, false, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(2, 0), Cursor::new(3, 0), lw));
+
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(2, 0), Cursor::new(5, 0), lw));
+
+        // The last paragraph has no trailing blank lines, so it takes the preceding ones.
+        let cursor = Cursor::new(7, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(6, 0), Cursor::new(7, 0), lw));
+
+        // Asking for more paragraphs than are left fails.
+        vctx.action.count = Some(3);
+        let cursor = Cursor::new(4, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx));
+        assert_eq!(er, None);
+    }
+
+    #[test]
+    fn test_range_horizontal_space() {
+        let rope = EditRope::from("a  \t b\nc\n");
+        let vwctx = ViewportContext::<Cursor>::default();
+        let vctx: VimContext = VimContext::default();
+        let count = Count::Contextual;
+        let rt = RangeType::HorizontalSpace;
+        let cw = TargetShape::CharWise;
+
+        // Cursor within the whitespace.
+        let cursor = Cursor::new(0, 2);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::exclusive(Cursor::new(0, 1), Cursor::new(0, 5), cw));
+
+        // Cursor right after the whitespace.
+        let cursor = Cursor::new(0, 5);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::exclusive(Cursor::new(0, 1), Cursor::new(0, 5), cw));
+
+        // Cursor right before the whitespace.
+        let cursor = Cursor::new(0, 1);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::exclusive(Cursor::new(0, 1), Cursor::new(0, 5), cw));
+
+        // No whitespace around the cursor.
+        let cursor = Cursor::new(1, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx));
+        assert_eq!(er, None);
+    }
+
+    #[test]
+    fn test_range_blank_lines() {
+        let rope = EditRope::from("a\n\n\n\nb\n\nc\nd\n");
+        let vwctx = ViewportContext::<Cursor>::default();
+        let vctx: VimContext = VimContext::default();
+        let count = Count::Contextual;
+        let rt = RangeType::BlankLines;
+        let lw = TargetShape::LineWise;
+
+        // On a non-blank line, select the following blank lines.
+        let cursor = Cursor::new(0, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(1, 0), Cursor::new(3, 0), lw));
+
+        // Among several blank lines, select all but one.
+        let cursor = Cursor::new(2, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(1, 0), Cursor::new(2, 0), lw));
+
+        // An isolated blank line selects itself.
+        let cursor = Cursor::new(5, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx)).unwrap();
+        assert_eq!(er, EditRange::inclusive(Cursor::new(5, 0), Cursor::new(5, 0), lw));
+
+        // No blank lines follow "c".
+        let cursor = Cursor::new(6, 0);
+        let er = rope.range(&cursor, &rt, true, &count, cmctx!(vwctx, vctx));
+        assert_eq!(er, None);
+    }
+
     #[test]
     fn test_len() {
         let rope = EditRope::from("\u{00AB}a\u{00BB}\n");
@@ -1,10 +1,19 @@
-use crate::editing::completion::LineCompleter;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::editing::completion::{LineCompleter, WordCompleter};
 
 /// Storage for text completion.
 #[derive(Default)]
 pub struct CompletionStore {
     /// Global collection of word completion candidates.
     pub lines: LineCompleter,
+
+    /// Words loaded from a dictionary file.
+    pub dictionary: WordCompleter,
 }
 
 impl CompletionStore {
@@ -17,4 +26,20 @@ impl CompletionStore {
     pub fn complete_word(&self, prefix: &str) -> Vec<String> {
         self.lines.complete_word(prefix)
     }
+
+    /// Complete the given word using the words loaded from dictionary files.
+    pub fn complete_dictionary(&self, prefix: &str) -> Vec<String> {
+        self.dictionary.complete_word(prefix)
+    }
+
+    /// Load the words in a file, like `/usr/share/dict/words`, for dictionary completion.
+    pub fn load_dictionary<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let words = fs::read_to_string(path)?;
+
+        for word in UnicodeSegmentation::unicode_words(words.as_str()) {
+            self.dictionary.word_incr(word);
+        }
+
+        Ok(())
+    }
 }
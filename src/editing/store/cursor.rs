@@ -1,5 +1,5 @@
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
 
 use crate::editing::{
@@ -128,12 +128,16 @@ where
     }
 }
 
+const MARK_RING_SIZE: usize = 16;
+
 /// Tracks important cursors and cursor groups:
 ///
 /// - Saved cursors (see [EditorAction::Mark])
+/// - Mark rings (see [EditorAction::MarkRing])
 /// - Saved cursor groups (see [CursorAction::Save])
 ///
 /// [EditorAction::Mark]: crate::editing::action::EditorAction::Mark
+/// [EditorAction::MarkRing]: crate::editing::action::EditorAction::MarkRing
 /// [CursorAction::Save]: crate::editing::action::CursorAction::Save
 pub struct CursorStore<I>
 where
@@ -145,6 +149,12 @@ where
     /// Tracks buffer-local marks.
     buffer: HashMap<I::ContentId, AdjustStore<Mark>>,
 
+    /// Tracks the global mark ring.
+    global_ring: VecDeque<(I::ContentId, Cursor)>,
+
+    /// Tracks buffer-local mark rings.
+    buffer_ring: HashMap<I::ContentId, VecDeque<Cursor>>,
+
     /// Tracks saved cursor groups.
     groups: HashMap<Register, (I::ContentId, CursorGroup)>,
 }
@@ -158,6 +168,8 @@ where
         CursorStore {
             global: HashMap::new(),
             buffer: HashMap::new(),
+            global_ring: VecDeque::new(),
+            buffer_ring: HashMap::new(),
             groups: HashMap::new(),
         }
     }
@@ -190,6 +202,58 @@ where
         }
     }
 
+    /// Push a [Cursor] onto the specified buffer's mark ring.
+    ///
+    /// If the most recent entry in the global mark ring is for a different buffer, then this
+    /// position is pushed onto the global mark ring as well.
+    pub fn push_mark(&mut self, id: I::ContentId, cursor: Cursor) {
+        let ring = self.buffer_ring.entry(id.clone()).or_default();
+
+        if ring.front() != Some(&cursor) {
+            ring.push_front(cursor.clone());
+            ring.truncate(MARK_RING_SIZE);
+        }
+
+        if !matches!(self.global_ring.front(), Some((owner, _)) if owner == &id) {
+            self.global_ring.push_front((id, cursor));
+            self.global_ring.truncate(MARK_RING_SIZE);
+        }
+    }
+
+    /// Take the most recent [Cursor] in the specified buffer's mark ring, and rotate it to the
+    /// back of the ring.
+    pub fn pop_mark(&mut self, id: I::ContentId) -> EditResult<Cursor, I> {
+        let ring = self.buffer_ring.get_mut(&id);
+        let cursor = ring.and_then(|ring| {
+            let cursor = ring.pop_front()?;
+            ring.push_back(cursor.clone());
+            Some(cursor)
+        });
+
+        cursor.ok_or_else(|| EditError::Failure("No mark set in this buffer".into()))
+    }
+
+    /// Take the most recent [Cursor] in the global mark ring, and rotate it to the back of the
+    /// ring.
+    ///
+    /// If the position belongs to a different buffer, then [EditError::WrongBuffer] is returned,
+    /// and the ring is left as is.
+    pub fn pop_global_mark(&mut self, id: I::ContentId) -> EditResult<Cursor, I> {
+        let (owner, cursor) = self
+            .global_ring
+            .front()
+            .cloned()
+            .ok_or_else(|| EditError::Failure("No global mark set".into()))?;
+
+        if owner != id {
+            return Err(EditError::WrongBuffer(owner));
+        }
+
+        self.global_ring.rotate_left(1);
+
+        Ok(cursor)
+    }
+
     /// Restore a cursor group from a given [Register].
     pub fn get_group(&self, id: I::ContentId, reg: &Register) -> EditResult<CursorGroup, I> {
         let (owner, group) = self
@@ -239,9 +303,17 @@ where
             val.zero_id(id);
         }
 
+        for val in self.global_ring.iter_mut() {
+            val.zero_id(id);
+        }
+
         if let Some(bmarks) = self.buffer.get_mut(id) {
             bmarks.zero();
         }
+
+        if let Some(bring) = self.buffer_ring.get_mut(id) {
+            bring.zero();
+        }
     }
 
     /// Adjust all marks associated with a buffer as described.
@@ -254,9 +326,17 @@ where
             val.adjust_id(id, adjs);
         }
 
+        for val in self.global_ring.iter_mut() {
+            val.adjust_id(id, adjs);
+        }
+
         if let Some(bmarks) = self.buffer.get_mut(id) {
             bmarks.adjust(adjs);
         }
+
+        if let Some(bring) = self.buffer_ring.get_mut(id) {
+            bring.adjust(adjs);
+        }
     }
 }
 
@@ -23,6 +23,7 @@
 use std::sync::{Arc, RwLock};
 
 use crate::editing::application::ApplicationInfo;
+use crate::editing::base::{MoveDirMod, Register, SearchQuery};
 use crate::editing::history::HistoryList;
 use crate::editing::rope::EditRope;
 
@@ -31,12 +32,14 @@ mod complete;
 mod cursor;
 mod digraph;
 mod register;
+mod search;
 
 pub use self::buffer::{BufferStore, SharedBuffer};
 pub use self::complete::CompletionStore;
 pub use self::cursor::{AdjustStore, CursorStore, GlobalAdjustable};
 pub use self::digraph::DigraphStore;
 pub use self::register::{RegisterCell, RegisterError, RegisterPutFlags, RegisterStore};
+pub use self::search::{SearchHighlight, SearchStore};
 
 const COMMAND_HISTORY_LEN: usize = 50;
 const SEARCH_HISTORY_LEN: usize = 50;
@@ -58,6 +61,9 @@ pub struct Store<I: ApplicationInfo> {
     /// Tracks globally-relevant cursors and cursor groups.
     pub cursors: CursorStore<I>,
 
+    /// Tracks which search matches get highlighted.
+    pub search: SearchStore,
+
     /// Tracks previous commands.
     pub commands: HistoryList<EditRope>,
 
@@ -83,6 +89,7 @@ where
             digraphs: DigraphStore::default(),
             registers: RegisterStore::default(),
             cursors: CursorStore::default(),
+            search: SearchStore::default(),
 
             commands: HistoryList::new("".into(), COMMAND_HISTORY_LEN),
             searches: HistoryList::new("".into(), SEARCH_HISTORY_LEN),
@@ -160,6 +167,60 @@ where
         self.searches.select(rope.clone());
         self.registers.set_last_search(rope);
     }
+
+    /// Add text typed into the search bar to the search history, and set
+    /// [Register::LastSearch] using the [queries](SearchQuery) parsed from it.
+    ///
+    /// [Register::LastSearch] gets set to the pattern of the final query, and its offset and
+    /// direction are remembered for repeating the search. The queries before it run once, the
+    /// next time that [Register::LastSearch] gets searched for. Empty patterns reuse the
+    /// pattern before them.
+    ///
+    /// [Register::LastSearch]: super::base::Register::LastSearch
+    pub fn set_last_search_queries<T: Into<EditRope>>(
+        &mut self,
+        text: T,
+        mut queries: Vec<SearchQuery>,
+    ) {
+        let rope = text.into();
+
+        if rope.is_empty() {
+            // Disallow empty searches.
+            return;
+        }
+
+        self.searches.select(rope);
+
+        let last = match queries.pop() {
+            Some(last) => last,
+            None => return,
+        };
+
+        let mut pattern = self.registers.get(&Register::LastSearch).unwrap_or_default().value;
+
+        for query in queries.iter_mut() {
+            if query.pattern.is_empty() {
+                query.pattern = pattern.to_string();
+            } else {
+                pattern = query.pattern.as_str().into();
+            }
+        }
+
+        if !last.pattern.is_empty() {
+            pattern = last.pattern.as_str().into();
+        }
+
+        let dir = queries.first().map(|q| q.dir).unwrap_or(last.dir);
+        let dir = if dir == last.dir {
+            MoveDirMod::Same
+        } else {
+            MoveDirMod::Flip
+        };
+
+        self.registers.set_last_search(pattern);
+        self.registers.set_last_search_offset(dir, last.offset);
+        self.registers.set_search_chain(queries);
+    }
 }
 
 impl<I> Default for Store<I>
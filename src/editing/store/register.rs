@@ -1,5 +1,5 @@
 use std::cell::{RefCell, RefMut};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use arboard::{Clipboard, Get, ImageData, Set};
 use bitflags::bitflags;
@@ -9,7 +9,7 @@ use arboard::{GetExtLinux, LinuxClipboardKind, SetExtLinux};
 
 use crate::{
     editing::base::TargetShape::{BlockWise, CharWise, LineWise},
-    editing::base::{Register, TargetShape},
+    editing::base::{MoveDir1D, MoveDirMod, Register, SearchOffset, SearchQuery, TargetShape},
     editing::rope::EditRope,
 };
 
@@ -74,9 +74,17 @@ bitflags! {
         /// This will skip setting [Register::Unnamed] to have the same value as the updated
         /// register.
         const NOTEXT = 0b00000100;
+
+        /// Prepend contents to register.
+        const PREPEND = 0b00001000;
     }
 }
 
+const KILL_RING_SIZE: usize = 120;
+
+/// The number of numbered registers, "1 through "9, that map onto the start of the kill ring.
+const NUMBERED_REGISTERS: usize = 9;
+
 /// Error while getting or setting a register value.
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
@@ -88,6 +96,16 @@ pub enum RegisterError {
     /// Failure to determine a macro register to use.
     #[error("No macro previously executed")]
     NoLastMacro,
+
+    /// There are no entries in the kill ring to cycle through.
+    #[error("Kill ring is empty")]
+    KillRingEmpty,
+
+    /// A numbered register lies past the end of the kill ring.
+    ///
+    /// The register is displayed counting from 1, like the numbered registers "1 through "9.
+    #[error("Register {} is past the end of the kill ring", .0 + 1)]
+    KillRingOffset(usize),
 }
 
 /// The current values mapped to by a [Register].
@@ -105,6 +123,8 @@ pub struct RegisterCell {
 /// Registers are used to save different types of values during editing:
 ///
 /// - Recently cut and copied text (see [EditAction::Delete] and [EditAction::Yank])
+/// - A ring of killed text, shared between [Register::KillRing] and [Register::RecentlyDeleted]
+/// - The most recently killed rectangle, kept separately from the kill ring
 /// - Last used commands, searches and substitution patterns
 /// - Recorded macros (see [MacroAction::ToggleRecording])
 ///
@@ -118,10 +138,19 @@ pub struct RegisterStore {
     last_command: RegisterCell,
     last_inserted: RegisterCell,
     last_search: RegisterCell,
+    last_search_dir: MoveDirMod,
+    last_search_offset: SearchOffset,
     last_yanked: RegisterCell,
-    last_deleted: Vec<RegisterCell>,
+    last_rectangle: RegisterCell,
     last_macro: Option<Register>,
 
+    /// Searches that need to run before [Register::LastSearch] the next time it's used.
+    search_chain: Vec<SearchQuery>,
+
+    kill_ring: VecDeque<RegisterCell>,
+    kill_ring_idx: usize,
+    kill_ring_size: usize,
+
     small_delete: RegisterCell,
 
     unnamed: RegisterCell,
@@ -222,9 +251,16 @@ impl RegisterStore {
             last_command: RegisterCell::default(),
             last_inserted: RegisterCell::default(),
             last_search: RegisterCell::default(),
+            last_search_dir: MoveDirMod::Same,
+            last_search_offset: SearchOffset::default(),
             last_yanked: RegisterCell::default(),
-            last_deleted: vec![RegisterCell::default(); 9],
+            last_rectangle: RegisterCell::default(),
             last_macro: None,
+            search_chain: vec![],
+
+            kill_ring: VecDeque::new(),
+            kill_ring_idx: 0,
+            kill_ring_size: KILL_RING_SIZE,
 
             small_delete: RegisterCell::default(),
 
@@ -244,9 +280,104 @@ impl RegisterStore {
         if cell.value.get_lines() < 1 {
             self.small_delete = cell;
         } else {
-            self.last_deleted.insert(0, cell);
-            self.last_deleted.truncate(9);
+            self._push_kill(cell, false);
+        }
+    }
+
+    fn _push_kill(&mut self, cell: RegisterCell, merged: bool) {
+        match self.kill_ring.front_mut() {
+            Some(head) if merged => {
+                *head = cell;
+            },
+            _ => {
+                self.kill_ring.push_front(cell);
+                self.kill_ring.truncate(self.kill_ring_size);
+            },
+        }
+
+        self.kill_ring_idx = 0;
+    }
+
+    /// Map a numbered register's offset onto the index of its entry in the kill ring.
+    ///
+    /// The numbered registers can always be used, so when the ring is smaller than them, the
+    /// ones past its end all refer to its oldest entry instead.
+    fn _kill_ring_offset(&self, off: usize) -> Result<usize, RegisterError> {
+        if off < NUMBERED_REGISTERS {
+            Ok(off.min(self.kill_ring_size - 1))
+        } else if off < self.kill_ring_size {
+            Ok(off)
+        } else {
+            Err(RegisterError::KillRingOffset(off))
+        }
+    }
+
+    /// Find the first non-blank kill ring entry at or after `idx`, moving towards older kills.
+    fn _kill_ring_filled(&self, idx: usize) -> Option<usize> {
+        let len = self.kill_ring.len();
+
+        (0..len)
+            .map(|n| (idx + n) % len)
+            .find(|idx| !self.kill_ring[*idx].value.is_empty())
+    }
+
+    /// Set the maximum number of entries to keep in the kill ring.
+    ///
+    /// If the ring currently contains more entries than this, then the oldest ones are dropped.
+    /// When the ring is smaller than the nine numbered registers, the ones past its end all
+    /// refer to its oldest entry.
+    pub fn set_kill_ring_size(&mut self, size: usize) {
+        let size = size.max(1);
+
+        self.kill_ring_size = size;
+        self.kill_ring.truncate(size);
+
+        if self.kill_ring_idx >= self.kill_ring.len() {
+            self.kill_ring_idx = 0;
+        }
+    }
+
+    /// Get the maximum number of entries kept in the kill ring.
+    pub fn get_kill_ring_size(&self) -> usize {
+        self.kill_ring_size
+    }
+
+    /// Move the kill ring's yank pointer `count` entries in the given direction, and return the
+    /// entry that [Register::KillRing] now refers to.
+    ///
+    /// [MoveDir1D::Previous] moves towards older kills, and [MoveDir1D::Next] moves towards
+    /// newer ones, wrapping around at either end of the ring. Blank entries, which pad the
+    /// ring when a numbered register is written to before the ones preceding it, are skipped.
+    pub fn cycle_kill_ring(
+        &mut self,
+        dir: MoveDir1D,
+        count: usize,
+    ) -> Result<RegisterCell, RegisterError> {
+        let len = self.kill_ring.len();
+        let filled = self.kill_ring.iter().filter(|cell| !cell.value.is_empty()).count();
+        let mut idx = self
+            ._kill_ring_filled(self.kill_ring_idx)
+            .ok_or(RegisterError::KillRingEmpty)?;
+
+        let step = |idx: usize| {
+            match dir {
+                MoveDir1D::Previous => (idx + 1) % len,
+                MoveDir1D::Next => (idx + len - 1) % len,
+            }
+        };
+        let blank = |idx: usize| self.kill_ring[idx].value.is_empty();
+
+        for _ in 0..count % filled {
+            idx = step(idx);
+
+            while blank(idx) {
+                idx = step(idx);
+            }
         }
+
+        self.kill_ring_idx = idx;
+
+        return Ok(self.kill_ring[idx].clone());
     }
 
     /// Get the current value of a [Register].
@@ -258,13 +389,23 @@ impl RegisterStore {
             Register::UnnamedMacro => self.unnamed_macro.clone(),
             Register::UnnamedCursorGroup => RegisterCell::default(),
             Register::RecentlyDeleted(off) => {
-                self.last_deleted.get(*off).cloned().unwrap_or_default()
+                self._kill_ring_offset(*off)
+                    .ok()
+                    .and_then(|off| self.kill_ring.get(off))
+                    .cloned()
+                    .unwrap_or_default()
+            },
+            Register::KillRing => {
+                self._kill_ring_filled(self.kill_ring_idx)
+                    .map(|idx| self.kill_ring[idx].clone())
+                    .unwrap_or_default()
             },
             Register::SmallDelete => self.small_delete.clone(),
             Register::Named(name) => self.named.get(name).cloned().unwrap_or_default(),
             Register::AltBufName => self.altbufname.clone(),
             Register::LastSearch => self.last_search.clone(),
             Register::LastYanked => self.last_yanked.clone(),
+            Register::LastRectangle => self.last_rectangle.clone(),
 
             /*
              * Operating system clipboards.
@@ -319,8 +460,8 @@ impl RegisterStore {
     /// Update the current value of a [Register] with `cell`. If none is specified, this updates
     /// the value of [Register::Unnamed].
     ///
-    /// The `append` flag controls whether this should wholly replace or append to the current
-    /// value.
+    /// The `append` and `prepend` flags control whether this should wholly replace or add to
+    /// the current value.
     ///
     /// The `del` flag indicates whether this register update is being done as part of a text
     /// deletion in a document.
@@ -330,8 +471,20 @@ impl RegisterStore {
         mut cell: RegisterCell,
         flags: RegisterPutFlags,
     ) -> Result<(), RegisterError> {
-        if flags.contains(RegisterPutFlags::APPEND) {
-            cell = self.get(reg)?.merge(&cell)
+        let merged = flags.intersects(RegisterPutFlags::APPEND | RegisterPutFlags::PREPEND);
+
+        if merged {
+            // Kills get merged into the most recent one, wherever the yank pointer is.
+            let current = match reg {
+                Register::KillRing => self.kill_ring.front().cloned().unwrap_or_default(),
+                reg => self.get(reg)?,
+            };
+
+            if flags.contains(RegisterPutFlags::APPEND) {
+                cell = current.merge(&cell)
+            } else {
+                cell = cell.merge(&current)
+            }
         }
 
         /*
@@ -361,10 +514,19 @@ impl RegisterStore {
             },
 
             Register::RecentlyDeleted(off) => {
-                if let Some(elem) = self.last_deleted.get_mut(*off) {
-                    *elem = cell.clone();
+                let off = self._kill_ring_offset(*off)?;
+
+                // Pad the ring out so that every numbered register within it can be written to.
+                while self.kill_ring.len() <= off {
+                    self.kill_ring.push_back(RegisterCell::default());
                 }
 
+                self.kill_ring[off] = cell.clone();
+
+                cell
+            },
+            Register::KillRing => {
+                self._push_kill(cell.clone(), merged);
                 cell
             },
             Register::SmallDelete => {
@@ -380,6 +542,10 @@ impl RegisterStore {
                 self.last_yanked = cell.clone();
                 cell
             },
+            Register::LastRectangle => {
+                self.last_rectangle = cell.clone();
+                cell
+            },
 
             /*
              * Operating system clipboards.
@@ -441,6 +607,33 @@ impl RegisterStore {
 
     pub(super) fn set_last_search<T: Into<EditRope>>(&mut self, rope: T) {
         self.last_search = RegisterCell::from(rope.into());
+        self.last_search_dir = MoveDirMod::Same;
+        self.last_search_offset = SearchOffset::default();
+        self.search_chain.clear();
+    }
+
+    pub(super) fn set_last_search_offset(&mut self, dir: MoveDirMod, offset: SearchOffset) {
+        self.last_search_dir = dir;
+        self.last_search_offset = offset;
+    }
+
+    pub(super) fn set_search_chain(&mut self, chain: Vec<SearchQuery>) {
+        self.search_chain = chain;
+    }
+
+    /// Get the offset to apply after finding a match for [Register::LastSearch], and how the
+    /// direction of the search it came from compares to the one that the search bar was
+    /// opened with.
+    pub fn get_last_search_offset(&self) -> (MoveDirMod, SearchOffset) {
+        (self.last_search_dir, self.last_search_offset)
+    }
+
+    /// Take the searches, set by [Store::set_last_search_queries], that need to run before
+    /// the next search for [Register::LastSearch].
+    ///
+    /// [Store::set_last_search_queries]: super::Store::set_last_search_queries
+    pub fn take_search_chain(&mut self) -> Vec<SearchQuery> {
+        std::mem::take(&mut self.search_chain)
     }
 }
 
@@ -475,4 +668,141 @@ mod tests {
         assert_eq!(c.merge(&b), RegisterCell::new(LineWise, EditRope::from("q\nr\ns\n1\n2\n")));
         assert_eq!(c.merge(&c), RegisterCell::new(LineWise, EditRope::from("q\nr\ns\nq\nr\ns\n")));
     }
+
+    #[test]
+    fn test_kill_ring() {
+        let mut store = RegisterStore::default();
+        let kill = |store: &mut RegisterStore, s: &str, flags| {
+            store.put(&Register::KillRing, RegisterCell::from(s), flags).unwrap();
+        };
+
+        // Cycling an empty ring fails.
+        let res = store.cycle_kill_ring(MoveDir1D::Previous, 1);
+        assert!(matches!(res, Err(RegisterError::KillRingEmpty)));
+
+        // Appending and prepending merge into the most recent entry.
+        kill(&mut store, "a", RegisterPutFlags::NONE);
+        kill(&mut store, "b", RegisterPutFlags::NONE);
+        kill(&mut store, "c", RegisterPutFlags::APPEND);
+        kill(&mut store, "d", RegisterPutFlags::PREPEND);
+        assert_eq!(store.get(&Register::KillRing).unwrap(), RegisterCell::from("dbc"));
+        assert_eq!(store.get(&Register::Unnamed).unwrap(), RegisterCell::from("dbc"));
+
+        // The ring's entries are available through the numbered registers.
+        assert_eq!(store.get(&Register::RecentlyDeleted(0)).unwrap(), RegisterCell::from("dbc"));
+        assert_eq!(store.get(&Register::RecentlyDeleted(1)).unwrap(), RegisterCell::from("a"));
+        assert_eq!(store.get(&Register::RecentlyDeleted(2)).unwrap(), RegisterCell::default());
+
+        // Multiline deletions also go into the ring.
+        let cell = RegisterCell::from((LineWise, "e\n"));
+        store
+            .put(&Register::Unnamed, cell.clone(), RegisterPutFlags::DELETE)
+            .unwrap();
+        assert_eq!(store.get(&Register::RecentlyDeleted(0)).unwrap(), cell);
+        assert_eq!(store.get(&Register::RecentlyDeleted(1)).unwrap(), RegisterCell::from("dbc"));
+
+        // Move the yank pointer around the ring.
+        let res = store.cycle_kill_ring(MoveDir1D::Previous, 1).unwrap();
+        assert_eq!(res, RegisterCell::from("dbc"));
+        assert_eq!(store.get(&Register::KillRing).unwrap(), RegisterCell::from("dbc"));
+
+        let res = store.cycle_kill_ring(MoveDir1D::Previous, 2).unwrap();
+        assert_eq!(res, cell);
+
+        let res = store.cycle_kill_ring(MoveDir1D::Next, 1).unwrap();
+        assert_eq!(res, RegisterCell::from("a"));
+
+        // A new kill resets the yank pointer.
+        kill(&mut store, "f", RegisterPutFlags::NONE);
+        assert_eq!(store.get(&Register::KillRing).unwrap(), RegisterCell::from("f"));
+
+        // Shrinking the ring drops the oldest entries.
+        store.set_kill_ring_size(2);
+        assert_eq!(store.get_kill_ring_size(), 2);
+        assert_eq!(store.get(&Register::RecentlyDeleted(1)).unwrap(), cell);
+
+        // Numbered registers past its end now refer to its oldest entry.
+        assert_eq!(store.get(&Register::RecentlyDeleted(2)).unwrap(), cell);
+
+        kill(&mut store, "g", RegisterPutFlags::NONE);
+        assert_eq!(store.get(&Register::RecentlyDeleted(0)).unwrap(), RegisterCell::from("g"));
+        assert_eq!(store.get(&Register::RecentlyDeleted(1)).unwrap(), RegisterCell::from("f"));
+        assert_eq!(store.get(&Register::RecentlyDeleted(2)).unwrap(), RegisterCell::from("f"));
+
+        // Appending merges into the most recent kill, even after moving the yank pointer.
+        store.cycle_kill_ring(MoveDir1D::Previous, 1).unwrap();
+        kill(&mut store, "h", RegisterPutFlags::APPEND);
+        assert_eq!(store.get(&Register::RecentlyDeleted(0)).unwrap(), RegisterCell::from("gh"));
+        assert_eq!(store.get(&Register::RecentlyDeleted(1)).unwrap(), RegisterCell::from("f"));
+    }
+
+    #[test]
+    fn test_numbered_registers() {
+        let mut store = RegisterStore::default();
+
+        // Numbered registers past the end of the ring can still be written to ("3yy).
+        let cell = RegisterCell::from((LineWise, "a\n"));
+        store
+            .put(&Register::RecentlyDeleted(2), cell.clone(), RegisterPutFlags::NONE)
+            .unwrap();
+        assert_eq!(store.get(&Register::RecentlyDeleted(2)).unwrap(), cell);
+        assert_eq!(store.get(&Register::Unnamed).unwrap(), cell);
+
+        // The registers before it stay empty, and yanking skips over them.
+        assert_eq!(store.get(&Register::RecentlyDeleted(0)).unwrap(), RegisterCell::default());
+        assert_eq!(store.get(&Register::KillRing).unwrap(), cell);
+        let res = store.cycle_kill_ring(MoveDir1D::Next, 1).unwrap();
+        assert_eq!(res, cell);
+
+        // New deletions push them further back.
+        let del = RegisterCell::from((LineWise, "b\n"));
+        store
+            .put(&Register::Unnamed, del.clone(), RegisterPutFlags::DELETE)
+            .unwrap();
+        assert_eq!(store.get(&Register::RecentlyDeleted(0)).unwrap(), del);
+        assert_eq!(store.get(&Register::RecentlyDeleted(3)).unwrap(), cell);
+
+        // Yank-pop skips over the blank entries used for padding.
+        let res = store.cycle_kill_ring(MoveDir1D::Previous, 1).unwrap();
+        assert_eq!(res, cell);
+        let res = store.cycle_kill_ring(MoveDir1D::Previous, 1).unwrap();
+        assert_eq!(res, del);
+        let res = store.cycle_kill_ring(MoveDir1D::Next, 3).unwrap();
+        assert_eq!(res, cell);
+
+        // Numbered registers past the end of the ring write to its oldest entry.
+        store.set_kill_ring_size(3);
+        let nine = RegisterCell::from("nine");
+        store
+            .put(&Register::RecentlyDeleted(8), nine.clone(), RegisterPutFlags::NONE)
+            .unwrap();
+        assert_eq!(store.get(&Register::RecentlyDeleted(8)).unwrap(), nine);
+        assert_eq!(store.get(&Register::RecentlyDeleted(2)).unwrap(), nine);
+        assert_eq!(store.get(&Register::RecentlyDeleted(3)).unwrap(), nine);
+        assert_eq!(store.get(&Register::RecentlyDeleted(9)).unwrap(), RegisterCell::default());
+
+        // Other offsets past the end of the ring can't be written to.
+        let res = store.put(&Register::RecentlyDeleted(9), cell.clone(), RegisterPutFlags::NONE);
+        let err = res.unwrap_err();
+        assert!(matches!(err, RegisterError::KillRingOffset(9)));
+        assert_eq!(err.to_string(), "Register 10 is past the end of the kill ring");
+    }
+
+    #[test]
+    fn test_last_rectangle() {
+        let mut store = RegisterStore::default();
+        let block = RegisterCell::from((BlockWise, "ab\ncd"));
+
+        store
+            .put(&Register::KillRing, RegisterCell::from("a"), RegisterPutFlags::NONE)
+            .unwrap();
+
+        // Killed rectangles don't go into the kill ring.
+        store
+            .put(&Register::LastRectangle, block.clone(), RegisterPutFlags::DELETE)
+            .unwrap();
+        assert_eq!(store.get(&Register::LastRectangle).unwrap(), block);
+        assert_eq!(store.get(&Register::KillRing).unwrap(), RegisterCell::from("a"));
+        assert_eq!(store.get(&Register::RecentlyDeleted(1)).unwrap(), RegisterCell::default());
+    }
 }
@@ -0,0 +1,167 @@
+use regex::Regex;
+
+use crate::editing::base::{Flip, MoveDir1D};
+
+/// Search matches that should be highlighted when drawing text.
+#[derive(Clone, Debug)]
+pub struct SearchHighlight {
+    /// The expression whose matches get highlighted.
+    pub needle: Regex,
+
+    /// When the user is still typing the search, the direction in which to look for the match
+    /// that would be jumped to if they submitted it, and how many matches away it is.
+    pub incremental: Option<(MoveDir1D, usize)>,
+}
+
+/// Tracks which search matches get highlighted.
+///
+/// The matches of the last search performed in a buffer get highlighted while
+/// [hlsearch](SearchStore::set_hlsearch) is enabled, which it isn't by default, unless highlighting has been temporarily
+/// turned off via [SearchStore::nohlsearch]. While a search is being typed into the command bar,
+/// its matches get highlighted instead.
+#[derive(Debug, Default)]
+pub struct SearchStore {
+    hlsearch: bool,
+    suspended: bool,
+    last: Option<Regex>,
+    incsearch: Option<(Regex, MoveDir1D)>,
+    incoffset: isize,
+}
+
+impl SearchStore {
+    /// Set whether the matches of the last search should be highlighted.
+    pub fn set_hlsearch(&mut self, hlsearch: bool) {
+        self.hlsearch = hlsearch;
+        self.suspended = false;
+    }
+
+    /// Stop highlighting the matches of the last search until the next search is performed.
+    pub fn nohlsearch(&mut self) {
+        self.suspended = true;
+    }
+
+    /// Update the expression used by the last search, and resume highlighting its matches.
+    pub fn set_last_regex(&mut self, needle: Regex) {
+        self.last = Some(needle);
+        self.suspended = false;
+    }
+
+    /// Get the expression used by the last search.
+    pub fn get_last_regex(&self) -> Option<&Regex> {
+        self.last.as_ref()
+    }
+
+    /// Set the expression currently being typed into the search bar, or clear it once the search
+    /// is submitted or aborted.
+    pub fn set_incsearch(&mut self, incsearch: Option<(Regex, MoveDir1D)>) {
+        self.incsearch = incsearch;
+        self.incoffset = 0;
+    }
+
+    /// Move the match that the search being typed would jump to by `count` matches, either onwards
+    /// in the search's direction ([MoveDir1D::Next]) or back ([MoveDir1D::Previous]).
+    pub fn move_incsearch(&mut self, dir: MoveDir1D, count: usize) {
+        if self.incsearch.is_none() {
+            return;
+        }
+
+        let count = count as isize;
+
+        match dir {
+            MoveDir1D::Next => self.incoffset = self.incoffset.saturating_add(count),
+            MoveDir1D::Previous => self.incoffset = self.incoffset.saturating_sub(count),
+        }
+    }
+
+    /// How many matches the search being typed has been moved by via
+    /// [SearchStore::move_incsearch].
+    pub fn get_incsearch_offset(&self) -> isize {
+        self.incoffset
+    }
+
+    /// Get the search matches that should currently be highlighted, if any.
+    pub fn get_highlight(&self) -> Option<SearchHighlight> {
+        if let Some((needle, dir)) = &self.incsearch {
+            let incremental = if self.incoffset < 0 {
+                (dir.flip(), self.incoffset.unsigned_abs())
+            } else {
+                (*dir, self.incoffset.unsigned_abs() + 1)
+            };
+            let hl = SearchHighlight {
+                needle: needle.clone(),
+                incremental: Some(incremental),
+            };
+
+            return Some(hl);
+        }
+
+        if !self.hlsearch || self.suspended {
+            return None;
+        }
+
+        let needle = self.last.clone()?;
+
+        Some(SearchHighlight { needle, incremental: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        let mut store = SearchStore::default();
+        assert!(store.get_highlight().is_none());
+
+        // Matches aren't highlighted until hlsearch gets turned on.
+        store.set_last_regex(Regex::new("foo").unwrap());
+        assert!(store.get_highlight().is_none());
+
+        store.set_hlsearch(true);
+        let hl = store.get_highlight().unwrap();
+        assert_eq!(hl.needle.as_str(), "foo");
+        assert_eq!(hl.incremental, None);
+
+        // Incremental searches take precedence.
+        let inc = (Regex::new("bar").unwrap(), MoveDir1D::Previous);
+        store.set_incsearch(Some(inc));
+        let hl = store.get_highlight().unwrap();
+        assert_eq!(hl.needle.as_str(), "bar");
+        assert_eq!(hl.incremental, Some((MoveDir1D::Previous, 1)));
+
+        // The match it would jump to can be moved in either direction.
+        store.move_incsearch(MoveDir1D::Next, 2);
+        assert_eq!(store.get_incsearch_offset(), 2);
+        assert_eq!(store.get_highlight().unwrap().incremental, Some((MoveDir1D::Previous, 3)));
+
+        store.move_incsearch(MoveDir1D::Previous, 3);
+        assert_eq!(store.get_incsearch_offset(), -1);
+        assert_eq!(store.get_highlight().unwrap().incremental, Some((MoveDir1D::Next, 1)));
+
+        // Typing more of the search resets it.
+        let inc = (Regex::new("bar").unwrap(), MoveDir1D::Previous);
+        store.set_incsearch(Some(inc));
+        assert_eq!(store.get_incsearch_offset(), 0);
+
+        store.set_incsearch(None);
+        assert_eq!(store.get_highlight().unwrap().needle.as_str(), "foo");
+
+        // Highlighting stays off until the next search.
+        store.nohlsearch();
+        assert!(store.get_highlight().is_none());
+
+        store.set_last_regex(Regex::new("baz").unwrap());
+        assert_eq!(store.get_highlight().unwrap().needle.as_str(), "baz");
+
+        // Turning hlsearch off keeps it off.
+        store.set_hlsearch(false);
+        assert!(store.get_highlight().is_none());
+
+        store.set_last_regex(Regex::new("foo").unwrap());
+        assert!(store.get_highlight().is_none());
+
+        store.set_hlsearch(true);
+        assert_eq!(store.get_highlight().unwrap().needle.as_str(), "foo");
+    }
+}
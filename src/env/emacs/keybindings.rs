@@ -10,6 +10,13 @@
 //! The keybindings here diverge from the defaults in Emacs in the following ways:
 //!
 //! - `C-_` and `C-x u` behave like `M-x undo-only`
+//! - `C-x r j` only executes keyboard macros that were saved to registers with `C-x C-k x`
+//! - `C-x f` only sets the fill column when given a prefix argument
+//! - `C-x C-o` on a non-blank line leaves point at the start of the following line
+//! - `C-x r t` types the string into every line of the rectangle as you go, until `C-g`
+//! - `C-x r N` starts counting from the prefix argument, instead of prompting for a format
+//! - `C-x (` and `<F3>` reset the macro counter even when a macro is already being recorded, and
+//!   `<F3>` doesn't insert the counter while recording
 //!
 use bitflags::bitflags;
 
@@ -17,10 +24,13 @@ use crate::editing::{
     action::{
         Action,
         CommandBarAction,
+        CursorAction,
         EditAction,
         EditorAction,
         HistoryAction,
         InsertTextAction,
+        MacroAction,
+        MarkRingAction,
         PromptAction,
         SelectionAction,
         WindowAction,
@@ -33,13 +43,16 @@ use crate::editing::{
         CloseFlags,
         CommandType,
         CompletionDisplay,
+        CompletionScope,
         CompletionSelection,
         CompletionType,
         Count,
+        CursorCloseTarget,
         EditTarget,
         FocusChange,
         InsertStyle,
         JoinStyle,
+        LinePrefix,
         MoveDir1D,
         MoveDir2D,
         MoveDirMod,
@@ -56,8 +69,10 @@ use crate::editing::{
         SearchType,
         SelectionCursorChange,
         SelectionResizeStyle,
+        SelectionSplitStyle,
         Specifier,
         TargetShape,
+        TargetShapeFilter,
         WindowTarget,
         WordStyle,
     },
@@ -113,10 +128,17 @@ impl MappedModes {
 
 #[derive(Clone, Debug)]
 enum InternalAction {
+    AddMacroCounter,
     ClearTargetShape(bool),
+    Kill(MoveDir1D),
+    Reprompt,
     SaveCounting(Option<usize>),
+    SetFillColumn,
+    SetMacroCounter,
     SetInsertStyle(InsertStyle),
+    SetActionShape(TargetShape),
     SetRegister(Register),
+    SetReplaceChar(Char),
     SetSearchRegexParams(MoveDir1D, bool),
     SetTargetShape(TargetShape, bool),
 }
@@ -124,6 +146,11 @@ enum InternalAction {
 impl InternalAction {
     pub fn run<I: ApplicationInfo>(&self, ctx: &mut EmacsContext<I>) {
         match self {
+            InternalAction::AddMacroCounter => {
+                let n = ctx.action.count.unwrap_or(1);
+
+                ctx.persist.counter = ctx.persist.counter.saturating_add(n);
+            },
             InternalAction::ClearTargetShape(shiftreq) => {
                 if *shiftreq {
                     if ctx.persist.shift {
@@ -134,6 +161,16 @@ impl InternalAction {
                     ctx.persist.shape = None;
                 }
             },
+            InternalAction::Kill(dir) => {
+                ctx.action.register = Some(Register::KillRing);
+
+                if ctx.persist.killing {
+                    ctx.action.register_append = Some(*dir);
+                }
+            },
+            InternalAction::Reprompt => {
+                ctx.persist.reprompt = true;
+            },
             InternalAction::SaveCounting(optn) => {
                 let counting = match (optn, ctx.action.counting) {
                     (Some(n1), Some(n2)) => n1.saturating_mul(10).saturating_add(n2),
@@ -149,6 +186,14 @@ impl InternalAction {
 
                 ctx.action.counting = None;
             },
+            InternalAction::SetFillColumn => {
+                if let Some(n) = ctx.action.count {
+                    ctx.persist.fill_column = n;
+                }
+            },
+            InternalAction::SetMacroCounter => {
+                ctx.persist.counter = ctx.action.count.unwrap_or(0);
+            },
             InternalAction::SetInsertStyle(style) => {
                 if style == &ctx.persist.insert {
                     ctx.persist.insert = !*style;
@@ -156,9 +201,15 @@ impl InternalAction {
                     ctx.persist.insert = *style;
                 }
             },
+            InternalAction::SetActionShape(shape) => {
+                ctx.action.shape = Some(*shape);
+            },
             InternalAction::SetRegister(reg) => {
                 ctx.action.register = Some(reg.clone());
             },
+            InternalAction::SetReplaceChar(c) => {
+                ctx.action.replace = Some(c.clone());
+            },
             InternalAction::SetSearchRegexParams(dir, incremental) => {
                 ctx.persist.regexsearch_dir = *dir;
                 ctx.persist.regexsearch_inc = *incremental;
@@ -175,6 +226,8 @@ impl InternalAction {
 enum ExternalAction<I: ApplicationInfo> {
     Something(Action<I>),
     Repeat(bool),
+    MacroStart,
+    MacroCounter,
 }
 
 impl<I: ApplicationInfo> ExternalAction<I> {
@@ -197,6 +250,31 @@ impl<I: ApplicationInfo> ExternalAction<I> {
 
                 return vec![Action::Repeat(RepeatType::LastAction)];
             },
+            ExternalAction::MacroStart => {
+                ctx.persist.repeating = false;
+
+                // Starting a new macro resets the counter.
+                ctx.persist.counter = ctx.action.count.unwrap_or(0);
+
+                return vec![MacroAction::StartRecording.into()];
+            },
+            ExternalAction::MacroCounter => {
+                ctx.persist.repeating = false;
+
+                let n = ctx.persist.counter;
+                ctx.persist.counter = n.saturating_add(ctx.action.count.unwrap_or(1));
+
+                return n
+                    .to_string()
+                    .chars()
+                    .map(|c| {
+                        let ch = Char::Single(c).into();
+                        let it = InsertTextAction::Type(ch, MoveDir1D::Previous, Count::Exact(1));
+
+                        it.into()
+                    })
+                    .collect();
+            },
         }
     }
 }
@@ -206,6 +284,8 @@ impl<I: ApplicationInfo> Clone for ExternalAction<I> {
         match self {
             ExternalAction::Something(act) => ExternalAction::Something(act.clone()),
             ExternalAction::Repeat(reqrep) => ExternalAction::Repeat(*reqrep),
+            ExternalAction::MacroStart => ExternalAction::MacroStart,
+            ExternalAction::MacroCounter => ExternalAction::MacroCounter,
         }
     }
 }
@@ -243,6 +323,14 @@ impl<I: ApplicationInfo> InputStep<I> {
     }
 }
 
+impl<I: ApplicationInfo> InputStep<I> {
+    fn is_submit(&self) -> bool {
+        self.external.iter().any(|act| {
+            matches!(act, ExternalAction::Something(Action::Prompt(PromptAction::Submit)))
+        })
+    }
+}
+
 impl<I: ApplicationInfo> Clone for InputStep<I> {
     fn clone(&self) -> Self {
         Self {
@@ -278,9 +366,22 @@ impl<I: ApplicationInfo> Step<TerminalKey> for InputStep<I> {
             iact.run(ctx);
         }
 
+        if !self.external.is_empty() {
+            // Consecutive kills accumulate into the same kill ring entry.
+            ctx.persist.killing =
+                self.internal.iter().any(|iact| matches!(iact, InternalAction::Kill(_)));
+        }
+
         let external: Vec<Action<I>> =
             self.external.iter().flat_map(|act| act.resolve(ctx)).collect();
 
+        if ctx.persist.reprompt && self.is_submit() {
+            // The command bar is going to prompt for more input, so stay in Command mode.
+            ctx.persist.reprompt = false;
+
+            return (external, Some(EmacsMode::Command));
+        }
+
         return (external, self.nextm);
     }
 }
@@ -362,46 +463,154 @@ macro_rules! motion {
 
 macro_rules! yank_target {
     ($target: expr) => {
-        edit_target!(EditAction::Yank, $target)
-    };
-    ($target: expr, $c: expr) => {
-        edit_target!(EditAction::Yank, $target, $c)
+        is!(
+            InternalAction::SetRegister(Register::KillRing),
+            EditorAction::Edit(Specifier::Exact(EditAction::Yank), $target)
+        )
     };
 }
 
 macro_rules! kill_target {
-    ($target: expr) => {
-        edit_target!(EditAction::Delete, $target)
-    };
-    ($target: expr, $c: expr) => {
-        edit_target!(EditAction::Delete, $target, $c)
+    ($dir: expr, $target: expr) => {
+        is!(
+            InternalAction::Kill($dir),
+            EditorAction::Edit(Specifier::Exact(EditAction::Delete), $target)
+        )
     };
 }
 
 macro_rules! kill {
-    ($mt: expr) => {
-        edit!(EditAction::Delete, $mt)
+    ($dir: expr, $mt: expr) => {
+        kill_target!($dir, EditTarget::Motion($mt, Count::Contextual))
     };
-    ($mt: expr, $c: expr) => {
-        edit!(EditAction::Delete, $mt, $c)
+    ($dir: expr, $mt: expr, $c: expr) => {
+        kill_target!($dir, EditTarget::Motion($mt, $c))
+    };
+}
+
+macro_rules! yank {
+    () => {
+        is!(
+            InternalAction::SetRegister(Register::KillRing),
+            InsertTextAction::Paste(PasteStyle::Cursor, Count::Contextual)
+        )
+    };
+}
+
+macro_rules! yank_pop {
+    ($dir: expr) => {
+        insert_text!(InsertTextAction::YankPop($dir, Count::Contextual))
+    };
+}
+
+macro_rules! undo_group {
+    ($ints: expr, $($ext: expr),+) => {
+        isv!($ints, vec![
+            ExternalAction::Something(HistoryAction::Checkpoint.into()),
+            $(ExternalAction::Something(Action::from($ext)),)+
+            ExternalAction::Something(HistoryAction::Checkpoint.into())
+        ])
+    };
+}
+
+macro_rules! erase_group {
+    ($target: expr) => {
+        undo_group!(
+            vec![InternalAction::SetRegister(Register::Blackhole)],
+            EditorAction::Edit(EditAction::Delete.into(), $target)
+        )
     };
 }
 
 macro_rules! just_one_space {
     () => {
-        isv!(vec![InternalAction::SetRegister(Register::Blackhole)], vec![
-            Action::from(EditorAction::Edit(
+        undo_group!(
+            vec![InternalAction::SetRegister(Register::Blackhole)],
+            EditorAction::Edit(
                 EditAction::Delete.into(),
-                RangeType::Word(WordStyle::Whitespace(false)).into()
-            ))
-            .into(),
-            Action::from(InsertTextAction::Type(
+                EditTarget::Range(RangeType::HorizontalSpace, true, Count::Exact(1))
+            ),
+            InsertTextAction::Type(
                 Char::Single(' ').into(),
                 MoveDir1D::Previous,
                 Count::Contextual
-            ))
-            .into()
-        ])
+            )
+        )
+    };
+}
+
+macro_rules! fill_paragraph {
+    () => {
+        undo_group!(
+            vec![],
+            EditorAction::Edit(
+                EditAction::Format.into(),
+                EditTarget::Range(RangeType::Paragraph, true, Count::Contextual)
+            )
+        )
+    };
+}
+
+macro_rules! rectangle {
+    ($ea: expr) => {
+        rectangle!($ea, vec![])
+    };
+    ($ea: expr, $ints: expr) => {
+        isv!(
+            [
+                vec![
+                    InternalAction::ClearTargetShape(false),
+                    InternalAction::SetActionShape(TargetShape::BlockWise),
+                ],
+                $ints,
+            ]
+            .concat(),
+            vec![ExternalAction::Something(
+                EditorAction::Edit($ea.into(), EditTarget::Selection).into()
+            )]
+        )
+    };
+}
+
+macro_rules! yank_rectangle {
+    () => {
+        isv!(
+            vec![
+                InternalAction::ClearTargetShape(false),
+                InternalAction::SetActionShape(TargetShape::BlockWise),
+                InternalAction::SetRegister(Register::LastRectangle),
+            ],
+            vec![ExternalAction::Something(
+                InsertTextAction::Paste(PasteStyle::Cursor, Count::Exact(1)).into()
+            )]
+        )
+    };
+}
+
+macro_rules! string_rectangle {
+    () => {
+        isv!(
+            vec![
+                InternalAction::ClearTargetShape(false),
+                InternalAction::SetActionShape(TargetShape::BlockWise),
+                InternalAction::SetRegister(Register::Blackhole),
+            ],
+            [
+                vec![ExternalAction::Something(
+                    EditorAction::Edit(EditAction::Motion.into(), EditTarget::CurrentPosition)
+                        .into()
+                )],
+                block_insert!(
+                    SelectionCursorChange::Beginning,
+                    [EditorAction::Edit(
+                        EditAction::Delete.into(),
+                        EditTarget::Selection
+                    )],
+                    []
+                ),
+            ]
+            .concat()
+        )
     };
 }
 
@@ -413,14 +622,78 @@ macro_rules! cmdbar_focus {
 
 macro_rules! cmdbar_search {
     ($dir: expr) => {
-        is!(
-            InternalAction::SetSearchRegexParams($dir, true),
-            Action::CommandBar(CommandBarAction::Focus(CommandType::Search)),
+        isv!(
+            vec![InternalAction::SetSearchRegexParams($dir, true)],
+            vec![
+                ExternalAction::Something(EditorAction::MarkRing(MarkRingAction::Push).into()),
+                ExternalAction::Something(CommandBarAction::Focus(CommandType::Search).into()),
+            ],
             EmacsMode::Search
         )
     };
 }
 
+macro_rules! query_replace {
+    ($regex: expr) => {
+        is!(InternalAction::Reprompt, CommandBarAction::QueryReplace($regex), EmacsMode::Command)
+    };
+}
+
+macro_rules! mark_jump {
+    ($ext: expr) => {
+        isv!(vec![InternalAction::ClearTargetShape(true)], vec![
+            ExternalAction::Something(EditorAction::MarkRing(MarkRingAction::Push).into()),
+            ExternalAction::Something($ext.into()),
+        ])
+    };
+}
+
+macro_rules! mark_pop {
+    ($act: expr) => {
+        is!(InternalAction::ClearTargetShape(false), EditorAction::MarkRing($act))
+    };
+}
+
+macro_rules! set_mark {
+    () => {
+        isv!(vec![InternalAction::SetTargetShape(TargetShape::CharWise, false)], vec![
+            ExternalAction::Something(EditorAction::MarkRing(MarkRingAction::Push).into()),
+            ExternalAction::Something(
+                EditorAction::Selection(SelectionAction::Resize(
+                    SelectionResizeStyle::Restart,
+                    EditTarget::CurrentPosition
+                ))
+                .into()
+            ),
+        ])
+    };
+}
+
+macro_rules! macro_end_and_call {
+    () => {
+        isv!(vec![], vec![
+            ExternalAction::Something(MacroAction::StopRecording.into()),
+            ExternalAction::Something(MacroAction::ExecuteRepeatable(Count::Contextual).into()),
+        ])
+    };
+}
+
+macro_rules! keyboard_quit {
+    () => {
+        isv!(
+            vec![InternalAction::ClearTargetShape(false)],
+            vec![
+                ExternalAction::Something(CursorAction::Close(CursorCloseTarget::Followers).into()),
+                ExternalAction::Something(
+                    EditorAction::Edit(EditAction::Motion.into(), EditTarget::CurrentPosition)
+                        .into()
+                ),
+            ],
+            EmacsMode::Insert
+        )
+    };
+}
+
 macro_rules! window {
     ($act: expr) => {
         act!(Action::Window($act))
@@ -473,10 +746,19 @@ macro_rules! search {
 
 macro_rules! search_word {
     ($style: expr) => {
-        edit_target!(
-            EditAction::Motion,
+        mark_jump!(EditorAction::Edit(
+            Specifier::Exact(EditAction::Motion),
             EditTarget::Search(SearchType::Word($style, true), MoveDirMod::Same, Count::Exact(1))
-        )
+        ))
+    };
+}
+
+macro_rules! jump_buffer {
+    ($term: expr) => {
+        mark_jump!(EditorAction::Edit(
+            Specifier::Exact(EditAction::Motion),
+            EditTarget::Boundary(RangeType::Buffer, true, $term, Count::Contextual)
+        ))
     };
 }
 
@@ -514,9 +796,9 @@ macro_rules! start_shift_selection {
 fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputStep<I>)> {
     [
         // Insert, Command and Search mode keybindings.
-        ( MAP, "<C-Y>", paste!(PasteStyle::Cursor) ),
-        ( MAP, "<M-BS>", kill!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
-        ( MAP, "<M-Del>", kill!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
+        ( MAP, "<C-Y>", yank!() ),
+        ( MAP, "<M-BS>", kill!(MoveDir1D::Previous, MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
+        ( MAP, "<M-Del>", kill!(MoveDir1D::Previous, MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
         ( MAP, "<BS>", erase!(MoveType::Column(MoveDir1D::Previous, true)) ),
 
         // Insert and Command mode keybindings.
@@ -525,9 +807,9 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( ICMAP, "<C-D>", erase!(MoveType::Column(MoveDir1D::Next, true)) ),
         ( ICMAP, "<C-E>", motion!(MoveType::LinePos(MovePosition::End), Count::MinusOne) ),
         ( ICMAP, "<C-F>", motion!(MoveType::Column(MoveDir1D::Next, true)) ),
-        ( ICMAP, "<C-K>", kill!(MoveType::LinePos(MovePosition::End), Count::MinusOne) ),
+        ( ICMAP, "<C-K>", kill!(MoveDir1D::Next, MoveType::LinePos(MovePosition::End), Count::MinusOne) ),
         ( ICMAP, "<C-X>z", isv!(vec![], vec![ExternalAction::Repeat(false)]) ),
-        ( ICMAP, "<C-W>", kill_target!(EditTarget::Selection) ),
+        ( ICMAP, "<C-W>", kill_target!(MoveDir1D::Next, EditTarget::Selection) ),
         ( ICMAP, "<C-Left>", motion!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
         ( ICMAP, "<C-Right>", motion!(MoveType::WordBegin(WordStyle::NonAlphaNum, MoveDir1D::Next)) ),
         ( ICMAP, "<C-S-Left>", start_shift_selection!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous).into()) ),
@@ -535,13 +817,14 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( ICMAP, "<M-b>", motion!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
         ( ICMAP, "<M-B>", start_shift_selection!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous).into()) ),
         ( ICMAP, "<M-c>", edit!(EditAction::ChangeCase(Case::Title), MoveType::WordEnd(WordStyle::Little, MoveDir1D::Next)) ),
-        ( ICMAP, "<M-d>", kill!(MoveType::WordEnd(WordStyle::Little, MoveDir1D::Next)) ),
+        ( ICMAP, "<M-d>", kill!(MoveDir1D::Next, MoveType::WordEnd(WordStyle::Little, MoveDir1D::Next)) ),
         ( ICMAP, "<M-f>", motion!(MoveType::WordBegin(WordStyle::NonAlphaNum, MoveDir1D::Next)) ),
         ( ICMAP, "<M-F>", start_shift_selection!(MoveType::WordBegin(WordStyle::NonAlphaNum, MoveDir1D::Next).into()) ),
         ( ICMAP, "<M-l>", edit!(EditAction::ChangeCase(Case::Lower), MoveType::WordEnd(WordStyle::Little, MoveDir1D::Next)) ),
         ( ICMAP, "<M-u>", edit!(EditAction::ChangeCase(Case::Upper), MoveType::WordEnd(WordStyle::Little, MoveDir1D::Next)) ),
         ( ICMAP, "<M-w>", yank_target!(EditTarget::Selection) ),
         ( ICMAP, "<M-W>", yank_target!(EditTarget::Selection) ),
+        ( ICMAP, "<M-y>", yank_pop!(MoveDir1D::Previous) ),
         ( ICMAP, "<M-Left>", motion!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
         ( ICMAP, "<M-Right>", motion!(MoveType::WordBegin(WordStyle::NonAlphaNum, MoveDir1D::Next)) ),
         ( ICMAP, "<M-S-Left>", start_shift_selection!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous).into()) ),
@@ -558,7 +841,7 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( ICMAP, "z", isv!(vec![], vec![ExternalAction::Repeat(true)]) ),
 
         // Insert mode keybindings.
-        ( IMAP, "<C-G>", is!(InternalAction::ClearTargetShape(false), EditorAction::Edit(EditAction::Motion.into(), EditTarget::CurrentPosition), EmacsMode::Insert) ),
+        ( IMAP, "<C-G>", keyboard_quit!() ),
         ( IMAP, "<C-J>", chartype!(Char::Single('\n')) ),
         ( IMAP, "<C-L>", scrollcp!(MovePosition::Middle, Axis::Vertical) ),
         ( IMAP, "<C-N>", motion!(MoveType::Line(MoveDir1D::Next)) ),
@@ -570,32 +853,46 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( IMAP, "<C-S>", cmdbar_search!(MoveDir1D::Next) ),
         ( IMAP, "<C-T>", unmapped!() ),
         ( IMAP, "<C-U><C-X>s", unmapped!() ),
+        ( IMAP, "<C-U><C-@>", mark_pop!(MarkRingAction::Pop) ),
+        ( IMAP, "<C-U><C-Space>", mark_pop!(MarkRingAction::Pop) ),
         ( IMAP, "<C-V>", scroll2d!(MoveDir2D::Down, ScrollSize::Page) ),
         ( IMAP, "<C-X><C-C>", window!(WindowAction::Close(WindowTarget::All, CloseFlags::QUIT)) ),
-        ( IMAP, "<C-X><C-O>", unmapped!() ),
+        ( IMAP, "<C-X><C-K><C-A>", iact!(InternalAction::AddMacroCounter, EmacsMode::Insert) ),
+        ( IMAP, "<C-X><C-K><C-C>", iact!(InternalAction::SetMacroCounter, EmacsMode::Insert) ),
+        ( IMAP, "<C-X><C-K><C-I>", isv!(vec![], vec![ExternalAction::MacroCounter]) ),
+        ( IMAP, "<C-X><C-K>x{register}", act!(MacroAction::Save.into()) ),
+        ( IMAP, "<C-X><C-@>", mark_pop!(MarkRingAction::PopGlobal) ),
+        ( IMAP, "<C-X><C-O>", erase_group!(RangeType::BlankLines.into()) ),
         ( IMAP, "<C-X><C-Q>", unmapped!() ),
         ( IMAP, "<C-X><C-S>", unmapped!() ),
         ( IMAP, "<C-X><C-T>", unmapped!() ),
         ( IMAP, "<C-X><C-W>", unmapped!() ),
         ( IMAP, "<C-X><C-X>", selection!(SelectionAction::CursorSet(SelectionCursorChange::SwapAnchor(false))) ),
         ( IMAP, "<C-X><C-Z>", act!(Action::Suspend) ),
-        ( IMAP, "<C-X><Space>", unmapped!() ),
+        ( IMAP, "<C-X><C-Space>", mark_pop!(MarkRingAction::PopGlobal) ),
+        ( IMAP, "<C-X><Space>", start_selection!(TargetShape::BlockWise) ),
         ( IMAP, "<C-X><Left>", window_switch!(OpenTarget::Offset(MoveDir1D::Previous, Count::Contextual)) ),
         ( IMAP, "<C-X><Right>", window_switch!(OpenTarget::Offset(MoveDir1D::Next, Count::Contextual)) ),
+        ( IMAP, "<C-X>(", isv!(vec![], vec![ExternalAction::MacroStart]) ),
+        ( IMAP, "<C-X>)", act!(MacroAction::StopRecording.into()) ),
         ( IMAP, "<C-X>b", unmapped!() ),
+        ( IMAP, "<C-X>e", macro_end_and_call!() ),
+        ( IMAP, "<C-X>f", iact!(InternalAction::SetFillColumn, EmacsMode::Insert) ),
         ( IMAP, "<C-X>h", start_selection!(TargetShape::CharWise, RangeType::Buffer.into()) ),
         ( IMAP, "<C-X>k", unmapped!() ),
         ( IMAP, "<C-X>o", window_focus!(FocusChange::Direction1D(MoveDir1D::Next, Count::Exact(1), true)) ),
-        ( IMAP, "<C-X>r<M-w>", unmapped!() ),
+        ( IMAP, "<C-X>r<M-w>", rectangle!(EditAction::Yank, vec![InternalAction::SetRegister(Register::LastRectangle)]) ),
         ( IMAP, "<C-X>rb", unmapped!() ),
-        ( IMAP, "<C-X>rc", unmapped!() ),
-        ( IMAP, "<C-X>rd", unmapped!() ),
-        ( IMAP, "<C-X>rk", unmapped!() ),
+        ( IMAP, "<C-X>rc", rectangle!(EditAction::Replace(false), vec![InternalAction::SetReplaceChar(Char::Single(' '))]) ),
+        ( IMAP, "<C-X>rd", rectangle!(EditAction::Delete, vec![InternalAction::SetRegister(Register::Blackhole)]) ),
+        ( IMAP, "<C-X>rj{register}", act!(MacroAction::Execute(Count::Contextual).into()) ),
+        ( IMAP, "<C-X>rk", rectangle!(EditAction::Delete, vec![InternalAction::SetRegister(Register::LastRectangle)]) ),
         ( IMAP, "<C-X>rl", unmapped!() ),
         ( IMAP, "<C-X>rm", unmapped!() ),
-        ( IMAP, "<C-X>ro", unmapped!() ),
-        ( IMAP, "<C-X>ry", unmapped!() ),
-        ( IMAP, "<C-X>rN", unmapped!() ),
+        ( IMAP, "<C-X>ro", rectangle!(EditAction::Prefix(LinePrefix::Blank)) ),
+        ( IMAP, "<C-X>rt", string_rectangle!() ),
+        ( IMAP, "<C-X>ry", yank_rectangle!() ),
+        ( IMAP, "<C-X>rN", rectangle!(EditAction::Prefix(LinePrefix::Number(Count::Contextual))) ),
         ( IMAP, "<C-X>s", unmapped!() ),
         ( IMAP, "<C-X>u", history!(HistoryAction::Undo(Count::Contextual)) ),
         ( IMAP, "<C-X>0", window_quit!(WindowTarget::Single, FocusChange::Current) ),
@@ -605,23 +902,29 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( IMAP, "<C-X><", scroll2d!(MoveDir2D::Left, ScrollSize::Page) ),
         ( IMAP, "<C-X>>", scroll2d!(MoveDir2D::Right, ScrollSize::Page) ),
         ( IMAP, "<C-Z>", act!(Action::Suspend) ),
-        ( IMAP, "<C-@>", start_selection!(TargetShape::CharWise) ),
+        ( IMAP, "<C-M-%>", query_replace!(true) ),
+        ( IMAP, "<C-@>", set_mark!() ),
         ( IMAP, "<C-_>", history!(HistoryAction::Undo(Count::Contextual)) ),
-        ( IMAP, "<C-Del>", kill!(MoveType::LinePos(MovePosition::End), Count::MinusOne) ),
-        ( IMAP, "<C-Space>", start_selection!(TargetShape::CharWise) ),
+        ( IMAP, "<C-Del>", kill!(MoveDir1D::Next, MoveType::LinePos(MovePosition::End), Count::MinusOne) ),
+        ( IMAP, "<C-Space>", set_mark!() ),
+        ( IMAP, "<M-q>", fill_paragraph!() ),
         ( IMAP, "<M-s>.", search_word!(WordStyle::Big) ),
         ( IMAP, "<M-t>", unmapped!() ),
         ( IMAP, "<M-v>", scroll2d!(MoveDir2D::Up, ScrollSize::Page) ),
         ( IMAP, "<M-x>", cmdbar_focus!(CommandType::Command, EmacsMode::Command) ),
-        ( IMAP, "<M-z>{char}", kill_target!(EditTarget::Search(SearchType::Char(true), MoveDirMod::Same, Count::Contextual)) ),
+        ( IMAP, "<M-z>{char}", kill_target!(MoveDir1D::Next, EditTarget::Search(SearchType::Char(true), MoveDirMod::Same, Count::Contextual)) ),
         ( IMAP, "<M-S>.", search_word!(WordStyle::Big) ),
-        ( IMAP, "<M-\\>", erase_range!(RangeType::Word(WordStyle::Whitespace(false))) ),
+        ( IMAP, "<M-%>", query_replace!(false) ),
+        ( IMAP, "<M-\\>", erase_group!(RangeType::HorizontalSpace.into()) ),
         ( IMAP, "<M-^>", edit!(EditAction::Join(JoinStyle::OneSpace), MoveType::Line(MoveDir1D::Previous)) ),
-        ( IMAP, "<M-<>", edit_buffer!(EditAction::Motion, MoveTerminus::Beginning) ),
-        ( IMAP, "<M->>", edit_buffer!(EditAction::Motion, MoveTerminus::End) ),
+        ( IMAP, "<M-/>", editor!(EditorAction::Complete(CompletionType::Word(CompletionScope::Nearest), CompletionSelection::List(MoveDir1D::Next), CompletionDisplay::None)) ),
+        ( IMAP, "<M-<>", jump_buffer!(MoveTerminus::Beginning) ),
+        ( IMAP, "<M->>", jump_buffer!(MoveTerminus::End) ),
         ( IMAP, "<M-Space>", just_one_space!() ),
         ( IMAP, "<S-Up>", start_shift_selection!(MoveType::Line(MoveDir1D::Previous).into()) ),
         ( IMAP, "<S-Down>", start_shift_selection!(MoveType::Line(MoveDir1D::Next).into()) ),
+        ( IMAP, "<F3>", isv!(vec![], vec![ExternalAction::MacroStart]) ),
+        ( IMAP, "<F4>", act!(MacroAction::StopOrExecute(Count::Contextual).into()) ),
         ( IMAP, "<Insert>", iact!(InternalAction::SetInsertStyle(InsertStyle::Replace)) ),
         ( IMAP, "<Up>", motion!(MoveType::Line(MoveDir1D::Previous)) ),
         ( IMAP, "<Down>", motion!(MoveType::Line(MoveDir1D::Next)) ),
@@ -771,6 +1074,8 @@ impl<I: ApplicationInfo> Default for EmacsMachine<TerminalKey, I> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::editing::context::EditContext;
+    use crate::env::emacs::ActionContext;
     use crate::input::bindings::BindingMachine;
     use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
@@ -804,6 +1109,7 @@ mod tests {
 
     const CMDBAR_ABORT: Action = Action::Prompt(PromptAction::Abort(false));
     const CMDBAR_SEARCH: Action = Action::CommandBar(CommandBarAction::Focus(CommandType::Search));
+    const MARK_PUSH: Action = Action::Editor(EditorAction::MarkRing(MarkRingAction::Push));
 
     #[test]
     fn test_selection_shift() {
@@ -842,10 +1148,11 @@ mod tests {
         // Start out in Insert mode.
         assert_eq!(vm.mode(), EmacsMode::Insert);
 
-        // <C-Space> begins selection.
+        // <C-Space> pushes the mark and begins selection.
         let act =
             SelectionAction::Resize(SelectionResizeStyle::Restart, EditTarget::CurrentPosition);
         vm.input_key(ctl!(' '));
+        assert_pop1!(vm, MARK_PUSH, ctx);
         assert_pop2!(vm, Action::from(act), ctx);
 
         // <Left> continues to select.
@@ -863,9 +1170,10 @@ mod tests {
         // Start out in Insert mode.
         assert_eq!(vm.mode(), EmacsMode::Insert);
 
-        // ^R moves to Search mode.
+        // ^R pushes the mark and moves to Search mode.
         ctx.persist.regexsearch_dir = MoveDir1D::Previous;
         vm.input_key(ctl!('r'));
+        assert_pop1!(vm, MARK_PUSH, ctx);
         assert_pop2!(vm, CMDBAR_SEARCH, ctx);
         assert_eq!(vm.mode(), EmacsMode::Search);
 
@@ -894,8 +1202,9 @@ mod tests {
         assert_pop2!(vm, CMDBAR_ABORT, ctx);
         assert_eq!(vm.mode(), EmacsMode::Insert);
 
-        // ^S moves to Search mode.
+        // ^S pushes the mark and moves to Search mode.
         vm.input_key(ctl!('s'));
+        assert_pop1!(vm, MARK_PUSH, ctx);
         assert_pop2!(vm, CMDBAR_SEARCH, ctx);
         assert_eq!(vm.mode(), EmacsMode::Search);
 
@@ -947,6 +1256,105 @@ mod tests {
         assert_pop2!(vm, typechar!(' '), ctx);
     }
 
+    #[test]
+    fn test_mark_ring() {
+        let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
+        let mut ctx = EmacsContext::default();
+
+        // M-< pushes the mark before jumping.
+        let act = Action::from(EditorAction::Edit(
+            EditAction::Motion.into(),
+            EditTarget::Boundary(
+                RangeType::Buffer,
+                true,
+                MoveTerminus::Beginning,
+                Count::Contextual,
+            ),
+        ));
+        vm.input_key(key!('<', KeyModifiers::ALT));
+        assert_pop1!(vm, MARK_PUSH, ctx);
+        assert_pop2!(vm, act, ctx);
+
+        // C-Space pushes the mark and starts a selection.
+        let act =
+            SelectionAction::Resize(SelectionResizeStyle::Restart, EditTarget::CurrentPosition);
+        ctx.persist.shape = Some(TargetShape::CharWise);
+        vm.input_key(ctl!(' '));
+        assert_pop1!(vm, MARK_PUSH, ctx);
+        assert_pop2!(vm, Action::from(act), ctx);
+
+        // C-U C-Space pops the buffer's mark ring, and ends the selection.
+        let act = Action::from(EditorAction::MarkRing(MarkRingAction::Pop));
+        ctx.persist.shape = None;
+        vm.input_key(ctl!('u'));
+        vm.input_key(ctl!(' '));
+        assert_pop2!(vm, act, ctx);
+
+        // C-X C-Space pops the global mark ring.
+        let act = Action::from(EditorAction::MarkRing(MarkRingAction::PopGlobal));
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!(' '));
+        assert_pop2!(vm, act, ctx);
+    }
+
+    #[test]
+    fn test_kill_ring() {
+        let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
+        let mut ctx = EmacsContext::default();
+
+        let kill_eol = Action::from(EditorAction::Edit(
+            EditAction::Delete.into(),
+            EditTarget::Motion(MoveType::LinePos(MovePosition::End), Count::MinusOne),
+        ));
+        let kill_word = Action::from(EditorAction::Edit(
+            EditAction::Delete.into(),
+            EditTarget::Motion(
+                MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous),
+                Count::Contextual,
+            ),
+        ));
+        let yank = Action::from(InsertTextAction::Paste(PasteStyle::Cursor, Count::Contextual));
+        let yank_pop =
+            Action::from(InsertTextAction::YankPop(MoveDir1D::Previous, Count::Contextual));
+
+        // The first ^K starts a new kill ring entry.
+        ctx.action.register = Some(Register::KillRing);
+        ctx.persist.killing = true;
+        vm.input_key(ctl!('k'));
+        assert_pop2!(vm, kill_eol, ctx);
+
+        // A second ^K appends to it.
+        ctx.action.register_append = Some(MoveDir1D::Next);
+        vm.input_key(ctl!('k'));
+        assert_pop2!(vm, kill_eol, ctx);
+
+        // Killing backwards prepends to it.
+        ctx.action.register_append = Some(MoveDir1D::Previous);
+        vm.input_key(key!(KeyCode::Backspace, KeyModifiers::ALT));
+        assert_pop2!(vm, kill_word, ctx);
+
+        // Moving the cursor breaks up the sequence of kills.
+        ctx.action = ActionContext::default();
+        ctx.persist.killing = false;
+        vm.input_key(key!(KeyCode::Left));
+        assert_pop2!(vm, mv!(MoveType::Column(MoveDir1D::Previous, true)), ctx);
+
+        ctx.action.register = Some(Register::KillRing);
+        ctx.persist.killing = true;
+        vm.input_key(ctl!('k'));
+        assert_pop2!(vm, kill_eol, ctx);
+
+        // ^Y pastes from the kill ring.
+        ctx.persist.killing = false;
+        vm.input_key(ctl!('y'));
+        assert_pop2!(vm, yank, ctx);
+
+        // M-y cycles to older entries.
+        ctx.action.register = None;
+        vm.input_key(key!('y', KeyModifiers::ALT));
+        assert_pop2!(vm, yank_pop, ctx);
+    }
+
     #[test]
     fn test_repeat_action() {
         let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
@@ -1035,4 +1443,329 @@ mod tests {
         vm.input_key(key!('z'));
         assert_pop2!(vm, typechar!('z'), ctx);
     }
+
+    #[test]
+    fn test_query_replace() {
+        let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
+        let mut ctx = EmacsContext::default();
+
+        let submit = Action::Prompt(PromptAction::Submit);
+
+        // M-% prompts for a search string in the command bar.
+        ctx.persist.reprompt = true;
+        let act = Action::CommandBar(CommandBarAction::QueryReplace(false));
+        vm.input_key(key!('%', KeyModifiers::ALT));
+        assert_pop2!(vm, act, ctx);
+        assert_eq!(vm.mode(), EmacsMode::Command);
+
+        vm.input_key(key!('a'));
+        assert_pop2!(vm, typechar!('a'), ctx);
+        assert_eq!(vm.mode(), EmacsMode::Command);
+
+        // Submitting the search string stays in Command mode to prompt for the replacement.
+        vm.input_key(key!(KeyCode::Enter));
+        ctx.persist.reprompt = false;
+        assert_pop2!(vm, submit.clone(), ctx);
+        assert_eq!(vm.mode(), EmacsMode::Command);
+
+        vm.input_key(key!('b'));
+        assert_pop2!(vm, typechar!('b'), ctx);
+        assert_eq!(vm.mode(), EmacsMode::Command);
+
+        // Submitting the replacement returns to Insert mode.
+        vm.input_key(key!(KeyCode::Enter));
+        assert_pop2!(vm, submit.clone(), ctx);
+        assert_eq!(vm.mode(), EmacsMode::Insert);
+
+        // C-M-% prompts for a regular expression.
+        ctx.persist.reprompt = true;
+        let act = Action::CommandBar(CommandBarAction::QueryReplace(true));
+        vm.input_key(key!('%', KeyModifiers::CONTROL | KeyModifiers::ALT));
+        assert_pop2!(vm, act, ctx);
+        assert_eq!(vm.mode(), EmacsMode::Command);
+
+        // Aborting the first prompt returns to Insert mode.
+        vm.input_key(ctl!('g'));
+        assert_pop2!(vm, CMDBAR_ABORT, ctx);
+        assert!(!vm.context().persist.reprompt);
+        assert_eq!(vm.mode(), EmacsMode::Insert);
+    }
+
+    #[test]
+    fn test_macros() {
+        let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
+        let mut ctx = EmacsContext::default();
+
+        let start = Action::from(MacroAction::StartRecording);
+        let stop = Action::from(MacroAction::StopRecording);
+        let stop_or_exec = Action::from(MacroAction::StopOrExecute(Count::Contextual));
+        let exec = Action::from(MacroAction::ExecuteRepeatable(Count::Contextual));
+        let counter = |c| {
+            let it = InsertTextAction::Type(Char::Single(c).into(), MoveDir1D::Previous, 1.into());
+            Action::from(it)
+        };
+
+        // C-x ( starts recording.
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('('));
+        assert_pop2!(vm, start.clone(), ctx);
+
+        // So does F3.
+        vm.input_key(key!(KeyCode::F(3)));
+        assert_pop2!(vm, start.clone(), ctx);
+
+        // C-x C-k TAB inserts the counter, and then increments it.
+        ctx.persist.counter = 1;
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('k'));
+        vm.input_key(key!(KeyCode::Tab));
+        assert_pop2!(vm, counter('0'), ctx);
+
+        // C-u 12 C-x C-k C-c sets the counter.
+        vm.input_key(ctl!('u'));
+        vm.input_key(key!('1'));
+        vm.input_key(key!('2'));
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('k'));
+        ctx.action.count = Some(12);
+        ctx.persist.counter = 12;
+        vm.input_key(ctl!('c'));
+        assert_pop2!(vm, Action::NoOp, ctx);
+
+        // C-x C-k C-a adds to the counter.
+        ctx.action.count = None;
+        ctx.persist.counter = 13;
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('k'));
+        vm.input_key(ctl!('a'));
+        assert_pop2!(vm, Action::NoOp, ctx);
+
+        ctx.persist.counter = 14;
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('k'));
+        vm.input_key(key!(KeyCode::Tab));
+        assert_pop1!(vm, counter('1'), ctx);
+        assert_pop2!(vm, counter('3'), ctx);
+
+        // C-x ) stops recording.
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!(')'));
+        assert_pop2!(vm, stop.clone(), ctx);
+
+        // C-x e stops any recording, and then executes the macro in a way that can be repeated.
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('e'));
+        assert_pop1!(vm, stop.clone(), ctx);
+        assert_pop2!(vm, exec, ctx);
+
+        // F4 stops recording, or executes the macro when not recording.
+        vm.input_key(key!(KeyCode::F(4)));
+        assert_pop2!(vm, stop_or_exec.clone(), ctx);
+
+        // C-x C-k x saves the macro to a register.
+        ctx.action.register = Some(Register::Named('a'));
+        let act = Action::from(MacroAction::Save);
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('k'));
+        vm.input_key(key!('x'));
+        vm.input_key(key!('a'));
+        assert_pop2!(vm, act, ctx);
+
+        // C-x r j executes a macro from a register.
+        let act = Action::from(MacroAction::Execute(Count::Contextual));
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('j'));
+        vm.input_key(key!('a'));
+        assert_pop2!(vm, act, ctx);
+
+        // F3 resets the counter to the prefix argument.
+        ctx.action.register = None;
+        ctx.action.count = Some(5);
+        ctx.persist.counter = 5;
+        vm.input_key(ctl!('u'));
+        vm.input_key(key!('5'));
+        vm.input_key(key!(KeyCode::F(3)));
+        assert_pop2!(vm, start, ctx);
+    }
+
+    #[test]
+    fn test_fill_and_spaces() {
+        let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
+        let mut ctx = EmacsContext::default();
+
+        let checkpoint = Action::from(HistoryAction::Checkpoint);
+        let erase = |rt| Action::from(EditorAction::Edit(EditAction::Delete.into(), rt));
+        let space = Action::from(InsertTextAction::Type(
+            Char::Single(' ').into(),
+            MoveDir1D::Previous,
+            Count::Contextual,
+        ));
+        let fill = Action::from(EditorAction::Edit(
+            EditAction::Format.into(),
+            EditTarget::Range(RangeType::Paragraph, true, Count::Contextual),
+        ));
+        let dabbrev = Action::from(EditorAction::Complete(
+            CompletionType::Word(CompletionScope::Nearest),
+            CompletionSelection::List(MoveDir1D::Next),
+            CompletionDisplay::None,
+        ));
+
+        // M-q fills the paragraph as a single undoable change.
+        assert_eq!(ctx.get_text_width(), 70);
+        vm.input_key(key!('q', KeyModifiers::ALT));
+        assert_pop1!(vm, checkpoint.clone(), ctx);
+        assert_pop1!(vm, fill.clone(), ctx);
+        assert_pop2!(vm, checkpoint.clone(), ctx);
+
+        // C-u 50 C-x f sets the fill column.
+        vm.input_key(ctl!('u'));
+        vm.input_key(key!('5'));
+        vm.input_key(key!('0'));
+        vm.input_key(ctl!('x'));
+        ctx.action.count = Some(50);
+        ctx.persist.fill_column = 50;
+        vm.input_key(key!('f'));
+        assert_pop2!(vm, Action::NoOp, ctx);
+        assert_eq!(vm.context().get_text_width(), 50);
+
+        // M-SPC deletes the surrounding whitespace and inserts a space.
+        ctx.action.count = None;
+        ctx.action.register = Some(Register::Blackhole);
+        vm.input_key(key!(' ', KeyModifiers::ALT));
+        let rt = EditTarget::Range(RangeType::HorizontalSpace, true, Count::Exact(1));
+        assert_pop1!(vm, checkpoint.clone(), ctx);
+        assert_pop1!(vm, erase(rt), ctx);
+        assert_pop1!(vm, space, ctx);
+        assert_pop2!(vm, checkpoint.clone(), ctx);
+
+        // M-\ only deletes the surrounding whitespace.
+        vm.input_key(key!('\\', KeyModifiers::ALT));
+        assert_pop1!(vm, checkpoint.clone(), ctx);
+        assert_pop1!(vm, erase(RangeType::HorizontalSpace.into()), ctx);
+        assert_pop2!(vm, checkpoint.clone(), ctx);
+
+        // C-x C-o deletes blank lines.
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('o'));
+        assert_pop1!(vm, checkpoint.clone(), ctx);
+        assert_pop1!(vm, erase(RangeType::BlankLines.into()), ctx);
+        assert_pop2!(vm, checkpoint, ctx);
+
+        // M-/ cycles through completions without creating checkpoints.
+        ctx.action.register = None;
+        vm.input_key(key!('/', KeyModifiers::ALT));
+        assert_pop2!(vm, dabbrev.clone(), ctx);
+        vm.input_key(key!('/', KeyModifiers::ALT));
+        assert_pop2!(vm, dabbrev, ctx);
+    }
+
+    #[test]
+    fn test_rectangles() {
+        let mut vm: EmacsMachine<TerminalKey> = EmacsMachine::default();
+        let mut ctx = EmacsContext::default();
+
+        let rect =
+            |ea| Action::from(EditorAction::Edit(Specifier::Exact(ea), EditTarget::Selection));
+        let block = |register, replace| {
+            ActionContext {
+                register,
+                replace,
+                shape: Some(TargetShape::BlockWise),
+                ..Default::default()
+            }
+        };
+
+        // C-x SPC starts a rectangular selection.
+        let act =
+            SelectionAction::Resize(SelectionResizeStyle::Restart, EditTarget::CurrentPosition);
+        ctx.persist.shape = Some(TargetShape::BlockWise);
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!(' '));
+        assert_pop2!(vm, Action::from(act), ctx);
+
+        // C-x r k kills the rectangle, and deactivates the mark.
+        ctx.persist.shape = None;
+        ctx.action = block(Some(Register::LastRectangle), None);
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('k'));
+        assert_pop2!(vm, rect(EditAction::Delete), ctx);
+
+        // C-x r M-w copies the rectangle.
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('w', KeyModifiers::ALT));
+        assert_pop2!(vm, rect(EditAction::Yank), ctx);
+
+        // C-x r y yanks the last killed rectangle.
+        let paste = InsertTextAction::Paste(PasteStyle::Cursor, Count::Exact(1));
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('y'));
+        assert_pop2!(vm, Action::from(paste), ctx);
+
+        // C-x r d deletes the rectangle without saving it.
+        ctx.action = block(Some(Register::Blackhole), None);
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('d'));
+        assert_pop2!(vm, rect(EditAction::Delete), ctx);
+
+        // C-x r c replaces the rectangle with spaces.
+        ctx.action = block(None, Some(Char::Single(' ')));
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('c'));
+        assert_pop2!(vm, rect(EditAction::Replace(false)), ctx);
+
+        // C-x r o shifts the rectangle over with blank space.
+        ctx.action = block(None, None);
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('o'));
+        assert_pop2!(vm, rect(EditAction::Prefix(LinePrefix::Blank)), ctx);
+
+        // C-u 5 C-x r N numbers the lines starting from 5.
+        let number = LinePrefix::Number(Count::Contextual);
+        ctx.action.count = Some(5);
+        vm.input_key(ctl!('u'));
+        vm.input_key(key!('5'));
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('N'));
+        assert_pop2!(vm, rect(EditAction::Prefix(number)), ctx);
+
+        // C-x r t deletes the rectangle, and then types into each of its lines.
+        let act = SelectionAction::Split(SelectionSplitStyle::Lines, TargetShapeFilter::BLOCK);
+        let cursor = SelectionAction::CursorSet(SelectionCursorChange::Beginning);
+        let split = CursorAction::Split(Count::MinusOne);
+        ctx.action = block(Some(Register::Blackhole), None);
+        vm.input_key(ctl!('x'));
+        vm.input_key(key!('r'));
+        vm.input_key(key!('t'));
+        assert_pop1!(
+            vm,
+            Action::from(EditorAction::Edit(
+                EditAction::Motion.into(),
+                EditTarget::CurrentPosition
+            )),
+            ctx
+        );
+        assert_pop1!(vm, Action::from(act), ctx);
+        assert_pop1!(vm, Action::from(cursor), ctx);
+        assert_pop1!(vm, rect(EditAction::Delete), ctx);
+        assert_pop2!(vm, Action::from(split), ctx);
+
+        ctx.action = ActionContext::default();
+        vm.input_key(key!('a'));
+        assert_pop2!(vm, typechar!('a'), ctx);
+
+        // C-g goes back to a single cursor.
+        let close = CursorAction::Close(CursorCloseTarget::Followers);
+        let current = EditorAction::Edit(EditAction::Motion.into(), EditTarget::CurrentPosition);
+        vm.input_key(ctl!('g'));
+        assert_pop1!(vm, Action::from(close), ctx);
+        assert_pop2!(vm, Action::from(current), ctx);
+    }
 }
@@ -56,6 +56,8 @@ impl<I: ApplicationInfo> Mode<Action<I>, EmacsContext<I>> for EmacsMode {
     fn enter(&self, _: Self, ctx: &mut EmacsContext<I>) -> Vec<Action<I>> {
         match self {
             EmacsMode::Insert => {
+                ctx.persist.reprompt = false;
+
                 return vec![];
             },
             EmacsMode::Command | EmacsMode::Search => {
@@ -73,7 +75,7 @@ impl<I: ApplicationInfo> Mode<Action<I>, EmacsContext<I>> for EmacsMode {
                     InsertStyle::Insert => {
                         return None;
                     },
-                    InsertStyle::Replace => {
+                    InsertStyle::Replace | InsertStyle::VirtualReplace => {
                         return Some("Overwrite mode enabled".into());
                     },
                 }
@@ -114,6 +116,7 @@ impl<I: ApplicationInfo> ModeKeys<TerminalKey, Action<I>, EmacsContext<I>> for E
         ctx: &mut EmacsContext<I>,
     ) -> (Vec<Action<I>>, Option<Self>) {
         ctx.persist.repeating = false;
+        ctx.persist.killing = false;
 
         match self {
             EmacsMode::Insert => {
@@ -161,6 +164,9 @@ pub(crate) struct ActionContext {
     pub(crate) count: Option<usize>,
     pub(crate) counting: Option<usize>,
     pub(crate) register: Option<Register>,
+    pub(crate) register_append: Option<MoveDir1D>,
+    pub(crate) replace: Option<Char>,
+    pub(crate) shape: Option<TargetShape>,
 }
 
 /// This is the context preserved across actions, and changes either with the mode, or through
@@ -170,6 +176,10 @@ pub(crate) struct PersistentContext {
     regexsearch_dir: MoveDir1D,
     regexsearch_inc: bool,
     repeating: bool,
+    killing: bool,
+    reprompt: bool,
+    counter: usize,
+    fill_column: usize,
     insert: InsertStyle,
     shape: Option<TargetShape>,
     shift: bool,
@@ -181,6 +191,10 @@ impl Default for PersistentContext {
             regexsearch_dir: MoveDir1D::Next,
             regexsearch_inc: true,
             repeating: false,
+            killing: false,
+            reprompt: false,
+            counter: 0,
+            fill_column: 70,
             insert: InsertStyle::Insert,
             shape: None,
             shift: false,
@@ -235,7 +249,7 @@ impl<I: ApplicationInfo> InputContext for EmacsContext<I> {
 
 impl<I: ApplicationInfo> EditContext for EmacsContext<I> {
     fn get_replace_char(&self) -> Option<Char> {
-        None
+        self.action.replace.clone()
     }
 
     fn get_search_regex(&self) -> Option<Regex> {
@@ -251,7 +265,7 @@ impl<I: ApplicationInfo> EditContext for EmacsContext<I> {
     }
 
     fn get_target_shape(&self) -> Option<TargetShape> {
-        self.persist.shape
+        self.action.shape.or(self.persist.shape)
     }
 
     fn get_insert_style(&self) -> Option<InsertStyle> {
@@ -267,12 +281,20 @@ impl<I: ApplicationInfo> EditContext for EmacsContext<I> {
     }
 
     fn get_register_append(&self) -> bool {
-        false
+        self.action.register_append.is_some()
+    }
+
+    fn get_register_append_dir(&self) -> MoveDir1D {
+        self.action.register_append.unwrap_or(MoveDir1D::Next)
     }
 
     fn is_search_incremental(&self) -> bool {
         self.persist.regexsearch_inc
     }
+
+    fn get_text_width(&self) -> usize {
+        self.persist.fill_column
+    }
 }
 
 impl<I: ApplicationInfo> Default for EmacsContext<I> {
@@ -337,6 +359,11 @@ impl<I: ApplicationInfo> InputKeyContext<TerminalKey, CommonKeyClass> for EmacsC
                     self.action.counting = Some(new);
                 }
             },
+            EdgeEvent::Class(CommonKeyClass::Register) => {
+                if let Some(c) = ke.get_char() {
+                    self.action.register = Some(Register::Named(c));
+                }
+            },
 
             // Track literals, codepoints, etc.
             EdgeEvent::Any => {
@@ -7,6 +7,8 @@
 //! - act!()
 //! - blackhole!()
 //!
+//! The block_insert!() macro also expects an `ExternalAction` type with a `Something` variant.
+//!
 macro_rules! unmapped {
     () => {
         isv!()
@@ -204,6 +206,23 @@ macro_rules! edit_selection {
     };
 }
 
+/// Split a blockwise selection into one per line, move each cursor to one of their sides, and
+/// then give every line its own cursor for typing into. The actions in the first list run
+/// before the cursors get split, and the actions in the second list run afterwards.
+macro_rules! block_insert {
+    ($cursor: expr, [$($pre: expr),*], [$($post: expr),*]) => {
+        vec![
+            ExternalAction::Something(
+                SelectionAction::Split(SelectionSplitStyle::Lines, TargetShapeFilter::BLOCK).into()
+            ),
+            ExternalAction::Something(SelectionAction::CursorSet($cursor).into()),
+            $(ExternalAction::Something(Action::from($pre)),)*
+            ExternalAction::Something(CursorAction::Split(Count::MinusOne).into()),
+            $(ExternalAction::Something(Action::from($post)),)*
+        ]
+    };
+}
+
 macro_rules! erase_target {
     ($et: expr) => {
         blackhole!(EditorAction::Edit(EditAction::Delete.into(), $et))
@@ -218,9 +237,3 @@ macro_rules! erase {
         erase_target!(EditTarget::Motion($mt, Count::Exact($c)))
     };
 }
-
-macro_rules! erase_range {
-    ($rt: expr) => {
-        erase_target!(EditTarget::Range($rt, true, Count::Contextual))
-    };
-}
@@ -21,6 +21,8 @@ use crate::{
             MoveDir1D,
             Register,
             RepeatType,
+            SearchCase,
+            SearchQuery,
             Specifier,
             TargetShape,
         },
@@ -112,10 +114,10 @@ macro_rules! delegate_context {
             MixedContext::Vim(c) => $invoke(c),
         }
     };
-    ($s: expr, $invoke: expr, $arg: expr) => {
+    ($s: expr, $invoke: expr, $($arg: expr),+) => {
         match $s {
-            MixedContext::Emacs(c) => $invoke(c, $arg),
-            MixedContext::Vim(c) => $invoke(c, $arg),
+            MixedContext::Emacs(c) => $invoke(c, $($arg),+),
+            MixedContext::Vim(c) => $invoke(c, $($arg),+),
         }
     };
 }
@@ -215,6 +217,18 @@ impl<I: ApplicationInfo> EditContext for MixedContext<I> {
         delegate_context!(self, EditContext::get_search_regex_dir)
     }
 
+    fn compile_search_regex(&self, pattern: &str, case: SearchCase) -> Result<Regex, regex::Error> {
+        delegate_context!(self, EditContext::compile_search_regex, pattern, case)
+    }
+
+    fn get_word_search_pattern(&self, word: &str, boundary: bool, case: SearchCase) -> String {
+        delegate_context!(self, EditContext::get_word_search_pattern, word, boundary, case)
+    }
+
+    fn get_search_queries(&self, text: &str, dir: MoveDir1D) -> Vec<SearchQuery> {
+        delegate_context!(self, EditContext::get_search_queries, text, dir)
+    }
+
     fn get_search_char(&self) -> Option<(MoveDir1D, bool, Char)> {
         delegate_context!(self, EditContext::get_search_char)
     }
@@ -223,6 +237,14 @@ impl<I: ApplicationInfo> EditContext for MixedContext<I> {
         delegate_context!(self, EditContext::get_target_shape)
     }
 
+    fn is_selecting(&self) -> bool {
+        delegate_context!(self, EditContext::is_selecting)
+    }
+
+    fn set_restored_shape(&self, shape: TargetShape) {
+        delegate_context!(self, EditContext::set_restored_shape, shape)
+    }
+
     fn get_insert_style(&self) -> Option<InsertStyle> {
         delegate_context!(self, EditContext::get_insert_style)
     }
@@ -239,9 +261,17 @@ impl<I: ApplicationInfo> EditContext for MixedContext<I> {
         delegate_context!(self, EditContext::get_register_append)
     }
 
+    fn get_register_append_dir(&self) -> MoveDir1D {
+        delegate_context!(self, EditContext::get_register_append_dir)
+    }
+
     fn is_search_incremental(&self) -> bool {
         delegate_context!(self, EditContext::is_search_incremental)
     }
+
+    fn get_text_width(&self) -> usize {
+        delegate_context!(self, EditContext::get_text_width)
+    }
 }
 
 /// Type for wrapping different keybindings in contexts where keybindings can be determined
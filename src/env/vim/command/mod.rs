@@ -15,7 +15,7 @@ use crate::input::{
 };
 
 use crate::editing::{
-    action::{Action, TabAction, WindowAction},
+    action::{Action, EditorAction, FoldAction, TabAction, WindowAction},
     application::{ApplicationInfo, ApplicationWindowId, EmptyInfo},
     base::{
         Axis,
@@ -936,6 +936,45 @@ pub fn vim_cmd_print<C: EditContext, I: ApplicationInfo>(
     Err(CommandError::Error("print is not yet implemented".into()))
 }
 
+/// The `:nohlsearch` command.
+///
+/// *Aliases:* `noh`
+///
+/// Stop highlighting the matches of the last search until the next search.
+pub fn vim_cmd_nohlsearch<C: EditContext, I: ApplicationInfo>(
+    desc: CommandDescription,
+    ctx: &mut CommandContext<C>,
+) -> CommandResult<C, I> {
+    if !desc.arg.text.is_empty() {
+        return Err(CommandError::InvalidArgument);
+    }
+
+    let action = EditorAction::ClearSearchHighlight;
+
+    Ok(CommandStep::Continue(action.into(), ctx.context.take()))
+}
+
+/// The `:fold` command.
+///
+/// *Aliases:* `fo`
+///
+/// Create a closed fold over the lines in the given range.
+pub fn vim_cmd_fold<C: EditContext, I: ApplicationInfo>(
+    desc: CommandDescription,
+    ctx: &mut CommandContext<C>,
+) -> CommandResult<C, I> {
+    if !desc.arg.text.is_empty() {
+        return Err(CommandError::InvalidArgument);
+    }
+
+    let range = desc
+        .range
+        .unwrap_or_else(|| RangeSpec::Single(RangeEnding(RangeEndingType::Current, vec![])));
+    let action = EditorAction::Fold(FoldAction::Create(range));
+
+    Ok(CommandStep::Continue(action.into(), ctx.context.take()))
+}
+
 fn vim_cmd_substitute<C: EditContext, I: ApplicationInfo>(
     _: CommandDescription,
     _: &mut CommandContext<C>,
@@ -972,6 +1011,16 @@ fn default_cmds<C: EditContext, I: ApplicationInfo>() -> Vec<VimCommand<C, I>> {
             aliases: strs!["p"],
             f: vim_cmd_print,
         },
+        VimCommand {
+            name: "nohlsearch".into(),
+            aliases: strs!["noh"],
+            f: vim_cmd_nohlsearch,
+        },
+        VimCommand {
+            name: "fold".into(),
+            aliases: strs!["fo"],
+            f: vim_cmd_fold,
+        },
         VimCommand {
             name: "substitute".into(),
             aliases: strs!["s"],
@@ -1146,6 +1195,7 @@ mod tests {
 
     use crate::editing::base::Axis::{Horizontal, Vertical};
     use crate::editing::base::MoveDir1D::{Next, Previous};
+    use crate::editing::base::RangeSearchInit;
 
     fn mkcmd() -> (VimCommandMachine<VimContext>, VimContext) {
         let cmds = VimCommandMachine::default();
@@ -1206,6 +1256,48 @@ mod tests {
         assert_eq!(res.unwrap(), vec![(act.clone(), ctx.clone())]);
     }
 
+    #[test]
+    fn test_nohlsearch() {
+        let (mut cmds, ctx) = mkcmd();
+
+        let act: Action = EditorAction::ClearSearchHighlight.into();
+        let res = cmds.input_cmd("noh", ctx.clone());
+        assert_eq!(res.unwrap(), vec![(act.clone(), ctx.clone())]);
+
+        let res = cmds.input_cmd("nohlsearch", ctx.clone());
+        assert_eq!(res.unwrap(), vec![(act.clone(), ctx.clone())]);
+
+        let res = cmds.input_cmd("nohlsearch foo", ctx.clone());
+        assert!(matches!(res, Err(CommandError::InvalidArgument)));
+    }
+
+    #[test]
+    fn test_fold() {
+        let (mut cmds, ctx) = mkcmd();
+
+        // Without a range, ":fold" folds the current line.
+        let range = RangeSpec::Single(RangeEnding(RangeEndingType::Current, vec![]));
+        let act: Action = EditorAction::Fold(FoldAction::Create(range)).into();
+        let res = cmds.input_cmd("fold", ctx.clone());
+        assert_eq!(res.unwrap(), vec![(act.clone(), ctx.clone())]);
+
+        let res = cmds.input_cmd("fo", ctx.clone());
+        assert_eq!(res.unwrap(), vec![(act.clone(), ctx.clone())]);
+
+        // Fold an explicit range of lines.
+        let range = RangeSpec::Double(
+            RangeEnding(RangeEndingType::Absolute(Count::Exact(2)), vec![]),
+            RangeEnding(RangeEndingType::Absolute(Count::Exact(5)), vec![]),
+            RangeSearchInit::Cursor,
+        );
+        let act: Action = EditorAction::Fold(FoldAction::Create(range)).into();
+        let res = cmds.input_cmd("2,5fold", ctx.clone());
+        assert_eq!(res.unwrap(), vec![(act.clone(), ctx.clone())]);
+
+        let res = cmds.input_cmd("fold foo", ctx.clone());
+        assert!(matches!(res, Err(CommandError::InvalidArgument)));
+    }
+
     #[test]
     fn test_split_direction() {
         let (mut cmds, ctx) = mkcmd();
@@ -57,6 +57,7 @@ use crate::editing::{
         CursorAction,
         EditAction,
         EditorAction,
+        FoldAction,
         HistoryAction,
         InsertTextAction,
         MacroAction,
@@ -73,6 +74,7 @@ use crate::editing::{
         CloseFlags,
         CommandType,
         CompletionDisplay,
+        CompletionScope,
         CompletionSelection,
         CompletionType,
         Count,
@@ -82,6 +84,7 @@ use crate::editing::{
         IndentChange,
         InsertStyle,
         JoinStyle,
+        Mark,
         MoveDir1D,
         MoveDir2D,
         MoveDirMod,
@@ -223,8 +226,11 @@ enum InternalAction {
     SetCursorDigraph,
     SetInsertStyle(InsertStyle),
     SetTargetShape(TargetShapeFilter, TargetShape),
+    Reselect,
     SetOperation(EditAction),
     SetPostMode(VimMode),
+    SetUndoJoin,
+    SetCompletionMode,
 }
 
 impl InternalAction {
@@ -273,6 +279,12 @@ impl InternalAction {
             InternalAction::SetCursorChar(c) => {
                 ctx.action.cursor = Some(*c);
             },
+            InternalAction::SetUndoJoin => {
+                ctx.action.undojoin = true;
+            },
+            InternalAction::SetCompletionMode => {
+                ctx.action.xmode = true;
+            },
             InternalAction::SetCursorDigraph => {
                 if ctx.ch.digraph1.is_some() {
                     ctx.action.cursor = ctx.ch.digraph1;
@@ -299,10 +311,20 @@ impl InternalAction {
                     },
                     None => {
                         ctx.persist.shape = Some(*shape);
+                        ctx.persist.selecting = false;
                     },
                     _ => {},
                 }
             },
+            InternalAction::Reselect => {
+                if ctx.persist.selected {
+                    // The buffer tells us the shape of the selection that it restores.
+                    ctx.persist.reselect();
+                } else if ctx.persist.shape.is_none() {
+                    // There's no previous selection to go back to.
+                    ctx.action.postmode = Some(VimMode::Normal);
+                }
+            },
             InternalAction::SetOperation(op) => {
                 ctx.action.operation = op.clone();
             },
@@ -987,6 +1009,25 @@ macro_rules! insert {
     };
 }
 
+macro_rules! insert_mark {
+    ($style: expr, $mark: expr) => {
+        isv!(
+            vec![InternalAction::SetInsertStyle($style)],
+            vec![
+                ExternalAction::Something(
+                    EditorAction::Edit(
+                        Specifier::Exact(EditAction::Motion),
+                        EditTarget::CharJump(Specifier::Exact($mark))
+                    )
+                    .into()
+                ),
+                ExternalAction::Something(CursorAction::Split(Count::MinusOne).into()),
+            ],
+            VimMode::Insert
+        )
+    };
+}
+
 macro_rules! paste_register {
     ($dir: expr, $reg: expr) => {
         is!(
@@ -1078,32 +1119,17 @@ macro_rules! insert_visual {
     ($cursor: expr) => {
         isv!(
             vec![InternalAction::SetInsertStyle(InsertStyle::Insert)],
-            vec![
-                ExternalAction::Something(
-                    SelectionAction::Split(SelectionSplitStyle::Lines, TargetShapeFilter::BLOCK)
-                        .into()
-                ),
-                ExternalAction::Something(SelectionAction::CursorSet($cursor).into()),
-                ExternalAction::Something(CursorAction::Split(Count::MinusOne).into()),
-            ],
+            block_insert!($cursor, [], []),
             VimMode::Insert
         )
     };
     ($cursor: expr, $mt: expr, $c: expr) => {
         isv!(
             vec![InternalAction::SetInsertStyle(InsertStyle::Insert)],
-            vec![
-                ExternalAction::Something(
-                    SelectionAction::Split(SelectionSplitStyle::Lines, TargetShapeFilter::BLOCK)
-                        .into()
-                ),
-                ExternalAction::Something(SelectionAction::CursorSet($cursor).into()),
-                ExternalAction::Something(CursorAction::Split(Count::MinusOne).into()),
-                ExternalAction::Something(
-                    EditorAction::Edit(EditAction::Motion.into(), EditTarget::Motion($mt, $c))
-                        .into()
-                ),
-            ],
+            block_insert!($cursor, [], [EditorAction::Edit(
+                EditAction::Motion.into(),
+                EditTarget::Motion($mt, $c)
+            )]),
             VimMode::Insert
         )
     };
@@ -1113,17 +1139,7 @@ macro_rules! change_visual {
     ($cursor: expr, $et: expr) => {
         isv!(
             vec![InternalAction::SetInsertStyle(InsertStyle::Insert)],
-            vec![
-                ExternalAction::Something(
-                    SelectionAction::Split(SelectionSplitStyle::Lines, TargetShapeFilter::BLOCK)
-                        .into()
-                ),
-                ExternalAction::Something(SelectionAction::CursorSet($cursor).into()),
-                ExternalAction::Something(
-                    EditorAction::Edit(EditAction::Delete.into(), $et).into()
-                ),
-                ExternalAction::Something(CursorAction::Split(Count::MinusOne).into()),
-            ],
+            block_insert!($cursor, [EditorAction::Edit(EditAction::Delete.into(), $et)], []),
             VimMode::Insert
         )
     };
@@ -1158,12 +1174,43 @@ macro_rules! visual {
     };
 }
 
+macro_rules! undojoin {
+    ($mt: expr) => {
+        undojoin!($mt, Count::Contextual)
+    };
+    ($mt: expr, $c: literal) => {
+        undojoin!($mt, Count::Exact($c))
+    };
+    ($mt: expr, $c: expr) => {
+        is!(
+            InternalAction::SetUndoJoin,
+            EditorAction::Edit(Specifier::Exact(EditAction::Motion), EditTarget::Motion($mt, $c))
+        )
+    };
+}
+
+macro_rules! reselect {
+    () => {
+        is!(
+            InternalAction::Reselect,
+            EditorAction::Selection(SelectionAction::Restore),
+            VimMode::Visual
+        )
+    };
+}
+
 macro_rules! select {
     ($shape: expr) => {
         start_selection!($shape, VimMode::Select)
     };
 }
 
+macro_rules! fold {
+    ($act: expr) => {
+        act!(EditorAction::Fold($act))
+    };
+}
+
 macro_rules! tab {
     ($act: expr) => {
         act!(Action::Tab($act), VimMode::Normal)
@@ -1502,6 +1549,8 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( NXMAP, "zG", unmapped!() ),
         ( NXMAP, "zh", scroll2d!(MoveDir2D::Left, ScrollSize::Cell) ),
         ( NXMAP, "zH", scroll2d!(MoveDir2D::Left, ScrollSize::HalfPage) ),
+        ( NXMAP, "zj", edit_end!(MoveType::Fold(MoveDir1D::Next)) ),
+        ( NXMAP, "zk", edit_end!(MoveType::Fold(MoveDir1D::Previous)) ),
         ( NXMAP, "zl", scroll2d!(MoveDir2D::Right, ScrollSize::Cell) ),
         ( NXMAP, "zL", scroll2d!(MoveDir2D::Right, ScrollSize::HalfPage) ),
         ( NXMAP, "zp", unmapped!() ),
@@ -1534,8 +1583,8 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( VOMAP, "iW", edit_range_end!(RangeType::Word(WordStyle::Big)) ),
         ( VOMAP, "as", edit_range_end!(RangeType::Sentence) ),
         ( VOMAP, "is", edit_range_end!(RangeType::Sentence) ),
-        ( VOMAP, "ap", edit_range_end!(RangeType::Paragraph) ),
-        ( VOMAP, "ip", edit_range_end!(RangeType::Paragraph) ),
+        ( VOMAP, "ap", edit_range_end!(RangeType::Paragraph, true) ),
+        ( VOMAP, "ip", edit_range_end!(RangeType::Paragraph, false) ),
         ( VOMAP, "a]", edit_range_end!(RangeType::Bracketed('[', ']'), true) ),
         ( VOMAP, "a[", edit_range_end!(RangeType::Bracketed('[', ']'), true) ),
         ( VOMAP, "i]", edit_range_end!(RangeType::Bracketed('[', ']'), false) ),
@@ -1577,7 +1626,7 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( NMAP, "dd", edit_lines!(EditAction::Delete) ),
         ( NMAP, "D", edit!(EditAction::Delete, MoveType::LinePos(MovePosition::End), Count::MinusOne) ),
         ( NMAP, "ga", unmapped!() ),
-        ( NMAP, "gi", unmapped!() ),
+        ( NMAP, "gi", insert_mark!(InsertStyle::Insert, Mark::LastInserted) ),
         ( NMAP, "gI", insert!(InsertStyle::Insert, MoveType::LinePos(MovePosition::Beginning), 0) ),
         ( NMAP, "gJ", edit_lines!(EditAction::Join(JoinStyle::NoChange)) ),
         ( NMAP, "gn", selection_resize_search!(SelectionResizeStyle::Object, MoveDir1D::Next) ),
@@ -1586,14 +1635,14 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( NMAP, "gqgq", edit_lines!(EditAction::Format) ),
         ( NMAP, "gqq", edit_lines!(EditAction::Format) ),
         ( NMAP, "gr", charreplace!(true) ),
-        ( NMAP, "gR", unmapped!() ),
+        ( NMAP, "gR", insert!(InsertStyle::VirtualReplace) ),
         ( NMAP, "gu", edit_motion!(EditAction::ChangeCase(Case::Lower)) ),
         ( NMAP, "gugu", edit_lines!(EditAction::ChangeCase(Case::Lower)) ),
         ( NMAP, "guu", edit_lines!(EditAction::ChangeCase(Case::Lower)) ),
         ( NMAP, "gU", edit_motion!(EditAction::ChangeCase(Case::Upper)) ),
         ( NMAP, "gUgU", edit_lines!(EditAction::ChangeCase(Case::Upper)) ),
         ( NMAP, "gUU", edit_lines!(EditAction::ChangeCase(Case::Upper)) ),
-        ( NMAP, "gv", unmapped!() ),
+        ( NMAP, "gv", reselect!() ),
         ( NMAP, "gw", edit_motion!(EditAction::Format) ),
         ( NMAP, "gwgw", edit_lines!(EditAction::Format) ),
         ( NMAP, "gww", edit_lines!(EditAction::Format) ),
@@ -1618,12 +1667,25 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( NMAP, "s", change!(MoveType::Column(MoveDir1D::Next, false)) ),
         ( NMAP, "S", change_range!(RangeType::Line) ),
         ( NMAP, "u", history!(HistoryAction::Undo(Count::Contextual)) ),
-        ( NMAP, "U", unmapped!() ),
+        ( NMAP, "U", history!(HistoryAction::LineUndo) ),
         ( NMAP, "x", edit!(EditAction::Delete, MoveType::Column(MoveDir1D::Next, false)) ),
         ( NMAP, "X", edit!(EditAction::Delete, MoveType::Column(MoveDir1D::Previous, false)) ),
         ( NMAP, "y", edit_motion!(EditAction::Yank) ),
         ( NMAP, "yy", edit_lines!(EditAction::Yank) ),
         ( NMAP, "Y", edit_lines!(EditAction::Yank) ),
+        ( NMAP, "za", fold!(FoldAction::Toggle(false)) ),
+        ( NMAP, "zA", fold!(FoldAction::Toggle(true)) ),
+        ( NMAP, "zc", fold!(FoldAction::Close(false)) ),
+        ( NMAP, "zC", fold!(FoldAction::Close(true)) ),
+        ( NMAP, "zd", fold!(FoldAction::Delete(false)) ),
+        ( NMAP, "zD", fold!(FoldAction::Delete(true)) ),
+        ( NMAP, "zE", fold!(FoldAction::DeleteAll) ),
+        ( NMAP, "zf", edit_motion!(EditAction::Fold) ),
+        ( NMAP, "zF", edit_lines!(EditAction::Fold) ),
+        ( NMAP, "zM", fold!(FoldAction::CloseAll) ),
+        ( NMAP, "zo", fold!(FoldAction::Open(false)) ),
+        ( NMAP, "zO", fold!(FoldAction::Open(true)) ),
+        ( NMAP, "zR", fold!(FoldAction::OpenAll) ),
         ( NMAP, "ZZ", window_close_one!(WindowTarget::Single, FocusChange::Current, CloseFlags::WQ) ),
         ( NMAP, "ZQ", window_close_one!(WindowTarget::Single, FocusChange::Current, CloseFlags::FQ) ),
         ( NMAP, "=", edit_motion!(EditAction::Indent(IndentChange::Auto)) ),
@@ -1677,6 +1739,7 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( XMAP, "gr", charreplace!(true, EditTarget::Selection) ),
         ( XMAP, "gu", edit_selection!(EditAction::ChangeCase(Case::Lower)) ),
         ( XMAP, "gU", edit_selection!(EditAction::ChangeCase(Case::Upper)) ),
+        ( XMAP, "gv", reselect!() ),
         ( XMAP, "gw", edit_selection!(EditAction::Format) ),
         ( XMAP, "g~", edit_selection!(EditAction::ChangeCase(Case::Toggle)) ),
         ( XMAP, "g<C-A>", edit_selection!(EditAction::ChangeNumber(NumberChange::Increase(Count::Contextual), true)) ),
@@ -1699,6 +1762,7 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( XMAP, "X", delete_selection_nochar!(SelectionCursorChange::Beginning, EditTarget::Selection) ),
         ( XMAP, "y", edit_selection!(EditAction::Yank) ),
         ( XMAP, "Y", edit_selection_nochar!(EditAction::Yank) ),
+        ( XMAP, "zf", edit_selection!(EditAction::Fold) ),
         ( XMAP, "~", edit_selection!(EditAction::ChangeCase(Case::Toggle)) ),
         ( XMAP, "=", edit_selection!(EditAction::Indent(IndentChange::Auto)) ),
         ( XMAP, "<", edit_selection!(EditAction::Indent(IndentChange::Decrease(Count::Contextual))) ),
@@ -1744,8 +1808,13 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( IMAP, "<C-E>", chartype!(Char::CopyLine(MoveDir1D::Next)) ),
         ( IMAP, "<C-G>j", unmapped!() ),
         ( IMAP, "<C-G>k", unmapped!() ),
-        ( IMAP, "<C-G>u", unmapped!() ),
-        ( IMAP, "<C-G>U", unmapped!() ),
+        ( IMAP, "<C-G>u", editor!(EditorAction::History(HistoryAction::Checkpoint)) ),
+        ( IMAP, "<C-G>U<Left>", undojoin!(MoveType::Column(MoveDir1D::Previous, false)) ),
+        ( IMAP, "<C-G>U<Right>", undojoin!(MoveType::Column(MoveDir1D::Next, false)) ),
+        ( IMAP, "<C-G>U<Home>", undojoin!(MoveType::LinePos(MovePosition::Beginning), 0) ),
+        ( IMAP, "<C-G>U<End>", undojoin!(MoveType::LinePos(MovePosition::End), 0) ),
+        ( IMAP, "<C-G>U<S-Left>", undojoin!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous)) ),
+        ( IMAP, "<C-G>U<S-Right>", undojoin!(MoveType::WordBegin(WordStyle::Little, MoveDir1D::Next)) ),
         ( IMAP, "<C-G><C-J>", unmapped!() ),
         ( IMAP, "<C-G><C-K>", unmapped!() ),
         ( IMAP, "<C-G><Down>", unmapped!() ),
@@ -1754,8 +1823,15 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( IMAP, "<C-O>", fallthrough!(VimMode::Normal) ),
         ( IMAP, "<C-P>", complete!(CompletionType::Auto, CompletionSelection::List(MoveDir1D::Previous), CompletionDisplay::List) ),
         ( IMAP, "<C-R><C-P>{register}", unmapped!() ),
-        ( IMAP, "<C-T>", edit_lines!(EditAction::Indent(IndentChange::Increase(Count::Exact(1)))) ),
+        ( IMAP, "<C-T>", edit_target!(EditAction::Indent(IndentChange::Increase(Count::Exact(1))), RangeType::Line.into()) ),
+        ( IMAP, "<C-X>", iact!(InternalAction::SetCompletionMode) ),
         ( IMAP, "<C-X><C-E>", scroll2d!(MoveDir2D::Down, ScrollSize::Cell) ),
+        ( IMAP, "<C-X><C-F>", complete!(CompletionType::File, CompletionSelection::List(MoveDir1D::Next), CompletionDisplay::List) ),
+        ( IMAP, "<C-X><C-K>", complete!(CompletionType::Dictionary, CompletionSelection::List(MoveDir1D::Next), CompletionDisplay::List) ),
+        ( IMAP, "<C-X><C-L>", complete!(CompletionType::Line(CompletionScope::Nearest), CompletionSelection::List(MoveDir1D::Previous), CompletionDisplay::List) ),
+        ( IMAP, "<C-X><C-N>", complete!(CompletionType::Word(CompletionScope::Buffer), CompletionSelection::List(MoveDir1D::Next), CompletionDisplay::List) ),
+        ( IMAP, "<C-X><C-O>", complete!(CompletionType::Application, CompletionSelection::List(MoveDir1D::Next), CompletionDisplay::List) ),
+        ( IMAP, "<C-X><C-P>", complete!(CompletionType::Word(CompletionScope::Buffer), CompletionSelection::List(MoveDir1D::Previous), CompletionDisplay::List) ),
         ( IMAP, "<C-X><C-Y>", scroll2d!(MoveDir2D::Up, ScrollSize::Cell) ),
         ( IMAP, "<C-Y>", chartype!(Char::CopyLine(MoveDir1D::Previous)) ),
         ( IMAP, "<Home>", edit!(EditAction::Motion, MoveType::LinePos(MovePosition::Beginning), 0) ),
@@ -1778,15 +1854,19 @@ fn default_keys<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, InputSt
         ( IMAP, "<PageUp>", scroll2d!(MoveDir2D::Up, ScrollSize::Page) ),
 
         // Command mode
-        ( CMAP, "<C-A>", unmapped!() ),
+        ( CMAP, "<C-A>", complete!(CompletionType::Auto, CompletionSelection::All, CompletionDisplay::None) ),
         ( CMAP, "<C-B>", edit!(EditAction::Motion, MoveType::LinePos(MovePosition::Beginning), 0) ),
         ( CMAP, "<C-C>", command_unfocus!() ),
         ( CMAP, "<C-D>", complete!(CompletionType::Auto, CompletionSelection::None, CompletionDisplay::Bar) ),
         ( CMAP, "<C-E>", edit!(EditAction::Motion, MoveType::LinePos(MovePosition::End), 0) ),
-        ( CMAP, "<C-G>", unmapped!() ),
-        ( CMAP, "<C-L>", unmapped!() ),
-        ( CMAP, "<C-N>", unmapped!() ),
-        ( CMAP, "<C-P>", unmapped!() ),
+        ( CMAP, "<C-G>", act!(Action::CommandBar(CommandBarAction::IncSearch(MoveDir1D::Next))) ),
+        ( CMAP, "<C-L>", complete!(CompletionType::Auto, CompletionSelection::Prefix, CompletionDisplay::None) ),
+        ( CMAP, "<C-N>", prompt!(PromptAction::Recall(MoveDir1D::Next, Count::Contextual, false)) ),
+        ( CMAP, "<C-P>", prompt!(PromptAction::Recall(MoveDir1D::Previous, Count::Contextual, false)) ),
+        ( CMAP, "<C-R><C-A>", act!(Action::CommandBar(CommandBarAction::InsertWord(WordStyle::Big))) ),
+        ( CMAP, "<C-R><C-L>", act!(Action::CommandBar(CommandBarAction::InsertLine)) ),
+        ( CMAP, "<C-R><C-W>", act!(Action::CommandBar(CommandBarAction::InsertWord(WordStyle::Little))) ),
+        ( CMAP, "<C-T>", act!(Action::CommandBar(CommandBarAction::IncSearch(MoveDir1D::Previous))) ),
         ( CMAP, "<C-\\><C-N>", command_unfocus!() ),
         ( CMAP, "<Home>", edit_buffer!(EditAction::Motion, MoveTerminus::Beginning, VimMode::Command) ),
         ( CMAP, "<End>", edit_buffer!(EditAction::Motion, MoveTerminus::End, VimMode::Command) ),
@@ -1889,7 +1969,7 @@ fn default_ctrlcd<I: ApplicationInfo>() -> Vec<(MappedModes, &'static str, Input
         ( NMAP, "<C-D>", scroll2d!(MoveDir2D::Down, ScrollSize::HalfPage) ),
 
         ( IMAP, "<C-C>", normal!() ),
-        ( IMAP, "<C-D>", edit_lines!(EditAction::Indent(IndentChange::Decrease(Count::Exact(1)))) ),
+        ( IMAP, "<C-D>", edit_target!(EditAction::Indent(IndentChange::Decrease(Count::Exact(1))), RangeType::Line.into()) ),
     ].to_vec()
 }
 
@@ -2078,6 +2158,7 @@ impl<I: ApplicationInfo> Default for VimMachine<TerminalKey, I> {
 mod tests {
     use super::*;
     use crate::editing::base::{CursorCloseTarget, Mark, Register};
+    use crate::editing::context::EditContext;
     use crate::input::bindings::BindingMachine;
     use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
@@ -2102,7 +2183,9 @@ mod tests {
             assert_pop1!($mm, Action::NoOp, $ctx);
 
             crate::input::InputContext::take(&mut $ctx);
+            $ctx.persist.selected = true;
             $ctx.persist.shape = None;
+            $ctx.persist.selecting = false;
             $ctx.persist.insert = None;
 
             assert_pop1!($mm, CURSOR_CLOSE, $ctx);
@@ -2316,6 +2399,20 @@ mod tests {
         vm.input_key(ctl!('c'));
         assert_insert_exit!(vm, ctx);
 
+        // Normal -> Insert mode using "gi".
+        let target = EditTarget::CharJump(Specifier::Exact(Mark::LastInserted));
+        let mov = Action::from(EditorAction::Edit(op.clone().into(), target));
+        ctx.persist.insert = Some(InsertStyle::Insert);
+        vm.input_key(key!('g'));
+        vm.input_key(key!('i'));
+        assert_pop1!(vm, mov, ctx);
+        assert_pop2!(vm, CURSOR_SPLIT, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+
+        // Insert -> Normal mode using ^C.
+        vm.input_key(ctl!('c'));
+        assert_insert_exit!(vm, ctx);
+
         // Normal -> Insert mode using "A".
         let mov = mvop!(op, MoveType::LinePos(MovePosition::End), 0);
         ctx.persist.insert = Some(InsertStyle::Insert);
@@ -2364,6 +2461,18 @@ mod tests {
         vm.input_key(ctl!('c'));
         assert_insert_exit!(vm, ctx);
 
+        // Normal -> Virtual Replace mode using "gR".
+        ctx.persist.insert = Some(InsertStyle::VirtualReplace);
+        vm.input_key(key!('g'));
+        vm.input_key(key!('R'));
+        assert_pop2!(vm, CURSOR_SPLIT, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+        assert_eq!(vm.context().persist.insert, Some(InsertStyle::VirtualReplace));
+
+        // Virtual Replace -> Normal mode using ^C.
+        vm.input_key(ctl!('c'));
+        assert_insert_exit!(vm, ctx);
+
         // Normal -> Visual mode (charwise) using "v".
         ctx.persist.shape = Some(TargetShape::CharWise);
         vm.input_key(key!('v'));
@@ -2574,6 +2683,67 @@ mod tests {
         assert_eq!(vm.mode(), VimMode::Insert);
     }
 
+    #[test]
+    fn test_command_mode() {
+        let mut vm: VimMachine<TerminalKey> = VimMachine::default();
+        let mut ctx = VimContext::default();
+
+        // Move to Command mode using ":".
+        ctx.persist.postcmd.2 = Some(Box::new(ctx.action.clone()));
+        vm.input_key(key!(':'));
+        assert_pop2!(vm, CMDBAR, ctx);
+        assert_eq!(vm.mode(), VimMode::Command);
+
+        ctx.persist.insert = Some(InsertStyle::Insert);
+
+        // <C-N> and <C-P> move through history without filtering by prefix.
+        let act = PromptAction::Recall(MoveDir1D::Next, Count::Contextual, false);
+        vm.input_key(ctl!('n'));
+        assert_pop2!(vm, Action::from(act), ctx);
+
+        let act = PromptAction::Recall(MoveDir1D::Previous, Count::Contextual, false);
+        vm.input_key(ctl!('p'));
+        assert_pop2!(vm, Action::from(act), ctx);
+
+        // <C-L> completes the longest common prefix.
+        let sel = CompletionSelection::Prefix;
+        let act = EditorAction::Complete(CompletionType::Auto, sel, CompletionDisplay::None);
+        vm.input_key(ctl!('l'));
+        assert_pop2!(vm, Action::from(act), ctx);
+
+        // <C-A> inserts all completions.
+        let sel = CompletionSelection::All;
+        let act = EditorAction::Complete(CompletionType::Auto, sel, CompletionDisplay::None);
+        vm.input_key(ctl!('a'));
+        assert_pop2!(vm, Action::from(act), ctx);
+
+        // <C-G> and <C-T> move between matches of the search being typed.
+        vm.input_key(ctl!('g'));
+        assert_pop2!(vm, Action::CommandBar(CommandBarAction::IncSearch(MoveDir1D::Next)), ctx);
+
+        vm.input_key(ctl!('t'));
+        let act = CommandBarAction::IncSearch(MoveDir1D::Previous);
+        assert_pop2!(vm, Action::CommandBar(act), ctx);
+
+        // <C-R><C-W>, <C-R><C-A> and <C-R><C-L> insert text from under the window's cursor.
+        ctx.action.cursor = Some('"');
+
+        let act = CommandBarAction::InsertWord(WordStyle::Little);
+        vm.input_key(ctl!('r'));
+        vm.input_key(ctl!('w'));
+        assert_pop2!(vm, Action::CommandBar(act), ctx);
+
+        let act = CommandBarAction::InsertWord(WordStyle::Big);
+        vm.input_key(ctl!('r'));
+        vm.input_key(ctl!('a'));
+        assert_pop2!(vm, Action::CommandBar(act), ctx);
+
+        vm.input_key(ctl!('r'));
+        vm.input_key(ctl!('l'));
+        assert_pop2!(vm, Action::CommandBar(CommandBarAction::InsertLine), ctx);
+        assert_eq!(vm.mode(), VimMode::Command);
+    }
+
     #[test]
     fn test_transitions_visual() {
         let mut vm: VimMachine<TerminalKey> = VimMachine::default();
@@ -2636,6 +2806,81 @@ mod tests {
         assert_visual_exit!(vm, ctx);
     }
 
+    #[test]
+    fn test_reselect() {
+        let mut vm: VimMachine<TerminalKey> = VimMachine::default();
+        let mut ctx = VimContext::default();
+        let restore = Action::from(EditorAction::Selection(SelectionAction::Restore));
+
+        // Using "gv" before anything has been selected stays in Normal mode.
+        vm.input_key(key!('g'));
+        vm.input_key(key!('v'));
+        assert_pop1!(vm, restore.clone(), ctx);
+        assert_pop2!(vm, CHECKPOINT, ctx);
+        assert_eq!(vm.mode(), VimMode::Normal);
+
+        // Select some lines with "V", and then leave Visual mode.
+        ctx.persist.shape = Some(TargetShape::LineWise);
+        vm.input_key(key!('V'));
+        assert_pop2!(vm, CURRENT_POS, ctx);
+        assert_eq!(vm.mode(), VimMode::Visual);
+
+        vm.input_key(key!(KeyCode::Esc));
+        assert_visual_exit!(vm, ctx);
+        assert!(vm.context().persist.selected);
+
+        // "gv" goes back into Visual mode, and shows the shape the buffer restored.
+        ctx.persist.selecting = true;
+        vm.input_key(key!('g'));
+        vm.input_key(key!('v'));
+        assert_pop2!(vm, restore.clone(), ctx);
+        assert_eq!(vm.mode(), VimMode::Visual);
+
+        vm.context().set_restored_shape(TargetShape::LineWise);
+        assert_eq!(vm.show_mode().unwrap(), "-- VISUAL LINE --");
+
+        // Typing "V" again leaves Visual mode.
+        ctx.persist.shape = Some(TargetShape::LineWise);
+        ctx.persist.selecting = false;
+        vm.input_key(key!('V'));
+        assert_visual_exit!(vm, ctx);
+
+        // Restoring a blockwise selection means that "v" switches to a charwise one.
+        ctx.persist.selecting = true;
+        vm.input_key(key!('g'));
+        vm.input_key(key!('v'));
+        assert_pop2!(vm, restore.clone(), ctx);
+        assert_eq!(vm.mode(), VimMode::Visual);
+
+        vm.context().set_restored_shape(TargetShape::BlockWise);
+        assert_eq!(vm.show_mode().unwrap(), "-- VISUAL BLOCK --");
+
+        ctx.persist.shape = Some(TargetShape::CharWise);
+        ctx.persist.selecting = false;
+        vm.input_key(key!('v'));
+        assert_pop2!(vm, CURRENT_POS, ctx);
+        assert_eq!(vm.mode(), VimMode::Visual);
+        assert_eq!(vm.show_mode().unwrap(), "-- VISUAL --");
+
+        // Using "gv" within Visual mode swaps with the previous selection, whatever its shape.
+        ctx.persist.shape = None;
+        ctx.persist.selecting = true;
+        vm.input_key(key!('g'));
+        vm.input_key(key!('v'));
+        assert_pop2!(vm, restore, ctx);
+        assert_eq!(vm.mode(), VimMode::Visual);
+
+        vm.context().set_restored_shape(TargetShape::LineWise);
+        assert_eq!(vm.show_mode().unwrap(), "-- VISUAL LINE --");
+
+        // Leaving Visual mode stops following it.
+        ctx.persist.shape = Some(TargetShape::LineWise);
+        ctx.persist.selecting = false;
+        vm.input_key(key!(KeyCode::Esc));
+        assert_visual_exit!(vm, ctx);
+        assert!(!vm.context().persist.selecting);
+    }
+
     #[test]
     fn test_transitions_visual_select() {
         let mut vm: VimMachine<TerminalKey> = VimMachine::default();
@@ -2741,6 +2986,7 @@ mod tests {
         assert_pop1!(vm, mov, ctx);
         assert_pop1!(vm, typechar!('H'), ctx);
 
+        ctx.persist.selected = true;
         ctx.persist.shape = None;
         assert_pop2!(vm, CURRENT_POS, ctx);
         assert_eq!(vm.mode(), VimMode::Insert);
@@ -3015,6 +3261,13 @@ mod tests {
         assert_pop1!(vm, mov, ctx);
         assert_normal!(vm, ctx);
 
+        // "U" undoes the latest changes on a line.
+        let act = Action::from(EditorAction::History(HistoryAction::LineUndo));
+        ctx.action.operation = EditAction::Motion;
+        vm.input_key(key!('U'));
+        assert_pop1!(vm, act, ctx);
+        assert_normal!(vm, ctx);
+
         ctx.action.operation = EditAction::Indent(IndentChange::Auto);
         vm.input_key(key!('='));
         vm.input_key(key!('w'));
@@ -3443,6 +3696,7 @@ mod tests {
         assert_pop1!(vm, mov, ctx);
 
         // We move back to Normal mode after deletion.
+        ctx.persist.selected = true;
         ctx.persist.shape = None;
         assert_pop1!(vm, CURSOR_CLOSE, ctx);
         assert_pop1!(vm, CURRENT_POS, ctx);
@@ -3517,6 +3771,7 @@ mod tests {
         assert_pop1!(vm, mov, ctx);
 
         // Move back into Normal mode after deletion.
+        ctx.persist.selected = true;
         ctx.persist.shape = None;
         assert_pop1!(vm, CURSOR_CLOSE, ctx);
         assert_pop1!(vm, CURRENT_POS, ctx);
@@ -3618,6 +3873,7 @@ mod tests {
         assert_pop1!(vm, mov, ctx);
 
         // Move back into Normal mode after deletion.
+        ctx.persist.selected = true;
         ctx.persist.shape = None;
         assert_pop1!(vm, CURSOR_CLOSE, ctx);
         assert_pop1!(vm, CURRENT_POS, ctx);
@@ -3660,6 +3916,7 @@ mod tests {
         assert_pop1!(vm, CURSOR_SPLIT, ctx);
 
         // Moves into Insert mode after "I".
+        ctx.persist.selected = true;
         ctx.persist.shape = None;
         assert_pop2!(vm, CURRENT_POS, ctx);
         assert_eq!(vm.mode(), VimMode::Insert);
@@ -3920,6 +4177,19 @@ mod tests {
         ctx.action.cursor = None;
         ctx.ch.oct = None;
 
+        // Shift the current line with ^T and ^D without leaving Insert mode.
+        let act =
+            rangeop!(EditAction::Indent(IndentChange::Increase(Count::Exact(1))), RangeType::Line);
+        vm.input_key(ctl!('t'));
+        assert_pop1!(vm, act, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+
+        let act =
+            rangeop!(EditAction::Indent(IndentChange::Decrease(Count::Exact(1))), RangeType::Line);
+        vm.input_key(ctl!('d'));
+        assert_pop1!(vm, act, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+
         // Enter Replace mode by pressing <Ins>.
         ctx.persist.insert = Some(InsertStyle::Replace);
         vm.input_key(key!(KeyCode::Insert));
@@ -3963,6 +4233,55 @@ mod tests {
         assert_insert_exit!(vm, ctx);
     }
 
+    #[test]
+    fn test_insert_completion() {
+        let mut vm: VimMachine<TerminalKey> = VimMachine::default();
+        let mut ctx = VimContext::default();
+
+        let next = CompletionSelection::List(MoveDir1D::Next);
+        let prev = CompletionSelection::List(MoveDir1D::Previous);
+        let list = CompletionDisplay::List;
+
+        ctx.persist.insert = Some(InsertStyle::Insert);
+        vm.input_key(key!('i'));
+        assert_pop2!(vm, CURSOR_SPLIT, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+        assert_eq!(vm.show_mode().unwrap(), "-- INSERT --");
+
+        let keys = [
+            (ctl!('l'), CompletionType::Line(CompletionScope::Nearest), prev.clone()),
+            (ctl!('f'), CompletionType::File, next.clone()),
+            (ctl!('n'), CompletionType::Word(CompletionScope::Buffer), next.clone()),
+            (ctl!('p'), CompletionType::Word(CompletionScope::Buffer), prev.clone()),
+            (ctl!('k'), CompletionType::Dictionary, next.clone()),
+            (ctl!('o'), CompletionType::Application, next.clone()),
+        ];
+
+        for (key, comptype, selection) in keys {
+            // Pressing <C-X> shows the submode.
+            vm.input_key(ctl!('x'));
+            assert_eq!(vm.pop(), None);
+            assert_eq!(vm.show_mode().unwrap(), "-- ^X mode (^]^D^E^F^I^K^L^N^O^Ps^U^V^Y)");
+
+            // Completing returns to Insert mode.
+            let act = EditorAction::Complete(comptype, selection, list.clone());
+            ctx.action.xmode = true;
+            vm.input_key(key);
+            assert_pop2!(vm, Action::from(act), ctx);
+            ctx.action.xmode = false;
+            assert_eq!(vm.mode(), VimMode::Insert);
+            assert_eq!(vm.show_mode().unwrap(), "-- INSERT --");
+        }
+
+        // Scrolling also leaves the submode.
+        let scroll = ScrollStyle::Direction2D(MoveDir2D::Down, ScrollSize::Cell, Count::Contextual);
+        ctx.action.xmode = true;
+        vm.input_key(ctl!('x'));
+        vm.input_key(ctl!('e'));
+        assert_pop2!(vm, Action::Scroll(scroll), ctx);
+        assert_eq!(vm.show_mode().unwrap(), "-- INSERT --");
+    }
+
     #[test]
     fn test_custom_operators() {
         let mut vm: VimMachine<TerminalKey> = VimMachine::default();
@@ -4199,6 +4518,65 @@ mod tests {
         assert_normal!(vm, ctx);
     }
 
+    #[test]
+    fn test_fold() {
+        let mut vm: VimMachine<TerminalKey> = VimMachine::default();
+        let mut ctx = VimContext::default();
+
+        // "zfj" creates a fold over the current and next line.
+        let mov = mv!(MoveType::Line(MoveDir1D::Next));
+        ctx.action.operation = EditAction::Fold;
+        vm.input_key(key!('z'));
+        vm.input_key(key!('f'));
+        vm.input_key(key!('j'));
+        assert_pop1!(vm, mov, ctx);
+        assert_normal!(vm, ctx);
+
+        // "zF" creates a fold over [count] lines.
+        let act = rangeop!(EditAction::Fold, RangeType::Line);
+        ctx.action.operation = EditAction::Motion;
+        ctx.action.count = Some(3);
+        vm.input_key(key!('3'));
+        vm.input_key(key!('z'));
+        vm.input_key(key!('F'));
+        assert_pop1!(vm, act, ctx);
+        assert_normal!(vm, ctx);
+
+        // "zo" and "zO" open folds.
+        ctx.action.count = None;
+        let act = Action::from(EditorAction::Fold(FoldAction::Open(false)));
+        vm.input_key(key!('z'));
+        vm.input_key(key!('o'));
+        assert_pop1!(vm, act, ctx);
+        assert_normal!(vm, ctx);
+
+        let act = Action::from(EditorAction::Fold(FoldAction::Open(true)));
+        vm.input_key(key!('z'));
+        vm.input_key(key!('O'));
+        assert_pop1!(vm, act, ctx);
+        assert_normal!(vm, ctx);
+
+        // "zM" closes all folds.
+        let act = Action::from(EditorAction::Fold(FoldAction::CloseAll));
+        vm.input_key(key!('z'));
+        vm.input_key(key!('M'));
+        assert_pop1!(vm, act, ctx);
+        assert_normal!(vm, ctx);
+
+        // "zj" and "zk" move between folds.
+        let mov = mv!(MoveType::Fold(MoveDir1D::Next));
+        vm.input_key(key!('z'));
+        vm.input_key(key!('j'));
+        assert_pop1!(vm, mov, ctx);
+        assert_normal!(vm, ctx);
+
+        let mov = mv!(MoveType::Fold(MoveDir1D::Previous));
+        vm.input_key(key!('z'));
+        vm.input_key(key!('k'));
+        assert_pop1!(vm, mov, ctx);
+        assert_normal!(vm, ctx);
+    }
+
     #[test]
     fn test_literal() {
         let mut vm: VimMachine<TerminalKey> = VimMachine::default();
@@ -4605,4 +4983,152 @@ mod tests {
         // Read the Checkpoint from pressing "." earlier.
         assert_normal!(vm, ctx);
     }
+
+    #[test]
+    fn test_edit_repeat_undo_break() {
+        let mut vm: VimMachine<TerminalKey> = VimMachine::default();
+        let mut ctx = VimContext::default();
+
+        let left = Action::Editor(EditorAction::Edit(
+            Specifier::Exact(EditAction::Motion),
+            EditTarget::Motion(MoveType::Column(MoveDir1D::Previous, false), Count::Contextual),
+        ));
+
+        // Move down a line, so we do SequenceStatus::Break.
+        vm.input_key(key!('j'));
+        assert_pop1!(vm, mv!(MoveType::Line(MoveDir1D::Next)), ctx);
+        assert_normal!(vm, ctx);
+
+        // Move to Insert mode.
+        ctx.persist.insert = Some(InsertStyle::Insert);
+        vm.input_key(key!('i'));
+        assert_pop2!(vm, CURSOR_SPLIT, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+
+        vm.input_key(key!('a'));
+        assert_pop1!(vm, typechar!('a'), ctx);
+
+        // Break up undo with <C-G>u, and stay in Insert mode.
+        vm.input_key(ctl!('g'));
+        vm.input_key(key!('u'));
+        assert_pop2!(vm, CHECKPOINT, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+
+        vm.input_key(key!('b'));
+        assert_pop1!(vm, typechar!('b'), ctx);
+
+        // Move left with <C-G>U, without breaking the sequence.
+        ctx.action.undojoin = true;
+        vm.input_key(ctl!('g'));
+        vm.input_key(key!('U'));
+        vm.input_key(key!(KeyCode::Left));
+        assert_pop2!(vm, left, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+        ctx.action.undojoin = false;
+
+        // The other cursor keys can also follow <C-G>U.
+        let home = Action::Editor(EditorAction::Edit(
+            Specifier::Exact(EditAction::Motion),
+            EditTarget::Motion(MoveType::LinePos(MovePosition::Beginning), Count::Exact(0)),
+        ));
+        let wleft = Action::Editor(EditorAction::Edit(
+            Specifier::Exact(EditAction::Motion),
+            EditTarget::Motion(
+                MoveType::WordBegin(WordStyle::Little, MoveDir1D::Previous),
+                Count::Contextual,
+            ),
+        ));
+
+        ctx.action.undojoin = true;
+        vm.input_key(ctl!('g'));
+        vm.input_key(key!('U'));
+        vm.input_key(key!(KeyCode::Home));
+        assert_pop2!(vm, home, ctx);
+        vm.input_key(ctl!('g'));
+        vm.input_key(key!('U'));
+        vm.input_key(key!(KeyCode::Left, KeyModifiers::SHIFT));
+        assert_pop2!(vm, wleft, ctx);
+        ctx.action.undojoin = false;
+
+        vm.input_key(key!('c'));
+        assert_pop1!(vm, typechar!('c'), ctx);
+
+        // Back to Normal mode.
+        vm.input_key(ctl!('c'));
+        assert_insert_exit!(vm, ctx);
+
+        // Press ".".
+        vm.input_key(key!('.'));
+        assert_pop1!(vm, Action::Repeat(RepeatType::EditSequence), ctx);
+
+        // Repeat the whole insertion, including the movement but not the checkpoint.
+        vm.repeat(RepeatType::EditSequence, Some(ctx.clone()));
+
+        ctx.persist.insert = Some(InsertStyle::Insert);
+        assert_pop1!(vm, CURSOR_SPLIT, ctx);
+        assert_pop1!(vm, typechar!('a'), ctx);
+        assert_pop1!(vm, typechar!('b'), ctx);
+        ctx.action.undojoin = true;
+        assert_pop1!(vm, left, ctx);
+        assert_pop1!(vm, home, ctx);
+        assert_pop1!(vm, wleft, ctx);
+        ctx.action.undojoin = false;
+        assert_pop1!(vm, typechar!('c'), ctx);
+
+        ctx.persist.insert = None;
+        assert_pop1!(vm, CURSOR_CLOSE, ctx);
+
+        // Read the Checkpoint from pressing "." earlier.
+        assert_normal!(vm, ctx);
+    }
+
+    #[test]
+    fn test_edit_repeat_vreplace_count() {
+        let mut vm: VimMachine<TerminalKey> = VimMachine::default();
+        let mut ctx = VimContext::default();
+
+        // Move down a line, so we do SequenceStatus::Break.
+        vm.input_key(key!('j'));
+        assert_pop1!(vm, mv!(MoveType::Line(MoveDir1D::Next)), ctx);
+        assert_normal!(vm, ctx);
+
+        // Type "3gRab<Esc>".
+        ctx.action.count = Some(3);
+        ctx.persist.insert = Some(InsertStyle::VirtualReplace);
+        vm.input_key(key!('3'));
+        vm.input_key(key!('g'));
+        vm.input_key(key!('R'));
+        assert_pop2!(vm, CURSOR_SPLIT, ctx);
+        assert_eq!(vm.mode(), VimMode::Insert);
+
+        ctx.action.count = None;
+        vm.input_key(key!('a'));
+        vm.input_key(key!('b'));
+        assert_pop1!(vm, typechar!('a'), ctx);
+        assert_pop1!(vm, typechar!('b'), ctx);
+
+        vm.input_key(key!(KeyCode::Esc));
+        assert_insert_exit!(vm, ctx);
+
+        // Press ".".
+        vm.input_key(key!('.'));
+        assert_pop1!(vm, Action::Repeat(RepeatType::EditSequence), ctx);
+
+        // Repeating splits the cursor with the same count, and types over the columns again.
+        vm.repeat(RepeatType::EditSequence, None);
+
+        ctx.action.count = Some(3);
+        ctx.persist.insert = Some(InsertStyle::VirtualReplace);
+        assert_pop1!(vm, CURSOR_SPLIT, ctx);
+
+        ctx.action.count = None;
+        assert_pop1!(vm, typechar!('a'), ctx);
+        assert_pop1!(vm, typechar!('b'), ctx);
+
+        ctx.persist.insert = None;
+        assert_pop1!(vm, CURSOR_CLOSE, ctx);
+
+        // Read the Checkpoint from pressing "." earlier.
+        assert_normal!(vm, ctx);
+    }
 }
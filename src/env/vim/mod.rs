@@ -6,6 +6,7 @@
 //! interfaces.
 //!
 use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
 
 use regex::Regex;
 
@@ -31,6 +32,8 @@ use crate::editing::{
         MoveType,
         Register,
         RepeatType,
+        SearchCase,
+        SearchQuery,
         Specifier,
         TargetShape,
     },
@@ -41,6 +44,7 @@ use super::{CharacterContext, CommonKeyClass};
 
 pub mod command;
 pub mod keybindings;
+pub mod pattern;
 
 /// Vim's input modes
 #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
@@ -76,6 +80,19 @@ pub enum VimMode {
 
 impl<I: ApplicationInfo> Mode<Action<I>, VimContext<I>> for VimMode {
     fn enter(&self, prev: Self, ctx: &mut VimContext<I>) -> Vec<Action<I>> {
+        if matches!(prev, VimMode::Visual | VimMode::Select) &&
+            !matches!(self, VimMode::Visual | VimMode::Select) &&
+            (ctx.persist.shape.is_some() || ctx.persist.selecting)
+        {
+            // Remember that there's now a selection for "gv" to go back to.
+            ctx.persist.selected = true;
+        }
+
+        if matches!(self, VimMode::Normal | VimMode::Insert | VimMode::Command) {
+            // We've left the restored selection.
+            ctx.persist.selecting = false;
+        }
+
         match self {
             VimMode::Normal => {
                 ctx.persist.shape = None;
@@ -169,7 +186,7 @@ impl<I: ApplicationInfo> Mode<Action<I>, VimContext<I>> for VimMode {
 
         let msg = match self {
             VimMode::Visual => {
-                match ctx.persist.shape {
+                match ctx.persist.get_shape() {
                     None | Some(TargetShape::CharWise) => "-- VISUAL --",
                     Some(TargetShape::LineWise) => "-- VISUAL LINE --",
                     Some(TargetShape::BlockWise) => "-- VISUAL BLOCK --",
@@ -177,17 +194,21 @@ impl<I: ApplicationInfo> Mode<Action<I>, VimContext<I>> for VimMode {
                 .into()
             },
             VimMode::Select => {
-                match ctx.persist.shape {
+                match ctx.persist.get_shape() {
                     None | Some(TargetShape::CharWise) => "-- SELECT --",
                     Some(TargetShape::LineWise) => "-- SELECT LINE --",
                     Some(TargetShape::BlockWise) => "-- SELECT BLOCK --",
                 }
                 .into()
             },
+            VimMode::Insert if ctx.action.xmode => {
+                "-- ^X mode (^]^D^E^F^I^K^L^N^O^Ps^U^V^Y)".into()
+            },
             VimMode::Insert => {
                 match ctx.persist.insert {
                     None | Some(InsertStyle::Insert) => "-- INSERT --",
                     Some(InsertStyle::Replace) => "-- REPLACE --",
+                    Some(InsertStyle::VirtualReplace) => "-- VREPLACE --",
                 }
                 .into()
             },
@@ -227,11 +248,20 @@ impl<I: ApplicationInfo> ModeSequence<RepeatType, Action<I>, VimContext<I>> for
                 }
             },
             VimMode::Visual | VimMode::Select => SequenceStatus::Track,
+            VimMode::Insert if ctx.action.undojoin => SequenceStatus::Track,
             _ => SequenceStatus::Break,
         };
 
+        let edit = match (self, action) {
+            (VimMode::Insert, Action::Editor(EditorAction::History(HistoryAction::Checkpoint))) => {
+                // Breaking up undo while typing still repeats the whole insertion.
+                SequenceStatus::Ignore
+            },
+            _ => action.is_edit_sequence(motion, ctx),
+        };
+
         return vec![
-            (RepeatType::EditSequence, action.is_edit_sequence(motion, ctx)),
+            (RepeatType::EditSequence, edit),
             (RepeatType::LastAction, action.is_last_action(ctx)),
             (RepeatType::LastSelection, action.is_last_selection(ctx)),
         ];
@@ -330,10 +360,47 @@ pub(crate) struct ActionContext {
     // Delayed mode transition.
     pub(crate) postmode: Option<VimMode>,
 
+    // Keep a cursor movement in Insert mode from breaking up the current edit.
+    pub(crate) undojoin: bool,
+
+    // Whether we're in the middle of an Insert mode "<C-X>" completion sequence.
+    pub(crate) xmode: bool,
+
     // Cursor indicator to show on-screen.
     pub(crate) cursor: Option<char>,
 }
 
+/// The shape of the selection that a buffer most recently restored for "gv".
+///
+/// Buffers report this through the copy of the context that accompanies [SelectionAction::Restore],
+/// so it's shared with the context that the keybindings keep.
+///
+/// [SelectionAction::Restore]: crate::editing::action::SelectionAction::Restore
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RestoredShape(Arc<RwLock<Option<TargetShape>>>);
+
+impl RestoredShape {
+    fn get(&self) -> Option<TargetShape> {
+        *self.0.read().unwrap()
+    }
+
+    fn set(&self, shape: Option<TargetShape>) {
+        *self.0.write().unwrap() = shape;
+    }
+
+    fn take(&self) -> Option<TargetShape> {
+        self.0.write().unwrap().take()
+    }
+}
+
+impl PartialEq for RestoredShape {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Eq for RestoredShape {}
+
 /// This is the context preserved across actions, and changes either with the mode, or through
 /// future keybinding sequences.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -343,6 +410,9 @@ pub(crate) struct PersistentContext {
     pub(crate) charsearch_params: (MoveDir1D, bool),
     pub(crate) charsearch: Option<Char>,
     pub(crate) shape: Option<TargetShape>,
+    pub(crate) selecting: bool,
+    pub(crate) selected: bool,
+    pub(crate) restored: RestoredShape,
     pub(crate) insert: Option<InsertStyle>,
     pub(crate) recording: Option<(Register, bool)>,
     pub(crate) postcmd: (VimMode, Option<TargetShape>, Option<Box<ActionContext>>),
@@ -399,6 +469,8 @@ impl<I: ApplicationInfo> InputContext for VimContext<I> {
 
 impl<I: ApplicationInfo> InputKeyContext<TerminalKey, CommonKeyClass> for VimContext<I> {
     fn event(&mut self, ev: &EdgeEvent<TerminalKey, CommonKeyClass>, ke: &TerminalKey) {
+        self.persist.follow_restored();
+
         match ev {
             EdgeEvent::Key(_) | EdgeEvent::Fallthrough => {
                 // Do nothing.
@@ -482,6 +554,29 @@ impl<I: ApplicationInfo> EditContext for VimContext<I> {
         self.persist.regexsearch_dir
     }
 
+    fn compile_search_regex(&self, pattern: &str, case: SearchCase) -> Result<Regex, regex::Error> {
+        pattern::compile(pattern, case)
+    }
+
+    fn get_word_search_pattern(&self, word: &str, boundary: bool, case: SearchCase) -> String {
+        let word = pattern::escape(word);
+        let word = if boundary {
+            format!("\\<{word}\\>")
+        } else {
+            word
+        };
+
+        if case == SearchCase::Smart && word.chars().any(char::is_uppercase) {
+            format!("\\c{word}")
+        } else {
+            word
+        }
+    }
+
+    fn get_search_queries(&self, text: &str, dir: MoveDir1D) -> Vec<SearchQuery> {
+        pattern::parse_queries(text, dir)
+    }
+
     fn get_search_char(&self) -> Option<(MoveDir1D, bool, Char)> {
         if let Some(c) = &self.persist.charsearch {
             let (dir, inc) = self.persist.charsearch_params;
@@ -496,6 +591,14 @@ impl<I: ApplicationInfo> EditContext for VimContext<I> {
         self.persist.shape
     }
 
+    fn is_selecting(&self) -> bool {
+        self.persist.selecting
+    }
+
+    fn set_restored_shape(&self, shape: TargetShape) {
+        self.persist.restored.set(Some(shape));
+    }
+
     fn get_insert_style(&self) -> Option<InsertStyle> {
         self.persist.insert
     }
@@ -537,11 +640,43 @@ impl Default for ActionContext {
 
             postmode: None,
 
+            undojoin: false,
+            xmode: false,
+
             cursor: None,
         }
     }
 }
 
+impl PersistentContext {
+    /// The shape of the current selection, including one that "gv" has restored.
+    pub(crate) fn get_shape(&self) -> Option<TargetShape> {
+        match self.shape {
+            None if self.selecting => self.restored.get(),
+            shape => shape,
+        }
+    }
+
+    /// Start using the shape of the selection that "gv" restored.
+    fn follow_restored(&mut self) {
+        if !self.selecting || self.shape.is_some() {
+            return;
+        }
+
+        if let Some(shape) = self.restored.take() {
+            self.shape = Some(shape);
+            self.selecting = false;
+        }
+    }
+
+    /// Prepare to follow the shape of the next selection that a buffer restores.
+    pub(crate) fn reselect(&mut self) {
+        self.restored.set(None);
+        self.shape = None;
+        self.selecting = true;
+    }
+}
+
 impl Default for PersistentContext {
     fn default() -> Self {
         PersistentContext {
@@ -551,6 +686,9 @@ impl Default for PersistentContext {
             charsearch: None,
             insert: None,
             shape: None,
+            selecting: false,
+            selected: false,
+            restored: RestoredShape::default(),
             recording: None,
             postcmd: Default::default(),
         }
@@ -622,6 +760,8 @@ fn register_to_char((reg, append): &(Register, bool)) -> Option<String> {
         Register::Unnamed => '"',
         Register::UnnamedMacro => '@',
         Register::UnnamedCursorGroup => return None,
+        Register::KillRing => return None,
+        Register::LastRectangle => return None,
         Register::SmallDelete => '-',
         Register::LastCommand => ':',
         Register::LastInserted => '.',
@@ -753,6 +893,14 @@ mod tests {
         // Mode string when replacing text.
         ctx.persist.insert = Some(InsertStyle::Replace);
         assert_eq!(insert.show(&ctx).unwrap(), "-- REPLACE --");
+
+        // Mode string when replacing screen columns.
+        ctx.persist.insert = Some(InsertStyle::VirtualReplace);
+        assert_eq!(insert.show(&ctx).unwrap(), "-- VREPLACE --");
+
+        // Mode string while typing a completion sequence.
+        ctx.action.xmode = true;
+        assert_eq!(insert.show(&ctx).unwrap(), "-- ^X mode (^]^D^E^F^I^K^L^N^O^Ps^U^V^Y)");
     }
 
     #[test]
@@ -0,0 +1,979 @@
+//! # Vim Search Patterns
+//!
+//! ## Overview
+//!
+//! This module translates Vim's regular expression syntax (see `:help pattern`) into the syntax
+//! used by the [regex] crate, so that searches typed by users behave the way they do in Vim.
+//!
+//! All four of Vim's "magic" levels are supported, and can be switched between at any point
+//! within a pattern:
+//!
+//! - `\v`, "very magic", where all of `^$.*[~+=?{()|&<>@%` have a special meaning
+//! - `\m`, "magic", the default, where only `^$.*[~` are special without a backslash
+//! - `\M`, "nomagic", where only `^` and `$` are special without a backslash
+//! - `\V`, "very nomagic", where only a backslash has a special meaning
+//!
+//! Constructs that can't be expressed using the [regex] crate, such as lookaround (`\@=`),
+//! backreferences (`\1`) and match offsets (`\zs`), produce an error instead of silently matching
+//! something else.
+//!
+//! This module also parses the [offsets](SearchOffset) that can follow a pattern typed into the
+//! search bar, like in `/foo/e+1;/bar`.
+//!
+//! ## Example
+//!
+//! ```
+//! use modalkit::editing::base::SearchCase;
+//! use modalkit::env::vim::pattern::compile;
+//!
+//! let re = compile("\\<\\(foo\\|bar\\)\\{2}\\>", SearchCase::Sensitive).unwrap();
+//! assert!(re.is_match("a foobar b"));
+//! assert!(!re.is_match("afoobar b"));
+//!
+//! let re = compile("\\v<(foo|bar){2}>", SearchCase::Sensitive).unwrap();
+//! assert!(re.is_match("a barfoo b"));
+//!
+//! let re = compile("\\cFOO", SearchCase::Sensitive).unwrap();
+//! assert!(re.is_match("foo"));
+//!
+//! let re = compile("Foo", SearchCase::Smart).unwrap();
+//! assert!(!re.is_match("foo"));
+//! ```
+use regex::{Error, Regex};
+
+use crate::editing::base::{MoveDir1D, SearchCase, SearchOffset, SearchQuery};
+
+/// Characters that can have a special meaning, depending on the current magic level.
+const SPECIAL: &str = "^$.*[~+=?{()|&<>@%";
+
+/// Characters that have a special meaning without a backslash when using `\m`.
+const MAGIC: &str = "^$.*[~";
+
+/// Characters that have a special meaning without a backslash when using `\M`.
+const NOMAGIC: &str = "^$";
+
+/// Characters escaped by [escape].
+const ESCAPED: &str = "\\/.*$^~[";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MagicLevel {
+    VeryMagic,
+    Magic,
+    NoMagic,
+    VeryNoMagic,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Token {
+    /// A character to match literally.
+    Literal(char),
+
+    /// A character with a special meaning, regardless of the magic level it was written at.
+    Magic(char),
+
+    /// A backslash followed by a character that doesn't depend on the magic level, like `\s`.
+    Escape(char),
+}
+
+/// What the most recently translated item was, to determine how to handle a following multi.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Last {
+    /// The start of the pattern, a group or an alternative.
+    Branch,
+
+    /// Nothing that a multi can apply to, like an anchor.
+    Nothing,
+
+    /// An atom.
+    Atom,
+
+    /// A multi, which can't be followed by another multi.
+    Multi,
+}
+
+fn unsupported(what: &str) -> Error {
+    Error::Syntax(format!("{what} is not supported"))
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::Syntax(msg.to_string())
+}
+
+/// The contents of a bracketed class for one of Vim's character class escapes, like `\s`.
+fn class(c: char) -> Option<(&'static str, bool)> {
+    let class = match c.to_ascii_lowercase() {
+        's' => " \\t",
+        'd' => "0-9",
+        'w' => "0-9A-Za-z_",
+        'a' => "A-Za-z",
+        'l' => "a-z",
+        'u' => "A-Z",
+        'x' => "0-9A-Fa-f",
+        'o' => "0-7",
+        'h' => "A-Za-z_",
+        _ => return None,
+    };
+
+    Some((class, c.is_ascii_uppercase()))
+}
+
+/// Create a bracketed class, making sure that it only matches a newline when `newline` is true.
+fn bracket(class: &str, negated: bool, newline: bool) -> String {
+    match (negated, newline) {
+        (false, false) => format!("[{class}]"),
+        (false, true) => format!("[{class}\\n]"),
+        (true, false) => format!("[^{class}\\n]"),
+        (true, true) => format!("[^{class}]"),
+    }
+}
+
+fn literal(c: char) -> String {
+    regex::escape(c.encode_utf8(&mut [0; 4]))
+}
+
+struct Translator {
+    chars: Vec<char>,
+    idx: usize,
+    level: MagicLevel,
+    ignore_case: Option<bool>,
+    has_upper: bool,
+    groups: usize,
+    last: Last,
+    out: String,
+}
+
+impl Translator {
+    fn new(pattern: &str) -> Self {
+        Translator {
+            chars: pattern.chars().collect(),
+            idx: 0,
+            level: MagicLevel::Magic,
+            ignore_case: None,
+            has_upper: false,
+            groups: 0,
+            last: Last::Branch,
+            out: String::new(),
+        }
+    }
+
+    fn token_at(&self, idx: usize) -> Result<Option<(Token, usize)>, Error> {
+        let c = match self.chars.get(idx) {
+            Some(c) => *c,
+            None => return Ok(None),
+        };
+
+        if c != '\\' {
+            let special = match self.level {
+                MagicLevel::VeryMagic => SPECIAL.contains(c),
+                MagicLevel::Magic => MAGIC.contains(c),
+                MagicLevel::NoMagic => NOMAGIC.contains(c),
+                MagicLevel::VeryNoMagic => false,
+            };
+
+            let tok = if special {
+                Token::Magic(c)
+            } else {
+                Token::Literal(c)
+            };
+
+            return Ok(Some((tok, idx + 1)));
+        }
+
+        let c = match self.chars.get(idx + 1) {
+            Some(c) => *c,
+            None => return Err(invalid("trailing backslash")),
+        };
+
+        let tok = if SPECIAL.contains(c) {
+            let special = match self.level {
+                MagicLevel::VeryMagic => false,
+                MagicLevel::Magic => !MAGIC.contains(c),
+                MagicLevel::NoMagic => !NOMAGIC.contains(c),
+                MagicLevel::VeryNoMagic => true,
+            };
+
+            if special {
+                Token::Magic(c)
+            } else {
+                Token::Literal(c)
+            }
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            Token::Escape(c)
+        } else {
+            Token::Literal(c)
+        };
+
+        return Ok(Some((tok, idx + 2)));
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, Error> {
+        match self.token_at(self.idx)? {
+            Some((tok, idx)) => {
+                self.idx = idx;
+
+                Ok(Some(tok))
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.get(self.idx).copied();
+
+        if c.is_some() {
+            self.idx += 1;
+        }
+
+        return c;
+    }
+
+    fn atom(&mut self, s: &str) {
+        self.out.push_str(s);
+        self.last = Last::Atom;
+    }
+
+    fn anchor(&mut self, s: &str) {
+        self.out.push_str(s);
+        self.last = Last::Nothing;
+    }
+
+    fn branch(&mut self, s: &str) {
+        self.out.push_str(s);
+        self.last = Last::Branch;
+    }
+
+    /// Whether a `$` at the current position is at the end of a branch.
+    fn at_branch_end(&self) -> Result<bool, Error> {
+        let res = match self.token_at(self.idx)? {
+            None => true,
+            Some((Token::Magic('|' | ')' | '&'), _)) => true,
+            Some((Token::Escape('n'), _)) => true,
+            Some(_) => false,
+        };
+
+        Ok(res)
+    }
+
+    fn multi(&mut self, c: char) -> Result<(), Error> {
+        match self.last {
+            Last::Branch | Last::Nothing => {
+                // Vim treats a multi with nothing before it as a literal character.
+                self.atom(&literal(c));
+
+                return Ok(());
+            },
+            Last::Multi => {
+                return Err(invalid("a multi cannot follow another multi"));
+            },
+            Last::Atom => {},
+        }
+
+        match c {
+            '*' => self.out.push('*'),
+            '+' => self.out.push('+'),
+            '=' | '?' => self.out.push('?'),
+            '{' => self.brace()?,
+            '@' => return Err(unsupported("lookaround (\\@)")),
+            _ => unreachable!(),
+        }
+
+        self.last = Last::Multi;
+
+        return Ok(());
+    }
+
+    /// Translate the contents of a `\{n,m}` multi.
+    fn brace(&mut self) -> Result<(), Error> {
+        let mut body = String::new();
+
+        loop {
+            match self.next_char() {
+                Some('}') => break,
+                Some('\\') if self.chars.get(self.idx) == Some(&'}') => {
+                    self.idx += 1;
+                    break;
+                },
+                Some(c) => body.push(c),
+                None => return Err(invalid("missing } after \\{")),
+            }
+        }
+
+        let (lazy, body) = match body.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, body.as_str()),
+        };
+
+        let count = |s: &str| -> Result<Option<usize>, Error> {
+            let s = s.trim();
+
+            if s.is_empty() {
+                Ok(None)
+            } else {
+                s.parse::<usize>()
+                    .map(Some)
+                    .map_err(|_| invalid("invalid count in \\{...}"))
+            }
+        };
+
+        let rep = match body.split_once(',') {
+            None => {
+                match count(body)? {
+                    None => "*".to_string(),
+                    Some(n) => format!("{{{n}}}"),
+                }
+            },
+            Some((n, m)) => {
+                match (count(n)?, count(m)?) {
+                    (None | Some(0), None) => "*".to_string(),
+                    (Some(n), None) => format!("{{{n},}}"),
+                    (n, Some(m)) => format!("{{{},{m}}}", n.unwrap_or(0)),
+                }
+            },
+        };
+
+        self.out.push_str(&rep);
+
+        if lazy {
+            self.out.push('?');
+        }
+
+        return Ok(());
+    }
+
+    /// Translate a `[...]` collection, returning false if there is no closing bracket.
+    fn collection(&mut self, newline: bool) -> Result<bool, Error> {
+        let start = self.idx;
+        let mut class = String::new();
+        let mut negated = false;
+        let mut first = true;
+
+        if self.chars.get(self.idx) == Some(&'^') {
+            self.idx += 1;
+            negated = true;
+        }
+
+        loop {
+            let c = match self.next_char() {
+                Some(c) => c,
+                None => {
+                    self.idx = start;
+
+                    return Ok(false);
+                },
+            };
+
+            match c {
+                ']' if !first => break,
+                '[' if matches!(self.chars.get(self.idx), Some(':' | '=' | '.')) => {
+                    let delim = self.chars[self.idx];
+                    let rest: String = self.chars[self.idx + 1..].iter().collect();
+                    let close = format!("{delim}]");
+
+                    if let Some(end) = rest.find(&close) {
+                        let name = &rest[..end];
+                        self.idx += 1 + name.chars().count() + 2;
+
+                        if delim == ':' {
+                            class.push_str(&Self::named_class(name)?);
+                        } else {
+                            let mut cs = name.chars();
+
+                            match (cs.next(), cs.next()) {
+                                (Some(c), None) => class.push_str(&literal(c)),
+                                _ => return Err(invalid("invalid equivalence class")),
+                            }
+                        }
+                    } else {
+                        class.push_str("\\[");
+                    }
+                },
+                '\\' => {
+                    let esc = match self.chars.get(self.idx) {
+                        Some('e') => Some("\\x1B"),
+                        Some('t') => Some("\\t"),
+                        Some('r') => Some("\\r"),
+                        Some('b') => Some("\\x08"),
+                        Some('n') => Some("\\n"),
+                        Some('\\') => Some("\\\\"),
+                        Some(']') => Some("\\]"),
+                        Some('^') => Some("\\^"),
+                        Some('-') => Some("\\-"),
+                        _ => None,
+                    };
+
+                    if let Some(esc) = esc {
+                        self.idx += 1;
+                        class.push_str(esc);
+                    } else {
+                        // Vim treats any other backslash inside a collection literally.
+                        class.push_str("\\\\");
+                    }
+                },
+                '-' => {
+                    let range = !first &&
+                        !class.ends_with('-') &&
+                        !matches!(self.chars.get(self.idx), Some(']') | None);
+
+                    if range {
+                        class.push('-');
+                    } else {
+                        class.push_str("\\-");
+                    }
+                },
+                '[' | ']' | '^' | '&' | '~' => {
+                    class.push('\\');
+                    class.push(c);
+                },
+                c => {
+                    self.has_upper |= c.is_uppercase();
+                    class.push(c);
+                },
+            }
+
+            first = false;
+        }
+
+        self.atom(&bracket(&class, negated, newline));
+
+        return Ok(true);
+    }
+
+    fn named_class(name: &str) -> Result<String, Error> {
+        let class = match name {
+            "alnum" | "alpha" | "blank" | "cntrl" | "digit" | "graph" | "lower" | "print" |
+            "punct" | "space" | "upper" | "xdigit" => format!("[:{name}:]"),
+            "return" => "\\r".into(),
+            "tab" => "\\t".into(),
+            "escape" => "\\x1B".into(),
+            "backspace" => "\\x08".into(),
+            _ => return Err(unsupported(&format!("[:{name}:]"))),
+        };
+
+        Ok(class)
+    }
+
+    /// Translate a numeric character code like `\%d123` or `\%u20AC`.
+    fn codepoint(&mut self, radix: u32, max: usize) -> Result<(), Error> {
+        let mut digits = String::new();
+
+        while digits.len() < max {
+            match self.chars.get(self.idx) {
+                Some(c) if c.is_digit(radix) => {
+                    digits.push(*c);
+                    self.idx += 1;
+                },
+                _ => break,
+            }
+        }
+
+        let c = u32::from_str_radix(&digits, radix)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| invalid("invalid character code after \\%"))?;
+
+        self.atom(&literal(c));
+
+        Ok(())
+    }
+
+    fn percent(&mut self) -> Result<(), Error> {
+        match self.next_char() {
+            Some('(') => {
+                self.groups += 1;
+                self.branch("(?:");
+            },
+            Some('^') => self.anchor("\\A"),
+            Some('$') => self.anchor("\\z"),
+            Some('d') => self.codepoint(10, usize::MAX)?,
+            Some('o') => self.codepoint(8, 4)?,
+            Some('x') => self.codepoint(16, 2)?,
+            Some('u') => self.codepoint(16, 4)?,
+            Some('U') => self.codepoint(16, 8)?,
+            Some('[') => return Err(unsupported("\\%[]")),
+            Some(c) => return Err(unsupported(&format!("\\%{c}"))),
+            None => return Err(invalid("trailing \\%")),
+        }
+
+        Ok(())
+    }
+
+    fn magic(&mut self, c: char) -> Result<(), Error> {
+        match c {
+            '^' => {
+                if self.level == MagicLevel::VeryMagic || self.last == Last::Branch {
+                    self.anchor("^");
+                } else {
+                    self.atom("\\^");
+                }
+            },
+            '$' => {
+                if self.level == MagicLevel::VeryMagic || self.at_branch_end()? {
+                    self.anchor("$");
+                } else {
+                    self.atom("\\$");
+                }
+            },
+            '.' => self.atom("."),
+            '[' => {
+                if !self.collection(false)? {
+                    self.atom("\\[");
+                }
+            },
+            '(' => {
+                self.groups += 1;
+                self.branch("(");
+            },
+            ')' => {
+                if self.groups == 0 {
+                    return Err(invalid("unmatched \\)"));
+                }
+
+                self.groups -= 1;
+                self.atom(")");
+            },
+            '|' => self.branch("|"),
+            '<' => self.anchor("\\b{start}"),
+            '>' => self.anchor("\\b{end}"),
+            '%' => self.percent()?,
+            '~' => return Err(unsupported("~ (the last substitute string)")),
+            '&' => return Err(unsupported("\\&")),
+            '*' | '+' | '=' | '?' | '{' | '@' => self.multi(c)?,
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn escape(&mut self, c: char) -> Result<(), Error> {
+        match c {
+            'v' => self.level = MagicLevel::VeryMagic,
+            'm' => self.level = MagicLevel::Magic,
+            'M' => self.level = MagicLevel::NoMagic,
+            'V' => self.level = MagicLevel::VeryNoMagic,
+            'c' => self.ignore_case = Some(true),
+            'C' => {
+                self.ignore_case.get_or_insert(false);
+            },
+            'n' => self.atom("\\n"),
+            't' => self.atom("\\t"),
+            'r' => self.atom("\\r"),
+            'e' => self.atom("\\x1B"),
+            'b' => self.atom("\\x08"),
+            '_' => {
+                match self.next_char() {
+                    Some('.') => self.atom("(?s:.)"),
+                    Some('^') => self.anchor("^"),
+                    Some('$') => self.anchor("$"),
+                    Some('[') => {
+                        if !self.collection(true)? {
+                            return Err(invalid("missing ] after \\_["));
+                        }
+                    },
+                    Some(c) => {
+                        let (class, negated) =
+                            class(c).ok_or_else(|| unsupported(&format!("\\_{c}")))?;
+
+                        self.atom(&bracket(class, negated, true));
+                    },
+                    None => return Err(invalid("trailing \\_")),
+                }
+            },
+            'z' => {
+                let what = match self.next_char() {
+                    Some(c) => format!("\\z{c}"),
+                    None => "\\z".into(),
+                };
+
+                return Err(unsupported(&what));
+            },
+            '0'..='9' => return Err(unsupported(&format!("backreference \\{c}"))),
+            c => {
+                let (class, negated) = class(c).ok_or_else(|| unsupported(&format!("\\{c}")))?;
+
+                self.atom(&bracket(class, negated, false));
+            },
+        }
+
+        Ok(())
+    }
+
+    fn translate(mut self, case: SearchCase) -> Result<String, Error> {
+        while let Some(tok) = self.next_token()? {
+            match tok {
+                Token::Literal(c) => {
+                    self.has_upper |= c.is_uppercase();
+                    self.atom(&literal(c));
+                },
+                Token::Magic(c) => self.magic(c)?,
+                Token::Escape(c) => self.escape(c)?,
+            }
+        }
+
+        if self.groups > 0 {
+            return Err(invalid("unmatched \\("));
+        }
+
+        let ignore_case = self.ignore_case.unwrap_or(match case {
+            SearchCase::Sensitive => false,
+            SearchCase::Insensitive => true,
+            SearchCase::Smart => !self.has_upper,
+        });
+        let flags = if ignore_case { "(?mi)" } else { "(?m)" };
+
+        return Ok(format!("{flags}{}", self.out));
+    }
+}
+
+/// Translate a pattern written using Vim's regular expression syntax into the syntax used by
+/// the [regex] crate.
+///
+/// Letter case is treated as described by `case`, unless the pattern contains `\c` or `\C`.
+pub fn translate(pattern: &str, case: SearchCase) -> Result<String, Error> {
+    Translator::new(pattern).translate(case)
+}
+
+/// Compile a pattern written using Vim's regular expression syntax.
+pub fn compile(pattern: &str, case: SearchCase) -> Result<Regex, Error> {
+    Regex::new(translate(pattern, case)?.as_str())
+}
+
+/// Escape text so that it gets matched literally when used in a "magic" pattern.
+pub fn escape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if ESCAPED.contains(c) {
+            res.push('\\');
+        }
+
+        res.push(c);
+    }
+
+    return res;
+}
+
+/// Split text typed after `/` or `?` at the first unescaped `term`.
+fn split_query(text: &str, term: char) -> (&str, Option<&str>) {
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == term {
+            return (&text[..i], Some(&text[i + 1..]));
+        }
+    }
+
+    return (text, None);
+}
+
+/// Parse a search offset, returning it and whatever text follows it.
+fn parse_offset(text: &str) -> (SearchOffset, &str) {
+    let (kind, rest) = match text.chars().next() {
+        Some(c @ ('e' | 's' | 'b')) => (c, &text[1..]),
+        Some('+' | '-' | '0'..='9') => ('l', text),
+        _ => return (SearchOffset::default(), text),
+    };
+
+    let (sign, rest) = match rest.chars().next() {
+        Some('+') => (Some(1), &rest[1..]),
+        Some('-') => (Some(-1), &rest[1..]),
+        _ => (None, rest),
+    };
+
+    let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let (digits, rest) = rest.split_at(len);
+
+    let n = match (sign, digits.parse::<isize>()) {
+        (sign, Ok(n)) => n.saturating_mul(sign.unwrap_or(1)),
+        (Some(sign), Err(_)) => sign,
+        (None, Err(_)) => 0,
+    };
+
+    let offset = match kind {
+        'e' => SearchOffset::End(n),
+        's' | 'b' => SearchOffset::Start(n),
+        _ => SearchOffset::Line(n),
+    };
+
+    return (offset, rest);
+}
+
+/// Parse text typed into the search bar after `/` (when `dir` is [MoveDir1D::Next]) or `?`
+/// (when `dir` is [MoveDir1D::Previous]).
+///
+/// The pattern can be followed by the prompt character and an offset, like `foo/e+1`, and then
+/// by `;` and another search, like `foo/;?bar?b-2`. See `:help search-offset` in Vim for
+/// details. Any other text after an offset gets ignored.
+pub fn parse_queries(text: &str, dir: MoveDir1D) -> Vec<SearchQuery> {
+    let mut queries = vec![];
+    let mut dir = dir;
+    let mut text = text;
+
+    loop {
+        let term = match dir {
+            MoveDir1D::Next => '/',
+            MoveDir1D::Previous => '?',
+        };
+
+        let (pattern, rest) = split_query(text, term);
+        let mut query = SearchQuery::new(pattern, dir);
+
+        let rest = match rest {
+            Some(rest) => rest,
+            None => {
+                queries.push(query);
+
+                return queries;
+            },
+        };
+
+        let (offset, rest) = parse_offset(rest);
+        query.offset = offset;
+        queries.push(query);
+
+        if let Some(rest) = rest.strip_prefix(";/") {
+            dir = MoveDir1D::Next;
+            text = rest;
+        } else if let Some(rest) = rest.strip_prefix(";?") {
+            dir = MoveDir1D::Previous;
+            text = rest;
+        } else {
+            return queries;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> Option<String> {
+        compile(pattern, SearchCase::Sensitive)
+            .unwrap()
+            .find(text)
+            .map(|m| m.as_str().to_string())
+    }
+
+    fn error(pattern: &str) -> String {
+        translate(pattern, SearchCase::Sensitive).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_translate_magic() {
+        assert_eq!(translate("a.b*", SearchCase::Sensitive).unwrap(), "(?m)a.b*");
+        assert_eq!(translate("a\\+b\\=c\\?", SearchCase::Sensitive).unwrap(), "(?m)a+b?c?");
+        assert_eq!(translate("\\(a\\|b\\)", SearchCase::Sensitive).unwrap(), "(?m)(a|b)");
+        assert_eq!(translate("\\%(a\\)", SearchCase::Sensitive).unwrap(), "(?m)(?:a)");
+        assert_eq!(
+            translate("a+b?c{1}(d)|", SearchCase::Sensitive).unwrap(),
+            "(?m)a\\+b\\?c\\{1\\}\\(d\\)\\|"
+        );
+
+        assert_eq!(matches("\\<foo\\>", "foobar foo"), Some("foo".into()));
+        assert_eq!(matches("a\\{2,3}", "aaaa"), Some("aaa".into()));
+        assert_eq!(matches("a\\{-2,3}", "aaaa"), Some("aa".into()));
+        assert_eq!(matches("a\\{-}b", "aaab"), Some("aaab".into()));
+        assert_eq!(matches("a\\{2}", "aaaa"), Some("aa".into()));
+        assert_eq!(matches("a\\{,2\\}", "aaaa"), Some("aa".into()));
+        assert_eq!(matches("\\d\\+", "ab 123"), Some("123".into()));
+        assert_eq!(matches("\\S\\+", "  abc\ndef"), Some("abc".into()));
+        assert_eq!(matches("\\_s\\+", "a \n b"), Some(" \n ".into()));
+        assert_eq!(matches("a\\_.b", "a\nb"), Some("a\nb".into()));
+        assert_eq!(matches("a.b", "a\nb"), None);
+    }
+
+    #[test]
+    fn test_translate_levels() {
+        // Very magic.
+        assert_eq!(matches("\\v<(foo|bar)+>", "a foobar b"), Some("foobar".into()));
+        assert_eq!(matches("\\va{2}", "aaa"), Some("aa".into()));
+        assert_eq!(matches("\\va\\{2}", "a{2}"), Some("a{2}".into()));
+        assert_eq!(matches("\\va=b", "b"), Some("b".into()));
+
+        // Nomagic.
+        assert_eq!(matches("\\Ma.b", "axb a.b"), Some("a.b".into()));
+        assert_eq!(matches("\\Ma\\.b", "axb"), Some("axb".into()));
+        assert_eq!(matches("\\M[ab]", "a [ab]"), Some("[ab]".into()));
+        assert_eq!(matches("\\M^a", "ba\na"), Some("a".into()));
+
+        // Very nomagic.
+        assert_eq!(matches("\\Va.b*$", "a.b*$"), Some("a.b*$".into()));
+        assert_eq!(matches("\\V^a", "^a"), Some("^a".into()));
+        assert_eq!(matches("\\V\\^a\\$", "^a\na"), Some("a".into()));
+        assert_eq!(matches("\\V\\(a\\|b\\)\\+", "xabba"), Some("abba".into()));
+
+        // Switching levels partway through.
+        assert_eq!(matches("\\Va.\\mb.", "a.bx"), Some("a.bx".into()));
+        assert_eq!(matches("\\Va.\\mb.", "axbx"), None);
+    }
+
+    #[test]
+    fn test_translate_anchors() {
+        assert_eq!(matches("^b", "a\nb"), Some("b".into()));
+        assert_eq!(matches("a$", "a b\nb a"), Some("a".into()));
+        assert_eq!(matches("a^b", "a^b"), Some("a^b".into()));
+        assert_eq!(matches("a$b", "a$b"), Some("a$b".into()));
+        assert_eq!(matches("\\(^b\\|c\\)", "ab\nb"), Some("b".into()));
+        assert_eq!(matches("*a", "b*a"), Some("*a".into()));
+        assert_eq!(matches("\\%^a", "ba\na"), None);
+        assert_eq!(matches("\\%^b", "ba\na"), Some("b".into()));
+    }
+
+    #[test]
+    fn test_translate_case() {
+        assert_eq!(matches("\\cfoo", "FOO"), Some("FOO".into()));
+        assert_eq!(matches("fo\\co", "FOO"), Some("FOO".into()));
+        assert_eq!(matches("\\Cfoo", "FOO"), None);
+        assert_eq!(matches("\\C\\cfoo", "FOO"), Some("FOO".into()));
+
+        let case_matches = |pattern: &str, case: SearchCase, text: &str| {
+            compile(pattern, case).unwrap().is_match(text)
+        };
+
+        // 'ignorecase' matches regardless of case.
+        assert!(case_matches("foo", SearchCase::Insensitive, "FOO"));
+        assert!(case_matches("Foo", SearchCase::Insensitive, "fOO"));
+        assert!(!case_matches("foo", SearchCase::Insensitive, "bar"));
+
+        // 'smartcase' only ignores case when the pattern has no uppercase letters.
+        assert!(case_matches("foo", SearchCase::Smart, "FOO"));
+        assert!(!case_matches("Foo", SearchCase::Smart, "fOO"));
+        assert!(!case_matches("[A-Z]o", SearchCase::Smart, "fo"));
+
+        // Uppercase letters in escapes don't count.
+        assert!(case_matches("\\Sfoo", SearchCase::Smart, "xFOO"));
+        assert!(case_matches("\\%x41", SearchCase::Smart, "a"));
+
+        // \c and \C take priority over both options.
+        assert!(case_matches("\\cFoo", SearchCase::Smart, "fOO"));
+        assert!(!case_matches("\\Cfoo", SearchCase::Insensitive, "FOO"));
+    }
+
+    #[test]
+    fn test_translate_collections() {
+        assert_eq!(matches("[a-c]\\+", "xxbca"), Some("bca".into()));
+        assert_eq!(matches("[^a-c]\\+", "abc\ndef"), Some("def".into()));
+        assert_eq!(matches("[]x]\\+", "a]x]"), Some("]x]".into()));
+        assert_eq!(matches("[-a]\\+", "b-a-"), Some("-a-".into()));
+        assert_eq!(matches("[[:digit:]]\\+", "ab12"), Some("12".into()));
+        assert_eq!(matches("[&~]\\+", "a&~"), Some("&~".into()));
+        assert_eq!(matches("\\_[a\\-]\\+", "b-\na"), Some("-\na".into()));
+        assert_eq!(matches("a[b", "a[b"), Some("a[b".into()));
+        assert_eq!(matches("\\%d97\\%x62\\%u0063", "abc"), Some("abc".into()));
+    }
+
+    #[test]
+    fn test_translate_unsupported() {
+        assert_eq!(error("foo\\zsbar"), "\\zs is not supported");
+        assert_eq!(error("\\(a\\)\\1"), "backreference \\1 is not supported");
+        assert_eq!(error("foo\\(bar\\)\\@="), "lookaround (\\@) is not supported");
+        assert_eq!(error("a\\&b"), "\\& is not supported");
+        assert_eq!(error("~"), "~ (the last substitute string) is not supported");
+        assert_eq!(error("\\%[abc]"), "\\%[] is not supported");
+        assert_eq!(error("\\%V"), "\\%V is not supported");
+        assert_eq!(error("\\k"), "\\k is not supported");
+
+        assert_eq!(error("\\(a"), "unmatched \\(");
+        assert_eq!(error("a\\)"), "unmatched \\)");
+        assert_eq!(error("a\\{2"), "missing } after \\{");
+        assert_eq!(error("a**"), "a multi cannot follow another multi");
+        assert_eq!(error("a\\"), "trailing backslash");
+    }
+
+    #[test]
+    fn test_escape() {
+        let text = "a.b*[c]^$~/\\";
+        let pattern = escape(text);
+
+        assert_eq!(pattern, "a\\.b\\*\\[c]\\^\\$\\~\\/\\\\");
+        assert_eq!(matches(&pattern, text), Some(text.into()));
+    }
+
+    #[test]
+    fn test_parse_queries() {
+        let query =
+            |pattern: &str, dir, offset| SearchQuery { pattern: pattern.into(), dir, offset };
+
+        // Plain patterns.
+        assert_eq!(parse_queries("foo", MoveDir1D::Next), vec![SearchQuery::new(
+            "foo",
+            MoveDir1D::Next
+        )]);
+        assert_eq!(parse_queries("a/b", MoveDir1D::Previous), vec![SearchQuery::new(
+            "a/b",
+            MoveDir1D::Previous
+        )]);
+        assert_eq!(parse_queries("a\\/b", MoveDir1D::Next), vec![SearchQuery::new(
+            "a\\/b",
+            MoveDir1D::Next
+        )]);
+        assert_eq!(parse_queries("foo/", MoveDir1D::Next), vec![SearchQuery::new(
+            "foo",
+            MoveDir1D::Next
+        )]);
+
+        // Line offsets.
+        assert_eq!(parse_queries("foo/+", MoveDir1D::Next), vec![query(
+            "foo",
+            MoveDir1D::Next,
+            SearchOffset::Line(1)
+        )]);
+        assert_eq!(parse_queries("foo/-", MoveDir1D::Next), vec![query(
+            "foo",
+            MoveDir1D::Next,
+            SearchOffset::Line(-1)
+        )]);
+        assert_eq!(parse_queries("foo/3", MoveDir1D::Next), vec![query(
+            "foo",
+            MoveDir1D::Next,
+            SearchOffset::Line(3)
+        )]);
+        assert_eq!(parse_queries("foo?-2", MoveDir1D::Previous), vec![query(
+            "foo",
+            MoveDir1D::Previous,
+            SearchOffset::Line(-2)
+        )]);
+
+        // Character offsets from the start and end of the match.
+        assert_eq!(parse_queries("pat/e", MoveDir1D::Next), vec![query(
+            "pat",
+            MoveDir1D::Next,
+            SearchOffset::End(0)
+        )]);
+        assert_eq!(parse_queries("pat/e+1", MoveDir1D::Next), vec![query(
+            "pat",
+            MoveDir1D::Next,
+            SearchOffset::End(1)
+        )]);
+        assert_eq!(parse_queries("pat/s-", MoveDir1D::Next), vec![query(
+            "pat",
+            MoveDir1D::Next,
+            SearchOffset::Start(-1)
+        )]);
+        assert_eq!(parse_queries("pat?b-2", MoveDir1D::Previous), vec![query(
+            "pat",
+            MoveDir1D::Previous,
+            SearchOffset::Start(-2)
+        )]);
+
+        // Searches chained with ";".
+        assert_eq!(parse_queries("/;//", MoveDir1D::Next), vec![
+            SearchQuery::new("", MoveDir1D::Next),
+            SearchQuery::new("", MoveDir1D::Next),
+        ]);
+        assert_eq!(parse_queries("foo/e;?bar?b+1", MoveDir1D::Next), vec![
+            query("foo", MoveDir1D::Next, SearchOffset::End(0)),
+            query("bar", MoveDir1D::Previous, SearchOffset::Start(1)),
+        ]);
+
+        // Anything else after the offset is ignored.
+        assert_eq!(parse_queries("foo/e1x;/bar", MoveDir1D::Next), vec![query(
+            "foo",
+            MoveDir1D::Next,
+            SearchOffset::End(1)
+        )]);
+    }
+}
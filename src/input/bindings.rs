@@ -982,6 +982,13 @@ where
     }
 }
 
+/// Whether a key is used to cancel an ongoing dialog.
+fn is_interrupt<Key: InputKey>(key: &Key) -> bool {
+    ["<Esc>", "<C-G>"]
+        .iter()
+        .any(|s| s.parse::<Key>().map_or(false, |k| &k == key))
+}
+
 /// Manage and process modal keybindings.
 pub struct ModalMachine<Key: InputKey, S: Step<Key>> {
     state: S::M,
@@ -1137,19 +1144,29 @@ where
     fn input_key(&mut self, input: Key) {
         // Ongoing dialogs intercept all keypresses.
         if let Some(dialog) = self.dialogs.last_mut() {
-            if let Some(c) = input.get_char() {
-                if let Some(mut acts) = dialog.input(c) {
-                    // Dialog-generated actions skip sequence tracking,
-                    // and go to the front of the action queue.
-                    while let Some(act) = acts.pop() {
-                        self.actions.push_front((act, S::C::default()));
-                    }
+            let interrupt = is_interrupt(&input);
+            let res = match input.get_char() {
+                Some(c) if !interrupt => dialog.input(c).map(|acts| (acts, true)),
+                _ => None,
+            }
+            .or_else(|| dialog.exit(interrupt).map(|acts| (acts, interrupt)));
 
-                    let _ = self.dialogs.pop();
-                }
+            let (mut acts, consumed) = match res {
+                Some(res) => res,
+                None => return,
+            };
+
+            // Dialog-generated actions skip sequence tracking,
+            // and go to the front of the action queue.
+            while let Some(act) = acts.pop() {
+                self.actions.push_front((act, S::C::default()));
             }
 
-            return;
+            let _ = self.dialogs.pop();
+
+            if consumed {
+                return;
+            }
         }
 
         let mut stack = vec![input];
@@ -1229,7 +1246,10 @@ mod tests {
     use super::*;
     use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
-    use crate::{input::key::TerminalKey, util::keycode_to_num};
+    use crate::{
+        input::{dialog::PromptChoice, key::TerminalKey},
+        util::keycode_to_num,
+    };
 
     macro_rules! once {
         ($ee: expr) => {
@@ -2794,4 +2814,43 @@ mod tests {
         assert_eq!(tm.mode(), TestMode::Insert);
         assert_eq!(tm.get_cursor_indicator(), None);
     }
+
+    #[test]
+    fn test_dialog_exit() {
+        let mut tm = TestMachine::default();
+        let ctx = TestContext::default();
+        let prompt = || {
+            let choices = vec![('y', vec![TestAction::Paste])];
+            let dialog = PromptChoice::new("Paste?", choices).on_exit(vec![TestAction::Query]);
+
+            Box::new(dialog)
+        };
+
+        // Picking a choice ends the dialog.
+        tm.run_dialog(prompt());
+        tm.input_key(key!('y'));
+        assert_pop2!(tm, TestAction::Paste, ctx);
+
+        // Escape and ^G end the dialog, and are consumed.
+        tm.run_dialog(prompt());
+        tm.input_key(key!(KeyCode::Esc));
+        assert_pop2!(tm, TestAction::Query, ctx);
+        assert_eq!(tm.mode(), TestMode::Insert);
+
+        tm.run_dialog(prompt());
+        tm.input_key(ctl!('g'));
+        assert_pop2!(tm, TestAction::Query, ctx);
+
+        // Any other key ends the dialog, and is then processed normally.
+        tm.run_dialog(prompt());
+        tm.input_key(key!('d'));
+        assert_pop1!(tm, TestAction::Query, ctx);
+        assert_pop2!(tm, TestAction::Type('d'), ctx);
+
+        tm.run_dialog(prompt());
+        tm.input_key(ctl!('l'));
+        assert_pop1!(tm, TestAction::Query, ctx);
+        assert_pop2!(tm, TestAction::NoOp, ctx);
+        assert_eq!(tm.mode(), TestMode::Normal);
+    }
 }
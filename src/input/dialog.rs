@@ -20,6 +20,18 @@ pub trait Dialog<A>: Debug + Send {
     /// The user's response to this interactive dialog. The user will be repeatedly
     /// prompted until this returns Some.
     fn input(&mut self, c: char) -> Option<Vec<A>>;
+
+    /// Called when the user presses a key that [Dialog::input] didn't accept, or that doesn't
+    /// correspond to a character. If this returns Some, then the dialog ends, and the returned
+    /// actions are run.
+    ///
+    /// When `interrupt` is `true`, the user pressed Escape or Control-G to cancel the dialog, and
+    /// the key is consumed. Otherwise, the key gets processed normally after the returned
+    /// actions.
+    #[allow(unused_variables)]
+    fn exit(&mut self, interrupt: bool) -> Option<Vec<A>> {
+        None
+    }
 }
 
 /// Interactively prompt the user for "y" or "n"
@@ -68,6 +80,60 @@ where
     }
 }
 
+/// Interactively prompt the user to pick a response with a single keypress.
+///
+/// Any other keypress ends the prompt, and runs the actions given to [PromptChoice::on_exit]. If
+/// the key wasn't Escape or Control-G, then it's processed normally afterwards.
+#[derive(Clone, Debug)]
+pub struct PromptChoice<A: Clone + Debug> {
+    choices: Vec<(char, Vec<A>)>,
+    exit: Vec<A>,
+    msg: Cow<'static, str>,
+}
+
+impl<A> PromptChoice<A>
+where
+    A: Clone + Debug,
+{
+    /// Create a new prompt with the given message, and the actions that each key results in.
+    pub fn new<T>(prompt: T, choices: Vec<(char, Vec<A>)>) -> Self
+    where
+        T: Into<Cow<'static, str>>,
+    {
+        PromptChoice { choices, exit: vec![], msg: prompt.into() }
+    }
+
+    /// Set the actions to run when the user leaves the prompt without picking a choice.
+    pub fn on_exit(mut self, actions: Vec<A>) -> Self {
+        self.exit = actions;
+        self
+    }
+}
+
+impl<A> Dialog<A> for PromptChoice<A>
+where
+    A: Clone + Debug + Send + 'static,
+{
+    fn render(&mut self, max_rows: usize, max_cols: usize) -> Vec<Cow<'_, str>> {
+        if max_rows == 0 {
+            return vec![];
+        }
+
+        return wrap(self.msg.as_ref(), max_cols);
+    }
+
+    fn input(&mut self, c: char) -> Option<Vec<A>> {
+        self.choices
+            .iter()
+            .find(|(choice, _)| *choice == c)
+            .map(|(_, actions)| actions.clone())
+    }
+
+    fn exit(&mut self, _: bool) -> Option<Vec<A>> {
+        Some(self.exit.clone())
+    }
+}
+
 fn find_end(s: &str, start: usize, mut rows: usize, width: usize) -> usize {
     let mut idx = 0;
     let mut full = true;
@@ -307,6 +373,27 @@ mod tests {
         assert_eq!(dialog.input('q'), Some(vec![]));
     }
 
+    #[test]
+    fn test_prompt_choice() {
+        let choices = vec![('y', vec![1]), (' ', vec![1]), ('n', vec![2, 3])];
+        let mut dialog = PromptChoice::new("Replace this match?", choices).on_exit(vec![4]);
+
+        let lines = dialog.render(1, 100);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].as_ref(), "Replace this match?");
+
+        // Each choice returns its actions.
+        assert_eq!(dialog.input('y'), Some(vec![1]));
+        assert_eq!(dialog.input(' '), Some(vec![1]));
+        assert_eq!(dialog.input('n'), Some(vec![2, 3]));
+
+        // Other keys aren't choices, and leave the prompt.
+        assert_eq!(dialog.input('Y'), None);
+        assert_eq!(dialog.input('q'), None);
+        assert_eq!(dialog.exit(false), Some(vec![4]));
+        assert_eq!(dialog.exit(true), Some(vec![4]));
+    }
+
     #[test]
     fn test_pager() {
         let mut dialog =
@@ -1,12 +1,14 @@
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::io::{self, stdout, BufWriter, Stdout};
-use std::ops::{Deref, DerefMut, RangeInclusive};
+use std::ops::{Deref, DerefMut, Range, RangeInclusive};
 
 use regex::Regex;
+use unicode_width::UnicodeWidthStr;
 
 use crossterm::{
     cursor::MoveTo,
-    style::{Print, PrintStyledContent, Stylize},
+    style::{Attribute, ContentStyle, Print, PrintStyledContent, StyledContent},
     terminal::ScrollUp,
     QueueableCommand,
 };
@@ -16,8 +18,8 @@ use crate::util::is_newline;
 use crate::editing::{
     action::{EditInfo, EditResult, Editable, EditorAction, Jumpable, UIResult},
     application::ApplicationInfo,
-    base::{MoveDir1D, PositionList, TargetShape, ViewportContext, Wrappable},
-    buffer::{CursorGroupId, EditBuffer},
+    base::{MoveDir1D, PositionList, TargetShape, ViewportContext, WordStyle, Wrappable},
+    buffer::{CursorGroupId, EditBuffer, FollowersInfo},
     context::EditContext,
     cursor::Cursor,
     history::{HistoryList, ScrollbackState},
@@ -25,6 +27,8 @@ use crate::editing::{
     store::Store,
 };
 
+use super::{HighlightSpan, Highlighter};
+
 pub struct EditorContext {
     pub stdout: BufWriter<Stdout>,
     pub top: u16,
@@ -48,6 +52,21 @@ where
 
     viewctx: ViewportContext<Cursor>,
     gid: CursorGroupId,
+
+    highlighter: Option<Box<dyn Highlighter>>,
+}
+
+/// Determine the column to start drawing the right-side prompt at, if there's enough room left
+/// after drawing `text` starting at column `x`.
+fn rprompt_column(width: usize, x: usize, text: &str, rprompt: &str) -> Option<u16> {
+    let used = x + text.width();
+    let rlen = rprompt.width();
+
+    if rlen == 0 || used.saturating_add(rlen) >= width {
+        return None;
+    }
+
+    u16::try_from(width - rlen).ok()
 }
 
 impl<I> Editor<I>
@@ -66,6 +85,7 @@ where
             scrollback: ScrollbackState::Pending,
             viewctx,
             gid,
+            highlighter: None,
         }
     }
 
@@ -77,6 +97,10 @@ where
         self.buffer.is_blank()
     }
 
+    pub fn set_highlighter(&mut self, highlighter: Option<Box<dyn Highlighter>>) {
+        self.highlighter = highlighter;
+    }
+
     fn _highlight_ranges(
         &self,
         line: usize,
@@ -136,9 +160,72 @@ where
         return ranges;
     }
 
+    fn _highlight_spans(&self, line: usize) -> Vec<HighlightSpan> {
+        match (&self.highlighter, self.buffer.lines(line).next()) {
+            (Some(highlighter), Some(text)) => highlighter.highlight(text.to_string().as_str()),
+            _ => vec![],
+        }
+    }
+
+    /// Break the text `s`, which covers the columns `start..end` of `line`, into runs of
+    /// identically styled text.
+    fn _styled_runs(
+        &self,
+        line: usize,
+        start: usize,
+        end: usize,
+        s: &str,
+        spans: &[HighlightSpan],
+        followers: &FollowersInfo,
+    ) -> Vec<(ContentStyle, String)> {
+        let mut styles = vec![ContentStyle::default(); s.chars().count()];
+        let len = styles.len();
+
+        let mut styled = |range: Range<usize>, f: &dyn Fn(&mut ContentStyle)| {
+            let rs = range.start.max(start).saturating_sub(start).min(len);
+            let re = range.end.min(end).saturating_sub(start).min(len);
+
+            for style in styles[rs..re.max(rs)].iter_mut() {
+                f(style);
+            }
+        };
+
+        for (range, style) in spans.iter() {
+            styled(range.clone(), &|s| *s = *style);
+        }
+
+        for range in self._highlight_ranges(line, start, end) {
+            let range = *range.start()..range.end().saturating_add(1);
+
+            styled(range, &|s| s.attributes.set(Attribute::Reverse));
+        }
+
+        for follower in followers.query((line, start)..(line, end)) {
+            let x = follower.value.x;
+
+            styled(x..x + 1, &|s| s.attributes.set(Attribute::Reverse));
+        }
+
+        let mut runs: Vec<(ContentStyle, String)> = vec![];
+
+        for (c, style) in s.chars().zip(styles) {
+            match runs.last_mut() {
+                Some((last, text)) if *last == style => {
+                    text.push(c);
+                },
+                _ => {
+                    runs.push((style, c.to_string()));
+                },
+            }
+        }
+
+        return runs;
+    }
+
     fn _redraw_wrap(
         &mut self,
         prompt: &Option<String>,
+        rprompt: Option<&str>,
         off: u16,
         context: &mut EditorContext,
     ) -> Result<u16, io::Error> {
@@ -218,6 +305,9 @@ where
             x = prompt.len() as u16;
         }
 
+        let finfo = self.buffer._follower_intervals(self.gid);
+        let mut spans = (usize::MAX, vec![]);
+
         for (line, start, end, s, cursor_line) in wrapped.into_iter() {
             if y >= bot {
                 break;
@@ -228,29 +318,21 @@ where
                 term_cursor = (x + coff, y);
             }
 
-            let ranges = self._highlight_ranges(line, start, end);
-
-            // XXX: need to highlight followers, too.
-            // let finfo = self.buffer._follower_intervals(self.gid);
-
-            let mut prev = 0;
+            if spans.0 != line {
+                spans = (line, self._highlight_spans(line));
+            }
 
             context.stdout.queue(MoveTo(x, y))?;
 
-            for range in ranges {
-                let rs = prev.max(*range.start());
-                let re = *range.end();
-
-                context.stdout.queue(Print(&s[prev..rs]))?;
-
-                let neg = s[(rs - start)..=(re - start)].negative();
-
-                prev = re.saturating_add(1);
-
-                context.stdout.queue(PrintStyledContent(neg))?;
+            for (style, text) in self._styled_runs(line, start, end, &s, &spans.1, &finfo) {
+                context.stdout.queue(PrintStyledContent(StyledContent::new(style, text)))?;
             }
 
-            context.stdout.queue(Print(&s[prev..]))?;
+            if let (Some(rprompt), (0, 0)) = (rprompt, (line, start)) {
+                if let Some(col) = rprompt_column(width, x as usize, &s, rprompt) {
+                    context.stdout.queue(MoveTo(col, y))?.queue(Print(rprompt))?;
+                }
+            }
 
             y += 1;
         }
@@ -264,9 +346,18 @@ where
         self.buffer.get().trim_end_matches(is_newline)
     }
 
+    pub fn get_cursor_word(&self, style: &WordStyle) -> Option<String> {
+        self.buffer.get_cursor_word(self.gid, style)
+    }
+
+    pub fn get_cursor_line(&self) -> Option<String> {
+        self.buffer.get_cursor_line(self.gid)
+    }
+
     fn _redraw_nowrap(
         &mut self,
         _: &Option<String>,
+        _: Option<&str>,
         _: u16,
         _: &mut EditorContext,
     ) -> Result<u16, io::Error> {
@@ -280,13 +371,14 @@ where
     pub fn redraw(
         &mut self,
         prompt: &Option<String>,
+        rprompt: Option<&str>,
         off: u16,
         context: &mut EditorContext,
     ) -> Result<u16, io::Error> {
         if self.viewctx.wrap {
-            self._redraw_wrap(prompt, off, context)
+            self._redraw_wrap(prompt, rprompt, off, context)
         } else {
-            self._redraw_nowrap(prompt, off, context)
+            self._redraw_nowrap(prompt, rprompt, off, context)
         }
     }
 
@@ -391,6 +483,8 @@ where
 mod tests {
     use super::super::{ReadLineId, ReadLineInfo};
     use super::*;
+    use crate::editing::cursor::{CursorGroup, CursorState};
+    use crossterm::style::Stylize;
 
     fn mked() -> Editor<ReadLineInfo> {
         Editor::new(ReadLineId::Line)
@@ -574,4 +668,105 @@ mod tests {
         assert_eq!(ed.scrollback, ScrollbackState::Typed);
         assert_eq!(history.strs(), v);
     }
+
+    fn keywords(line: &str) -> Vec<HighlightSpan> {
+        line.match_indices("if")
+            .map(|(idx, _)| line[..idx].chars().count())
+            .map(|start| (start..start + 2, ContentStyle::new().blue()))
+            .collect()
+    }
+
+    #[test]
+    fn test_styled_runs_highlighter() {
+        let mut ed = mkedstr("if a if b\n");
+        ed.set_highlighter(Some(Box::new(keywords)));
+
+        let blue = ContentStyle::new().blue();
+        let plain = ContentStyle::default();
+        let finfo = ed.buffer._follower_intervals(ed.gid);
+        let spans = ed._highlight_spans(0);
+
+        let runs = ed._styled_runs(0, 0, 9, "if a if b", &spans, &finfo);
+        assert_eq!(runs, vec![
+            (blue, "if".into()),
+            (plain, " a ".into()),
+            (blue, "if".into()),
+            (plain, " b".into()),
+        ]);
+
+        // Spans are clipped to the wrapped segment being drawn.
+        let runs = ed._styled_runs(0, 3, 6, "a i", &spans, &finfo);
+        assert_eq!(runs, vec![(plain, "a ".into()), (blue, "i".into())]);
+    }
+
+    #[test]
+    fn test_styled_runs_highlighter_unicode() {
+        let mut ed = mkedstr("é if 日本 if\n");
+        ed.set_highlighter(Some(Box::new(keywords)));
+
+        let blue = ContentStyle::new().blue();
+        let plain = ContentStyle::default();
+        let finfo = ed.buffer._follower_intervals(ed.gid);
+        let spans = ed._highlight_spans(0);
+        assert_eq!(spans, vec![(2..4, blue), (8..10, blue)]);
+
+        let runs = ed._styled_runs(0, 0, 10, "é if 日本 if", &spans, &finfo);
+        assert_eq!(runs, vec![
+            (plain, "é ".into()),
+            (blue, "if".into()),
+            (plain, " 日本 ".into()),
+            (blue, "if".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_styled_runs_selection_followers() {
+        let mut ed = mkedstr("if a if b\n");
+        ed.set_highlighter(Some(Box::new(keywords)));
+
+        let leader =
+            CursorState::Selection(Cursor::new(0, 5), Cursor::new(0, 3), TargetShape::CharWise);
+        let members = vec![CursorState::Location(Cursor::new(0, 8))];
+        ed.buffer.set_group(ed.gid, CursorGroup { leader, members });
+
+        let blue = ContentStyle::new().blue();
+        let plain = ContentStyle::default();
+        let mut blue_rev = blue;
+        blue_rev.attributes.set(Attribute::Reverse);
+        let mut plain_rev = plain;
+        plain_rev.attributes.set(Attribute::Reverse);
+
+        let finfo = ed.buffer._follower_intervals(ed.gid);
+        let spans = ed._highlight_spans(0);
+
+        let runs = ed._styled_runs(0, 0, 9, "if a if b", &spans, &finfo);
+        assert_eq!(runs, vec![
+            (blue, "if".into()),
+            (plain, " ".into()),
+            (plain_rev, "a ".into()),
+            (blue_rev, "i".into()),
+            (blue, "f".into()),
+            (plain, " ".into()),
+            (plain_rev, "b".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_rprompt_column() {
+        assert_eq!(rprompt_column(20, 0, "", "[12:00]"), Some(13));
+        assert_eq!(rprompt_column(20, 2, "0123456789", "[12:00]"), Some(13));
+
+        // Hide the prompt once the input reaches it.
+        assert_eq!(rprompt_column(20, 2, "01234567890", "[12:00]"), None);
+        assert_eq!(rprompt_column(20, 0, "0123456789012345678901234", "[12:00]"), None);
+
+        // Wide characters take up two columns, and combining characters don't take up any.
+        assert_eq!(rprompt_column(20, 2, "日本語日本", "[12:00]"), Some(13));
+        assert_eq!(rprompt_column(20, 2, "日本語日本語", "[12:00]"), None);
+        let combining = "e\u{301}".repeat(10);
+        assert_eq!(rprompt_column(20, 2, &combining, "[12:00]"), Some(13));
+
+        // Nothing to draw.
+        assert_eq!(rprompt_column(20, 0, "", ""), None);
+    }
 }
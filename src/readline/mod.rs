@@ -12,7 +12,7 @@
 //!     env::vim::keybindings::{VimBindings, VimMachine},
 //!     input::bindings::InputBindings,
 //!     input::key::TerminalKey,
-//!     readline::{ReadLine, ReadLineInfo},
+//!     readline::{ReadLine, ReadLineError, ReadLineInfo},
 //! };
 //!
 //! fn main() -> Result<(), std::io::Error> {
@@ -31,6 +31,10 @@
 //!                     println!("User typed: {:?}", s);
 //!                 }
 //!             },
+//!             Err(ReadLineError::EndOfInput) => {
+//!                 // Input was piped in, and we've reached its end.
+//!                 return Ok(());
+//!             },
 //!             Err(e) => {
 //!                 // Print out editor error messages.
 //!                 println!("{}", e);
@@ -40,7 +44,8 @@
 //! }
 //! ```
 //!
-use std::io::{self, Write};
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
 use std::process;
 use std::time::Duration;
 
@@ -49,8 +54,9 @@ use regex::Regex;
 use crossterm::{
     cursor::{Hide as CursorHide, MoveTo, Show as CursorShow},
     event::{poll, read, Event},
-    style::Print,
+    style::{ContentStyle, Print},
     terminal::{Clear, ClearType},
+    tty::IsTty,
     QueueableCommand,
 };
 
@@ -69,7 +75,18 @@ use crate::editing::{
         UIError,
     },
     application::{ApplicationContentId, ApplicationInfo, ApplicationWindowId},
-    base::{CommandType, Count, EditTarget, MoveDir1D, MoveDirMod, MoveType, Register, RepeatType},
+    base::{
+        CommandType,
+        Count,
+        EditTarget,
+        Flip,
+        MoveDir1D,
+        MoveDirMod,
+        MoveType,
+        Register,
+        RepeatType,
+        SearchCase,
+    },
     context::EditContext,
     history::HistoryList,
     key::KeyManager,
@@ -91,6 +108,9 @@ use self::editor::{Editor, EditorContext};
 
 const HISTORY_LENGTH: usize = 100;
 
+/// Terminal dimensions to assume when we're not attached to a terminal.
+const DEFAULT_DIMENSIONS: (u16, u16) = (80, 24);
+
 enum InternalResult {
     Submitted(EditRope),
     Nothing,
@@ -135,6 +155,51 @@ impl ApplicationInfo for ReadLineInfo {
     }
 }
 
+/// A range of columns within a line, and the style to draw them with.
+pub type HighlightSpan = (Range<usize>, ContentStyle);
+
+/// Trait for objects that can style the text being edited in a [ReadLine].
+///
+/// This is implemented for closures that take the line's text and return [HighlightSpan]s, so a
+/// highlighter can be as simple as:
+///
+/// ```
+/// use modalkit::crossterm::style::{ContentStyle, Stylize};
+/// use modalkit::readline::HighlightSpan;
+///
+/// let highlighter = |line: &str| -> Vec<HighlightSpan> {
+///     match line.find("sudo") {
+///         Some(idx) => {
+///             // Spans are in characters, so convert the byte offset that str::find returns.
+///             let start = line[..idx].chars().count();
+///
+///             vec![(start..start + 4, ContentStyle::new().red().bold())]
+///         },
+///         None => vec![],
+///     }
+/// };
+///
+/// assert_eq!(highlighter("ls; sudo").first().map(|s| s.0.clone()), Some(4..8));
+/// assert_eq!(highlighter("ö; sudo").first().map(|s| s.0.clone()), Some(3..7));
+/// ```
+pub trait Highlighter {
+    /// Determine how to style the text on a line.
+    ///
+    /// The ranges of the returned spans are character offsets into `line`. Any text not covered
+    /// by a span is drawn using the default style. Visual selections and cursor followers are
+    /// highlighted on top of these styles.
+    fn highlight(&self, line: &str) -> Vec<HighlightSpan>;
+}
+
+impl<F> Highlighter for F
+where
+    F: Fn(&str) -> Vec<HighlightSpan>,
+{
+    fn highlight(&self, line: &str) -> Vec<HighlightSpan> {
+        (self)(line)
+    }
+}
+
 /// Error type for [ReadLine] editor.
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
@@ -161,6 +226,12 @@ where
     /// Failure due to using an unknown command.
     #[error("Unknown command: {0:?}")]
     UnknownCommand(String),
+
+    /// There is no more input to read.
+    ///
+    /// This is only returned when reading from a non-interactive input, like a pipe or file.
+    #[error("End of input")]
+    EndOfInput,
 }
 
 /// Result type when using [ReadLine::readline].
@@ -181,6 +252,10 @@ where
     dimensions: (u16, u16),
     ct: Option<CommandType>,
     sd: MoveDir1D,
+    rprompt: Option<String>,
+
+    interactive: bool,
+    plain_prompt: bool,
 
     line: Editor<I>,
     cmd: Editor<I>,
@@ -193,13 +268,21 @@ where
     I: ApplicationInfo<ContentId = ReadLineId>,
 {
     /// Create a new instance.
+    ///
+    /// If either stdin or stdout is not a terminal, then [ReadLine::readline] will fall back to
+    /// reading plain lines from stdin.
     pub fn new<B: BindingMachine<TerminalKey, Action<I>, RepeatType, C> + 'static>(
         bindings: B,
     ) -> Result<Self, std::io::Error>
     where
         I::Store: Default,
     {
-        let dimensions = crossterm::terminal::size()?;
+        let interactive = io::stdin().is_tty() && io::stdout().is_tty();
+        let dimensions = if interactive {
+            crossterm::terminal::size()?
+        } else {
+            crossterm::terminal::size().unwrap_or(DEFAULT_DIMENSIONS)
+        };
         let context = EditorContext::default();
 
         let store = Store::<I>::default();
@@ -224,6 +307,10 @@ where
             dimensions,
             ct: None,
             sd: MoveDir1D::Next,
+            rprompt: None,
+
+            interactive,
+            plain_prompt: true,
 
             line,
             cmd,
@@ -233,8 +320,49 @@ where
         return Ok(rl);
     }
 
+    /// Set the [Highlighter] used to style the text that the user types.
+    pub fn set_highlighter<H: Highlighter + 'static>(&mut self, highlighter: H) {
+        self.line.set_highlighter(Some(Box::new(highlighter)));
+    }
+
+    /// Remove any previously set [Highlighter].
+    pub fn clear_highlighter(&mut self) {
+        self.line.set_highlighter(None);
+    }
+
+    /// Set a prompt to show right-aligned on the first line of input.
+    ///
+    /// This can be used to show information like the time, the last exit status or the current
+    /// mode. The prompt is hidden whenever the typed text gets close enough to overlap it.
+    pub fn set_right_prompt(&mut self, rprompt: Option<String>) {
+        self.rprompt = rprompt;
+    }
+
+    /// Returns true if input is being read interactively from a terminal.
+    pub fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Control whether the prompt gets printed when reading input from something other than a
+    /// terminal.
+    ///
+    /// By default the prompt is printed.
+    pub fn set_plain_prompt(&mut self, print: bool) {
+        self.plain_prompt = print;
+    }
+
     /// Prompt the user for input.
+    ///
+    /// When stdin or stdout isn't a terminal, this reads the next line from stdin instead, and
+    /// returns [ReadLineError::EndOfInput] once there's nothing left to read.
     pub fn readline(&mut self, prompt: Option<String>) -> ReadLineResult<I> {
+        if !self.interactive {
+            let mut stdin = io::stdin().lock();
+            let mut stdout = io::stdout().lock();
+
+            return self.readline_plain(prompt, &mut stdin, &mut stdout);
+        }
+
         crossterm::terminal::enable_raw_mode()?;
 
         self.init()?;
@@ -266,6 +394,33 @@ where
         }
     }
 
+    fn readline_plain<R: BufRead, W: Write>(
+        &mut self,
+        prompt: Option<String>,
+        input: &mut R,
+        output: &mut W,
+    ) -> ReadLineResult<I> {
+        if let (true, Some(prompt)) = (self.plain_prompt, prompt) {
+            output.write_all(prompt.as_bytes())?;
+            output.flush()?;
+        }
+
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Err(ReadLineError::EndOfInput);
+        }
+
+        // Match the text returned by the interactive editor, which always has a single, trailing
+        // newline.
+        let mut line = line.trim_end_matches(is_newline).to_string();
+        line.push('\n');
+
+        self.history.select(EditRope::from(line.as_str()));
+
+        return Ok(line);
+    }
+
     fn step(&mut self, prompt: &Option<String>) -> Result<TerminalKey, ReadLineError<I>> {
         loop {
             self.redraw(prompt)?;
@@ -337,18 +492,59 @@ where
 
                 Ok(InternalResult::Nothing)
             },
+            CommandBarAction::QueryReplace(_) => {
+                // There's nothing worth replacing interactively within a single line.
+                Ok(InternalResult::Nothing)
+            },
             CommandBarAction::Unfocus => {
                 self.ct = None;
 
+                Ok(InternalResult::Nothing)
+            },
+            CommandBarAction::InsertWord(style) => {
+                let text = self.line.get_cursor_word(style);
+                self.insert_cmd(text, &ctx)?;
+
+                Ok(InternalResult::Nothing)
+            },
+            CommandBarAction::InsertLine => {
+                let text = self.line.get_cursor_line();
+                self.insert_cmd(text, &ctx)?;
+
+                Ok(InternalResult::Nothing)
+            },
+            CommandBarAction::IncSearch(dir) => {
+                let dir = match dir {
+                    MoveDir1D::Next => self.sd,
+                    MoveDir1D::Previous => self.sd.flip(),
+                };
+
+                self.incsearch_move(dir, &ctx)?;
+
                 Ok(InternalResult::Nothing)
             },
         }
     }
 
+    fn insert_cmd(&mut self, text: Option<String>, ctx: &C) -> EditResult<EditInfo, I> {
+        if self.ct.is_none() {
+            return Ok(None);
+        }
+
+        if let Some(text) = text {
+            let act = InsertTextAction::Transcribe(text, MoveDir1D::Previous, Count::Exact(1));
+            let act = EditorAction::InsertText(act);
+
+            self.cmd.editor_command(&act, ctx, &mut self.store)
+        } else {
+            Ok(None)
+        }
+    }
+
     fn prompt(&mut self, act: PromptAction, ctx: C) -> Result<InternalResult, ReadLineError<I>> {
         match act {
             PromptAction::Submit => {
-                let res = self.submit();
+                let res = self.submit(&ctx);
                 self.ct = None;
 
                 return res;
@@ -414,11 +610,11 @@ where
         }
     }
 
-    fn get_cmd_regex(&mut self) -> EditResult<Regex, I> {
+    fn get_cmd_regex(&mut self, ctx: &C) -> EditResult<Regex, I> {
         let text = self.cmd.get_trim();
 
         if !text.is_empty() {
-            let re = Regex::new(text.to_string().as_ref())?;
+            let re = ctx.compile_search_regex(text.to_string().as_ref(), SearchCase::Sensitive)?;
 
             return Ok(re);
         }
@@ -431,20 +627,20 @@ where
             .recall(&mut self.store.searches, MoveDir1D::Previous, false, 1)
             .ok_or(EditError::NoSearch)?;
 
-        let re = Regex::new(text.to_string().as_ref())?;
+        let re = ctx.compile_search_regex(text.to_string().as_ref(), SearchCase::Sensitive)?;
 
         self.cmd.set_text(text);
 
         return Ok(re);
     }
 
-    fn get_regex(&mut self) -> EditResult<Regex, I> {
+    fn get_regex(&mut self, ctx: &C) -> EditResult<Regex, I> {
         let re = if let Some(CommandType::Search) = self.ct {
-            self.get_cmd_regex()?
+            self.get_cmd_regex(ctx)?
         } else {
             let text = self.store.registers.get(&Register::LastSearch)?.value;
 
-            Regex::new(text.to_string().as_ref())?
+            ctx.compile_search_regex(text.to_string().as_ref(), SearchCase::Sensitive)?
         };
 
         return Ok(re);
@@ -452,7 +648,7 @@ where
 
     fn search(&mut self, flip: MoveDirMod, count: Count, ctx: &C) -> EditResult<EditInfo, I> {
         let count = ctx.resolve(&count);
-        let needle = self.get_regex()?;
+        let needle = self.get_regex(ctx)?;
         let dir = ctx.get_search_regex_dir();
         let dir = flip.resolve(&dir);
 
@@ -478,7 +674,7 @@ where
             }
 
             let needle = self.cmd.get_trim().to_string();
-            let needle = Regex::new(needle.as_ref())?;
+            let needle = ctx.compile_search_regex(needle.as_ref(), SearchCase::Sensitive)?;
 
             if let Some(text) = self.line.find(&mut self.history, &needle, self.sd, true) {
                 self.line.set_text(text);
@@ -488,6 +684,20 @@ where
         Ok(())
     }
 
+    /// Move to another history entry matching the search being typed.
+    fn incsearch_move(&mut self, dir: MoveDir1D, ctx: &C) -> Result<(), EditError<I>> {
+        if let Some(CommandType::Search) = self.ct {
+            let needle = self.cmd.get_trim().to_string();
+            let needle = ctx.compile_search_regex(needle.as_ref(), SearchCase::Sensitive)?;
+
+            if let Some(text) = self.line.find(&mut self.history, &needle, dir, false) {
+                self.line.set_text(text);
+            }
+        }
+
+        Ok(())
+    }
+
     fn edit(&mut self, act: EditorAction, ctx: &C) -> EditResult<EditInfo, I> {
         match act {
             EditorAction::Edit(ea, EditTarget::Motion(MoveType::Line(dir), count)) => {
@@ -521,7 +731,7 @@ where
         }
     }
 
-    fn submit(&mut self) -> Result<InternalResult, ReadLineError<I>> {
+    fn submit(&mut self, ctx: &C) -> Result<InternalResult, ReadLineError<I>> {
         match self.ct {
             None => {
                 let text = focused_mut!(self).reset();
@@ -532,7 +742,9 @@ where
             },
             Some(CommandType::Search) => {
                 let text = self.reset_cmd();
-                let needle = match Regex::new(text.to_string().as_ref()) {
+                let needle = match ctx
+                    .compile_search_regex(text.to_string().as_ref(), SearchCase::Sensitive)
+                {
                     Err(e) => return Err(EditError::from(e).into()),
                     Ok(r) => r,
                 };
@@ -571,11 +783,12 @@ where
             .queue(MoveTo(0, self.context.top))?
             .queue(Clear(ClearType::FromCursorDown))?;
 
-        let lines = self.line.redraw(prompt, 0, &mut self.context)?;
+        let rprompt = self.rprompt.as_deref();
+        let lines = self.line.redraw(prompt, rprompt, 0, &mut self.context)?;
 
         if self.ct.is_some() {
             let p = self.cmd_prompt();
-            let _ = self.cmd.redraw(&p, lines, &mut self.context);
+            let _ = self.cmd.redraw(&p, None, lines, &mut self.context);
         }
 
         self.context.stdout.queue(CursorShow)?;
@@ -696,3 +909,53 @@ where
         return Ok(InternalResult::Nothing);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::vim::keybindings::{VimBindings, VimMachine};
+    use crate::env::vim::VimContext;
+    use crate::input::bindings::InputBindings;
+
+    fn mkrl() -> ReadLine<VimContext<ReadLineInfo>> {
+        let mut vi = VimMachine::<TerminalKey, ReadLineInfo>::empty();
+        VimBindings::default().submit_on_enter().setup(&mut vi);
+
+        ReadLine::new(vi).unwrap()
+    }
+
+    #[test]
+    fn test_readline_plain() {
+        let mut rl = mkrl();
+        let mut input = io::Cursor::new("hello\nworld\r\nno newline");
+        let mut output = vec![];
+
+        let res = rl.readline_plain(Some("> ".into()), &mut input, &mut output).unwrap();
+        assert_eq!(res, "hello\n");
+
+        let res = rl.readline_plain(Some("> ".into()), &mut input, &mut output).unwrap();
+        assert_eq!(res, "world\n");
+
+        let res = rl.readline_plain(None, &mut input, &mut output).unwrap();
+        assert_eq!(res, "no newline\n");
+
+        let res = rl.readline_plain(Some("> ".into()), &mut input, &mut output);
+        assert!(matches!(res, Err(ReadLineError::EndOfInput)));
+
+        assert_eq!(output, b"> > > ");
+        assert_eq!(rl.history.strs(), vec!["", "hello\n", "world\n", "no newline\n"]);
+    }
+
+    #[test]
+    fn test_readline_plain_no_prompt() {
+        let mut rl = mkrl();
+        let mut input = io::Cursor::new("hello\n");
+        let mut output = vec![];
+
+        rl.set_plain_prompt(false);
+
+        let res = rl.readline_plain(Some("> ".into()), &mut input, &mut output).unwrap();
+        assert_eq!(res, "hello\n");
+        assert_eq!(output, b"");
+    }
+}
@@ -8,6 +8,7 @@
 //! the bottom of the terminal window.
 //!
 //! [Screen]: super::screen::Screen
+use std::borrow::Cow;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
@@ -18,13 +19,17 @@ use crate::editing::{
         Action,
         CommandAction,
         CommandBarAction,
+        EditError,
+        EditInfo,
         EditResult,
+        Editable,
         EditorAction,
         PromptAction,
         Promptable,
+        QueryReplaceAction,
     },
     application::ApplicationInfo,
-    base::{CommandType, Count, EditTarget, MoveDir1D, MoveDirMod, SearchType},
+    base::{CommandType, Count, EditTarget, MoveDir1D, MoveDirMod, SearchCase, SearchType},
     completion::CompletionList,
     context::EditContext,
     history::ScrollbackState,
@@ -38,11 +43,22 @@ use super::{
     WindowOps,
 };
 
+/// Progress through prompting for the arguments to [CommandBarAction::QueryReplace].
+enum QueryReplacePrompt {
+    /// Prompting for the string to search for.
+    Search(bool),
+
+    /// Prompting for the text to replace the search string with.
+    Replacement(bool, String),
+}
+
 /// Persistent state for rendering [CommandBar].
 pub struct CommandBarState<I: ApplicationInfo> {
     scrollback: ScrollbackState,
     searchdir: MoveDir1D,
+    search_case: SearchCase,
     cmdtype: CommandType,
+    query: Option<QueryReplacePrompt>,
     tbox_cmd: TextBoxState<I>,
     tbox_search: TextBoxState<I>,
 }
@@ -59,7 +75,9 @@ where
         CommandBarState {
             scrollback: ScrollbackState::Pending,
             searchdir: MoveDir1D::Next,
+            search_case: SearchCase::default(),
             cmdtype: CommandType::Command,
+            query: None,
             tbox_cmd: TextBoxState::new(buffer_cmd),
             tbox_search: TextBoxState::new(buffer_search),
         }
@@ -76,9 +94,25 @@ where
         self.searchdir = dir;
     }
 
+    /// Set how letter case gets treated when previewing the search being typed.
+    pub fn set_search_case(&mut self, case: SearchCase) {
+        self.search_case = case;
+    }
+
+    /// Prompt for a search string and then its replacement, before starting an interactive
+    /// replacement.
+    ///
+    /// If `regex` is `true`, then the search string will be treated as a regular expression.
+    pub fn set_query_replace(&mut self, regex: bool) {
+        self.cmdtype = CommandType::Search;
+        self.searchdir = MoveDir1D::Next;
+        self.query = Some(QueryReplacePrompt::Search(regex));
+    }
+
     /// Reset the contents of the bar, and return the contents as an [EditRope].
     pub fn reset(&mut self) -> EditRope {
         self.scrollback = ScrollbackState::Pending;
+        self.query = None;
 
         self.deref_mut().reset()
     }
@@ -87,6 +121,31 @@ where
     pub fn reset_text(&mut self) -> String {
         self.reset().to_string()
     }
+
+    /// Update the search being previewed in the windows while the user types it, if searches are
+    /// incremental.
+    fn incsearch<C: EditContext>(&self, ctx: &C, store: &mut Store<I>) {
+        if self.cmdtype != CommandType::Search || self.query.is_some() {
+            return;
+        }
+
+        if !ctx.is_search_incremental() {
+            return;
+        }
+
+        let text = self.deref().get_text();
+        let incsearch = ctx
+            .get_search_queries(text.trim_end_matches('\n'), self.searchdir)
+            .pop()
+            .filter(|query| !query.pattern.is_empty())
+            .and_then(|query| {
+                let needle = ctx.compile_search_regex(&query.pattern, self.search_case);
+
+                needle.ok().map(|needle| (needle, query.dir))
+            });
+
+        store.search.set_incsearch(incsearch);
+    }
 }
 
 impl<I> Deref for CommandBarState<I>
@@ -115,6 +174,25 @@ where
     }
 }
 
+impl<C, I> Editable<C, Store<I>, I> for CommandBarState<I>
+where
+    C: EditContext,
+    I: ApplicationInfo,
+{
+    fn editor_command(
+        &mut self,
+        act: &EditorAction,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let res = self.deref_mut().editor_command(act, ctx, store)?;
+
+        self.incsearch(ctx, store);
+
+        Ok(res)
+    }
+}
+
 impl<C, I> PromptActions<C, Store<I>, I> for CommandBarState<I>
 where
     C: Default + EditContext,
@@ -122,6 +200,9 @@ where
 {
     fn submit(&mut self, ctx: &C, store: &mut Store<I>) -> EditResult<Vec<(Action<I>, C)>, I> {
         let unfocus = CommandBarAction::Unfocus.into();
+        let incoffset = store.search.get_incsearch_offset();
+
+        store.search.set_incsearch(None);
 
         let action = match self.cmdtype {
             CommandType::Command => {
@@ -133,12 +214,47 @@ where
                 CommandAction::Execute(text).into()
             },
             CommandType::Search => {
+                match self.query.take() {
+                    Some(QueryReplacePrompt::Search(regex)) => {
+                        let needle = self.deref().get().trim_end_matches(|c| c == '\n');
+
+                        if needle.is_empty() {
+                            self.query = Some(QueryReplacePrompt::Search(regex));
+
+                            let msg = "No search string given".to_string();
+                            let err = EditError::Failure(msg);
+
+                            return Err(err);
+                        }
+
+                        // Keep the bar focused so that we can prompt for the replacement.
+                        let _ = self.reset();
+                        self.query =
+                            Some(QueryReplacePrompt::Replacement(regex, needle.to_string()));
+
+                        return Ok(vec![]);
+                    },
+                    Some(QueryReplacePrompt::Replacement(regex, needle)) => {
+                        let replacement = self.reset().trim_end_matches(|c| c == '\n').to_string();
+                        let act = QueryReplaceAction::Start(needle, replacement, regex);
+                        let action = EditorAction::QueryReplace(act).into();
+
+                        return Ok(vec![(unfocus, ctx.clone()), (action, ctx.clone())]);
+                    },
+                    None => {},
+                }
+
                 let text = self.reset().trim();
+                let queries = ctx.get_search_queries(text.to_string().as_str(), self.searchdir);
 
-                store.set_last_search(text);
+                store.set_last_search_queries(text, queries);
 
-                let dir = MoveDirMod::Same;
-                let count = Count::Contextual;
+                // Jump to the match that <C-G> and <C-T> moved the incremental search to.
+                let (dir, count) = match incoffset {
+                    0 => (MoveDirMod::Same, Count::Contextual),
+                    n if n > 0 => (MoveDirMod::Same, Count::Exact(n.unsigned_abs() + 1)),
+                    n => (MoveDirMod::Flip, Count::Exact(n.unsigned_abs())),
+                };
                 let target = EditTarget::Search(SearchType::Regex, dir, count);
 
                 EditorAction::Edit(Default::default(), target).into()
@@ -159,6 +275,8 @@ where
 
         let text = self.reset().trim();
 
+        store.search.set_incsearch(None);
+
         match self.cmdtype {
             CommandType::Search => {
                 store.set_aborted_search(text);
@@ -193,6 +311,7 @@ where
 
         if let Some(text) = text {
             self.set_text(text);
+            self.incsearch(ctx, store);
         }
 
         Ok(vec![])
@@ -259,13 +378,21 @@ where
 
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         if self.focused {
-            let prompt = match (state.cmdtype, state.searchdir) {
-                (CommandType::Command, _) => ":",
-                (CommandType::Search, MoveDir1D::Next) => "/",
-                (CommandType::Search, MoveDir1D::Previous) => "?",
+            let prompt: Cow<'_, str> = match (&state.query, state.cmdtype, state.searchdir) {
+                (Some(QueryReplacePrompt::Search(false)), _, _) => "Query replace: ".into(),
+                (Some(QueryReplacePrompt::Search(true)), _, _) => "Query replace regexp: ".into(),
+                (Some(QueryReplacePrompt::Replacement(false, needle)), _, _) => {
+                    format!("Query replace {needle} with: ").into()
+                },
+                (Some(QueryReplacePrompt::Replacement(true, needle)), _, _) => {
+                    format!("Query replace regexp {needle} with: ").into()
+                },
+                (None, CommandType::Command, _) => ":".into(),
+                (None, CommandType::Search, MoveDir1D::Next) => "/".into(),
+                (None, CommandType::Search, MoveDir1D::Previous) => "?".into(),
             };
 
-            let tbox = TextBox::new().prompt(prompt).oneline();
+            let tbox = TextBox::new().prompt(prompt.as_ref()).oneline();
             let tbox_state = match state.cmdtype {
                 CommandType::Command => &mut state.tbox_cmd,
                 CommandType::Search => &mut state.tbox_search,
@@ -286,3 +413,37 @@ where
         CommandBar::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editing::action::InsertTextAction;
+    use crate::editing::application::EmptyInfo;
+    use crate::env::vim::VimContext;
+
+    #[test]
+    fn test_incsearch_case() {
+        let mut store = Store::<EmptyInfo>::default();
+        let mut cmdbar = CommandBarState::new(&mut store);
+        let mut ctx = VimContext::<EmptyInfo>::default();
+        ctx.persist.regexsearch_inc = true;
+
+        let act = InsertTextAction::Transcribe("foo".into(), MoveDir1D::Previous, 1.into());
+        let act = EditorAction::InsertText(act);
+
+        // Searches are case sensitive by default.
+        cmdbar.set_type(CommandType::Search, MoveDir1D::Next);
+        cmdbar.editor_command(&act, &ctx, &mut store).unwrap();
+        let hl = store.search.get_highlight().unwrap();
+        assert!(hl.needle.is_match("foo"));
+        assert!(!hl.needle.is_match("FOO"));
+
+        // The preview follows the case setting of the searched buffer.
+        cmdbar.reset();
+        cmdbar.set_search_case(SearchCase::Insensitive);
+        cmdbar.editor_command(&act, &ctx, &mut store).unwrap();
+        let hl = store.search.get_highlight().unwrap();
+        assert!(hl.needle.is_match("foo"));
+        assert!(hl.needle.is_match("FOO"));
+    }
+}
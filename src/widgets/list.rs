@@ -52,12 +52,15 @@ use crate::editing::{
         Editable,
         EditorAction,
         EditorActions,
+        FoldAction,
         HistoryAction,
         InfoMessage,
         InsertTextAction,
         Jumpable,
+        MarkRingAction,
         PromptAction,
         Promptable,
+        QueryReplaceAction,
         Scrollable,
         Searchable,
         SelectionAction,
@@ -89,6 +92,7 @@ use crate::editing::{
         Register,
         ScrollSize,
         ScrollStyle,
+        SearchCase,
         SearchType,
         TargetShape,
         ViewportContext,
@@ -399,6 +403,7 @@ where
             // These movements don't map meaningfully onto a list.
             MoveType::BufferByteOffset => None,
             MoveType::Column(_, _) => None,
+            MoveType::Fold(_) => None,
             MoveType::ItemMatch => None,
             MoveType::LineColumnOffset => None,
             MoveType::LinePercent => None,
@@ -516,6 +521,8 @@ where
 
         match range {
             RangeType::Bracketed(_, _) => None,
+            RangeType::BlankLines => None,
+            RangeType::HorizontalSpace => None,
             RangeType::Item => None,
             RangeType::Quote(_) => None,
             RangeType::Word(_) => None,
@@ -682,7 +689,7 @@ where
                                 let lsearch = store.registers.get(&Register::LastSearch)?;
                                 let lsearch = lsearch.value.to_string();
 
-                                Regex::new(lsearch.as_ref())?
+                                ctx.compile_search_regex(lsearch.as_ref(), SearchCase::Sensitive)?
                             },
                         };
 
@@ -753,7 +760,7 @@ where
                                 let lsearch = store.registers.get(&Register::LastSearch)?;
                                 let lsearch = lsearch.value.to_string();
 
-                                Regex::new(lsearch.as_ref())?
+                                ctx.compile_search_regex(lsearch.as_ref(), SearchCase::Sensitive)?
                             },
                         };
 
@@ -799,6 +806,12 @@ where
 
                 return Ok(info);
             },
+            EditAction::Fold => {
+                let msg = "Cannot fold items inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
 
             // Everything else is a modifying action.
             EditAction::ChangeCase(_) => Err(EditError::ReadOnly),
@@ -807,6 +820,7 @@ where
             EditAction::Format => Err(EditError::ReadOnly),
             EditAction::Indent(_) => Err(EditError::ReadOnly),
             EditAction::Join(_) => Err(EditError::ReadOnly),
+            EditAction::Prefix(_) => Err(EditError::ReadOnly),
             EditAction::Replace(_) => Err(EditError::ReadOnly),
         }
     }
@@ -819,6 +833,37 @@ where
         Ok(None)
     }
 
+    fn mark_ring(
+        &mut self,
+        act: &MarkRingAction,
+        _: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        let cursor = match act {
+            MarkRingAction::Push => {
+                let cursor = Cursor::new(self.cursor.position, 0);
+                store.cursors.push_mark(self.id.clone(), cursor);
+
+                return Ok(None);
+            },
+            MarkRingAction::Pop => store.cursors.pop_mark(self.id.clone())?,
+            MarkRingAction::PopGlobal => store.cursors.pop_global_mark(self.id.clone())?,
+        };
+
+        self.cursor = cursor.y.into();
+
+        Ok(None)
+    }
+
+    fn query_replace(
+        &mut self,
+        _: &QueryReplaceAction,
+        _: &C,
+        _: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        Err(EditError::ReadOnly)
+    }
+
     fn complete(
         &mut self,
         _: &CompletionType,
@@ -833,6 +878,13 @@ where
         Err(err)
     }
 
+    fn fold(&mut self, _: &FoldAction, _: &C, _: &mut Store<I>) -> EditResult<EditInfo, I> {
+        let msg = "Cannot fold items inside a list";
+        let err = EditError::Failure(msg.into());
+
+        Err(err)
+    }
+
     fn insert_text(
         &mut self,
         _: &InsertTextAction,
@@ -859,6 +911,7 @@ where
     ) -> EditResult<EditInfo, I> {
         match act {
             HistoryAction::Checkpoint => Ok(None),
+            HistoryAction::LineUndo => Err(EditError::Failure("Nothing to undo".into())),
             HistoryAction::Undo(_) => Err(EditError::Failure("Nothing to undo".into())),
             HistoryAction::Redo(_) => Err(EditError::Failure("Nothing to redo".into())),
         }
@@ -921,12 +974,20 @@ where
         match act {
             EditorAction::Cursor(act) => self.cursor_command(act, ctx, store),
             EditorAction::Edit(ea, et) => self.edit(&ctx.resolve(ea), et, ctx, store),
+            EditorAction::Fold(act) => self.fold(act, ctx, store),
             EditorAction::History(act) => self.history_command(act, ctx, store),
             EditorAction::InsertText(act) => self.insert_text(act, ctx, store),
             EditorAction::Mark(name) => self.mark(ctx.resolve(name), ctx, store),
+            EditorAction::MarkRing(act) => self.mark_ring(act, ctx, store),
+            EditorAction::QueryReplace(act) => self.query_replace(act, ctx, store),
             EditorAction::Selection(act) => self.selection_command(act, ctx, store),
 
             EditorAction::Complete(sel, ct, disp) => self.complete(sel, ct, disp, ctx, store),
+            EditorAction::ClearSearchHighlight => {
+                store.search.nohlsearch();
+
+                Ok(None)
+            },
         }
     }
 }
@@ -1229,6 +1290,10 @@ where
         self.items.get(self.cursor.position).and_then(ListItem::get_word)
     }
 
+    fn get_cursor_line(&self) -> Option<String> {
+        self.items.get(self.cursor.position).map(ToString::to_string)
+    }
+
     fn get_selected_word(&self) -> Option<String> {
         self.items.get(self.cursor.position).and_then(ListItem::get_word)
     }
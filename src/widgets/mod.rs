@@ -38,6 +38,7 @@ use crate::editing::{
         MoveDir2D,
         MovePosition,
         ScrollSize,
+        SearchCase,
         WordStyle,
         WriteFlags,
     },
@@ -134,6 +135,11 @@ pub trait WindowOps<I: ApplicationInfo>: TerminalCursor {
     /// the provided set of flags.
     fn close(&mut self, flags: CloseFlags, store: &mut Store<I>) -> bool;
 
+    /// Called when this window is about to be replaced by another one in the same part of the
+    /// screen, such as when switching to another buffer or to the alternate window.
+    #[allow(unused_variables)]
+    fn leave(&mut self, store: &mut Store<I>) {}
+
     /// Draw this window into the buffer for the prescribed area.
     fn draw(&mut self, area: Rect, buf: &mut Buffer, focused: bool, store: &mut Store<I>);
 
@@ -143,6 +149,16 @@ pub trait WindowOps<I: ApplicationInfo>: TerminalCursor {
     /// Returns the word following the current cursor position in this window.
     fn get_cursor_word(&self, style: &WordStyle) -> Option<String>;
 
+    /// Returns the line that the cursor is currently positioned on in this window.
+    fn get_cursor_line(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns how letter case gets treated when searching this window.
+    fn get_search_case(&self) -> SearchCase {
+        SearchCase::default()
+    }
+
     /// Returns the currently selected text in this window.
     fn get_selected_word(&self) -> Option<String>;
 
@@ -39,6 +39,7 @@ use crate::editing::{
         Editable,
         EditorAction,
         InfoMessage,
+        InsertTextAction,
         Jumpable,
         PromptAction,
         Promptable,
@@ -687,6 +688,17 @@ where
         self.cmdbar.set_type(ct, dir);
         self.clear_message();
 
+        if let Some(case) = self.current_window().map(WindowOps::get_search_case) {
+            self.cmdbar.set_search_case(case);
+        }
+
+        Ok(None)
+    }
+
+    fn focus_query_replace(&mut self, regex: bool) -> EditResult<EditInfo, I> {
+        self.focus_command(CommandType::Search, MoveDir1D::Next)?;
+        self.cmdbar.set_query_replace(regex);
+
         Ok(None)
     }
 
@@ -702,10 +714,47 @@ where
         &mut self,
         act: &CommandBarAction,
         ctx: &C,
+        store: &mut Store<I>,
     ) -> EditResult<EditInfo, I> {
         match act {
             CommandBarAction::Focus(ct) => self.focus_command(*ct, ctx.get_search_regex_dir()),
+            CommandBarAction::QueryReplace(regex) => self.focus_query_replace(*regex),
             CommandBarAction::Unfocus => self.focus_window(),
+            CommandBarAction::InsertWord(style) => {
+                let text = self.current_window().and_then(|w| w.get_cursor_word(style));
+
+                self.insert_command(text, ctx, store)
+            },
+            CommandBarAction::InsertLine => {
+                let text = self.current_window().and_then(WindowOps::get_cursor_line);
+
+                self.insert_command(text, ctx, store)
+            },
+            CommandBarAction::IncSearch(dir) => {
+                store.search.move_incsearch(*dir, 1);
+
+                Ok(None)
+            },
+        }
+    }
+
+    fn insert_command<C: EditContext>(
+        &mut self,
+        text: Option<String>,
+        ctx: &C,
+        store: &mut Store<I>,
+    ) -> EditResult<EditInfo, I> {
+        if self.focused != CurrentFocus::Command {
+            return Ok(None);
+        }
+
+        if let Some(text) = text {
+            let act = InsertTextAction::Transcribe(text, MoveDir1D::Previous, Count::Exact(1));
+            let act = EditorAction::InsertText(act);
+
+            self.cmdbar.editor_command(&act, ctx, store)
+        } else {
+            Ok(None)
         }
     }
 
@@ -8,6 +8,11 @@
 //! - Toggling wrapped and non-wrapped views of the buffer's
 //! - Scrolling through the buffer's contents
 //! - Rendering line annotations in left and right gutters
+//! - Numbering lines, and showing signs next to them
+//! - Expanding tabs, laying out wide characters, and showing control characters as `^X`
+//! - Highlighting the matches of the last search, and of incremental searches as they get typed
+//! - Drawing virtual text inline, at the end of lines, or on its own rows above lines
+//! - Showing each closed fold as a single summary row
 //!
 //! [EditBuffer]: crate::editing::buffer::EditBuffer
 //!
@@ -35,16 +40,17 @@
 //! assert_eq!(tbox.has_lines(6), 6);
 //! assert_eq!(tbox.has_lines(8), 6);
 //! ```
-use std::convert::TryInto;
 use std::iter::Iterator;
 use std::marker::PhantomData;
+use std::ops::Deref;
 
 use ropey::RopeSlice;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use tui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Modifier, Style},
+    style::{Color, Modifier, Style},
     widgets::{Block, StatefulWidget, Widget},
 };
 
@@ -68,6 +74,7 @@ use crate::editing::{
         Axis,
         CloseFlags,
         Count,
+        Mark,
         MoveDir1D,
         MoveDir2D,
         MoveDirMod,
@@ -75,18 +82,21 @@ use crate::editing::{
         PositionList,
         ScrollSize,
         ScrollStyle,
+        SearchCase,
         TargetShape,
         ViewportContext,
         WordStyle,
         Wrappable,
         WriteFlags,
     },
-    buffer::{CursorGroupId, FollowersInfo, HighlightInfo},
+    buffer::{CursorGroupId, EditBuffer, FollowersInfo, HighlightInfo},
     completion::CompletionList,
     context::EditContext,
     cursor::Cursor,
-    rope::EditRope,
-    store::{SharedBuffer, Store},
+    fold::Fold,
+    lineinfo::{RangeId, VirtualSpace},
+    rope::{layout::LineLayout, EditRope},
+    store::{SearchHighlight, SharedBuffer, Store},
 };
 
 use super::{ScrollActions, TerminalCursor, WindowOps};
@@ -125,11 +135,172 @@ impl RightGutterInfo {
     }
 }
 
+/// Line annotation shown in the sign column, such as a breakpoint or a diagnostic marker.
+///
+/// Only the first [SIGN_COLUMN_WIDTH] columns of the text are shown.
+pub struct SignInfo {
+    text: String,
+    style: Style,
+}
+
+impl SignInfo {
+    /// Create a new instance.
+    pub fn new(text: String, style: Style) -> Self {
+        SignInfo { text, style }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer) {
+        let _ = buf.set_stringn(area.x, area.y, &self.text, area.width as usize, self.style);
+    }
+}
+
+/// How many columns the sign column takes up when it's shown.
+pub const SIGN_COLUMN_WIDTH: u16 = 2;
+
+/// How a [TextBox] numbers the lines it shows.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LineNumbers {
+    /// Don't show line numbers.
+    #[default]
+    None,
+
+    /// Show the number of each line.
+    Absolute,
+
+    /// Show how many lines away from the cursor's line each line is.
+    Relative,
+
+    /// Show how many lines away from the cursor's line each line is, and the number of the
+    /// cursor's line.
+    Hybrid,
+}
+
+impl LineNumbers {
+    /*
+     * The width of the number column, including the space separating it from the text.
+     */
+    fn width(&self, lines: usize) -> u16 {
+        if let LineNumbers::None = self {
+            return 0;
+        }
+
+        let digits = lines.max(1).to_string().len().max(3);
+
+        (digits + 1) as u16
+    }
+
+    fn text(&self, line: usize, cursor: usize, width: usize) -> String {
+        let w = width.saturating_sub(1);
+
+        match self {
+            LineNumbers::None => String::new(),
+            LineNumbers::Absolute => format!("{:>w$} ", line + 1),
+            LineNumbers::Relative => format!("{:>w$} ", line.abs_diff(cursor)),
+            LineNumbers::Hybrid if line == cursor => format!("{:<w$} ", line + 1),
+            LineNumbers::Hybrid => format!("{:>w$} ", line.abs_diff(cursor)),
+        }
+    }
+}
+
+/// Where [VirtualText] gets drawn, relative to the position it's attached to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VirtualTextPosition {
+    /// Draw the text just before the character it's attached to, shifting the rest of the line
+    /// over to make room for it.
+    Inline,
+
+    /// Draw the text after the end of the line.
+    EndOfLine,
+
+    /// Draw the text on its own row above the line.
+    Above,
+}
+
+/// Text that gets drawn alongside a buffer's contents without being a part of them, such as type
+/// hints, blame information, or diagnostics.
+///
+/// Virtual text is attached to a position within the buffer, and moves along with it as the
+/// buffer is edited. Cursors move over it as if it weren't there.
+#[derive(Clone, Debug)]
+pub struct VirtualText {
+    text: String,
+    style: Style,
+    position: VirtualTextPosition,
+}
+
+impl VirtualText {
+    /// Create a new instance.
+    pub fn new(text: String, style: Style, position: VirtualTextPosition) -> Self {
+        VirtualText { text, style, position }
+    }
+}
+
+impl VirtualSpace for VirtualText {
+    fn inline_width(&self) -> usize {
+        match self.position {
+            VirtualTextPosition::Inline => UnicodeWidthStr::width(self.text.as_str()),
+            VirtualTextPosition::EndOfLine | VirtualTextPosition::Above => 0,
+        }
+    }
+
+    fn rows_above(&self) -> usize {
+        match self.position {
+            VirtualTextPosition::Above => 1,
+            VirtualTextPosition::Inline | VirtualTextPosition::EndOfLine => 0,
+        }
+    }
+}
+
+/*
+ * The virtual text attached to a single line. Inline text is sorted by the index of the
+ * character that it gets drawn before.
+ */
+#[derive(Default)]
+struct LineVirtualText {
+    inline: Vec<(usize, VirtualText)>,
+    eol: Vec<VirtualText>,
+    above: Vec<VirtualText>,
+}
+
+impl LineVirtualText {
+    fn new<I: ApplicationInfo>(text: &EditBuffer<I>, line: usize) -> Self {
+        let mut vt = LineVirtualText::default();
+
+        for range in text.get_line_range_info::<VirtualText>(line) {
+            let info = range.info.clone();
+
+            match info.position {
+                VirtualTextPosition::Inline => vt.inline.push((range.start.x, info)),
+                VirtualTextPosition::EndOfLine => vt.eol.push(info),
+                VirtualTextPosition::Above => vt.above.push(info),
+            }
+        }
+
+        vt.inline.sort_by_key(|(x, _)| *x);
+
+        vt
+    }
+}
+
+/*
+ * Where the columns to the left and right of the text get drawn.
+ */
+struct Gutters {
+    left: Rect,
+    signs: Rect,
+    numbers: Rect,
+    right: Rect,
+    numbering: LineNumbers,
+}
+
 /// Persistent state for [TextBox].
 pub struct TextBoxState<I: ApplicationInfo = EmptyInfo> {
     buffer: SharedBuffer<I>,
     group_id: CursorGroupId,
     readonly: bool,
+    search: Option<SearchHighlight>,
+    numbering: LineNumbers,
+    signs: bool,
 
     viewctx: ViewportContext<Cursor>,
     term_cursor: (u16, u16),
@@ -144,24 +315,491 @@ pub struct TextBox<'a, I: ApplicationInfo = EmptyInfo> {
     lgutter_width: u16,
     rgutter_width: u16,
 
+    search_style: Style,
+    incsearch_style: Style,
+    fold_style: Style,
+
     _pc: PhantomData<I>,
 }
 
+/*
+ * Search matches to highlight, followed by the match that an incremental search would jump to.
+ */
+type SearchInfo = (HighlightInfo, HighlightInfo);
+
+/*
+ * A row of the text area: either a part of a line, a virtual line shown above it, or the summary
+ * of a closed fold.
+ */
+enum TextRow {
+    Text {
+        lidx: usize,
+        line: usize,
+        start: usize,
+        end: usize,
+        left: usize,
+        cursor_line: bool,
+        first: bool,
+    },
+    Virtual {
+        line: usize,
+        text: VirtualText,
+    },
+    Fold {
+        line: usize,
+        text: String,
+        cursor_line: bool,
+    },
+}
+
+impl TextRow {
+    fn line(&self) -> usize {
+        match self {
+            TextRow::Text { line, .. } => *line,
+            TextRow::Virtual { line, .. } => *line,
+            TextRow::Fold { line, .. } => *line,
+        }
+    }
+
+    fn fold<I: ApplicationInfo>(
+        text: &EditBuffer<I>,
+        fold: &Fold,
+        s: &RopeSlice,
+        cursor: usize,
+    ) -> Self {
+        let level = text
+            .get_folds()
+            .iter()
+            .filter(|f| f.start() <= fold.start() && f.end() >= fold.end())
+            .count();
+        let summary = s.to_string().replace('\t', " ");
+        let text = format!("+-{}{:>3} lines: {}", "-".repeat(level), fold.lines(), summary.trim());
+
+        TextRow::Fold {
+            line: fold.start(),
+            text,
+            cursor_line: fold.contains(cursor),
+        }
+    }
+}
+
+/*
+ * Where a piece of virtual text gets drawn, in screen columns relative to the line start.
+ */
+struct VirtualCell {
+    text: String,
+    style: Style,
+    col: usize,
+    width: usize,
+}
+
+/*
+ * A line laid out for drawing, along with the virtual text shown on it. End-of-line virtual text
+ * goes after the line end, separated by a space.
+ */
+struct LineView {
+    layout: LineLayout,
+    virt: Vec<VirtualCell>,
+}
+
+impl LineView {
+    fn new(s: &RopeSlice<'_>, tabstop: usize, wrap: Option<usize>, vt: &LineVirtualText) -> Self {
+        let inline = vt
+            .inline
+            .iter()
+            .map(|(x, text)| (*x, UnicodeWidthStr::width(text.text.as_str())))
+            .collect::<Vec<_>>();
+        let mut layout = LineLayout::new(s, tabstop, wrap, &inline);
+        layout.above = vt.above.len();
+
+        let mut virt = layout
+            .inline
+            .iter()
+            .zip(vt.inline.iter())
+            .map(|(cell, (_, text))| {
+                VirtualCell {
+                    text: text.text.clone(),
+                    style: text.style,
+                    col: cell.col,
+                    width: cell.width,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut ecol = layout.end + 1;
+
+        for text in vt.eol.iter() {
+            let width = UnicodeWidthStr::width(text.text.as_str());
+
+            virt.push(VirtualCell {
+                text: text.text.clone(),
+                style: text.style,
+                col: ecol,
+                width,
+            });
+
+            ecol += width + 1;
+        }
+
+        LineView { layout, virt }
+    }
+}
+
+impl Deref for LineView {
+    type Target = LineLayout;
+
+    fn deref(&self) -> &Self::Target {
+        &self.layout
+    }
+}
+
+/*
+ * Characters `start..end` of a line, drawn with column `left` of the line at screen position
+ * `(x, y)`, and limited to `width` cells.
+ */
+struct LineRow<'a> {
+    layout: &'a LineView,
+    line: usize,
+    start: usize,
+    end: usize,
+    left: usize,
+    width: usize,
+    x: u16,
+    y: u16,
+}
+
+impl<'a> LineRow<'a> {
+    fn draw(&self, buf: &mut Buffer) {
+        let right = self.left + self.width;
+        let mut s = String::new();
+        let mut used = 0;
+
+        for cell in &self.layout.cells[self.start..self.end] {
+            let end = cell.col + cell.width;
+
+            if cell.width == 0 {
+                if used > 0 && cell.col <= right {
+                    s.push(cell.c);
+                }
+
+                continue;
+            }
+
+            if end <= self.left {
+                continue;
+            }
+
+            if cell.col >= right {
+                break;
+            }
+
+            let x1 = cell.col.max(self.left) - self.left;
+            let x2 = end.min(right) - self.left;
+
+            s.push_str(&" ".repeat(x1.saturating_sub(used)));
+
+            let text = cell.text();
+
+            if cell.col >= self.left && end <= right {
+                s.push_str(&text);
+            } else if text.chars().count() == cell.width {
+                let skip = self.left.saturating_sub(cell.col);
+                s.extend(text.chars().skip(skip).take(x2 - x1));
+            } else {
+                // Wide characters that are partially offscreen get blanked out.
+                s.push_str(&" ".repeat(x2 - x1));
+            }
+
+            used = x2;
+        }
+
+        let _ = buf.set_stringn(self.x, self.y, s, self.width, Style::default());
+
+        for vc in self.layout.virt.iter() {
+            let end = vc.col + vc.width;
+
+            if end <= self.left || vc.col >= right {
+                continue;
+            }
+
+            // Skip over any part of the text that has been scrolled past.
+            let mut skip = self.left.saturating_sub(vc.col);
+            let text = vc.text.chars().skip_while(|c| {
+                let w = UnicodeWidthChar::width(*c).unwrap_or(0);
+                let skipping = skip > 0;
+                skip = skip.saturating_sub(w);
+                skipping
+            });
+            let text = text.collect::<String>();
+
+            let x1 = vc.col.max(self.left) - self.left;
+            let x2 = end.min(right) - self.left;
+
+            let _ = buf.set_stringn(self.x + x1 as u16, self.y, text, x2 - x1, vc.style);
+        }
+    }
+
+    /*
+     * The onscreen area taken up by characters `h1` through `h2`.
+     */
+    fn area(&self, h1: usize, h2: usize) -> Option<Rect> {
+        let (c1, _) = self.layout.span(h1);
+        let (c2, w2) = self.layout.span(h2);
+
+        let x1 = c1.max(self.left);
+        let x2 = (c2 + w2).min(self.left + self.width);
+
+        if x1 >= x2 {
+            return None;
+        }
+
+        let x = self.x + (x1 - self.left) as u16;
+        let w = (x2 - x1) as u16;
+
+        Rect::new(x, self.y, w, 1).into()
+    }
+
+    fn cursor(&self, idx: usize) -> (u16, u16) {
+        let (col, _) = self.layout.span(idx);
+        let off = col.saturating_sub(self.left) as u16;
+
+        (self.x + off, self.y)
+    }
+}
+
+/*
+ * The column and width of the cursor within its line, when not wrapping.
+ */
+fn cursor_cols<I: ApplicationInfo>(text: &EditBuffer<I>, cursor: &Cursor) -> (usize, usize) {
+    let tabstop = text.get_indent_options().tabstop;
+    let vt = LineVirtualText::new(text, cursor.y);
+
+    text.lines(cursor.y)
+        .next()
+        .map(|s| LineView::new(&s, tabstop, None, &vt).span(cursor.x))
+        .unwrap_or((0, 1))
+}
+
+/*
+ * The places on a line where the top of the viewport can start, as the index of the first
+ * character on each row, along with how many rows get drawn before the next place.
+ *
+ * Virtual text above a line gets drawn along with its first row, and a closed fold takes up a
+ * single row at its first line.
+ */
+fn line_rows<I: ApplicationInfo>(
+    text: &EditBuffer<I>,
+    line: usize,
+    wrap: Option<usize>,
+) -> Vec<(usize, usize)> {
+    if let Some(fold) = text.get_closed_fold(line) {
+        return if line == fold.start() {
+            vec![(0, 1)]
+        } else {
+            vec![]
+        };
+    }
+
+    let s = match text.lines(line).next() {
+        Some(s) => s,
+        None => return vec![],
+    };
+
+    let tabstop = text.get_indent_options().tabstop;
+    let vt = LineVirtualText::new(text, line);
+    let layout = LineView::new(&s, tabstop, wrap, &vt);
+
+    let mut rows = match wrap {
+        Some(width) => layout.rows(width).into_iter().map(|(r, _)| (r.start, 1)).collect(),
+        None => vec![(0, 1)],
+    };
+
+    rows[0].1 += layout.above;
+
+    return rows;
+}
+
+/*
+ * The index of the row within `rows` that column `x` of the line gets drawn on.
+ */
+fn row_index(rows: &[(usize, usize)], x: usize) -> usize {
+    rows.iter().rposition(|(start, _)| *start <= x).unwrap_or(0)
+}
+
+/*
+ * The places where the top of the viewport can start, going down from the one for `cursor`.
+ */
+fn rows_after<'a, I: ApplicationInfo>(
+    text: &'a EditBuffer<I>,
+    cursor: &Cursor,
+    wrap: Option<usize>,
+) -> impl Iterator<Item = (Cursor, usize)> + 'a {
+    let top = cursor.clone();
+
+    (top.y..text.get_lines()).flat_map(move |line| {
+        let mut rows = line_rows(text, line, wrap);
+
+        if line == top.y {
+            let _ = rows.drain(..row_index(&rows, top.x));
+        }
+
+        rows.into_iter().map(move |(x, n)| (Cursor::new(line, x), n))
+    })
+}
+
+/*
+ * The places where the top of the viewport can start, going up from the one for `cursor`.
+ */
+fn rows_before<'a, I: ApplicationInfo>(
+    text: &'a EditBuffer<I>,
+    cursor: &Cursor,
+    wrap: Option<usize>,
+) -> impl Iterator<Item = (Cursor, usize)> + 'a {
+    let bot = cursor.clone();
+
+    (0..=bot.y).rev().flat_map(move |line| {
+        let mut rows = line_rows(text, line, wrap);
+
+        if line == bot.y {
+            rows.truncate(row_index(&rows, bot.x) + 1);
+        }
+
+        rows.into_iter().rev().map(move |(x, n)| (Cursor::new(line, x), n))
+    })
+}
+
+/*
+ * The highest place the top of the viewport can start while showing the cursor's row within
+ * the first `height` rows.
+ */
+fn corner_above<I: ApplicationInfo>(
+    text: &EditBuffer<I>,
+    cursor: &Cursor,
+    height: usize,
+    wrap: Option<usize>,
+) -> Cursor {
+    let mut corner = None;
+    let mut used = 0;
+
+    for (pos, rows) in rows_before(text, cursor, wrap) {
+        if corner.is_some() && used + rows > height {
+            break;
+        }
+
+        used += rows;
+        corner = Some(pos);
+    }
+
+    corner.unwrap_or_else(|| Cursor::new(cursor.y, 0))
+}
+
+/*
+ * Move the top of the viewport by `count` rows.
+ */
+fn scroll_corner<I: ApplicationInfo>(
+    text: &EditBuffer<I>,
+    corner: &mut Cursor,
+    dir: MoveDir1D,
+    count: usize,
+    wrap: Option<usize>,
+) {
+    let mut left = count;
+    let mut pos = None;
+
+    match dir {
+        MoveDir1D::Previous => {
+            for (prev, rows) in rows_before(text, corner, wrap).skip(1) {
+                if left == 0 {
+                    break;
+                }
+
+                left = left.saturating_sub(rows);
+                pos = Some(prev);
+            }
+        },
+        MoveDir1D::Next => {
+            let mut iter = rows_after(text, corner, wrap);
+            let mut rows = iter.next().map(|(_, rows)| rows).unwrap_or(0);
+
+            while left > 0 {
+                match iter.next() {
+                    Some((next, n)) => {
+                        left = left.saturating_sub(rows);
+                        rows = n;
+                        pos = Some(next);
+                    },
+                    None => break,
+                }
+            }
+        },
+    }
+
+    if let Some(pos) = pos {
+        corner.set_y(pos.y);
+
+        if wrap.is_some() {
+            corner.set_x(pos.x);
+        }
+    }
+}
+
+/*
+ * If wrapped lines or virtual text push the cursor's row past the bottom of the viewport, move
+ * the top of the viewport down so that it's visible again.
+ */
+fn shift_corner_rows<I: ApplicationInfo>(
+    text: &EditBuffer<I>,
+    cursor: &Cursor,
+    corner: &mut Cursor,
+    height: usize,
+    wrap: Option<usize>,
+) {
+    let top = match wrap {
+        Some(_) => corner.clone(),
+        None => Cursor::new(corner.y, 0),
+    };
+
+    if *cursor < top {
+        return;
+    }
+
+    let used = rows_after(text, &top, wrap)
+        .take_while(|(pos, _)| pos <= cursor)
+        .map(|(_, rows)| rows)
+        .sum::<usize>();
+
+    if used > height {
+        let pos = corner_above(text, cursor, height, wrap);
+
+        corner.set_y(pos.y);
+
+        if wrap.is_some() {
+            corner.set_x(pos.x);
+        }
+    }
+}
+
 /*
  * If the cursor has moved outside of the viewport, update the corner of the viewport so that the
  * cursor is visible onscreen again.
  */
-fn shift_corner_nowrap(cursor: &Cursor, corner: &mut Cursor, width: usize, height: usize) {
+fn shift_corner_nowrap(
+    cursor: &Cursor,
+    (col, cw): (usize, usize),
+    corner: &mut Cursor,
+    width: usize,
+    height: usize,
+) {
     if cursor.y < corner.y {
         corner.set_y(cursor.y);
     } else if cursor.y >= corner.y + height {
         corner.set_y(cursor.y - height + 1);
     }
 
-    if cursor.x < corner.x {
-        corner.set_x(cursor.x);
-    } else if cursor.x >= corner.x + width {
-        corner.set_x(cursor.x - width + 1);
+    if col < corner.x {
+        corner.set_x(col);
+    } else if col + cw > corner.x + width {
+        corner.set_x((col + cw).saturating_sub(width));
     }
 }
 
@@ -184,34 +822,92 @@ fn shift_corner_oneline(cursor: &Cursor, corner: &mut Cursor) {
     }
 }
 
-fn shift_corner(
+fn shift_corner<I: ApplicationInfo>(
     viewctx: &mut ViewportContext<Cursor>,
     cursor: &Cursor,
-    width: usize,
-    height: usize,
+    text: &EditBuffer<I>,
 ) {
+    let (width, height) = viewctx.dimensions;
+
     if viewctx.wrap {
         shift_corner_wrap(cursor, &mut viewctx.corner, height);
     } else {
-        shift_corner_nowrap(cursor, &mut viewctx.corner, width, height);
+        let cols = cursor_cols(text, cursor);
+        shift_corner_nowrap(cursor, cols, &mut viewctx.corner, width, height);
     }
+
+    shift_corner_rows(text, cursor, &mut viewctx.corner, height, viewctx.wrap.then_some(width));
 }
 
 /*
  * If the cursor has moved outside of the viewport, move the cursor back within the boundaries of
  * the viewport, so it is visible onscreen again.
+ *
+ * Wrapped lines and virtual text count towards the height of the viewport. When lines aren't
+ * wrapped, the corner's column is a screen column, and the cursor gets moved to the first or last
+ * character that is entirely onscreen.
  */
-fn shift_cursor(cursor: &mut Cursor, corner: &Cursor, width: usize, height: usize) {
+fn shift_cursor<I: ApplicationInfo>(
+    cursor: &mut Cursor,
+    viewctx: &ViewportContext<Cursor>,
+    text: &EditBuffer<I>,
+) {
+    let corner = &viewctx.corner;
+    let (width, height) = viewctx.dimensions;
+    let wrap = viewctx.wrap.then_some(width);
+
+    // Find the last row that is shown onscreen.
+    let mut last = Cursor::new(corner.y, corner.x);
+    let mut used = 0;
+
+    for (pos, rows) in rows_after(text, corner, wrap) {
+        if used > 0 && used + rows > height {
+            break;
+        }
+
+        used += rows;
+        last = pos;
+    }
+
     if cursor.y < corner.y {
         cursor.set_y(corner.y);
-    } else if cursor.y >= corner.y + height {
-        cursor.set_y(corner.y + height - 1);
+    } else if cursor.y > last.y {
+        cursor.set_y(last.y);
     }
 
-    if cursor.x < corner.x {
-        cursor.set_x(corner.x);
-    } else if cursor.x >= corner.x + width {
-        cursor.set_x(corner.x + width - 1);
+    if viewctx.wrap {
+        if cursor.y == corner.y && cursor.x < corner.x {
+            cursor.set_x(corner.x);
+        }
+
+        if cursor.y == last.y {
+            // Move the cursor back onto the last row if it's on one below it.
+            let rows = line_rows(text, last.y, wrap);
+            let next = rows.iter().map(|(start, _)| *start).find(|start| *start > last.x);
+
+            if let Some(next) = next.filter(|next| cursor.x >= *next) {
+                cursor.set_x(next - 1);
+            }
+        }
+
+        return;
+    }
+
+    let tabstop = text.get_indent_options().tabstop;
+    let vt = LineVirtualText::new(text, cursor.y);
+    let layout = match text.lines(cursor.y).next() {
+        Some(s) => LineView::new(&s, tabstop, None, &vt),
+        None => return,
+    };
+
+    let left = corner.x;
+    let right = corner.x + width;
+    let (col, cw) = layout.span(cursor.x);
+
+    if col < left {
+        cursor.set_x(layout.first_within(left));
+    } else if col + cw > right {
+        cursor.set_x(layout.last_within(left, right));
     }
 }
 
@@ -230,6 +926,9 @@ where
             buffer,
             group_id,
             readonly: false,
+            search: None,
+            numbering: LineNumbers::None,
+            signs: false,
 
             viewctx,
             term_cursor: (0, 0),
@@ -292,6 +991,58 @@ where
         self.buffer.write().unwrap().set_line_info(line, info);
     }
 
+    /// Create or update the sign shown next to a line when the sign column is enabled.
+    pub fn set_sign(&mut self, line: usize, s: String, style: Option<Style>) {
+        let style = style.unwrap_or_default();
+        let info = SignInfo::new(s, style);
+
+        self.buffer.write().unwrap().set_line_info(line, info);
+    }
+
+    /// Remove the sign shown next to a line.
+    pub fn clear_sign(&mut self, line: usize) {
+        let _ = self.buffer.write().unwrap().remove_line_info::<SignInfo>(line);
+    }
+
+    /// Attach [VirtualText] to a position in the buffer, and return an identifier that can be
+    /// used to remove it later with [remove_virtual_text](Self::remove_virtual_text).
+    ///
+    /// Inline text gets drawn before the character at `cursor`, while text at the end of or
+    /// above the line only uses the cursor's line.
+    pub fn add_virtual_text(&mut self, cursor: Cursor, text: VirtualText) -> RangeId {
+        let mut buffer = self.buffer.write().unwrap();
+
+        buffer.set_range_info_space::<VirtualText>();
+        buffer.add_range_info(cursor.clone(), cursor, text)
+    }
+
+    /// Remove the [VirtualText] added with the given identifier, and return it if it hasn't
+    /// already been removed.
+    pub fn remove_virtual_text(&mut self, id: RangeId) -> Option<VirtualText> {
+        let range = self.buffer.write().unwrap().remove_range_info::<VirtualText>(id)?;
+
+        Some(range.info)
+    }
+
+    /// Remove all of the [VirtualText] attached to the buffer.
+    pub fn clear_virtual_text(&mut self) {
+        let _ = self.buffer.write().unwrap().clear_range_info::<VirtualText>();
+    }
+
+    /// Control whether the text box shows a column for [signs](SignInfo) to the left of the
+    /// text.
+    pub fn set_sign_column(&mut self, signs: bool) {
+        self.signs = signs;
+    }
+
+    /// Set how the text box numbers the lines it shows.
+    ///
+    /// The number column is made wide enough for the number of lines in the buffer, and is left
+    /// blank next to wrapped continuations of a line.
+    pub fn set_line_numbers(&mut self, numbering: LineNumbers) {
+        self.numbering = numbering;
+    }
+
     /// Control whether the text box should wrap long lines when displaying them.
     pub fn set_wrap(&mut self, wrap: bool) {
         self.viewctx.set_wrap(wrap);
@@ -330,12 +1081,14 @@ where
             }
 
             let mut fline = false;
+            let buffer = self.buffer.read().unwrap();
+            let tabstop = buffer.get_indent_options().tabstop;
 
-            for line in self.buffer.read().unwrap().lines(0) {
-                let clen = line.len_chars();
-                count += 1;
-                count += clen.saturating_sub(1) / width;
-                fline |= clen > 0 && clen % width == 0;
+            for (idx, line) in buffer.lines(0).enumerate() {
+                let vt = LineVirtualText::new(&buffer, idx);
+                let layout = LineView::new(&line, tabstop, Some(width), &vt);
+                count += layout.height();
+                fline |= layout.end > 0 && layout.end % width == 0;
 
                 if count >= max {
                     return max;
@@ -458,11 +1211,16 @@ where
             ScrollSize::Page => count.saturating_mul(width),
         };
 
+        let wrap = self.viewctx.wrap.then_some(width);
+        let mut cursor = self.get_cursor();
+        let mut buffer = self.buffer.write().unwrap();
+        let corner = &mut self.viewctx.corner;
+
         match (dir, self.viewctx.wrap) {
-            (MoveDir2D::Up, _) => self.viewctx.corner.up(rows),
-            (MoveDir2D::Down, _) => self.viewctx.corner.down(rows),
-            (MoveDir2D::Left, false) => self.viewctx.corner.left(cols),
-            (MoveDir2D::Right, false) => self.viewctx.corner.right(cols),
+            (MoveDir2D::Up, _) => scroll_corner(&buffer, corner, MoveDir1D::Previous, rows, wrap),
+            (MoveDir2D::Down, _) => scroll_corner(&buffer, corner, MoveDir1D::Next, rows, wrap),
+            (MoveDir2D::Left, false) => corner.left(cols),
+            (MoveDir2D::Right, false) => corner.right(cols),
             (MoveDir2D::Left | MoveDir2D::Right, true) => (),
         };
 
@@ -472,11 +1230,9 @@ where
          * column, though, so we clamp it after shifting it. Since the cursor should never
          * be off-screen, this also sets a boundary of how far we can move the viewport.
          */
-        let mut cursor = self.get_cursor();
-        let mut buffer = self.buffer.write().unwrap();
-        shift_cursor(&mut cursor, &self.viewctx.corner, width, height);
+        shift_cursor(&mut cursor, &self.viewctx, &buffer);
         buffer.clamp(&mut cursor, c2cgi!(self, ctx));
-        shift_corner(&mut self.viewctx, &cursor, width, height);
+        shift_corner(&mut self.viewctx, &cursor, &buffer);
         buffer.set_leader(self.group_id, cursor);
 
         Ok(None)
@@ -495,34 +1251,39 @@ where
 
         let (width, height) = self.viewctx.dimensions;
         let cursor = self.get_cursor();
-        shift_corner(&mut self.viewctx, &cursor, width, height);
+        let buffer = self.buffer.read().unwrap();
+        let (col, cw) = cursor_cols(&buffer, &cursor);
+        shift_corner(&mut self.viewctx, &cursor, &buffer);
 
         match (axis, pos) {
             (Axis::Horizontal, MovePosition::Beginning) => {
-                self.viewctx.corner.set_x(cursor.x);
+                self.viewctx.corner.set_x(col);
             },
             (Axis::Horizontal, MovePosition::Middle) => {
-                let off = cursor.x.saturating_add(1).saturating_sub(width / 2);
+                let off = col.saturating_add(cw).saturating_sub(width / 2);
 
                 self.viewctx.corner.set_x(off);
             },
             (Axis::Horizontal, MovePosition::End) => {
-                let off = cursor.x.saturating_add(1).saturating_sub(width);
+                let off = col.saturating_add(cw).saturating_sub(width);
 
                 self.viewctx.corner.set_x(off);
             },
-            (Axis::Vertical, MovePosition::Beginning) => {
-                self.viewctx.corner.set_y(cursor.y);
-            },
-            (Axis::Vertical, MovePosition::Middle) => {
-                let off = cursor.y.saturating_add(1).saturating_sub(height / 2);
+            (Axis::Vertical, pos) => {
+                let rows = match pos {
+                    MovePosition::Beginning => 1,
+                    MovePosition::Middle => height / 2,
+                    MovePosition::End => height,
+                };
 
-                self.viewctx.corner.set_y(off);
-            },
-            (Axis::Vertical, MovePosition::End) => {
-                let off = cursor.y.saturating_add(1).saturating_sub(height);
+                let wrap = self.viewctx.wrap.then_some(width);
+                let corner = corner_above(&buffer, &cursor, rows, wrap);
+
+                self.viewctx.corner.set_y(corner.y);
 
-                self.viewctx.corner.set_y(off);
+                if self.viewctx.wrap {
+                    self.viewctx.corner.set_x(corner.x);
+                }
             },
         }
 
@@ -540,26 +1301,26 @@ where
         let max = buffer.get_lines();
         let line = ctx.resolve(count).min(max).saturating_sub(1);
 
-        let height = self.viewctx.get_height();
+        let (width, height) = self.viewctx.dimensions;
+        let cursor = Cursor::new(line, 0);
 
-        buffer.set_leader(self.group_id, Cursor::new(line, 0));
+        let rows = match pos {
+            MovePosition::Beginning => 1,
+            MovePosition::Middle => height / 2,
+            MovePosition::End => height,
+        };
 
-        match pos {
-            MovePosition::Beginning => {
-                self.viewctx.corner.set_y(line);
-            },
-            MovePosition::Middle => {
-                let off = line.saturating_add(1).saturating_sub(height / 2);
+        let wrap = self.viewctx.wrap.then_some(width);
+        let corner = corner_above(&buffer, &cursor, rows, wrap);
 
-                self.viewctx.corner.set_y(off);
-            },
-            MovePosition::End => {
-                let off = line.saturating_add(1).saturating_sub(height);
+        self.viewctx.corner.set_y(corner.y);
 
-                self.viewctx.corner.set_y(off);
-            },
+        if self.viewctx.wrap {
+            self.viewctx.corner.set_x(corner.x);
         }
 
+        buffer.set_leader(self.group_id, cursor);
+
         Ok(None)
     }
 }
@@ -614,16 +1375,28 @@ where
             buffer,
             group_id,
             readonly: self.readonly,
+            search: self.search.clone(),
+            numbering: self.numbering,
+            signs: self.signs,
 
             viewctx: self.viewctx.clone(),
             term_cursor: (0, 0),
         }
     }
 
-    fn close(&mut self, _: CloseFlags, _: &mut Store<I>) -> bool {
+    fn close(&mut self, _: CloseFlags, store: &mut Store<I>) -> bool {
+        self.leave(store);
+
         true
     }
 
+    fn leave(&mut self, store: &mut Store<I>) {
+        // Remember where we were when we left the buffer.
+        let mut buffer = self.buffer.write().unwrap();
+        let leader = buffer.get_leader(self.group_id);
+        store.cursors.set_mark(buffer.id(), Mark::BufferLastExited, leader);
+    }
+
     fn write(&mut self, _: Option<&str>, _: WriteFlags, _: &mut Store<I>) -> UIResult<EditInfo, I> {
         if self.readonly {
             return Err(EditError::ReadOnly.into());
@@ -632,7 +1405,9 @@ where
         }
     }
 
-    fn draw(&mut self, area: Rect, buf: &mut Buffer, _: bool, _: &mut Store<I>) {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer, _: bool, store: &mut Store<I>) {
+        self.search = store.search.get_highlight();
+
         TextBox::new().render(area, buf, self);
     }
 
@@ -644,6 +1419,14 @@ where
         self.buffer.read().unwrap().get_cursor_word(self.group_id, style)
     }
 
+    fn get_cursor_line(&self) -> Option<String> {
+        self.buffer.read().unwrap().get_cursor_line(self.group_id)
+    }
+
+    fn get_search_case(&self) -> SearchCase {
+        self.buffer.read().unwrap().get_search_case()
+    }
+
     fn get_selected_word(&self) -> Option<String> {
         self.buffer.read().unwrap().get_selected_word(self.group_id)
     }
@@ -663,6 +1446,10 @@ where
             lgutter_width: 0,
             rgutter_width: 0,
 
+            search_style: Style::default().fg(Color::Black).bg(Color::Yellow),
+            incsearch_style: Style::default().fg(Color::Black).bg(Color::LightRed),
+            fold_style: Style::default().fg(Color::Blue).bg(Color::Gray),
+
             _pc: PhantomData,
         }
     }
@@ -700,48 +1487,77 @@ where
         self
     }
 
+    /// Set the style used to highlight search matches.
+    pub fn search_style(mut self, style: Style) -> Self {
+        self.search_style = style;
+        self
+    }
+
+    /// Set the style used to highlight the match that an incremental search would jump to.
+    pub fn incsearch_style(mut self, style: Style) -> Self {
+        self.incsearch_style = style;
+        self
+    }
+
+    /// Set the style used to draw the rows shown for closed folds.
+    pub fn fold_style(mut self, style: Style) -> Self {
+        self.fold_style = style;
+        self
+    }
+
     #[inline]
-    fn _highlight_followers(
-        &self,
-        line: usize,
-        start: usize,
-        end: usize,
-        (x, y): (u16, u16),
-        followers: &FollowersInfo,
-        buf: &mut Buffer,
-    ) {
+    fn _render_fold(&self, text: &str, x: u16, y: u16, width: usize, buf: &mut Buffer) {
+        let fill = "-".repeat(width.saturating_sub(text.width()));
+        let text = format!("{text}{fill}");
+
+        let _ = buf.set_stringn(x, y, text, width, self.fold_style);
+    }
+
+    #[inline]
+    fn _highlight_followers(&self, row: &LineRow<'_>, followers: &FollowersInfo, buf: &mut Buffer) {
         let hlstyled = Style::default().add_modifier(Modifier::REVERSED);
-        let cs = (line, start);
-        let ce = (line, end);
+        let cs = (row.line, row.start);
+        let ce = (row.line, row.end);
 
         for follower in followers.query(cs..ce) {
-            let fx = x + (follower.value.x - start) as u16;
-            let fa = Rect::new(fx, y, 1, 1);
-            buf.set_style(fa, hlstyled);
+            if let Some(fa) = row.area(follower.value.x, follower.value.x) {
+                buf.set_style(fa, hlstyled);
+            }
         }
     }
 
     #[inline]
-    fn _set_style(&self, start: usize, h1: usize, h2: usize, (x, y): (u16, u16), buf: &mut Buffer) {
-        let tx: u16 = x + (h1 - start) as u16;
-        let selwidth: u16 = (h2 - h1 + 1).try_into().unwrap();
+    fn _set_style(&self, row: &LineRow<'_>, h1: usize, h2: usize, style: Style, buf: &mut Buffer) {
+        if let Some(selarea) = row.area(h1, h2) {
+            buf.set_style(selarea, style);
+        }
+    }
 
+    #[inline]
+    fn _highlight_selections(&self, row: &LineRow<'_>, hinfo: &HighlightInfo, buf: &mut Buffer) {
         let hlstyled = Style::default().add_modifier(Modifier::REVERSED);
-        let selarea = Rect::new(tx, y, selwidth, 1);
 
-        buf.set_style(selarea, hlstyled);
+        self._highlight_line(row, hinfo, hlstyled, buf);
+    }
+
+    #[inline]
+    fn _highlight_search(&self, row: &LineRow<'_>, sinfo: &SearchInfo, buf: &mut Buffer) {
+        self._highlight_line(row, &sinfo.0, self.search_style, buf);
+        self._highlight_line(row, &sinfo.1, self.incsearch_style, buf);
     }
 
     #[inline]
     fn _highlight_line(
         &self,
-        line: usize,
-        start: usize,
-        end: usize,
-        (x, y): (u16, u16),
+        row: &LineRow<'_>,
         hls: &HighlightInfo,
+        style: Style,
         buf: &mut Buffer,
     ) {
+        let line = row.line;
+        let start = row.start;
+        let end = row.end;
+
         for selection in hls.query_point(line) {
             let (sb, se, shape) = &selection.value;
 
@@ -758,15 +1574,13 @@ where
                     };
 
                     if range.contains(&x1) && range.contains(&x2) {
-                        self._set_style(start, x1, x2, (x, y), buf);
+                        self._set_style(row, x1, x2, style, buf);
                     }
                 },
                 TargetShape::LineWise => {
-                    let hlstyled = Style::default().add_modifier(Modifier::REVERSED);
-                    let selwidth: u16 = (end - start).try_into().unwrap();
-                    let selarea = Rect::new(x, y, selwidth, 1);
-
-                    buf.set_style(selarea, hlstyled);
+                    if start < end {
+                        self._set_style(row, start, maxcol, style, buf);
+                    }
                 },
                 TargetShape::BlockWise => {
                     let lx = sb.x.min(se.x);
@@ -776,7 +1590,7 @@ where
                     let x2 = rx.min(maxcol);
 
                     if range.contains(&x1) && range.contains(&x2) {
-                        self._set_style(start, x1, x2, (x, y), buf);
+                        self._set_style(row, x1, x2, style, buf);
                     }
                 },
             }
@@ -786,7 +1600,7 @@ where
     fn _render_lines_wrap(
         &mut self,
         area: Rect,
-        gutters: (Rect, Rect),
+        gutters: Gutters,
         buf: &mut Buffer,
         hinfo: HighlightInfo,
         finfo: FollowersInfo,
@@ -808,97 +1622,191 @@ where
         let cursor = state.get_cursor();
         shift_corner_wrap(&cursor, &mut state.viewctx.corner, height);
 
-        let cby = state.viewctx.corner.y;
-        let cbx = state.viewctx.corner.x;
+        let text = state.buffer.read().unwrap();
 
-        let unstyled = Style::default();
+        // Closed folds are shown starting from their first line.
+        if let Some(fold) = text.get_closed_fold(state.viewctx.corner.y) {
+            state.viewctx.corner.set_y(fold.start());
+            state.viewctx.corner.set_x(0);
+        }
 
-        let text = state.buffer.read().unwrap();
+        shift_corner_rows(&text, &cursor, &mut state.viewctx.corner, height, Some(width));
 
+        let cby = state.viewctx.corner.y;
+        let cbx = state.viewctx.corner.x;
+
+        let mut layouts = Vec::new();
         let mut wrapped = Vec::new();
         let mut sawcursor = false;
+        let mut hidden = cby;
 
-        for (loff, s) in text.lines_at(cby, cbx).enumerate() {
+        for (loff, s) in text.lines(cby).enumerate() {
             if wrapped.len() >= height && sawcursor {
                 break;
             }
 
-            let base = if loff == 0 { cbx } else { 0 };
             let line = cby + loff;
+
+            if line < hidden {
+                continue;
+            }
+
+            if let Some(fold) = text.get_closed_fold(line) {
+                let row = TextRow::fold(&text, fold, &s, cursor.y);
+                sawcursor |= matches!(row, TextRow::Fold { cursor_line: true, .. });
+                hidden = fold.end() + 1;
+                wrapped.push(row);
+                continue;
+            }
+
+            let vt = LineVirtualText::new(&text, line);
+            let layout = LineView::new(&s, text.get_indent_options().tabstop, Some(width), &vt);
+            let slen = layout.len();
+            let lidx = layouts.len();
+
+            // Skip any rows that come before the corner.
+            let skip = if loff == 0 { cbx } else { 0 };
+            let mut rows = layout.rows(width).into_iter().peekable();
+
+            if skip == 0 {
+                for vtext in vt.above {
+                    wrapped.push(TextRow::Virtual { line, text: vtext });
+                }
+            }
             let mut first = true;
-            let mut off = 0;
-            let slen = s.len_chars();
-
-            while off < slen && (wrapped.len() < height || !sawcursor) {
-                let start = off;
-                let end = (start + width).min(slen);
-                let swrapped = s.slice(start..end).to_string();
-                off = end;
-
-                let start = base + start;
-                let end = base + end;
-                let slen = base + slen;
-
-                let full = end - start == width;
-                let last = end == slen && cursor.x == slen;
-                let cursor_line = line == cursor.y && ((start..end).contains(&cursor.x) || last);
-
-                if cursor_line && full && last {
-                    wrapped.push((line, start, end, swrapped, false, first));
-                    wrapped.push((line, end, end, " ".to_string(), true, first));
+
+            while let Some((range, left)) = rows.next() {
+                if wrapped.len() >= height && sawcursor {
+                    break;
+                }
+
+                let last = rows.peek().is_none();
+                let full = layout.end >= left + width;
+                let eol = last && line == cursor.y && cursor.x == slen;
+                let cursor_line = line == cursor.y && (range.contains(&cursor.x) || eol);
+
+                if range.start < skip && range.end <= skip {
+                    if eol && full {
+                        // The cursor is on the row after this one, at the end of the line.
+                        wrapped.push(TextRow::Text {
+                            lidx,
+                            line,
+                            start: slen,
+                            end: slen,
+                            left: left + width,
+                            cursor_line: true,
+                            first,
+                        });
+                        sawcursor = true;
+                    }
+
+                    continue;
+                }
+
+                let (start, end) = (range.start, range.end);
+
+                if cursor_line && full && eol {
+                    let cursor_line = false;
+                    wrapped.push(TextRow::Text {
+                        lidx,
+                        line,
+                        start,
+                        end,
+                        left,
+                        cursor_line,
+                        first,
+                    });
+                    wrapped.push(TextRow::Text {
+                        lidx,
+                        line,
+                        start: slen,
+                        end: slen,
+                        left: left + width,
+                        cursor_line: true,
+                        first: false,
+                    });
                 } else {
-                    wrapped.push((line, start, end, swrapped, cursor_line, first));
+                    wrapped.push(TextRow::Text {
+                        lidx,
+                        line,
+                        start,
+                        end,
+                        left,
+                        cursor_line,
+                        first,
+                    });
                 }
 
                 sawcursor |= cursor_line;
-
                 first = false;
             }
 
-            if slen == 0 {
-                let cursor_line = line == cursor.y;
-                wrapped.push((line, base, base, s.to_string(), cursor_line, true));
-                sawcursor |= cursor_line;
-            }
+            layouts.push(layout);
         }
 
         if wrapped.len() > height {
             let n = wrapped.len() - height;
             let _ = wrapped.drain(..n);
-            let (line, start, _, _, _, _) = wrapped.first().unwrap();
-            state.viewctx.corner.set_y(*line);
-            state.viewctx.corner.set_x(*start);
+
+            match wrapped.first().unwrap() {
+                TextRow::Text { line, start, .. } => {
+                    state.viewctx.corner.set_y(*line);
+                    state.viewctx.corner.set_x(*start);
+                },
+                TextRow::Virtual { line, .. } | TextRow::Fold { line, .. } => {
+                    state.viewctx.corner.set_y(*line);
+                    state.viewctx.corner.set_x(0);
+                },
+            }
         }
 
-        for (line, start, end, s, cursor_line, first) in wrapped.into_iter() {
+        let top = wrapped.first().map(TextRow::line).unwrap_or(cby);
+        let bot_line = wrapped.last().map(|w| w.line() + 1).unwrap_or(top);
+        let sinfo = text._search_intervals(&cursor, state.search.as_ref(), top..bot_line);
+
+        for row in wrapped.into_iter() {
             if y >= bot {
                 break;
             }
 
-            if first {
-                let lgutter = text.get_line_info::<LeftGutterInfo>(line);
-                let rgutter = text.get_line_info::<RightGutterInfo>(line);
+            let (lidx, line, start, end, left, cursor_line, first) = match row {
+                TextRow::Text { lidx, line, start, end, left, cursor_line, first } => {
+                    (lidx, line, start, end, left, cursor_line, first)
+                },
+                TextRow::Virtual { text: vtext, .. } => {
+                    let _ = buf.set_stringn(x, y, &vtext.text, width, vtext.style);
+                    y += 1;
+                    continue;
+                },
+                TextRow::Fold { line, text: ftext, cursor_line } => {
+                    self._render_gutters(&gutters, &text, line, cursor.y, y, buf);
+                    self._render_fold(&ftext, x, y, width, buf);
 
-                if let Some(lgi) = lgutter {
-                    let lga = Rect::new(gutters.0.x, y, gutters.0.width, 0);
-                    lgi.render(lga, buf);
-                }
+                    if cursor_line {
+                        state.term_cursor = (x, y);
+                    }
 
-                if let Some(rgi) = rgutter {
-                    let rga = Rect::new(gutters.1.x, y, gutters.1.width, 0);
-                    rgi.render(rga, buf);
-                }
+                    y += 1;
+                    continue;
+                },
+            };
+
+            if first {
+                self._render_gutters(&gutters, &text, line, cursor.y, y, buf);
             }
 
-            let _ = buf.set_stringn(x, y, s, width, unstyled);
+            let layout = &layouts[lidx];
+            let row = LineRow { layout, line, start, end, left, width, x, y };
+
+            row.draw(buf);
 
             if cursor_line {
-                let coff = (cursor.x - start) as u16;
-                state.term_cursor = (x + coff, y);
+                state.term_cursor = row.cursor(cursor.x);
             }
 
-            self._highlight_followers(line, start, end, (x, y), &finfo, buf);
-            self._highlight_line(line, start, end, (x, y), &hinfo, buf);
+            self._highlight_search(&row, &sinfo, buf);
+            self._highlight_followers(&row, &finfo, buf);
+            self._highlight_selections(&row, &hinfo, buf);
 
             y += 1;
         }
@@ -912,8 +1820,7 @@ where
         finfo: FollowersInfo,
         state: &mut TextBoxState<I>,
     ) {
-        let right = area.right();
-        let mut x = area.left();
+        let x = area.left();
         let y = area.top();
 
         let width = area.width as usize;
@@ -925,134 +1832,136 @@ where
         let cby = state.viewctx.corner.y;
         let cbx = state.viewctx.corner.x;
 
-        let unstyled = Style::default();
-
         let text = state.buffer.read().unwrap();
 
+        /*
+         * Lay the lines out one after another, separated by "^J", and track where each one starts
+         * in the joined text. Separators are recorded with no layout.
+         */
+        let mut layouts = Vec::new();
         let mut joined = Vec::new();
-        let mut sawcursor = false;
-        let mut len = 0;
-        let mut off = cbx;
+        let mut ccols = None;
+        let mut pos = 0;
 
-        for (loff, s) in text.lines_at(cby, cbx).enumerate() {
-            if len >= width && sawcursor {
-                break;
+        for (loff, s) in text.lines(cby).enumerate() {
+            if let Some((cp, cw)) = ccols {
+                if pos >= width.max(cp + cw) {
+                    break;
+                }
             }
 
-            let base = if loff == 0 { cbx } else { 0 };
             let line = cby + loff;
-            let slen = s.len_chars();
-
-            while off < slen && (len <= width || !sawcursor) {
-                let start = off;
-                let end = (start + width).min(slen);
-                let swrapped = s.slice(start..end);
-
-                off = end;
-
-                let start = base + start;
-                let end = base + end;
-                let slen = base + slen;
 
-                let full = end - start == width;
-                let last = end == slen && cursor.x == slen;
-                let cursor_line = line == cursor.y && ((start..end).contains(&cursor.x) || last);
-
-                let wlen = swrapped.len_chars();
-
-                if cursor_line && full && last {
-                    joined.push((line, start, end, swrapped, wlen, false));
-                    joined.push((line, end, end, RopeSlice::from(" "), 1, true));
-                    len += wlen + 1;
-                } else {
-                    joined.push((line, start, end, swrapped, wlen, cursor_line));
-                    len += wlen;
-                }
-
-                sawcursor |= cursor_line;
+            if loff > 0 {
+                joined.push((None, line, 0, pos));
+                pos += 2;
             }
 
-            if slen == 0 {
-                let cursor_line = line == cursor.y;
-                joined.push((line, 0, 0, s, 0, cursor_line));
-                sawcursor |= cursor_line;
-            }
+            // Only inline virtual text fits in a single line.
+            let mut vt = LineVirtualText::new(&text, line);
+            vt.eol.clear();
 
-            joined.push((line, slen, slen, RopeSlice::from("^J"), 2, false));
-            len += 2;
+            let layout = LineView::new(&s, text.get_indent_options().tabstop, None, &vt);
+            let start = if loff == 0 { cbx.min(layout.len()) } else { 0 };
+            let (scol, _) = layout.span(start);
 
-            // Reset for next iteration.
-            off = 0;
-        }
+            if line == cursor.y {
+                let (col, cw) = layout.span(cursor.x);
+                ccols = Some((pos + col.saturating_sub(scol), cw));
+            }
 
-        if !joined.is_empty() {
-            // Remove the last ^J.
-            joined.pop();
-            len -= 2;
+            joined.push((Some(layouts.len()), line, start, pos));
+            pos += layout.end - scol;
+            layouts.push(layout);
         }
 
-        if len > width {
-            let mut n = 0;
-
-            for (idx, (_, ref mut start, _, ref mut s, slen, ref cursor_line)) in
-                joined.iter_mut().enumerate()
-            {
-                if len <= width {
-                    break;
+        // Scroll just far enough to keep the cursor onscreen.
+        let (cp, cw) = ccols.unwrap_or((0, 1));
+        let mut off = (cp + cw).saturating_sub(width);
+
+        if off > 0 {
+            /*
+             * Move the corner to the first character that is entirely onscreen, so that the next
+             * render starts from the same place.
+             */
+            for (lidx, line, start, pos) in joined.iter() {
+                let (lidx, start, pos) = match lidx {
+                    Some(lidx) => (*lidx, *start, *pos),
+                    None => continue,
+                };
+
+                let layout = &layouts[lidx];
+                let (scol, _) = layout.span(start);
+                let p = |idx: usize| pos + layout.span(idx).0 - scol;
+
+                if p(layout.len()) < off {
+                    continue;
                 }
 
-                let diff = len - width;
-                n = idx;
+                let idx = (start..=layout.len()).find(|idx| p(*idx) >= off).unwrap();
 
-                if *cursor_line {
-                    let into = cursor.x - *start;
-                    let rm = diff.min(into);
-                    *s = s.slice(rm..);
-                    *start += rm;
-                    break;
-                } else if *slen > diff {
-                    *s = s.slice(diff..);
-                    *start += diff;
-                    break;
-                } else {
-                    len -= *slen;
-                    continue;
-                }
+                off = p(idx);
+                state.viewctx.corner.set_y(*line);
+                state.viewctx.corner.set_x(idx);
+                break;
             }
-
-            let _ = joined.drain(..n);
-            let (line, start, _, _, _, _) = joined.first().unwrap();
-            state.viewctx.corner.set_y(*line);
-            state.viewctx.corner.set_x(*start);
         }
 
         state.term_cursor = (x, y);
 
-        for (line, start, end, s, _, cursor_line) in joined.into_iter() {
-            if x >= right {
+        let bot = joined.last().map(|j| j.1 + 1).unwrap_or(cby);
+        let sinfo = text._search_intervals(&cursor, state.search.as_ref(), cby..bot);
+
+        for (lidx, line, start, pos) in joined.into_iter() {
+            if pos >= off + width {
                 break;
             }
 
-            let s = s.to_string();
-            let w = (right - x) as usize;
-            let (xres, _) = buf.set_stringn(x, y, s, w, unstyled);
+            let sx = pos.saturating_sub(off);
+            let rx = x + sx as u16;
+            let rw = width - sx;
 
-            if cursor_line {
-                let coff = cursor.x.saturating_sub(start) as u16;
-                state.term_cursor = (x + coff, y);
-            }
+            let lidx = match lidx {
+                Some(lidx) => lidx,
+                None => {
+                    if pos >= off {
+                        let _ = buf.set_stringn(rx, y, "^J", rw, Style::default());
+                    }
 
-            self._highlight_followers(line, start, end, (x, y), &finfo, buf);
-            self._highlight_line(line, start, end, (x, y), &hinfo, buf);
+                    continue;
+                },
+            };
+
+            let layout = &layouts[lidx];
+            let end = layout.len();
+            let left = layout.span(start).0 + off.saturating_sub(pos);
+            let row = LineRow {
+                layout,
+                line,
+                start,
+                end,
+                left,
+                width: rw,
+                x: rx,
+                y,
+            };
+
+            row.draw(buf);
+
+            if line == cursor.y {
+                state.term_cursor = row.cursor(cursor.x);
+            }
 
-            x = xres;
+            self._highlight_search(&row, &sinfo, buf);
+            self._highlight_followers(&row, &finfo, buf);
+            self._highlight_selections(&row, &hinfo, buf);
         }
     }
 
     fn _render_lines_nowrap(
         &mut self,
         area: Rect,
-        gutters: (Rect, Rect),
+        gutters: Gutters,
         buf: &mut Buffer,
         hinfo: HighlightInfo,
         finfo: FollowersInfo,
@@ -1062,58 +1971,173 @@ where
         let x = area.left();
         let mut y = area.top();
 
-        let height = area.height as usize;
-        let width = area.width as usize;
+        let height = area.height as usize;
+        let width = area.width as usize;
+
+        let cursor = state.get_cursor();
+        let text = state.buffer.read().unwrap();
+
+        // If the cursor has moved off-screen, update the viewport corner.
+        let ccols = cursor_cols(&text, &cursor);
+        shift_corner_nowrap(&cursor, ccols, &mut state.viewctx.corner, width, height);
+
+        // Closed folds are shown starting from their first line.
+        if let Some(fold) = text.get_closed_fold(state.viewctx.corner.y) {
+            state.viewctx.corner.set_y(fold.start());
+        }
+
+        shift_corner_rows(&text, &cursor, &mut state.viewctx.corner, height, None);
+
+        let cby = state.viewctx.corner.y;
+        let cbx = state.viewctx.corner.x;
+
+        let mut layouts = Vec::new();
+        let mut rows = Vec::new();
+        let mut hidden = cby;
+
+        for (loff, s) in text.lines(cby).enumerate() {
+            let line = cby + loff;
+
+            if rows.len() >= height && line > cursor.y {
+                break;
+            }
+
+            if line < hidden {
+                continue;
+            }
+
+            if let Some(fold) = text.get_closed_fold(line) {
+                hidden = fold.end() + 1;
+                rows.push(TextRow::fold(&text, fold, &s, cursor.y));
+                continue;
+            }
+
+            let vt = LineVirtualText::new(&text, line);
+            let layout = LineView::new(&s, text.get_indent_options().tabstop, None, &vt);
+            let start = layout.index(cbx);
+            let end = layout.len();
+            let lidx = layouts.len();
+            let cursor_line = line == cursor.y;
+
+            for vtext in vt.above {
+                rows.push(TextRow::Virtual { line, text: vtext });
+            }
+
+            rows.push(TextRow::Text {
+                lidx,
+                line,
+                start,
+                end,
+                left: cbx,
+                cursor_line,
+                first: true,
+            });
+            layouts.push(layout);
+        }
+
+        // Virtual lines can push the cursor's line past the bottom, so scroll down if needed.
+        let crow = rows
+            .iter()
+            .position(|row| {
+                matches!(
+                    row,
+                    TextRow::Text { cursor_line: true, .. } |
+                        TextRow::Fold { cursor_line: true, .. }
+                )
+            })
+            .unwrap_or(0);
+
+        if crow >= height {
+            let _ = rows.drain(..=crow - height);
+            state.viewctx.corner.set_y(rows[0].line());
+        }
+
+        rows.truncate(height);
 
-        // If the cursor has moved off-screen, update the viewport corner.
-        let cursor = state.get_cursor();
-        shift_corner_nowrap(&cursor, &mut state.viewctx.corner, width, height);
+        let top = rows.first().map(TextRow::line).unwrap_or(cby);
+        let bot_line = rows.last().map(|r| r.line() + 1).unwrap_or(top);
+        let sinfo = text._search_intervals(&cursor, state.search.as_ref(), top..bot_line);
 
-        let cby = state.viewctx.corner.y;
-        let cbx = state.viewctx.corner.x;
+        for row in rows.into_iter() {
+            if y >= bot {
+                break;
+            }
 
-        let unstyled = Style::default();
+            let (lidx, line, start, end) = match row {
+                TextRow::Text { lidx, line, start, end, .. } => (lidx, line, start, end),
+                TextRow::Virtual { text: vtext, .. } => {
+                    let _ = buf.set_stringn(x, y, &vtext.text, width, vtext.style);
+                    y += 1;
+                    continue;
+                },
+                TextRow::Fold { line, text: ftext, cursor_line } => {
+                    self._render_gutters(&gutters, &text, line, cursor.y, y, buf);
+                    self._render_fold(&ftext, x, y, width, buf);
 
-        let text = state.buffer.read().unwrap();
-        let mut line = cby;
-        let mut lines = text.lines(line);
+                    if cursor_line {
+                        state.term_cursor = (x, y);
+                    }
 
-        while y < bot {
-            if let Some(s) = lines.next() {
-                let lgutter = text.get_line_info::<LeftGutterInfo>(line);
-                let rgutter = text.get_line_info::<RightGutterInfo>(line);
+                    y += 1;
+                    continue;
+                },
+            };
 
-                let slen = s.len_chars();
-                let start = cbx;
-                let end = slen;
+            let row = LineRow {
+                layout: &layouts[lidx],
+                line,
+                start,
+                end,
+                left: cbx,
+                width,
+                x,
+                y,
+            };
 
-                if let Some(lgi) = lgutter {
-                    let lga = Rect::new(gutters.0.x, y, gutters.0.width, 0);
-                    lgi.render(lga, buf);
-                }
+            self._render_gutters(&gutters, &text, line, cursor.y, y, buf);
 
-                if cbx < slen {
-                    let _ = buf.set_stringn(x, y, s.slice(start..end).to_string(), width, unstyled);
-                }
+            row.draw(buf);
 
-                if let Some(rgi) = rgutter {
-                    let rga = Rect::new(gutters.1.x, y, gutters.1.width, 0);
-                    rgi.render(rga, buf);
-                }
+            if line == cursor.y {
+                state.term_cursor = row.cursor(cursor.x);
+            }
 
-                if line == cursor.y && (start..=end).contains(&cursor.x) {
-                    let coff = (cursor.x - start) as u16;
-                    state.term_cursor = (x + coff, y);
-                }
+            self._highlight_search(&row, &sinfo, buf);
+            self._highlight_followers(&row, &finfo, buf);
+            self._highlight_selections(&row, &hinfo, buf);
 
-                self._highlight_followers(line, start, end, (x, y), &finfo, buf);
-                self._highlight_line(line, start, end, (x, y), &hinfo, buf);
+            y += 1;
+        }
+    }
 
-                y += 1;
-                line += 1;
-            } else {
-                break;
-            }
+    fn _render_gutters(
+        &self,
+        gutters: &Gutters,
+        text: &EditBuffer<I>,
+        line: usize,
+        cursor: usize,
+        y: u16,
+        buf: &mut Buffer,
+    ) {
+        if let Some(lgi) = text.get_line_info::<LeftGutterInfo>(line) {
+            let lga = Rect::new(gutters.left.x, y, gutters.left.width, 0);
+            lgi.render(lga, buf);
+        }
+
+        if let Some(sign) = text.get_line_info::<SignInfo>(line) {
+            let sa = Rect::new(gutters.signs.x, y, gutters.signs.width, 0);
+            sign.render(sa, buf);
+        }
+
+        if gutters.numbers.width > 0 {
+            let width = gutters.numbers.width as usize;
+            let num = gutters.numbering.text(line, cursor, width);
+            let _ = buf.set_stringn(gutters.numbers.x, y, num, width, Style::default());
+        }
+
+        if let Some(rgi) = text.get_line_info::<RightGutterInfo>(line) {
+            let rga = Rect::new(gutters.right.x, y, gutters.right.width, 0);
+            rgi.render(rga, buf);
         }
     }
 
@@ -1137,16 +2161,28 @@ where
             return;
         }
 
-        let (lgw, rgw) = if area.width <= self.lgutter_width + self.rgutter_width {
-            (0, 0)
+        let sw = if state.signs { SIGN_COLUMN_WIDTH } else { 0 };
+        let nw = state.numbering.width(state.get_lines());
+        let gutterw = self.lgutter_width + sw + nw + self.rgutter_width;
+
+        let (lgw, sw, nw, rgw) = if area.width <= gutterw {
+            (0, 0, 0, 0)
         } else {
-            (self.lgutter_width, self.rgutter_width)
+            (self.lgutter_width, sw, nw, self.rgutter_width)
         };
-        let textw = area.width - lgw - rgw;
+        let textw = area.width - lgw - sw - nw - rgw;
         let lga = Rect::new(area.x, area.y, lgw, area.height);
-        let texta = Rect::new(area.x + lgw, area.y, textw, area.height);
-        let rga = Rect::new(area.x + lgw + textw, area.y, rgw, area.height);
-        let gutters = (lga, rga);
+        let sa = Rect::new(lga.right(), area.y, sw, area.height);
+        let na = Rect::new(sa.right(), area.y, nw, area.height);
+        let texta = Rect::new(na.right(), area.y, textw, area.height);
+        let rga = Rect::new(texta.right(), area.y, rgw, area.height);
+        let gutters = Gutters {
+            left: lga,
+            signs: sa,
+            numbers: na,
+            right: rga,
+            numbering: state.numbering,
+        };
 
         state.set_term_info(texta);
 
@@ -1210,10 +2246,12 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::editing::action::{EditAction, HistoryAction};
-    use crate::editing::base::{EditTarget, MoveDir1D, MoveType};
+    use crate::editing::action::InfoMessage;
+    use crate::editing::action::{EditAction, FoldAction, HistoryAction};
+    use crate::editing::base::{EditTarget, IndentOptions, MoveDir1D, MoveType, SearchType};
     use crate::editing::store::Store;
     use crate::env::vim::VimContext;
+    use regex::Regex;
 
     macro_rules! mv {
         ($mt: expr) => {
@@ -1264,6 +2302,12 @@ mod tests {
         return (b, ctx, store);
     }
 
+    fn set_tabstop(tbox: &mut TextBoxState, tabstop: usize) {
+        let opts = IndentOptions { tabstop, ..Default::default() };
+
+        tbox.buffer().write().unwrap().set_indent_options(opts);
+    }
+
     #[test]
     fn test_scroll_dir1d() {
         let (mut tbox, mut ctx, mut store) = mkboxstr(
@@ -1516,6 +2560,49 @@ mod tests {
         assert_eq!(tbox.get_cursor(), Cursor::new(0, 0));
     }
 
+    #[test]
+    fn test_close_mark() {
+        let (mut tbox, ctx, mut store) = mkboxstr("foo\nbar\nbaz");
+        let id = tbox.buffer.read().unwrap().id();
+
+        let mov = mv!(MoveType::BufferLineOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(tbox.get_cursor(), Cursor::new(1, 0));
+
+        // Closing the window remembers where we left the buffer.
+        assert!(tbox.close(CloseFlags::NONE, &mut store));
+        let mark = store.cursors.get_mark(id.clone(), Mark::BufferLastExited).unwrap();
+        assert_eq!(mark, Cursor::new(1, 0));
+
+        // So does switching to another window.
+        let mov = mv!(MoveType::BufferLineOffset, 3);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        tbox.leave(&mut store);
+        assert_eq!(store.cursors.get_mark(id, Mark::BufferLastExited).unwrap(), Cursor::new(2, 0));
+    }
+
+    #[test]
+    fn test_cursor_word_line() {
+        let (mut tbox, ctx, mut store) = mkboxstr("foo\nbar.baz quux\n\nend");
+
+        let mov = mv!(MoveType::BufferLineOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(tbox.get_cursor(), Cursor::new(1, 0));
+
+        assert_eq!(tbox.get_cursor_word(&WordStyle::Little).unwrap(), "bar");
+        assert_eq!(tbox.get_cursor_word(&WordStyle::Big).unwrap(), "bar.baz");
+        assert_eq!(tbox.get_cursor_line().unwrap(), "bar.baz quux");
+
+        // An empty line is returned as-is.
+        let mov = mv!(MoveType::BufferLineOffset, 3);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(tbox.get_cursor_line().unwrap(), "");
+    }
+
     #[test]
     fn test_render_nowrap() {
         let (mut tbox, ctx, mut store) = mkboxstr("foo\nbar\nbaz\nquux 1 2 3 4 5");
@@ -1564,4 +2651,515 @@ mod tests {
         assert_eq!(tbox.get_cursor(), Cursor::new(0, 0));
         assert_eq!(tbox.get_term_cursor(), (2, 8).into());
     }
+
+    #[test]
+    fn test_render_tabs() {
+        let (mut tbox, ctx, mut store) = mkboxstr("a\tb\n\tc\n");
+
+        set_tabstop(&mut tbox, 4);
+
+        let area = Rect::new(0, 0, 8, 2);
+        let mut buffer = Buffer::empty(area);
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["a   b   ", "    c   "]));
+        assert_eq!(tbox.get_term_cursor(), (0, 0).into());
+
+        // Cursor on the tab is drawn at its first cell.
+        let mov = mv!(MoveType::LineColumnOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(tbox.get_term_cursor(), (1, 0).into());
+
+        // Cursor after the tab is drawn at the next tabstop.
+        let mov = mv!(MoveType::LineColumnOffset, 3);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(tbox.get_term_cursor(), (4, 0).into());
+
+        // Tabs are cut short at the end of a wrapped row.
+        tbox.set_text("abcdef\tg\n");
+        set_tabstop(&mut tbox, 8);
+
+        let mut buffer = Buffer::empty(area);
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["abcdef  ", "g       "]));
+    }
+
+    #[test]
+    fn test_render_wide_chars() {
+        let (mut tbox, ctx, mut store) = mkboxstr("日本語ab");
+
+        let area = Rect::new(0, 0, 5, 2);
+        let mut buffer = Buffer::empty(area);
+
+        // The third character doesn't fit on the first row, and gets pushed onto the next.
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["日本 ", "語ab "]));
+        assert_eq!(tbox.has_lines(5), 2);
+
+        let mov = mv!(MoveType::LineColumnOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(tbox.get_term_cursor(), (2, 0).into());
+
+        let mov = mv!(MoveType::LineColumnOffset, 4);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(tbox.get_term_cursor(), (2, 1).into());
+
+        // Without wrapping, the viewport scrolls by screen columns, and partially visible wide
+        // characters are left blank.
+        tbox.set_wrap(false);
+
+        let area = Rect::new(0, 0, 4, 1);
+        let mut buffer = Buffer::empty(area);
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec![" 語a"]));
+        assert_eq!(tbox.viewctx.corner, Cursor::new(0, 3));
+        assert_eq!(tbox.get_term_cursor(), (3, 0).into());
+
+        // Moving back onto a wide character scrolls it entirely into view.
+        let mov = mv!(MoveType::LineColumnOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        let mut buffer = Buffer::empty(area);
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["本語"]));
+        assert_eq!(tbox.viewctx.corner, Cursor::new(0, 2));
+        assert_eq!(tbox.get_term_cursor(), (0, 0).into());
+    }
+
+    #[test]
+    fn test_render_control_chars() {
+        let (mut tbox, ctx, mut store) = mkboxstr("a\u{1}b\u{7f}c\u{1b}\n");
+
+        let area = Rect::new(0, 0, 10, 1);
+        let mut buffer = Buffer::empty(area);
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["a^Ab^?c^[ "]));
+
+        let mov = mv!(MoveType::LineColumnOffset, 5);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(tbox.get_term_cursor(), (6, 0).into());
+    }
+
+    #[test]
+    fn test_render_highlight_columns() {
+        let (mut tbox, mut ctx, mut store) = mkboxstr("\t本x\n");
+
+        set_tabstop(&mut tbox, 4);
+
+        let area = Rect::new(0, 0, 8, 1);
+        let mut buffer = Buffer::empty(area);
+
+        // Select from the tab through the wide character.
+        ctx.persist.shape = Some(TargetShape::CharWise);
+
+        let mov = mv!(MoveType::LineColumnOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        TextBox::new().render(area, &mut buffer, &mut tbox);
+        assert_eq!(tbox.get_term_cursor(), (4, 0).into());
+
+        let reversed = |x: u16| buffer.get(x, 0).modifier.contains(Modifier::REVERSED);
+        assert_eq!((0..8).map(reversed).collect::<Vec<_>>(), vec![
+            true, true, true, true, true, true, false, false
+        ]);
+    }
+
+    #[test]
+    fn test_render_line_numbers() {
+        let (mut tbox, ctx, mut store) = mkboxstr("a\nb\nc\n");
+
+        let area = Rect::new(0, 0, 8, 3);
+
+        macro_rules! render {
+            () => {{
+                let mut buffer = Buffer::empty(area);
+                TextBox::new().render(area, &mut buffer, &mut tbox);
+                buffer
+            }};
+        }
+
+        tbox.set_line_numbers(LineNumbers::Absolute);
+        assert_eq!(render!(), Buffer::with_lines(vec!["  1 a   ", "  2 b   ", "  3 c   "]));
+        assert_eq!(tbox.get_term_cursor(), (4, 0).into());
+
+        let mov = mv!(MoveType::BufferLineOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        tbox.set_line_numbers(LineNumbers::Relative);
+        assert_eq!(render!(), Buffer::with_lines(vec!["  1 a   ", "  0 b   ", "  1 c   "]));
+        assert_eq!(tbox.get_term_cursor(), (4, 1).into());
+
+        tbox.set_line_numbers(LineNumbers::Hybrid);
+        assert_eq!(render!(), Buffer::with_lines(vec!["  1 a   ", "2   b   ", "  1 c   "]));
+
+        // The column grows to fit the number of lines.
+        tbox.set_line_numbers(LineNumbers::Absolute);
+        tbox.set_text("a\n".repeat(1000));
+        assert_eq!(render!(), Buffer::with_lines(vec!["   1 a  ", "   2 a  ", "   3 a  "]));
+
+        // Wrapped lines only get numbered on their first row.
+        tbox.set_text("abcdefgh\nb\n");
+        assert_eq!(render!(), Buffer::with_lines(vec!["  1 abcd", "    efgh", "  2 b   "]));
+
+        tbox.set_wrap(false);
+        assert_eq!(render!(), Buffer::with_lines(vec!["  1 abcd", "  2 b   ", "        "]));
+    }
+
+    #[test]
+    fn test_render_signs() {
+        let (mut tbox, _, _) = mkboxstr("a\nb\nc\n");
+
+        let area = Rect::new(0, 0, 8, 3);
+
+        macro_rules! render {
+            () => {{
+                let mut buffer = Buffer::empty(area);
+                TextBox::new().render(area, &mut buffer, &mut tbox);
+                buffer
+            }};
+        }
+
+        tbox.set_sign(1, ">>".into(), None);
+        tbox.set_sign(2, "E!!".into(), None);
+
+        // Signs aren't shown until the column is enabled.
+        assert_eq!(render!(), Buffer::with_lines(vec!["a       ", "b       ", "c       "]));
+
+        tbox.set_sign_column(true);
+        assert_eq!(render!(), Buffer::with_lines(vec!["  a     ", ">>b     ", "E!c     "]));
+        assert_eq!(tbox.get_term_cursor(), (2, 0).into());
+
+        // The sign column goes before the line numbers.
+        tbox.set_line_numbers(LineNumbers::Absolute);
+        assert_eq!(render!(), Buffer::with_lines(vec!["    1 a ", ">>  2 b ", "E!  3 c "]));
+
+        tbox.clear_sign(1);
+        assert_eq!(render!(), Buffer::with_lines(vec!["    1 a ", "    2 b ", "E!  3 c "]));
+    }
+
+    #[test]
+    fn test_render_virtual_text() {
+        let (mut tbox, ctx, mut store) = mkboxstr("abc\ndef\n");
+
+        let mut area = Rect::new(0, 0, 8, 4);
+
+        macro_rules! render {
+            () => {{
+                let mut buffer = Buffer::empty(area);
+                TextBox::new().render(area, &mut buffer, &mut tbox);
+                buffer
+            }};
+        }
+
+        let style = Style::default();
+        let vt = VirtualText::new("<T>".into(), style, VirtualTextPosition::Inline);
+        tbox.add_virtual_text(Cursor::new(0, 1), vt);
+        let vt = VirtualText::new("!".into(), style, VirtualTextPosition::EndOfLine);
+        let bang = tbox.add_virtual_text(Cursor::new(1, 0), vt);
+        let vt = VirtualText::new("^^".into(), style, VirtualTextPosition::Above);
+        tbox.add_virtual_text(Cursor::new(1, 0), vt);
+
+        assert_eq!(
+            render!(),
+            Buffer::with_lines(vec!["a<T>bc  ", "^^      ", "def !   ", "        "])
+        );
+
+        // The cursor moves over inline text.
+        let mov = mv!(MoveType::Column(MoveDir1D::Next, false), 1);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(tbox.get_cursor(), Cursor::new(0, 1));
+        render!();
+        assert_eq!(tbox.get_term_cursor(), (4, 0).into());
+
+        // Inline text wraps along with the line.
+        area.width = 5;
+        assert_eq!(render!(), Buffer::with_lines(vec!["a<T>b", "c    ", "^^   ", "def !"]));
+        assert_eq!(tbox.get_term_cursor(), (4, 0).into());
+
+        // Virtual lines count towards the height when keeping the cursor onscreen.
+        tbox.set_wrap(false);
+        area.height = 2;
+
+        let mov = mv!(MoveType::Line(MoveDir1D::Next), 1);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(render!(), Buffer::with_lines(vec!["^^   ", "def !"]));
+        assert_eq!(tbox.get_term_cursor(), (1, 1).into());
+
+        // Virtual text can be removed individually.
+        assert_eq!(tbox.remove_virtual_text(bang).unwrap().text, "!");
+        assert!(tbox.remove_virtual_text(bang).is_none());
+        assert_eq!(render!(), Buffer::with_lines(vec!["^^   ", "def  "]));
+
+        tbox.clear_virtual_text();
+        assert_eq!(render!(), Buffer::with_lines(vec!["def  ", "     "]));
+    }
+
+    #[test]
+    fn test_virtual_text_rows() {
+        let (mut tbox, ctx, mut store) = mkboxstr("abcdef\nghi\njkl\nmno\n");
+
+        tbox.set_term_info(Rect::new(0, 0, 4, 3));
+
+        let style = Style::default();
+        let vt = VirtualText::new("<T>".into(), style, VirtualTextPosition::Inline);
+        tbox.add_virtual_text(Cursor::new(0, 2), vt);
+        let vt = VirtualText::new("^^".into(), style, VirtualTextPosition::Above);
+        tbox.add_virtual_text(Cursor::new(2, 0), vt);
+
+        macro_rules! motion {
+            ($mt: expr) => {{
+                let act = EditorAction::Edit(EditAction::Motion.into(), mv!($mt.clone(), 1));
+                tbox.editor_command(&act, &ctx, &mut store).unwrap();
+                tbox.get_cursor()
+            }};
+        }
+
+        // The first line gets drawn as "ab", "<T>c" and "def", and screen motions account for
+        // the columns taken up by the inline text.
+        let next = MoveType::ScreenLine(MoveDir1D::Next);
+        let prev = MoveType::ScreenLine(MoveDir1D::Previous);
+
+        assert_eq!(motion!(MoveType::Column(MoveDir1D::Next, false)), Cursor::new(0, 1));
+        assert_eq!(motion!(next), Cursor::new(0, 2));
+        assert_eq!(motion!(next), Cursor::new(0, 5));
+        assert_eq!(motion!(prev), Cursor::new(0, 2));
+
+        // Scrolling counts rows of virtual text above a line, which are shown with its first row.
+        let (dir, size) = (MoveDir2D::Down, ScrollSize::Cell);
+        dirscroll!(tbox, dir, size, Count::Exact(3), &ctx, store);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(1, 0));
+        assert_eq!(tbox.get_cursor(), Cursor::new(1, 2));
+
+        dirscroll!(tbox, dir, size, Count::Exact(1), &ctx, store);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(2, 0));
+
+        dirscroll!(tbox, dir, size, Count::Exact(1), &ctx, store);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(3, 0));
+
+        let dir = MoveDir2D::Up;
+        dirscroll!(tbox, dir, size, Count::Exact(2), &ctx, store);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(2, 0));
+        assert_eq!(tbox.get_cursor(), Cursor::new(3, 2));
+
+        dirscroll!(tbox, dir, size, Count::Exact(1), &ctx, store);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(1, 0));
+        assert_eq!(tbox.get_cursor(), Cursor::new(2, 2));
+
+        // The virtual text above the third line counts towards the rows in the viewport.
+        assert_eq!(motion!(MoveType::ViewportPos(MovePosition::End)), Cursor::new(2, 0));
+
+        let dir = MoveDir2D::Down;
+        dirscroll!(tbox, dir, size, Count::Exact(1), &ctx, store);
+        cursorpos!(tbox, MovePosition::End, Axis::Vertical, &ctx, store);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(1, 0));
+
+        // Keeping the cursor onscreen makes room for the virtual text above its line.
+        tbox.viewctx.corner = Cursor::new(0, 2);
+        assert_eq!(motion!(MoveType::Line(MoveDir1D::Next)), Cursor::new(3, 0));
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 3));
+        TextBox::new().render(Rect::new(0, 0, 4, 3), &mut buffer, &mut tbox);
+        assert_eq!(tbox.viewctx.corner, Cursor::new(2, 0));
+        assert_eq!(buffer, Buffer::with_lines(vec!["^^  ", "jkl ", "mno "]));
+    }
+
+    #[test]
+    fn test_render_folds() {
+        let (mut tbox, ctx, mut store) = mkboxstr("a\n  b\n  c\nd\n");
+
+        let area = Rect::new(0, 0, 20, 3);
+
+        macro_rules! render {
+            () => {{
+                let mut buffer = Buffer::empty(area);
+                TextBox::new()
+                    .fold_style(Style::default())
+                    .render(area, &mut buffer, &mut tbox);
+                buffer
+            }};
+        }
+
+        // Fold the first three lines.
+        let mov = mv!(MoveType::Line(MoveDir1D::Next), 2);
+        let act = EditorAction::Edit(EditAction::Fold.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(
+            render!(),
+            Buffer::with_lines(vec![
+                "+--  3 lines: a-----",
+                "d                   ",
+                "                    ",
+            ])
+        );
+        assert_eq!(tbox.get_term_cursor(), (0, 0).into());
+
+        // Moving down skips over the folded lines.
+        let mov = mv!(MoveType::Line(MoveDir1D::Next), 1);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(tbox.get_cursor(), Cursor::new(3, 0));
+        render!();
+        assert_eq!(tbox.get_term_cursor(), (0, 1).into());
+
+        // Open the fold, and then fold the two indented lines inside of it.
+        let mov = mv!(MoveType::Line(MoveDir1D::Previous), 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        let act = EditorAction::Fold(FoldAction::Open(false));
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(
+            render!(),
+            Buffer::with_lines(vec![
+                "a                   ",
+                "  b                 ",
+                "  c                 "
+            ])
+        );
+
+        let mov = mv!(MoveType::Line(MoveDir1D::Next), 1);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        let mov = mv!(MoveType::Line(MoveDir1D::Next), 1);
+        let act = EditorAction::Edit(EditAction::Fold.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(
+            render!(),
+            Buffer::with_lines(vec![
+                "a                   ",
+                "+---  2 lines: b----",
+                "d                   ",
+            ])
+        );
+        assert_eq!(tbox.get_term_cursor(), (0, 1).into());
+
+        // Folds get drawn the same way when lines aren't wrapped.
+        tbox.set_wrap(false);
+        assert_eq!(
+            render!(),
+            Buffer::with_lines(vec![
+                "a                   ",
+                "+---  2 lines: b----",
+                "d                   ",
+            ])
+        );
+        assert_eq!(tbox.get_term_cursor(), (0, 1).into());
+    }
+
+    #[test]
+    fn test_render_search_highlight() {
+        let (mut tbox, ctx, mut store) = mkboxstr("foo bar\nboo\n");
+
+        let area = Rect::new(0, 0, 8, 2);
+        let mut buffer = Buffer::empty(area);
+
+        let bg = |buffer: &Buffer, x: u16, y: u16| buffer.get(x, y).bg;
+        let row = |buffer: &Buffer, y: u16| (0..4).map(|x| bg(buffer, x, y)).collect::<Vec<_>>();
+        let none = Color::Reset;
+        let hl = Color::Yellow;
+        let cur = Color::LightRed;
+
+        // Searching shows which match we're on.
+        store.search.set_hlsearch(true);
+        store.set_last_search("o");
+
+        let mov = EditTarget::Search(SearchType::Regex, MoveDirMod::Same, Count::Contextual);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        let res = tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(res, Some(InfoMessage::from("[1/4]")));
+
+        let res = tbox.editor_command(&act, &ctx, &mut store).unwrap();
+        assert_eq!(res, Some(InfoMessage::from("[2/4]")));
+
+        // All of the matches get highlighted.
+        tbox.draw(area, &mut buffer, true, &mut store);
+        assert_eq!(row(&buffer, 0), vec![none, hl, hl, none]);
+        assert_eq!(row(&buffer, 1), vec![none, hl, hl, none]);
+
+        // The match an incremental search would jump to gets highlighted differently.
+        let inc = (Regex::new("o+").unwrap(), MoveDir1D::Next);
+        store.search.set_incsearch(Some(inc));
+
+        let mut buffer = Buffer::empty(area);
+        tbox.draw(area, &mut buffer, true, &mut store);
+        assert_eq!(row(&buffer, 0), vec![none, hl, hl, none]);
+        assert_eq!(row(&buffer, 1), vec![none, cur, cur, none]);
+
+        // It can be moved back to an earlier match.
+        store.search.move_incsearch(MoveDir1D::Previous, 1);
+
+        let mut buffer = Buffer::empty(area);
+        tbox.draw(area, &mut buffer, true, &mut store);
+        assert_eq!(row(&buffer, 0), vec![none, cur, cur, none]);
+        assert_eq!(row(&buffer, 1), vec![none, hl, hl, none]);
+
+        // Highlighting can be turned off until the next search.
+        store.search.set_incsearch(None);
+
+        let act = EditorAction::ClearSearchHighlight;
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        let mut buffer = Buffer::empty(area);
+        tbox.draw(area, &mut buffer, true, &mut store);
+        assert_eq!(row(&buffer, 0), vec![none; 4]);
+        assert_eq!(row(&buffer, 1), vec![none; 4]);
+    }
+
+    #[test]
+    fn test_render_oneline_tabs() {
+        let (mut tbox, ctx, mut store) = mkboxstr("a\tb\nc");
+
+        set_tabstop(&mut tbox, 4);
+
+        let area = Rect::new(0, 0, 10, 1);
+        let mut buffer = Buffer::empty(area);
+
+        TextBox::new().prompt("> ").oneline().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["> a   b^Jc"]));
+
+        // Moving to the end of the last line scrolls just enough to show the cursor.
+        let mov = mv!(MoveType::BufferLineOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        let mut buffer = Buffer::empty(area);
+        TextBox::new().prompt("> ").oneline().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec!["> a   b^Jc"]));
+        assert_eq!(tbox.get_term_cursor(), (9, 0).into());
+
+        tbox.set_text("a\tb\ncd");
+        let mov = mv!(MoveType::BufferLineOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        let mov = mv!(MoveType::LineColumnOffset, 2);
+        let act = EditorAction::Edit(EditAction::Motion.into(), mov);
+        tbox.editor_command(&act, &ctx, &mut store).unwrap();
+
+        let mut buffer = Buffer::empty(area);
+        TextBox::new().prompt("> ").oneline().render(area, &mut buffer, &mut tbox);
+        assert_eq!(buffer, Buffer::with_lines(vec![">    b^Jcd"]));
+        assert_eq!(tbox.viewctx.corner, Cursor::new(0, 1));
+        assert_eq!(tbox.get_term_cursor(), (9, 0).into());
+    }
 }
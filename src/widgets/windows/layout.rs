@@ -1760,7 +1760,7 @@ where
 
         let w = match target {
             OpenTarget::Alternate => {
-                slot.alternate();
+                slot.alternate(store);
 
                 return Ok(None);
             },
@@ -1779,7 +1779,7 @@ where
             OpenTarget::List(count) => W::posn(ctx.resolve(count), store)?,
             OpenTarget::Name(name) => W::find(name.clone(), store)?,
             OpenTarget::Offset(dir, count) => {
-                slot.offset(*dir, ctx.resolve(count));
+                slot.offset(*dir, ctx.resolve(count), store);
 
                 return Ok(None);
             },
@@ -1796,7 +1796,7 @@ where
             OpenTarget::Unnamed => W::unnamed(store)?,
         };
 
-        slot.open(w);
+        slot.open(w, store);
 
         Ok(None)
     }
@@ -2150,17 +2150,18 @@ mod tests {
         dirty: bool,
         term_area: Rect,
         id: Option<usize>,
+        left: usize,
     }
 
     impl TestWindow {
         fn new() -> Self {
-            TestWindow { dirty: true, term_area: Rect::default(), id: None }
+            TestWindow { dirty: true, term_area: Rect::default(), id: None, left: 0 }
         }
     }
 
     impl From<Option<usize>> for TestWindow {
         fn from(id: Option<usize>) -> Self {
-            TestWindow { dirty: true, term_area: Rect::default(), id }
+            TestWindow { dirty: true, term_area: Rect::default(), id, left: 0 }
         }
     }
 
@@ -2189,6 +2190,10 @@ mod tests {
             self.write(None, flags, store).is_ok()
         }
 
+        fn leave(&mut self, _: &mut Store<TestApp>) {
+            self.left += 1;
+        }
+
         fn write(
             &mut self,
             _: Option<&str>,
@@ -2831,6 +2836,41 @@ mod tests {
         assert_eq!(tree.get().unwrap().id, Some(2));
     }
 
+    #[test]
+    fn test_window_switch_leave() {
+        let (mut store, ctx) = mkstorectx();
+        let mut tree = WindowLayoutState::new(TestWindow::new());
+        let next = MoveDir1D::Next;
+        let prev = MoveDir1D::Previous;
+
+        // Switching away from a window lets it know that it's being left.
+        window_switch!(tree, OpenTarget::Name("1".into()), &ctx, store);
+        window_switch!(tree, OpenTarget::Name("2".into()), &ctx, store);
+        assert_eq!(tree.get().unwrap().id, Some(2));
+        assert_eq!(tree.get().unwrap().left, 0);
+
+        window_switch!(tree, OpenTarget::Alternate, &ctx, store);
+        assert_eq!(tree.get().unwrap().id, Some(1));
+        assert_eq!(tree.get().unwrap().left, 1);
+
+        window_switch!(tree, OpenTarget::Alternate, &ctx, store);
+        assert_eq!(tree.get().unwrap().id, Some(2));
+        assert_eq!(tree.get().unwrap().left, 1);
+
+        window_switch!(tree, OpenTarget::Offset(prev, 1.into()), &ctx, store);
+        assert_eq!(tree.get().unwrap().id, Some(1));
+        assert_eq!(tree.get().unwrap().left, 2);
+
+        window_switch!(tree, OpenTarget::Offset(next, 1.into()), &ctx, store);
+        assert_eq!(tree.get().unwrap().id, Some(2));
+        assert_eq!(tree.get().unwrap().left, 2);
+
+        // Offsets that don't go anywhere leave the window alone.
+        window_switch!(tree, OpenTarget::Offset(next, 1.into()), &ctx, store);
+        assert_eq!(tree.get().unwrap().id, Some(2));
+        assert_eq!(tree.get().unwrap().left, 2);
+    }
+
     #[test]
     fn test_layout_as_description() {
         use WindowLayoutDescription::{Split, Window};
@@ -21,19 +21,41 @@ impl<W> WindowSlot<W> {
         WindowSlot { current, prev: vec![], next: vec![] }
     }
 
-    pub fn open(&mut self, w: W) {
+    pub fn open<I: ApplicationInfo>(&mut self, w: W, store: &mut Store<I>)
+    where
+        W: WindowOps<I>,
+    {
+        self.current.leave(store);
+
         let alt = std::mem::replace(&mut self.current, w);
 
         self.prev.push(alt);
     }
 
-    pub fn alternate(&mut self) {
+    pub fn alternate<I: ApplicationInfo>(&mut self, store: &mut Store<I>)
+    where
+        W: WindowOps<I>,
+    {
         if let Some(alt) = self.prev.last_mut() {
+            self.current.leave(store);
+
             std::mem::swap(alt, &mut self.current);
         }
     }
 
-    pub fn offset(&mut self, dir: MoveDir1D, count: usize) {
+    pub fn offset<I: ApplicationInfo>(&mut self, dir: MoveDir1D, count: usize, store: &mut Store<I>)
+    where
+        W: WindowOps<I>,
+    {
+        let others = match dir {
+            MoveDir1D::Previous => &self.prev,
+            MoveDir1D::Next => &self.next,
+        };
+
+        if count > 0 && !others.is_empty() {
+            self.current.leave(store);
+        }
+
         match dir {
             MoveDir1D::Previous => {
                 for _ in 0..count {
@@ -190,6 +212,10 @@ where
         return true;
     }
 
+    fn leave(&mut self, store: &mut Store<I>) {
+        self.current.leave(store);
+    }
+
     fn write(
         &mut self,
         path: Option<&str>,
@@ -211,6 +237,10 @@ where
         self.current.get_cursor_word(style)
     }
 
+    fn get_cursor_line(&self) -> Option<String> {
+        self.current.get_cursor_line()
+    }
+
     fn get_selected_word(&self) -> Option<String> {
         self.current.get_selected_word()
     }